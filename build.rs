@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("codec_menus.rs");
+    fs::write(out_path, codec_menus()).expect("Failed to write codec menus");
+}
+
+/// Menu enums which are written out by hand in src/control/codec.rs
+const HAND_WRITTEN: &[&str] = &[
+    "v4l2_mpeg_video_bitrate_mode",
+    "v4l2_mpeg_video_h264_level",
+    "v4l2_mpeg_video_h264_profile",
+];
+
+/// Menu enums whose name does not follow the name of their control ID
+const CONTROL_IDS: &[(&str, &str)] = &[
+    (
+        "v4l2_mpeg_video_h264_fmo_change_dir",
+        "V4L2_CID_MPEG_VIDEO_H264_FMO_CHANGE_DIRECTION",
+    ),
+    (
+        "v4l2_vp8_golden_frame_sel",
+        "V4L2_CID_MPEG_VIDEO_VPX_GOLDEN_FRAME_SEL",
+    ),
+    (
+        "v4l2_vp8_num_partitions",
+        "V4L2_CID_MPEG_VIDEO_VPX_NUM_PARTITIONS",
+    ),
+    (
+        "v4l2_vp8_num_ref_frames",
+        "V4L2_CID_MPEG_VIDEO_VPX_NUM_REF_FRAMES",
+    ),
+];
+
+/// Generates a menu_control! invocation for every enum of the codec control class
+///
+/// The enums are read from the bindings of the sys crate in use, so codec menus added by newer
+/// kernels show up as soon as the bindings are generated from their headers.
+fn codec_menus() -> String {
+    // Exported by the build scripts of the sys crates through their "links" key
+    let path = match env::var("DEP_V4L2_SYS_BINDINGS").or_else(|_| env::var("DEP_V4L2_BINDINGS")) {
+        Ok(path) => path,
+        Err(_) => return String::new(),
+    };
+    println!("cargo:rerun-if-changed={}", path);
+    let bindings = fs::read_to_string(&path).expect("Failed to read bindings");
+
+    // bindgen emits C enums as a type alias followed by one constant per enumerator
+    let mut enums = Vec::new();
+    let mut consts = Vec::new();
+    for item in bindings.split(';') {
+        let item = item.split_whitespace().collect::<Vec<_>>().join(" ");
+        let item = match item.rfind("pub ") {
+            Some(start) => &item[start..],
+            None => continue,
+        };
+        if let Some(alias) = item.strip_prefix("pub type ") {
+            if let Some((name, _)) = alias.split_once(" = ") {
+                enums.push(name.to_string());
+            }
+        } else if let Some(decl) = item.strip_prefix("pub const ") {
+            let (name, rest) = match decl.split_once(": ") {
+                Some(split) => split,
+                None => continue,
+            };
+            let (typ, value) = match rest.split_once(" = ") {
+                Some(split) => split,
+                None => continue,
+            };
+            consts.push((name.to_string(), typ.to_string(), value.to_string()));
+        }
+    }
+
+    let mut out = String::new();
+    for name in &enums {
+        if !(name.starts_with("v4l2_mpeg_")
+            || name.starts_with("v4l2_cid_mpeg_")
+            || name.starts_with("v4l2_vp8_"))
+            || HAND_WRITTEN.contains(&name.as_str())
+        {
+            continue;
+        }
+
+        let cid = match CONTROL_IDS.iter().find(|(menu, _)| menu == name) {
+            Some((_, cid)) => cid.to_string(),
+            None => format!(
+                "V4L2_CID_{}",
+                name.trim_start_matches("v4l2_")
+                    .trim_start_matches("cid_")
+                    .to_uppercase()
+            ),
+        };
+        // Enums without a matching control (e.g. ones used by stateless codec payloads) are
+        // not menus
+        let id = match consts.iter().find(|(c, typ, _)| *c == cid && typ == "u32") {
+            Some((_, _, id)) => id.parse::<u32>().expect("Invalid control ID"),
+            None => continue,
+        };
+
+        let prefix = format!("{}_", name);
+        let enumerators: Vec<_> = consts
+            .iter()
+            .filter(|(_, typ, _)| typ == name)
+            .filter_map(|(c, _, value)| {
+                // Older headers misspell some of the slice modes as MULTI_SICE_MODE
+                let c = c
+                    .strip_prefix(&prefix)?
+                    .replace("_MULTI_SICE_", "_MULTI_SLICE_");
+                Some((c, value.as_str()))
+            })
+            .collect();
+        if enumerators.is_empty() {
+            continue;
+        }
+
+        let menu = camel_case(
+            cid.trim_start_matches("V4L2_CID_MPEG_")
+                .trim_start_matches("VIDEO_"),
+        );
+        let _ = writeln!(out, "menu_control! {{");
+        let _ = writeln!(out, "    /// `{}`", cid);
+        let _ = writeln!(out, "    {} = {:#010x} {{", menu, id);
+
+        let common = common_prefix(enumerators.iter().map(|(c, _)| c.as_str())).to_string();
+        let mut names = HashSet::new();
+        let mut values = HashSet::new();
+        for (enumerator, value) in enumerators {
+            let mut variant = camel_case(&enumerator[common.len()..]);
+            // Identifiers cannot start with a digit, keep the last word of the prefix, e.g.
+            // LEVEL_1_0 becomes Level1_0
+            if variant.starts_with(|c: char| c.is_ascii_digit()) {
+                let word = common.trim_end_matches('_').rsplit('_').next().unwrap();
+                variant = camel_case(word) + &variant;
+            }
+            // Aliases would result in duplicate discriminants
+            if !names.insert(variant.clone()) || !values.insert(value) {
+                continue;
+            }
+            let _ = writeln!(out, "        {:<28}= {},", variant, value);
+        }
+
+        let _ = writeln!(out, "    }}");
+        let _ = writeln!(out, "}}");
+        let _ = writeln!(out);
+    }
+
+    out
+}
+
+/// Returns the longest prefix ending with an underscore shared by all enumerators
+fn common_prefix<'a>(mut names: impl Iterator<Item = &'a str>) -> &'a str {
+    let first = match names.next() {
+        Some(first) => first,
+        None => return "",
+    };
+    // Never consume a whole enumerator, the variant would be left without a name
+    let mut len = first.rfind('_').map_or(0, |i| i + 1);
+    for name in names {
+        len = first
+            .bytes()
+            .zip(name.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+        len = first[..len].rfind('_').map_or(0, |i| i + 1);
+    }
+    &first[..len]
+}
+
+/// Converts an upper case C identifier to camel case, e.g. HIGH_10_INTRA to High10Intra
+fn camel_case(name: &str) -> String {
+    let mut out = String::new();
+    let mut prev_digit = false;
+    for word in name.split('_').filter(|word| !word.is_empty()) {
+        // Separate numbers like 1_0 which would be ambiguous otherwise
+        if prev_digit && word.starts_with(|c: char| c.is_ascii_digit()) {
+            out.push('_');
+        }
+
+        let (number, _) = word.split_at(word.len() - 1);
+        if !number.is_empty() && number.bytes().all(|c| c.is_ascii_digit()) {
+            // Keep unit suffixes such as 32K or 1B
+            out.push_str(word);
+        } else {
+            let mut chars = word.chars();
+            if let Some(first) = chars.next() {
+                out.push(first.to_ascii_uppercase());
+                out.extend(chars.map(|c| c.to_ascii_lowercase()));
+            }
+        }
+        prev_digit = word.ends_with(|c: char| c.is_ascii_digit());
+    }
+    out
+}
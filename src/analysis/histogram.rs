@@ -0,0 +1,283 @@
+//! Luma histograms and exposure statistics
+//!
+//! Raw sensors without an auto exposure loop of their own need software to look at each frame
+//! and adjust the exposure and gain controls. The histogram tells how bright a frame is and how
+//! much of it is crushed to black or blown out to white.
+
+use std::io;
+
+use crate::analysis::{Luma, Sampling};
+use crate::format::Format;
+use crate::frame::FrameView;
+
+/// Distribution of the luma values of a frame
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    // Counting into several histograms avoids stalls when neighbouring samples are equal, which
+    // is the common case in images. They are only merged when the histogram is read.
+    partial: [[u32; 256]; 4],
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Histogram {
+            partial: [[0; 256]; 4],
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Computes the histogram of a frame
+    ///
+    /// Supports the same formats as `Luma::from_frame`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Frame data
+    /// * `fmt` - Format of the frame
+    /// * `step` - Only look at every step-th pixel of every step-th line, 1 to look at all pixels
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::analysis::histogram::Histogram;
+    /// use v4l::buffer::Type;
+    /// use v4l::io::traits::CaptureStream;
+    /// use v4l::prelude::*;
+    /// use v4l::video::Capture;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    ///
+    /// let (buf, _) = stream.next().unwrap();
+    /// let hist = Histogram::from_frame(buf, &fmt, 2).unwrap();
+    /// println!(
+    ///     "mean {:.1}, {:.1}% clipped highlights",
+    ///     hist.mean(),
+    ///     hist.clipped_high(250) * 100.0
+    /// );
+    /// ```
+    pub fn from_frame(buf: &[u8], fmt: &Format, step: u32) -> io::Result<Self> {
+        let sampling = Sampling::of(&fmt.fourcc).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot extract luma from {}", fmt.fourcc),
+            )
+        })?;
+        let view = FrameView::new(buf, fmt)?;
+        let plane = view.plane(0).expect("frame without planes");
+
+        let step = step.max(1) as usize;
+        let mut hist = Histogram::default();
+        for line in plane.lines().step_by(step) {
+            match (sampling, step) {
+                (Sampling::Plane, 1) => hist.add(line),
+                _ => {
+                    for x in (0..fmt.width as usize).step_by(step) {
+                        hist.partial[0][sampling.sample(line, x) as usize] += 1;
+                        hist.count += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(hist)
+    }
+
+    /// Computes the histogram of an already extracted luma image
+    ///
+    /// # Arguments
+    ///
+    /// * `luma` - Luma image
+    pub fn from_luma(luma: &Luma) -> Self {
+        let mut hist = Histogram::default();
+        hist.add(&luma.data);
+        hist
+    }
+
+    /// Adds luma samples to the histogram
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Luma values
+    pub fn add(&mut self, samples: &[u8]) {
+        let [bins0, bins1, bins2, bins3] = &mut self.partial;
+        let mut chunks = samples.chunks_exact(4);
+        for chunk in &mut chunks {
+            bins0[chunk[0] as usize] += 1;
+            bins1[chunk[1] as usize] += 1;
+            bins2[chunk[2] as usize] += 1;
+            bins3[chunk[3] as usize] += 1;
+        }
+        for sample in chunks.remainder() {
+            bins0[*sample as usize] += 1;
+        }
+        self.count += samples.len() as u64;
+    }
+
+    /// Returns the number of samples per luma value
+    pub fn bins(&self) -> [u32; 256] {
+        let mut bins = self.partial[0];
+        for partial in &self.partial[1..] {
+            for (bin, count) in bins.iter_mut().zip(partial.iter()) {
+                *bin += count;
+            }
+        }
+        bins
+    }
+
+    /// Returns the total number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the mean luma value, from 0.0 to 255.0
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+
+        let sum: u64 = self
+            .bins()
+            .iter()
+            .enumerate()
+            .map(|(value, count)| value as u64 * *count as u64)
+            .sum();
+        sum as f64 / self.count as f64
+    }
+
+    /// Returns the luma value below which a given percentage of the samples lie
+    ///
+    /// # Arguments
+    ///
+    /// * `percentile` - Percentile from 0.0 to 100.0, e.g. 50.0 for the median
+    pub fn percentile(&self, percentile: f64) -> u8 {
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * self.count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (value, count) in self.bins().iter().enumerate() {
+            seen += *count as u64;
+            if seen >= rank.max(1) {
+                return value as u8;
+            }
+        }
+        255
+    }
+
+    /// Returns the fraction of samples at or below a threshold, i.e. crushed shadows
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Luma value, e.g. 5
+    pub fn clipped_low(&self, threshold: u8) -> f64 {
+        self.fraction(&self.bins()[..=threshold as usize])
+    }
+
+    /// Returns the fraction of samples at or above a threshold, i.e. blown highlights
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Luma value, e.g. 250
+    pub fn clipped_high(&self, threshold: u8) -> f64 {
+        self.fraction(&self.bins()[threshold as usize..])
+    }
+
+    fn fraction(&self, bins: &[u32]) -> f64 {
+        if self.count == 0 {
+            return 0.0;
+        }
+        bins.iter().map(|count| *count as u64).sum::<u64>() as f64 / self.count as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::FourCC;
+
+    #[test]
+    fn add_samples() {
+        // lengths which do and do not fill the partial histograms evenly
+        for samples in &[
+            &[][..],
+            &[7],
+            &[7, 7, 7, 7],
+            &[1, 2, 3, 4, 5, 5, 5],
+            &[255; 9],
+        ] {
+            let mut expected = [0u32; 256];
+            for sample in samples.iter() {
+                expected[*sample as usize] += 1;
+            }
+
+            let mut hist = Histogram::default();
+            hist.add(samples);
+            assert_eq!(hist.bins()[..], expected[..], "{:?}", samples);
+            assert_eq!(hist.count(), samples.len() as u64);
+
+            // adding again accumulates
+            hist.add(samples);
+            assert_eq!(hist.count(), 2 * samples.len() as u64);
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let mut hist = Histogram::default();
+        assert_eq!(hist.mean(), 0.0);
+        assert_eq!(hist.clipped_low(5), 0.0);
+
+        hist.add(&[0, 0, 10, 20, 30, 40, 250, 255]);
+        assert_eq!(hist.mean(), 605.0 / 8.0);
+        for (percentile, value) in &[
+            (0.0, 0),
+            (25.0, 0),
+            (30.0, 10),
+            (50.0, 20),
+            (75.0, 40),
+            (100.0, 255),
+            (150.0, 255),
+        ] {
+            assert_eq!(hist.percentile(*percentile), *value, "{}", percentile);
+        }
+        assert_eq!(hist.clipped_low(5), 0.25);
+        assert_eq!(hist.clipped_high(250), 0.25);
+        assert_eq!(hist.clipped_high(0), 1.0);
+    }
+
+    #[test]
+    fn from_frame() {
+        // 4x2 frames, the GREY lines are padded to a stride of 6 bytes
+        let grey = Format {
+            stride: 6,
+            ..Format::new(4, 2, FourCC::new(b"GREY"))
+        };
+        let yuyv = Format::new(4, 2, FourCC::new(b"YUYV"));
+        let grey_buf = [1, 2, 3, 4, 99, 99, 5, 6, 7, 8];
+        let yuyv_buf = [1, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0, 7, 0, 8, 0];
+
+        for (buf, fmt, step, samples) in &[
+            (&grey_buf[..], &grey, 1, &[1, 2, 3, 4, 5, 6, 7, 8][..]),
+            (&grey_buf[..], &grey, 2, &[1, 3]),
+            (&yuyv_buf[..], &yuyv, 1, &[1, 2, 3, 4, 5, 6, 7, 8]),
+            (&yuyv_buf[..], &yuyv, 2, &[1, 3]),
+        ] {
+            let mut expected = Histogram::default();
+            expected.add(samples);
+            let hist = Histogram::from_frame(buf, fmt, *step).unwrap();
+            assert_eq!(
+                hist.bins()[..],
+                expected.bins()[..],
+                "{} / {}",
+                fmt.fourcc,
+                step
+            );
+            assert_eq!(hist.count(), expected.count());
+        }
+
+        let mjpg = Format::new(4, 2, FourCC::new(b"MJPG"));
+        assert!(Histogram::from_frame(&[0; 8], &mjpg, 1).is_err());
+    }
+}
@@ -0,0 +1,138 @@
+//! Lightweight image analysis on captured frames
+//!
+//! These utilities work on the luma (brightness) channel only, which is cheap to extract from
+//! the YUV formats most devices deliver and sufficient for tasks like motion detection or
+//! exposure control, without pulling in a computer vision framework.
+//!
+//! This module is only available with the `analysis` feature.
+
+use std::io;
+
+use crate::format::{Format, FourCC};
+use crate::frame::FrameView;
+
+pub mod histogram;
+pub mod motion;
+
+/// Location of the luma samples within a line
+#[derive(Debug, Copy, Clone)]
+enum Sampling {
+    /// one byte per pixel, e.g. the first plane of planar formats
+    Plane,
+    /// interleaved with chroma, e.g. YUYV
+    Packed { offset: usize },
+    /// computed from RGB, with the byte offsets of the red and blue samples
+    Rgb { red: usize, blue: usize },
+}
+
+impl Sampling {
+    fn of(fourcc: &FourCC) -> Option<Self> {
+        Some(match &fourcc.repr {
+            b"GREY" | b"NV12" | b"NV21" | b"NM12" | b"NM21" | b"NV16" | b"NV61" | b"NM16"
+            | b"NM61" | b"YU12" | b"YV12" | b"YM12" | b"YM21" | b"422P" | b"YM16" | b"YM61" => {
+                Sampling::Plane
+            }
+            b"YUYV" | b"YVYU" => Sampling::Packed { offset: 0 },
+            b"UYVY" | b"VYUY" => Sampling::Packed { offset: 1 },
+            b"RGB3" => Sampling::Rgb { red: 0, blue: 2 },
+            b"BGR3" => Sampling::Rgb { red: 2, blue: 0 },
+            _ => return None,
+        })
+    }
+
+    /// Returns the luma value of a pixel
+    #[inline]
+    fn sample(&self, line: &[u8], x: usize) -> u8 {
+        match *self {
+            Sampling::Plane => line[x],
+            Sampling::Packed { offset } => line[x * 2 + offset],
+            Sampling::Rgb { red, blue } => {
+                let px = &line[x * 3..x * 3 + 3];
+                // BT.601 weights in 8-bit fixed point
+                ((px[red] as u32 * 77 + px[1] as u32 * 150 + px[blue] as u32 * 29) >> 8) as u8
+            }
+        }
+    }
+}
+
+/// Luma channel of a frame, possibly downscaled
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Luma {
+    /// width in samples
+    pub width: u32,
+    /// height in samples
+    pub height: u32,
+    /// factor by which the frame was downscaled in each direction
+    pub scale: u32,
+    /// tightly packed samples
+    pub data: Vec<u8>,
+}
+
+impl Luma {
+    /// Extracts the luma channel of a frame
+    ///
+    /// Supported formats are GREY, packed and planar YUV as well as RGB3 and BGR3.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Frame data
+    /// * `fmt` - Format of the frame
+    /// * `scale` - Downscaling factor, each output sample is the average of a `scale` x `scale`
+    ///   block of pixels. Pixels which do not fill a complete block are ignored.
+    pub fn from_frame(buf: &[u8], fmt: &Format, scale: u32) -> io::Result<Self> {
+        let sampling = Sampling::of(&fmt.fourcc).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot extract luma from {}", fmt.fourcc),
+            )
+        })?;
+        let view = FrameView::new(buf, fmt)?;
+        let plane = view.plane(0).expect("frame without planes");
+
+        let scale = scale.max(1);
+        let width = (fmt.width / scale).max(1);
+        let height = (fmt.height / scale).max(1);
+        let block = scale.min(fmt.width).max(1) as usize;
+        let block_lines = scale.min(fmt.height).max(1) as usize;
+
+        let mut data = Vec::with_capacity(width as usize * height as usize);
+        let mut sums = vec![0u32; width as usize];
+        for y in 0..height as usize {
+            for sum in sums.iter_mut() {
+                *sum = 0;
+            }
+            for line in 0..block_lines {
+                let line = plane
+                    .line((y * block_lines + line) as u32)
+                    .expect("line outside of the frame");
+                for (x, sum) in sums.iter_mut().enumerate() {
+                    for i in 0..block {
+                        *sum += sampling.sample(line, x * block + i) as u32;
+                    }
+                }
+            }
+            let count = (block * block_lines) as u32;
+            data.extend(sums.iter().map(|sum| (sum / count) as u8));
+        }
+
+        Ok(Luma {
+            width,
+            height,
+            scale,
+            data,
+        })
+    }
+
+    /// Returns a sample
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - Column
+    /// * `y` - Row
+    pub fn get(&self, x: u32, y: u32) -> Option<u8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(self.data[(y * self.width + x) as usize])
+    }
+}
@@ -0,0 +1,316 @@
+//! Motion detection by frame differencing
+//!
+//! Each frame is reduced to a downscaled luma image and compared against the previous one.
+//! Samples whose brightness changed by more than a threshold are grouped into connected regions,
+//! whose bounding boxes are reported in frame coordinates.
+
+use std::io;
+
+use crate::analysis::Luma;
+use crate::format::Format;
+
+/// Rectangle in frame coordinates
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Region {
+    /// left edge in pixels
+    pub x: u32,
+    /// top edge in pixels
+    pub y: u32,
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+}
+
+/// Result of comparing a frame against its predecessor
+#[derive(Debug, Clone)]
+pub struct Motion {
+    /// Fraction of samples which changed by more than the threshold, from 0.0 to 1.0
+    pub changed: f64,
+    /// Mean absolute difference over all samples, from 0.0 to 255.0
+    pub mean_difference: f64,
+    /// Bounding boxes of the changed regions, largest first
+    pub regions: Vec<Region>,
+}
+
+impl Motion {
+    /// Returns true if any region changed
+    pub fn detected(&self) -> bool {
+        !self.regions.is_empty()
+    }
+}
+
+/// Stateful motion detector
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::analysis::motion::Detector;
+/// use v4l::buffer::Type;
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+/// use v4l::video::Capture;
+///
+/// let dev = Device::new(0).unwrap();
+/// let fmt = dev.format().unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// let mut detector = Detector::new(8);
+///
+/// loop {
+///     let (buf, _) = stream.next().unwrap();
+///     if let Some(motion) = detector.process(buf, &fmt).unwrap() {
+///         for region in &motion.regions {
+///             println!("motion at {:?}", region);
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Detector {
+    scale: u32,
+    threshold: u8,
+    min_area: usize,
+    previous: Option<Luma>,
+    difference: Option<Luma>,
+}
+
+impl Detector {
+    /// Returns a detector
+    ///
+    /// # Arguments
+    ///
+    /// * `scale` - Downscaling factor applied before comparing frames, larger values are faster
+    ///   and less sensitive to noise, but miss small objects
+    pub fn new(scale: u32) -> Self {
+        Detector {
+            scale: scale.max(1),
+            threshold: 25,
+            min_area: 4,
+            previous: None,
+            difference: None,
+        }
+    }
+
+    /// Sets the brightness change above which a sample counts as changed (default: 25)
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Absolute luma difference
+    pub fn set_threshold(&mut self, threshold: u8) {
+        self.threshold = threshold;
+    }
+
+    /// Sets the minimum size of reported regions (default: 4)
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Minimum number of changed samples in the downscaled image
+    pub fn set_min_area(&mut self, samples: usize) {
+        self.min_area = samples;
+    }
+
+    /// Forgets the previous frame, e.g. after the camera has been moved
+    pub fn reset(&mut self) {
+        self.previous = None;
+        self.difference = None;
+    }
+
+    /// Returns the absolute difference image of the last processed frame
+    pub fn difference(&self) -> Option<&Luma> {
+        self.difference.as_ref()
+    }
+
+    /// Compares a frame against the previous one
+    ///
+    /// Returns None for the first frame and whenever the frame size changed, since there is
+    /// nothing to compare against.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Frame data
+    /// * `fmt` - Format of the frame
+    pub fn process(&mut self, buf: &[u8], fmt: &Format) -> io::Result<Option<Motion>> {
+        let current = Luma::from_frame(buf, fmt, self.scale)?;
+        let previous = match self.previous.take() {
+            Some(previous)
+                if (previous.width, previous.height) == (current.width, current.height) =>
+            {
+                previous
+            }
+            _ => {
+                self.previous = Some(current);
+                self.difference = None;
+                return Ok(None);
+            }
+        };
+
+        let data: Vec<u8> = previous
+            .data
+            .iter()
+            .zip(&current.data)
+            .map(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() as u8)
+            .collect();
+
+        let sum: u64 = data.iter().map(|d| *d as u64).sum();
+        let changed = data.iter().filter(|d| **d > self.threshold).count();
+        let total = data.len().max(1) as f64;
+
+        self.previous = Some(current);
+        let difference = Luma { data, ..previous };
+        let regions = self.regions(&difference);
+        self.difference = Some(difference);
+
+        Ok(Some(Motion {
+            changed: changed as f64 / total,
+            mean_difference: sum as f64 / total,
+            regions,
+        }))
+    }
+
+    /// Groups changed samples into 8-connected regions and returns their bounding boxes
+    fn regions(&self, diff: &Luma) -> Vec<Region> {
+        let (width, height) = (diff.width as usize, diff.height as usize);
+        let mut visited = vec![false; diff.data.len()];
+        let mut stack = Vec::new();
+        let mut regions = Vec::new();
+
+        for start in 0..diff.data.len() {
+            if visited[start] || diff.data[start] <= self.threshold {
+                continue;
+            }
+
+            visited[start] = true;
+            stack.push(start);
+            let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+            let mut area = 0;
+
+            while let Some(index) = stack.pop() {
+                let (x, y) = (index % width, index / width);
+                x0 = x0.min(x);
+                y0 = y0.min(y);
+                x1 = x1.max(x);
+                y1 = y1.max(y);
+                area += 1;
+
+                for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                    for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                        let neighbour = ny * width + nx;
+                        if !visited[neighbour] && diff.data[neighbour] > self.threshold {
+                            visited[neighbour] = true;
+                            stack.push(neighbour);
+                        }
+                    }
+                }
+            }
+
+            if area >= self.min_area {
+                regions.push((
+                    area,
+                    Region {
+                        x: x0 as u32 * diff.scale,
+                        y: y0 as u32 * diff.scale,
+                        width: (x1 - x0 + 1) as u32 * diff.scale,
+                        height: (y1 - y0 + 1) as u32 * diff.scale,
+                    },
+                ));
+            }
+        }
+
+        regions.sort_by_key(|(area, _)| std::cmp::Reverse(*area));
+        regions.into_iter().map(|(_, region)| region).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::FourCC;
+
+    /// Returns an 8x8 GREY frame with the given pixels set to white
+    fn frame(pixels: &[(usize, usize)]) -> Vec<u8> {
+        let mut buf = vec![0; 64];
+        for (x, y) in pixels {
+            buf[y * 8 + x] = 255;
+        }
+        buf
+    }
+
+    #[test]
+    fn detect_regions() {
+        let fmt = Format::new(8, 8, FourCC::new(b"GREY"));
+        let square = [(1, 1), (2, 1), (1, 2), (2, 2)];
+        let diagonal = [(5, 4), (6, 5), (7, 6), (7, 7)];
+        let region = |x, y, width, height| Region {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        #[allow(clippy::type_complexity)]
+        let cases: &[(u32, &[(usize, usize)], &[Region])] = &[
+            // nothing changed
+            (1, &[], &[]),
+            // a single pixel is below the minimum area
+            (1, &[(3, 3)], &[]),
+            (1, &square, &[region(1, 1, 2, 2)]),
+            // diagonal neighbours are connected, larger regions come first
+            (
+                1,
+                &[
+                    (1, 1),
+                    (2, 1),
+                    (1, 2),
+                    (2, 2),
+                    (5, 4),
+                    (6, 5),
+                    (7, 6),
+                    (7, 7),
+                    (4, 3),
+                ],
+                &[region(4, 3, 4, 5), region(1, 1, 2, 2)],
+            ),
+            (1, &diagonal, &[region(5, 4, 3, 4)]),
+            // regions are reported in frame coordinates
+            (2, &[(0, 0), (2, 0), (0, 2), (2, 2)], &[region(0, 0, 4, 4)]),
+        ];
+
+        for (scale, pixels, regions) in cases {
+            let mut detector = Detector::new(*scale);
+            assert!(detector.process(&frame(&[]), &fmt).unwrap().is_none());
+
+            let motion = detector.process(&frame(pixels), &fmt).unwrap().unwrap();
+            assert_eq!(motion.regions, *regions, "{:?}", pixels);
+            assert_eq!(motion.detected(), !regions.is_empty());
+        }
+    }
+
+    #[test]
+    fn statistics() {
+        let fmt = Format::new(8, 8, FourCC::new(b"GREY"));
+        let mut detector = Detector::new(1);
+        detector.set_threshold(100);
+        assert!(detector.process(&frame(&[]), &fmt).unwrap().is_none());
+
+        let mut buf = frame(&[(0, 0), (1, 0)]);
+        buf[63] = 64;
+        let motion = detector.process(&buf, &fmt).unwrap().unwrap();
+        assert_eq!(motion.changed, 2.0 / 64.0);
+        assert_eq!(motion.mean_difference, (255.0 * 2.0 + 64.0) / 64.0);
+        assert_eq!(detector.difference().unwrap().data, buf);
+    }
+
+    #[test]
+    fn restart_on_size_change() {
+        let mut detector = Detector::new(1);
+        let small = Format::new(4, 4, FourCC::new(b"GREY"));
+        let large = Format::new(8, 8, FourCC::new(b"GREY"));
+
+        assert!(detector.process(&[0; 16], &small).unwrap().is_none());
+        assert!(detector.process(&frame(&[]), &large).unwrap().is_none());
+        assert!(detector.process(&frame(&[]), &large).unwrap().is_some());
+        detector.reset();
+        assert!(detector.process(&frame(&[]), &large).unwrap().is_none());
+        assert!(detector.difference().is_none());
+    }
+}
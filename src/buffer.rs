@@ -1,5 +1,6 @@
 use bitflags::bitflags;
-use std::fmt;
+use std::time::Duration;
+use std::{fmt, io};
 
 use crate::timestamp::Timestamp;
 
@@ -30,6 +31,27 @@ pub enum Type {
     Private             = 0x80,
 }
 
+impl Type {
+    /// Returns true if buffers of this type carry data from the application to the device
+    pub fn is_output(&self) -> bool {
+        matches!(
+            self,
+            Type::VideoOutput
+                | Type::VbiOutput
+                | Type::SlicedVbiOutput
+                | Type::VideoOutputOverlay
+                | Type::VideoOutputMplane
+                | Type::SdrOutput
+                | Type::MetaOutput
+        )
+    }
+
+    /// Returns true if buffers of this type use the multi-planar API
+    pub fn is_multiplanar(&self) -> bool {
+        matches!(self, Type::VideoCaptureMplane | Type::VideoOutputMplane)
+    }
+}
+
 bitflags! {
     #[allow(clippy::unreadable_literal)]
     pub struct Flags: u32 {
@@ -98,6 +120,77 @@ impl fmt::Display for Flags {
     }
 }
 
+impl Flags {
+    /// Returns the flags which may be set by the application when queueing a buffer
+    ///
+    /// These are the flags the kernel takes from the application on VIDIOC_QBUF: the timecode,
+    /// the memory-to-memory hold flag, the cache hints and the request file descriptor. The frame
+    /// type (KEYFRAME, PFRAME, BFRAME) is only passed on for output buffers, e.g. to an encoder,
+    /// capture drivers report it on dequeue instead.
+    ///
+    /// The timestamp source (TSTAMP_SRC_*) and LAST are excluded: they describe how the driver
+    /// filled the buffer and are overwritten by it. Drain a memory-to-memory device with the
+    /// STOP command of the encoder or decoder instead of flagging the last buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_type` - Type of the queue the buffer belongs to
+    pub fn queueable(buf_type: Type) -> Flags {
+        let flags = Flags::TIMECODE
+            | Flags::M2M_HOLD_CAPTURE_BUF
+            | Flags::NO_CACHE_INVALIDATE
+            | Flags::NO_CACHE_CLEAN
+            | Flags::REQUEST_FD;
+
+        if buf_type.is_output() {
+            flags | Flags::KEYFRAME | Flags::PFRAME | Flags::BFRAME
+        } else {
+            flags
+        }
+    }
+}
+
+/// Clock a buffer timestamp was taken from
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Clock {
+    /// The driver did not tell
+    Unknown,
+    /// CLOCK_MONOTONIC, comparable with clock_gettime() and across devices
+    Monotonic,
+    /// Copied from the corresponding output buffer by memory-to-memory devices
+    Copy,
+}
+
+/// Moment of the capture process a buffer timestamp refers to
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TimestampSource {
+    /// End of frame, i.e. when the last pixel was received (the default)
+    EndOfFrame,
+    /// Start of exposure of the first line
+    StartOfExposure,
+}
+
+impl Flags {
+    /// Returns the clock the timestamp was taken from, or None for unknown flag values
+    pub fn clock(&self) -> Option<Clock> {
+        match *self & Flags::TIMESTAMP_MASK {
+            Flags::TIMESTAMP_UNKNOWN => Some(Clock::Unknown),
+            Flags::TIMESTAMP_MONOTONIC => Some(Clock::Monotonic),
+            Flags::TIMESTAMP_COPY => Some(Clock::Copy),
+            _ => None,
+        }
+    }
+
+    /// Returns what the timestamp refers to, or None for unknown flag values
+    pub fn timestamp_source(&self) -> Option<TimestampSource> {
+        match *self & Flags::TSTAMP_SRC_MASK {
+            Flags::TSTAMP_SRC_EOF => Some(TimestampSource::EndOfFrame),
+            Flags::TSTAMP_SRC_SOE => Some(TimestampSource::StartOfExposure),
+            _ => None,
+        }
+    }
+}
+
 /// Buffer metadata, mostly used not to convolute the main buffer structs
 #[derive(Copy, Clone, Default)]
 pub struct Metadata {
@@ -111,4 +204,134 @@ pub struct Metadata {
     pub timestamp: Timestamp,
     /// Sequence number, counting the frames
     pub sequence: u32,
+    /// Latency of the buffer as measured by the stream
+    pub latency: Latency,
+}
+
+impl Metadata {
+    /// Returns the clock the timestamp was taken from
+    ///
+    /// Only monotonic timestamps can be related to the system time or to other devices, see
+    /// `Flags::clock`.
+    pub fn clock(&self) -> Clock {
+        self.flags.clock().unwrap_or(Clock::Unknown)
+    }
+
+    /// Returns what the timestamp refers to
+    ///
+    /// Drivers which do not specify the source timestamp buffers at the end of the frame, see
+    /// `Flags::timestamp_source`.
+    pub fn timestamp_source(&self) -> TimestampSource {
+        self.flags
+            .timestamp_source()
+            .unwrap_or(TimestampSource::EndOfFrame)
+    }
+
+    /// Returns the part of a dequeued capture buffer holding data, as indicated by bytesused
+    ///
+    /// Capture drivers always set bytesused, so a value of zero means the buffer is empty, e.g.
+    /// the last buffer of a drained decoder.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Buffer the metadata belongs to
+    pub fn payload<'b>(&self, buf: &'b [u8]) -> &'b [u8] {
+        &buf[..(self.bytesused as usize).min(buf.len())]
+    }
+}
+
+/// Metadata of a single plane of a multi-planar buffer
+#[derive(Debug, Default, Copy, Clone)]
+pub struct PlaneMetadata {
+    /// Number of bytes occupied by the data in the plane, including data_offset
+    pub bytesused: u32,
+    /// Size of the plane in bytes
+    pub length: u32,
+    /// Offset of the data from the start of the plane
+    pub data_offset: u32,
+}
+
+/// Latency of a single buffer
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Latency {
+    /// Time the buffer spent in the driver, from VIDIOC_QBUF to VIDIOC_DQBUF
+    pub queued: Option<Duration>,
+    /// Time between the driver timestamp and the buffer being dequeued
+    ///
+    /// Only available if the driver uses monotonic timestamps.
+    pub delivery: Option<Duration>,
+}
+
+/// Buffer ownership state as tracked by streams
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    /// Owned by the application, not yet handed to the driver
+    Free,
+    /// Owned by the driver, waiting to be filled (capture) or consumed (output)
+    Queued,
+    /// Owned by the application after being returned by the driver
+    Dequeued,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            State::Free => write!(f, "free"),
+            State::Queued => write!(f, "queued"),
+            State::Dequeued => write!(f, "dequeued"),
+        }
+    }
+}
+
+/// Invalid buffer state transition
+///
+/// Streams report these errors wrapped in an `io::Error` of kind `InvalidInput` instead of
+/// passing the request on to the driver, which would usually answer with a bare EINVAL.
+/// Use `io::Error::get_ref()` and downcast to retrieve the details.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// There is no buffer at this index
+    InvalidIndex(usize),
+    /// The buffer at this index is already owned by the driver
+    AlreadyQueued(usize),
+    /// No buffer is owned by the driver, so there is nothing to dequeue
+    NothingQueued,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::InvalidIndex(index) => write!(f, "invalid buffer index: {}", index),
+            StateError::AlreadyQueued(index) => write!(f, "buffer {} is already queued", index),
+            StateError::NothingQueued => write!(f, "no buffers are queued"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl From<StateError> for io::Error {
+    fn from(err: StateError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queueable() {
+        let capture = Flags::queueable(Type::VideoCapture);
+        let output = Flags::queueable(Type::VideoOutput);
+
+        assert!(capture.contains(Flags::TIMECODE | Flags::REQUEST_FD | Flags::NO_CACHE_CLEAN));
+        assert!(!capture.intersects(Flags::KEYFRAME | Flags::PFRAME | Flags::BFRAME));
+        assert!(output.contains(Flags::KEYFRAME | Flags::PFRAME | Flags::BFRAME));
+
+        for flags in &[capture, output] {
+            assert!(!flags.intersects(Flags::TSTAMP_SRC_SOE | Flags::LAST));
+            assert!(!flags.intersects(Flags::QUEUED | Flags::DONE | Flags::ERROR));
+        }
+    }
 }
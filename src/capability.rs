@@ -74,8 +74,14 @@ pub struct Capabilities {
     /// Version number MAJOR.MINOR.PATCH
     pub version: (u8, u8, u8),
 
-    /// Capability flags
+    /// Capabilities of the opened device node
+    ///
+    /// Multi-function devices expose several nodes (e.g. video and metadata capture for UVC
+    /// cameras), use these flags to decide what the node itself supports. They equal
+    /// `physical_caps` for old drivers which do not report per-node capabilities.
     pub capabilities: Flags,
+    /// Capabilities of the physical device as a whole, across all of its device nodes
+    pub physical_caps: Flags,
 }
 
 impl From<v4l2_capability> for Capabilities {
@@ -94,7 +100,12 @@ impl From<v4l2_capability> for Capabilities {
                 .trim_matches(char::from(0))
                 .to_string(),
             version: (0, 0, 0),
-            capabilities: Flags::from(cap.device_caps),
+            capabilities: if cap.capabilities & Flags::DEVICE_CAPS.bits() != 0 {
+                Flags::from(cap.device_caps)
+            } else {
+                Flags::from(cap.capabilities)
+            },
+            physical_caps: Flags::from(cap.capabilities),
         };
 
         caps.version.0 = ((cap.version >> 16) & 0xff) as u8;
@@ -115,6 +126,7 @@ impl fmt::Display for Capabilities {
             self.version.0, self.version.1, self.version.2
         )?;
         writeln!(f, "Capabilites : {}", self.capabilities)?;
+        writeln!(f, "Physical    : {}", self.physical_caps)?;
         Ok(())
     }
 }
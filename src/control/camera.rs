@@ -0,0 +1,127 @@
+//! Camera control class along with the camera related controls of the user class
+
+menu_control! {
+    /// Automatic exposure mode
+    ExposureAuto = 0x009a0901 {
+        /// Automatic exposure time and iris aperture
+        Auto                        = 0,
+        /// Manual exposure time and iris aperture
+        Manual                      = 1,
+        /// Manual exposure time, automatic iris aperture
+        ShutterPriority             = 2,
+        /// Automatic exposure time, manual iris aperture
+        AperturePriority            = 3,
+    }
+}
+
+integer_control! {
+    /// Exposure time in units of 100 µs, only used with manual exposure
+    ExposureAbsolute(u32) = 0x009a0902
+}
+
+boolean_control! {
+    /// Enables continuous automatic focus
+    FocusAuto = 0x009a090c
+}
+
+menu_control! {
+    /// Power line frequency filter to avoid flicker
+    PowerLineFrequency = 0x00980918 {
+        Disabled                    = 0,
+        Hz50                        = 1,
+        Hz60                        = 2,
+        Auto                        = 3,
+    }
+}
+
+boolean_control! {
+    /// Enables automatic white balance
+    AutoWhiteBalance = 0x0098090c
+}
+
+menu_control! {
+    /// White balance preset, or automatic and manual white balance
+    WhiteBalancePreset = 0x009a0914 {
+        Manual                      = 0,
+        Auto                        = 1,
+        Incandescent                = 2,
+        Fluorescent                 = 3,
+        FluorescentH                = 4,
+        Horizon                     = 5,
+        Daylight                    = 6,
+        Flash                       = 7,
+        Cloudy                      = 8,
+        Shade                       = 9,
+    }
+}
+
+menu_control! {
+    /// Automatic ISO sensitivity mode
+    IsoSensitivityAuto = 0x009a0918 {
+        Manual                      = 0,
+        Auto                        = 1,
+    }
+}
+
+menu_control! {
+    /// Area of the frame used to determine the exposure
+    ExposureMetering = 0x009a0919 {
+        Average                     = 0,
+        CenterWeighted              = 1,
+        Spot                        = 2,
+        Matrix                      = 3,
+    }
+}
+
+menu_control! {
+    /// Scene mode, adjusts several camera controls at once
+    SceneMode = 0x009a091a {
+        None                        = 0,
+        Backlight                   = 1,
+        BeachSnow                   = 2,
+        CandleLight                 = 3,
+        DawnDusk                    = 4,
+        FallColors                  = 5,
+        Fireworks                   = 6,
+        Landscape                   = 7,
+        Night                       = 8,
+        PartyIndoor                 = 9,
+        Portrait                    = 10,
+        Sports                      = 11,
+        Sunset                      = 12,
+        Text                        = 13,
+    }
+}
+
+menu_control! {
+    /// Distance range for automatic focus
+    AutoFocusRange = 0x009a091f {
+        Auto                        = 0,
+        Normal                      = 1,
+        Macro                       = 2,
+        Infinity                    = 3,
+    }
+}
+
+menu_control! {
+    /// Color effect
+    ColorFx = 0x0098091f {
+        None                        = 0,
+        Bw                          = 1,
+        Sepia                       = 2,
+        Negative                    = 3,
+        Emboss                      = 4,
+        Sketch                      = 5,
+        SkyBlue                     = 6,
+        GrassGreen                  = 7,
+        SkinWhiten                  = 8,
+        Vivid                       = 9,
+        Aqua                        = 10,
+        ArtFreeze                   = 11,
+        Silhouette                  = 12,
+        Solarization                = 13,
+        Antique                     = 14,
+        SetCbCr                     = 15,
+        SetRgb                      = 16,
+    }
+}
@@ -0,0 +1,193 @@
+//! Codec (MPEG) control class, mostly used by hardware encoders and decoders
+//!
+//! The menu controls are generated from the bindings at build time, one enum per menu of the
+//! class. Enable the "bindgen" feature to pick up menus which were added by newer kernels.
+
+integer_control! {
+    /// Average bitrate in bits per second
+    Bitrate(u32) = 0x009909cf
+}
+
+integer_control! {
+    /// Peak bitrate in bits per second, only used in variable bitrate mode
+    BitratePeak(u32) = 0x009909d0
+}
+
+integer_control! {
+    /// Distance between two keyframes (I-frames)
+    GopSize(u32) = 0x009909cb
+}
+
+button_control! {
+    /// Makes the encoder produce a keyframe (I-frame) as soon as possible
+    ForceKeyFrame = 0x009909e5
+}
+
+menu_control! {
+    /// Bitrate control mode
+    BitrateMode = 0x009909ce {
+        /// Variable bitrate
+        Vbr                         = 0,
+        /// Constant bitrate
+        Cbr                         = 1,
+        /// Constant quality
+        Cq                          = 2,
+    }
+}
+
+menu_control! {
+    /// H.264 profile
+    H264Profile = 0x00990a6b {
+        Baseline                    = 0,
+        ConstrainedBaseline         = 1,
+        Main                        = 2,
+        Extended                    = 3,
+        High                        = 4,
+        High10                      = 5,
+        High422                     = 6,
+        High444Predictive           = 7,
+        High10Intra                 = 8,
+        High422Intra                = 9,
+        High444Intra                = 10,
+        Cavlc444Intra               = 11,
+        ScalableBaseline            = 12,
+        ScalableHigh                = 13,
+        ScalableHighIntra           = 14,
+        StereoHigh                  = 15,
+        MultiviewHigh               = 16,
+        ConstrainedHigh             = 17,
+    }
+}
+
+menu_control! {
+    /// H.264 level
+    H264Level = 0x00990a67 {
+        L1_0                        = 0,
+        L1B                         = 1,
+        L1_1                        = 2,
+        L1_2                        = 3,
+        L1_3                        = 4,
+        L2_0                        = 5,
+        L2_1                        = 6,
+        L2_2                        = 7,
+        L3_0                        = 8,
+        L3_1                        = 9,
+        L3_2                        = 10,
+        L4_0                        = 11,
+        L4_1                        = 12,
+        L4_2                        = 13,
+        L5_0                        = 14,
+        L5_1                        = 15,
+        L5_2                        = 16,
+        L6_0                        = 17,
+        L6_1                        = 18,
+        L6_2                        = 19,
+    }
+}
+
+// The menu controls of the codec class are generated from the enums in the bindings by build.rs
+include!(concat!(env!("OUT_DIR"), "/codec_menus.rs"));
+
+#[cfg(all(test, feature = "v4l2-sys"))]
+mod tests {
+    use super::*;
+    use crate::control::TypedControl;
+    use crate::v4l_sys::*;
+
+    macro_rules! assert_variants {
+        ($menu:ident { $($variant:ident = $value:ident,)* }) => {
+            $(assert_eq!($menu::$variant as i64, $value as i64);)*
+        };
+    }
+
+    #[test]
+    fn variants_match_bindings() {
+        assert_variants!(BitrateMode {
+            Vbr = v4l2_mpeg_video_bitrate_mode_V4L2_MPEG_VIDEO_BITRATE_MODE_VBR,
+            Cbr = v4l2_mpeg_video_bitrate_mode_V4L2_MPEG_VIDEO_BITRATE_MODE_CBR,
+            Cq = v4l2_mpeg_video_bitrate_mode_V4L2_MPEG_VIDEO_BITRATE_MODE_CQ,
+        });
+        assert_variants!(H264Profile {
+            Baseline = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_BASELINE,
+            ConstrainedBaseline = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_CONSTRAINED_BASELINE,
+            Main = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_MAIN,
+            Extended = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_EXTENDED,
+            High = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH,
+            High10 = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_10,
+            High422 = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_422,
+            High444Predictive = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_444_PREDICTIVE,
+            High10Intra = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_10_INTRA,
+            High422Intra = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_422_INTRA,
+            High444Intra = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_HIGH_444_INTRA,
+            Cavlc444Intra = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_CAVLC_444_INTRA,
+            ScalableBaseline = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_SCALABLE_BASELINE,
+            ScalableHigh = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_SCALABLE_HIGH,
+            ScalableHighIntra = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_SCALABLE_HIGH_INTRA,
+            StereoHigh = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_STEREO_HIGH,
+            MultiviewHigh = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_MULTIVIEW_HIGH,
+            ConstrainedHigh = v4l2_mpeg_video_h264_profile_V4L2_MPEG_VIDEO_H264_PROFILE_CONSTRAINED_HIGH,
+        });
+        assert_variants!(H264Level {
+            L1_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_1_0,
+            L1B = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_1B,
+            L1_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_1_1,
+            L1_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_1_2,
+            L1_3 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_1_3,
+            L2_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_2_0,
+            L2_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_2_1,
+            L2_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_2_2,
+            L3_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_3_0,
+            L3_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_3_1,
+            L3_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_3_2,
+            L4_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_4_0,
+            L4_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_4_1,
+            L4_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_4_2,
+            L5_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_5_0,
+            L5_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_5_1,
+            L5_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_5_2,
+            L6_0 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_6_0,
+            L6_1 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_6_1,
+            L6_2 = v4l2_mpeg_video_h264_level_V4L2_MPEG_VIDEO_H264_LEVEL_6_2,
+        });
+    }
+
+    #[test]
+    fn ids_match_bindings() {
+        assert_eq!(Bitrate::ID, V4L2_CID_MPEG_VIDEO_BITRATE);
+        assert_eq!(BitratePeak::ID, V4L2_CID_MPEG_VIDEO_BITRATE_PEAK);
+        assert_eq!(GopSize::ID, V4L2_CID_MPEG_VIDEO_GOP_SIZE);
+        assert_eq!(ForceKeyFrame::ID, V4L2_CID_MPEG_VIDEO_FORCE_KEY_FRAME);
+        assert_eq!(BitrateMode::ID, V4L2_CID_MPEG_VIDEO_BITRATE_MODE);
+        assert_eq!(H264Profile::ID, V4L2_CID_MPEG_VIDEO_H264_PROFILE);
+        assert_eq!(H264Level::ID, V4L2_CID_MPEG_VIDEO_H264_LEVEL);
+    }
+
+    #[test]
+    fn generated_menus() {
+        assert_eq!(H264EntropyMode::ID, V4L2_CID_MPEG_VIDEO_H264_ENTROPY_MODE);
+        assert_eq!(
+            H264EntropyMode::Cabac as i64,
+            v4l2_mpeg_video_h264_entropy_mode_V4L2_MPEG_VIDEO_H264_ENTROPY_MODE_CABAC as i64
+        );
+        assert_eq!(MultiSliceMode::MaxBytes as i32, 2);
+
+        // menus whose enum is not named after the control
+        assert_eq!(VpxNumPartitions::ID, V4L2_CID_MPEG_VIDEO_VPX_NUM_PARTITIONS);
+        assert_eq!(VpxNumPartitions::Vpx8Partitions as i32, 3);
+        assert_eq!(
+            H264FmoChangeDirection::ID,
+            V4L2_CID_MPEG_VIDEO_H264_FMO_CHANGE_DIRECTION
+        );
+
+        // enumerators starting with a digit keep the last word of their prefix
+        assert_eq!(Vp9Level::Level6_2 as i32, 13);
+        assert_eq!(H264VuiSarIdc::Idc1x1 as i32, 1);
+        assert_eq!(HevcSizeOfLengthField::Size4 as i32, 3);
+        assert_eq!(AudioL2Bitrate::Bitrate384K as i32, 13);
+
+        assert_eq!(
+            HevcLevel::from_control(HevcLevel::Level5_1.into_control()),
+            Some(HevcLevel::Level5_1)
+        );
+    }
+}
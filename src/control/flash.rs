@@ -0,0 +1,124 @@
+//! Flash control class, used by camera flash and torch LEDs
+
+use bitflags::bitflags;
+use std::fmt;
+
+use crate::control::{Control, TypedControl};
+
+menu_control! {
+    /// Operating mode of the flash LED
+    LedMode = 0x009c0901 {
+        /// LED is off
+        None                        = 0,
+        /// LED is used as flash and fires when strobed
+        Flash                       = 1,
+        /// LED is continuously on
+        Torch                       = 2,
+    }
+}
+
+menu_control! {
+    /// Source of the flash strobe
+    StrobeSource = 0x009c0902 {
+        /// Strobe is triggered through the Strobe control
+        Software                    = 0,
+        /// Strobe is triggered by an external source, usually the sensor
+        External                    = 1,
+    }
+}
+
+button_control! {
+    /// Fires the flash, only works in flash mode with a software strobe source
+    Strobe = 0x009c0903
+}
+
+button_control! {
+    /// Stops a flash strobe immediately
+    StrobeStop = 0x009c0904
+}
+
+boolean_control! {
+    /// Whether the flash is currently strobing, read-only
+    StrobeStatus = 0x009c0905
+}
+
+integer_control! {
+    /// Hardware timeout of the flash strobe in microseconds
+    Timeout(u32) = 0x009c0906
+}
+
+integer_control! {
+    /// Intensity of the flash strobe in mA
+    Intensity(u32) = 0x009c0907
+}
+
+integer_control! {
+    /// Intensity of the LED in torch mode in mA
+    TorchIntensity(u32) = 0x009c0908
+}
+
+integer_control! {
+    /// Intensity of the indicator LED in µA
+    IndicatorIntensity(u32) = 0x009c0909
+}
+
+boolean_control! {
+    /// Enables charging of the flash capacitor
+    Charge = 0x009c090b
+}
+
+boolean_control! {
+    /// Whether the flash is ready to strobe, read-only
+    Ready = 0x009c090c
+}
+
+bitflags! {
+    /// Faults reported by the flash hardware, read-only
+    ///
+    /// Reading the faults clears them on most hardware.
+    #[allow(clippy::unreadable_literal)]
+    pub struct Faults: u32 {
+        const OVER_VOLTAGE          = 0x00000001;
+        const TIMEOUT               = 0x00000002;
+        const OVER_TEMPERATURE      = 0x00000004;
+        const SHORT_CIRCUIT         = 0x00000008;
+        const OVER_CURRENT          = 0x00000010;
+        const INDICATOR             = 0x00000020;
+        const UNDER_VOLTAGE         = 0x00000040;
+        const INPUT_VOLTAGE         = 0x00000080;
+        const LED_OVER_TEMPERATURE  = 0x00000100;
+    }
+}
+
+impl From<u32> for Faults {
+    fn from(flags: u32) -> Self {
+        Faults::from_bits_truncate(flags)
+    }
+}
+
+impl From<Faults> for u32 {
+    fn from(faults: Faults) -> Self {
+        faults.bits()
+    }
+}
+
+impl fmt::Display for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl TypedControl for Faults {
+    const ID: u32 = 0x009c090a;
+
+    fn from_control(ctrl: Control) -> Option<Self> {
+        match ctrl {
+            Control::Value(val) => Some(Faults::from(val as u32)),
+            _ => None,
+        }
+    }
+
+    fn into_control(self) -> Control {
+        Control::Value(self.bits() as i32)
+    }
+}
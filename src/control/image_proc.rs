@@ -0,0 +1,27 @@
+//! Image processing control class, mostly used to configure raw sensor subdevices
+
+integer_control! {
+    /// Index of the CSI-2 link frequency to use
+    ///
+    /// This is an integer menu control, the actual frequencies in Hz are reported as the menu
+    /// items of the control description.
+    LinkFreq(u32) = 0x009f0901
+}
+
+integer_control! {
+    /// Pixel rate in the source in pixels per second, usually read-only
+    PixelRate(i64) = 0x009f0902
+}
+
+integer_control! {
+    /// Index of the test pattern to generate, 0 usually disables the pattern
+    ///
+    /// The available patterns are driver specific and reported as the menu items of the
+    /// control description.
+    TestPattern(u32) = 0x009f0903
+}
+
+integer_control! {
+    /// Digital gain applied to all color components, the driver defines the unit
+    DigitalGain(u32) = 0x009f0905
+}
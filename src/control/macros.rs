@@ -0,0 +1,164 @@
+macro_rules! menu_control {
+    (
+        $(#[$outer:meta])*
+        $name:ident = $id:literal {
+            $(
+                $(#[$inner:meta])*
+                $variant:ident = $value:expr,
+            )*
+        }
+    ) => {
+        $(#[$outer])*
+        #[rustfmt::skip]
+        #[repr(i32)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub enum $name {
+            $(
+                $(#[$inner])*
+                $variant = $value,
+            )*
+        }
+
+        impl $crate::control::TypedControl for $name {
+            const ID: u32 = $id;
+
+            fn from_control(ctrl: $crate::control::Control) -> Option<Self> {
+                match ctrl {
+                    $(
+                        $crate::control::Control::Value(val) if val == $value => {
+                            Some($name::$variant)
+                        }
+                    )*
+                    _ => None,
+                }
+            }
+
+            fn into_control(self) -> $crate::control::Control {
+                $crate::control::Control::Value(self as i32)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                std::fmt::Debug::fmt(self, f)
+            }
+        }
+    };
+}
+
+macro_rules! integer_control {
+    (
+        $(#[$outer:meta])*
+        $name:ident(i64) = $id:literal
+    ) => {
+        integer_control! {
+            @impl $(#[$outer])* $name(i64) = $id, $crate::control::Type::Integer64
+        }
+    };
+    (
+        $(#[$outer:meta])*
+        $name:ident($typ:ty) = $id:literal
+    ) => {
+        integer_control! {
+            @impl $(#[$outer])* $name($typ) = $id, $crate::control::Type::Integer
+        }
+    };
+    (
+        @impl $(#[$outer:meta])*
+        $name:ident($typ:ty) = $id:literal, $ctype:expr
+    ) => {
+        $(#[$outer])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct $name(pub $typ);
+
+        impl $crate::control::TypedControl for $name {
+            const ID: u32 = $id;
+            const TYPE: $crate::control::Type = $ctype;
+
+            fn from_control(ctrl: $crate::control::Control) -> Option<Self> {
+                match ctrl {
+                    $crate::control::Control::Value(val) => {
+                        std::convert::TryFrom::try_from(val).ok().map($name)
+                    }
+                    $crate::control::Control::Value64(val) => {
+                        std::convert::TryFrom::try_from(val).ok().map($name)
+                    }
+                    _ => None,
+                }
+            }
+
+            fn into_control(self) -> $crate::control::Control {
+                // 64-bit controls must always be written through the extended control API
+                if $ctype == $crate::control::Type::Integer64 {
+                    return $crate::control::Control::Value64(self.0 as i64);
+                }
+
+                match std::convert::TryFrom::try_from(self.0) {
+                    Ok(val) => $crate::control::Control::Value(val),
+                    Err(_) => $crate::control::Control::Value64(self.0 as i64),
+                }
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+macro_rules! button_control {
+    (
+        $(#[$outer:meta])*
+        $name:ident = $id:literal
+    ) => {
+        $(#[$outer])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct $name;
+
+        impl $crate::control::TypedControl for $name {
+            const ID: u32 = $id;
+
+            fn from_control(_: $crate::control::Control) -> Option<Self> {
+                Some($name)
+            }
+
+            fn into_control(self) -> $crate::control::Control {
+                $crate::control::Control::Value(0)
+            }
+        }
+    };
+}
+
+macro_rules! boolean_control {
+    (
+        $(#[$outer:meta])*
+        $name:ident = $id:literal
+    ) => {
+        $(#[$outer])*
+        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+        pub struct $name(pub bool);
+
+        impl $crate::control::TypedControl for $name {
+            const ID: u32 = $id;
+
+            fn from_control(ctrl: $crate::control::Control) -> Option<Self> {
+                match ctrl {
+                    $crate::control::Control::Value(val) => Some($name(val != 0)),
+                    _ => None,
+                }
+            }
+
+            fn into_control(self) -> $crate::control::Control {
+                $crate::control::Control::Value(self.0 as i32)
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
@@ -0,0 +1,588 @@
+use bitflags::bitflags;
+use std::convert::{TryFrom, TryInto};
+use std::{fmt, io, mem, str};
+
+use crate::v4l_sys::*;
+
+#[macro_use]
+mod macros;
+
+pub mod camera;
+pub mod codec;
+pub mod flash;
+pub mod image_proc;
+
+/// Control data type
+#[allow(clippy::unreadable_literal)]
+#[rustfmt::skip]
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Type {
+    Integer         = 1,
+    Boolean         = 2,
+    Menu            = 3,
+    Button          = 4,
+    Integer64       = 5,
+    CtrlClass       = 6,
+    String          = 7,
+    Bitmask         = 8,
+    IntegerMenu     = 9,
+
+    /* Compound types are >= 0x0100 */
+    CompoundTypes   = 0x0100,
+    U8              = 0x1000,
+    U16             = 0x0101,
+    U32             = 0x0102,
+    Area            = 0x0106,
+}
+
+impl TryFrom<u32> for Type {
+    type Error = ();
+
+    fn try_from(repr: u32) -> Result<Self, Self::Error> {
+        match repr {
+            1 => Ok(Type::Integer),
+            2 => Ok(Type::Boolean),
+            3 => Ok(Type::Menu),
+            4 => Ok(Type::Button),
+            5 => Ok(Type::Integer64),
+            6 => Ok(Type::CtrlClass),
+            7 => Ok(Type::String),
+            8 => Ok(Type::Bitmask),
+            9 => Ok(Type::IntegerMenu),
+
+            0x0100 => Ok(Type::U8),
+            0x0101 => Ok(Type::U16),
+            0x0102 => Ok(Type::U32),
+            0x0106 => Ok(Type::Area),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Into<u32> for Type {
+    fn into(self) -> u32 {
+        self as u32
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Control class, the upper bits of a control ID
+#[allow(clippy::unreadable_literal)]
+#[rustfmt::skip]
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Class {
+    User            = 0x00980000,
+    Codec           = 0x00990000,
+    Camera          = 0x009a0000,
+    FmTx            = 0x009b0000,
+    Flash           = 0x009c0000,
+    Jpeg            = 0x009d0000,
+    ImageSource     = 0x009e0000,
+    ImageProc       = 0x009f0000,
+    Dv              = 0x00a00000,
+    FmRx            = 0x00a10000,
+    RfTuner         = 0x00a20000,
+    Detect          = 0x00a30000,
+    CodecStateless  = 0x00a40000,
+    Colorimetry     = 0x00a50000,
+}
+
+impl Class {
+    /// Returns the class a control belongs to
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - Control identifier
+    pub fn of(id: u32) -> Option<Self> {
+        Class::try_from(id & 0x0fff0000).ok()
+    }
+}
+
+impl TryFrom<u32> for Class {
+    type Error = ();
+
+    fn try_from(repr: u32) -> Result<Self, Self::Error> {
+        match repr {
+            0x00980000 => Ok(Class::User),
+            0x00990000 => Ok(Class::Codec),
+            0x009a0000 => Ok(Class::Camera),
+            0x009b0000 => Ok(Class::FmTx),
+            0x009c0000 => Ok(Class::Flash),
+            0x009d0000 => Ok(Class::Jpeg),
+            0x009e0000 => Ok(Class::ImageSource),
+            0x009f0000 => Ok(Class::ImageProc),
+            0x00a00000 => Ok(Class::Dv),
+            0x00a10000 => Ok(Class::FmRx),
+            0x00a20000 => Ok(Class::RfTuner),
+            0x00a30000 => Ok(Class::Detect),
+            0x00a40000 => Ok(Class::CodecStateless),
+            0x00a50000 => Ok(Class::Colorimetry),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+bitflags! {
+    #[allow(clippy::unreadable_literal)]
+    pub struct Flags: u32 {
+        const DISABLED              = 0x0001;
+        const GRABBED               = 0x0002;
+        const READ_ONLY             = 0x0004;
+        const UPDATE                = 0x0008;
+        const INACTIVE              = 0x0010;
+        const SLIDER                = 0x0020;
+        const WRITE_ONLY            = 0x0040;
+        const VOLATILE              = 0x0080;
+        const HAS_PAYLOAD           = 0x0100;
+        const EXECUTE_ON_WRITE      = 0x0200;
+        const MODIFY_LAYOUT         = 0x0400;
+
+        const NEXT_CTRL             = 0x80000000;
+        const NEXT_COMPOUND         = 0x40000000;
+    }
+}
+
+impl From<u32> for Flags {
+    fn from(flags: u32) -> Self {
+        Flags::from_bits_truncate(flags)
+    }
+}
+
+impl Into<u32> for Flags {
+    fn into(self) -> u32 {
+        self.bits()
+    }
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Debug)]
+/// Device control menu item
+pub enum MenuItem {
+    Name(String),
+    Value(i64),
+}
+
+impl fmt::Display for MenuItem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MenuItem::Name(name) => {
+                write!(f, "{}", name)?;
+            }
+            MenuItem::Value(value) => {
+                write!(f, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<(Type, v4l2_querymenu)> for MenuItem {
+    type Error = ();
+
+    fn try_from(item: (Type, v4l2_querymenu)) -> Result<Self, Self::Error> {
+        unsafe {
+            match item.0 {
+                Type::Menu => Ok(MenuItem::Name(
+                    str::from_utf8(&item.1.__bindgen_anon_1.name)
+                        .unwrap()
+                        .trim_matches(char::from(0))
+                        .to_string(),
+                )),
+                Type::IntegerMenu => Ok(MenuItem::Value(item.1.__bindgen_anon_1.value)),
+                _ => Err(()),
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Device control description
+pub struct Description {
+    /// Control identifier, set by the the application
+    pub id: u32,
+    /// Type of control
+    pub typ: Type,
+    /// Name of the control, intended for the user
+    pub name: String,
+    /// Minimum value, inclusive
+    pub minimum: i32,
+    /// Maximum value, inclusive
+    pub maximum: i32,
+    /// Step size, always positive
+    pub step: i32,
+    /// Default value
+    pub default: i32,
+    /// Control flags
+    pub flags: Flags,
+
+    /// Items for menu controls (only valid if typ is a menu type)
+    pub items: Option<Vec<(u32, MenuItem)>>,
+}
+
+impl From<v4l2_queryctrl> for Description {
+    fn from(ctrl: v4l2_queryctrl) -> Self {
+        Description {
+            id: ctrl.id,
+            typ: Type::try_from(ctrl.type_).unwrap(),
+            name: str::from_utf8(&ctrl.name)
+                .unwrap()
+                .trim_matches(char::from(0))
+                .to_string(),
+            minimum: ctrl.minimum,
+            maximum: ctrl.maximum,
+            step: ctrl.step,
+            default: ctrl.default_value,
+            flags: Flags::from(ctrl.flags),
+            items: None,
+        }
+    }
+}
+
+impl Description {
+    /// Checks whether a value can be written to this control
+    ///
+    /// Drivers usually reject invalid values with a bare EINVAL, so this can be used to find out
+    /// what is actually wrong with a value before handing it to the driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to check
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     if let Ok(controls) = dev.query_controls() {
+    ///         for ctrl in controls {
+    ///             if let Err(e) = ctrl.validate(ctrl.default) {
+    ///                 println!("{}: {}", ctrl.name, e);
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn validate(&self, value: i32) -> Result<(), ValidationError> {
+        if self.flags.contains(Flags::READ_ONLY) {
+            return Err(ValidationError::ReadOnly);
+        }
+        if self.flags.contains(Flags::DISABLED) {
+            return Err(ValidationError::Disabled);
+        }
+
+        match self.typ {
+            Type::Button => Ok(()),
+            Type::Bitmask => {
+                if value as u32 & !(self.maximum as u32) != 0 {
+                    Err(ValidationError::InvalidBits {
+                        value: value as u32,
+                        mask: self.maximum as u32,
+                    })
+                } else {
+                    Ok(())
+                }
+            }
+            Type::Integer | Type::Boolean | Type::Menu | Type::IntegerMenu => {
+                if value < self.minimum || value > self.maximum {
+                    return Err(ValidationError::OutOfRange {
+                        value,
+                        minimum: self.minimum,
+                        maximum: self.maximum,
+                    });
+                }
+
+                match &self.items {
+                    Some(items) => {
+                        if !items.iter().any(|(index, _)| *index as i32 == value) {
+                            return Err(ValidationError::InvalidMenuItem(value));
+                        }
+                    }
+                    None => {
+                        // the difference may not fit into 32 bits, compute it like clamp() does
+                        let offset = i64::from(value) - i64::from(self.minimum);
+                        if self.step > 1 && offset % i64::from(self.step) != 0 {
+                            return Err(ValidationError::InvalidStep {
+                                value,
+                                minimum: self.minimum,
+                                step: self.step,
+                            });
+                        }
+                    }
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the valid value which is closest to the one given
+    ///
+    /// The value is clamped to the range of the control and rounded to the nearest step. For
+    /// menu controls with known items, the closest supported item is picked.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - Value to clamp
+    pub fn clamp(&self, value: i32) -> i32 {
+        match self.typ {
+            Type::Bitmask => return value & self.maximum,
+            Type::Integer | Type::Boolean | Type::Menu | Type::IntegerMenu => {}
+            _ => return value,
+        }
+
+        if let Some(items) = &self.items {
+            if let Some((index, _)) = items
+                .iter()
+                .min_by_key(|(index, _)| (i64::from(*index) - i64::from(value)).abs())
+            {
+                return *index as i32;
+            }
+        }
+
+        let value = value.max(self.minimum).min(self.maximum);
+        if self.step <= 1 {
+            return value;
+        }
+
+        // round to the nearest step, without leaving the valid range
+        let offset = i64::from(value) - i64::from(self.minimum);
+        let step = i64::from(self.step);
+        let mut steps = (offset + step / 2) / step;
+        if i64::from(self.minimum) + steps * step > i64::from(self.maximum) {
+            steps -= 1;
+        }
+        (i64::from(self.minimum) + steps * step) as i32
+    }
+}
+
+impl fmt::Display for Description {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "ID         : {}", self.id)?;
+        writeln!(f, "Type       : {}", self.typ)?;
+        writeln!(f, "Name       : {}", self.name)?;
+        writeln!(f, "Minimum    : {}", self.minimum)?;
+        writeln!(f, "Maximum    : {}", self.maximum)?;
+        writeln!(f, "Step       : {}", self.step)?;
+        writeln!(f, "Default    : {}", self.default)?;
+        writeln!(f, "Flags      : {}", self.flags)?;
+        if let Some(items) = &self.items {
+            writeln!(f, "Menu ==>")?;
+            for item in items {
+                writeln!(f, " * {}", item.1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// Reason why a value cannot be written to a control
+pub enum ValidationError {
+    /// The control cannot be written at all
+    ReadOnly,
+    /// The control is disabled by the driver
+    Disabled,
+    /// The value is outside of the valid range
+    OutOfRange {
+        value: i32,
+        minimum: i32,
+        maximum: i32,
+    },
+    /// The value is not a multiple of the step size, starting from the minimum
+    InvalidStep { value: i32, minimum: i32, step: i32 },
+    /// The value is not one of the menu items supported by the driver
+    InvalidMenuItem(i32),
+    /// The value contains bits which are not part of the mask
+    InvalidBits { value: u32, mask: u32 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::ReadOnly => write!(f, "control is read-only"),
+            ValidationError::Disabled => write!(f, "control is disabled"),
+            ValidationError::OutOfRange {
+                value,
+                minimum,
+                maximum,
+            } => write!(
+                f,
+                "value {} is out of range [{}, {}]",
+                value, minimum, maximum
+            ),
+            ValidationError::InvalidStep {
+                value,
+                minimum,
+                step,
+            } => write!(
+                f,
+                "value {} does not match step size {} starting at {}",
+                value, step, minimum
+            ),
+            ValidationError::InvalidMenuItem(value) => {
+                write!(f, "value {} is not a valid menu item", value)
+            }
+            ValidationError::InvalidBits { value, mask } => write!(
+                f,
+                "value {:#x} contains bits outside of mask {:#x}",
+                value, mask
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl From<ValidationError> for io::Error {
+    fn from(err: ValidationError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Device control value
+pub enum Control {
+    /* single values */
+    Value(i32),
+    Value64(i64),
+    String(String),
+    /* compound values, e.g. stateless codec parameters */
+    Compound(Vec<u8>),
+}
+
+/// Values addressed by an extended control call
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Which {
+    /// Current values of the device
+    Current,
+    /// Default values, read-only
+    Default,
+    /// Values bound to a media request, applied when the request is processed
+    ///
+    /// Contains the file descriptor of the request as returned by MEDIA_IOC_REQUEST_ALLOC.
+    Request(std::os::raw::c_int),
+}
+
+impl Which {
+    /// Returns the raw which value and the request file descriptor
+    pub(crate) fn as_raw(&self) -> (u32, i32) {
+        match *self {
+            Which::Current => (V4L2_CTRL_WHICH_CUR_VAL, 0),
+            Which::Default => (V4L2_CTRL_WHICH_DEF_VAL, 0),
+            Which::Request(fd) => (V4L2_CTRL_WHICH_REQUEST_VAL, fd),
+        }
+    }
+}
+
+impl From<v4l2_control> for Control {
+    fn from(ctrl: v4l2_control) -> Self {
+        Control::Value(ctrl.value)
+    }
+}
+
+impl TryInto<v4l2_control> for Control {
+    type Error = ();
+
+    fn try_into(self) -> Result<v4l2_control, Self::Error> {
+        unsafe {
+            let mut ctrl: v4l2_control = mem::zeroed();
+            match self {
+                Control::Value(val) => {
+                    ctrl.value = val;
+                    Ok(ctrl)
+                }
+                _ => Err(()),
+            }
+        }
+    }
+}
+
+/// Control with a strongly typed value
+///
+/// Typed controls can be accessed through `Device::typed_control` and
+/// `Device::set_typed_control`, so there is no need to deal with raw IDs and magic integers.
+pub trait TypedControl: Sized {
+    /// Control identifier
+    const ID: u32;
+
+    /// Data type of the control, 64-bit controls are accessed through the extended control API
+    const TYPE: Type = Type::Integer;
+
+    /// Converts a raw control value, returns None if the value is invalid for this control
+    fn from_control(ctrl: Control) -> Option<Self>;
+
+    /// Converts into a raw control value
+    fn into_control(self) -> Control;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn integer(minimum: i32, maximum: i32, step: i32) -> Description {
+        Description {
+            id: 0,
+            typ: Type::Integer,
+            name: String::new(),
+            minimum,
+            maximum,
+            step,
+            default: minimum,
+            flags: Flags::empty(),
+            items: None,
+        }
+    }
+
+    #[test]
+    fn validate_full_range() {
+        let desc = integer(i32::MIN, i32::MAX, 2);
+        assert_eq!(desc.validate(i32::MIN), Ok(()));
+        assert_eq!(desc.validate(i32::MAX - 1), Ok(()));
+        assert_eq!(desc.validate(0), Ok(()));
+        assert_eq!(
+            desc.validate(i32::MAX),
+            Err(ValidationError::InvalidStep {
+                value: i32::MAX,
+                minimum: i32::MIN,
+                step: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_step() {
+        let desc = integer(-10, 10, 5);
+        assert_eq!(desc.validate(-5), Ok(()));
+        assert_eq!(desc.validate(10), Ok(()));
+        assert!(desc.validate(3).is_err());
+        assert!(desc.validate(11).is_err());
+    }
+
+    #[test]
+    fn clamp_full_range() {
+        let desc = integer(i32::MIN, i32::MAX, 2);
+        assert_eq!(desc.clamp(i32::MAX), i32::MAX - 1);
+        assert_eq!(desc.clamp(i32::MIN), i32::MIN);
+        assert_eq!(desc.clamp(1), 2);
+    }
+}
@@ -0,0 +1,149 @@
+use std::io;
+
+use crate::convert::Converter;
+use crate::format::{Format, FourCC};
+
+/// Arrangement of the color filters in a 2x2 block of 8-bit raw bayer data
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// BA81
+    Bggr,
+    /// GBRG
+    Gbrg,
+    /// GRBG
+    Grbg,
+    /// RGGB
+    Rggb,
+}
+
+impl Pattern {
+    /// All known patterns
+    pub const ALL: [Pattern; 4] = [Pattern::Bggr, Pattern::Gbrg, Pattern::Grbg, Pattern::Rggb];
+
+    /// Returns the pixelformat of the pattern
+    pub fn fourcc(&self) -> FourCC {
+        match self {
+            Pattern::Bggr => FourCC::new(b"BA81"),
+            Pattern::Gbrg => FourCC::new(b"GBRG"),
+            Pattern::Grbg => FourCC::new(b"GRBG"),
+            Pattern::Rggb => FourCC::new(b"RGGB"),
+        }
+    }
+
+    /// Returns the offsets of the red and blue samples within a 2x2 block as (x, y)
+    fn offsets(&self) -> ((usize, usize), (usize, usize)) {
+        match self {
+            Pattern::Bggr => ((1, 1), (0, 0)),
+            Pattern::Gbrg => ((0, 1), (1, 0)),
+            Pattern::Grbg => ((1, 0), (0, 1)),
+            Pattern::Rggb => ((0, 0), (1, 1)),
+        }
+    }
+}
+
+/// Converts 8-bit raw bayer data to RGB3
+///
+/// Each 2x2 block is demosaiced by nearest neighbour interpolation, i.e. all four pixels share
+/// the red and blue sample of the block and the average of its green samples. This is fast but
+/// halves the effective resolution; register a better converter if quality matters.
+#[derive(Debug, Copy, Clone)]
+pub struct Debayer {
+    pattern: Pattern,
+}
+
+impl Debayer {
+    /// Returns a converter for a given pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Arrangement of the color filters
+    pub fn new(pattern: Pattern) -> Self {
+        Debayer { pattern }
+    }
+}
+
+impl Converter for Debayer {
+    fn input(&self) -> FourCC {
+        self.pattern.fourcc()
+    }
+
+    fn output(&self) -> FourCC {
+        FourCC::new(b"RGB3")
+    }
+
+    fn convert(&self, src: &[u8], fmt: &Format, dest: &mut Vec<u8>) -> io::Result<()> {
+        let width = fmt.width as usize;
+        let height = fmt.height as usize;
+        let stride = (fmt.stride as usize).max(width);
+        let len = match height {
+            0 => 0,
+            height => stride * (height - 1) + width,
+        };
+        if src.len() < len || width < 2 || height < 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "invalid bayer frame: {} bytes for {}x{}",
+                    src.len(),
+                    width,
+                    height
+                ),
+            ));
+        }
+
+        let ((rx, ry), (bx, by)) = self.pattern.offsets();
+        let sample = |x: usize, y: usize| src[y * stride + x];
+
+        dest.clear();
+        dest.resize(width * height * 3, 0);
+        for y in 0..height {
+            // odd sizes reuse the last complete block
+            let y0 = (y & !1).min(height - 2);
+            for x in 0..width {
+                let x0 = (x & !1).min(width - 2);
+                let r = sample(x0 + rx, y0 + ry);
+                let b = sample(x0 + bx, y0 + by);
+                let g =
+                    (sample(x0 + 1 - rx, y0 + ry) as u16 + sample(x0 + rx, y0 + 1 - ry) as u16) / 2;
+
+                let offset = (y * width + x) * 3;
+                dest[offset..offset + 3].copy_from_slice(&[r, g as u8, b]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn debayer_2x2() {
+        // samples at (0, 0), (1, 0), (0, 1) and (1, 1)
+        let src = [10, 20, 30, 40];
+        for &(pattern, rgb) in &[
+            (Pattern::Bggr, [40, 25, 10]),
+            (Pattern::Gbrg, [30, 25, 20]),
+            (Pattern::Grbg, [20, 25, 30]),
+            (Pattern::Rggb, [10, 25, 40]),
+        ] {
+            let fmt = Format::new(2, 2, pattern.fourcc());
+            let mut dest = Vec::new();
+            Debayer::new(pattern)
+                .convert(&src, &fmt, &mut dest)
+                .unwrap();
+            assert_eq!(dest, rgb.repeat(4), "{:?}", pattern);
+        }
+    }
+
+    #[test]
+    fn reject_short_frame() {
+        let fmt = Format::new(2, 2, Pattern::Rggb.fourcc());
+        let mut dest = Vec::new();
+        assert!(Debayer::new(Pattern::Rggb)
+            .convert(&[0; 3], &fmt, &mut dest)
+            .is_err());
+    }
+}
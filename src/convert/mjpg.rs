@@ -0,0 +1,51 @@
+use std::io;
+
+use jpeg_decoder::{Decoder, PixelFormat};
+
+use crate::convert::Converter;
+use crate::format::{Format, FourCC};
+
+/// Decodes MJPG frames to RGB3
+///
+/// Only available with the `jpeg` feature.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct MjpgToRgb;
+
+impl Converter for MjpgToRgb {
+    fn input(&self) -> FourCC {
+        FourCC::new(b"MJPG")
+    }
+
+    fn output(&self) -> FourCC {
+        FourCC::new(b"RGB3")
+    }
+
+    fn convert(&self, src: &[u8], _fmt: &Format, dest: &mut Vec<u8>) -> io::Result<()> {
+        let mut decoder = Decoder::new(src);
+        let pixels = decoder
+            .decode()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing JPEG header"))?;
+
+        dest.clear();
+        match info.pixel_format {
+            PixelFormat::RGB24 => dest.extend_from_slice(&pixels),
+            PixelFormat::L8 => {
+                dest.reserve(pixels.len() * 3);
+                for luma in pixels {
+                    dest.extend_from_slice(&[luma, luma, luma]);
+                }
+            }
+            format => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported JPEG pixel format: {:?}", format),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
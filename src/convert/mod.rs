@@ -0,0 +1,321 @@
+//! Pluggable conversion of frames between pixel formats
+//!
+//! Many devices only offer formats such as YUYV, MJPG or raw bayer data, while most consumers
+//! expect RGB. Converters transform frames from one format into another and a `Registry` finds a
+//! chain of converters between the format of a stream and the desired output format.
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
+
+use crate::buffer::Metadata;
+use crate::format::{Format, FourCC};
+use crate::io::adapters::FrameSource;
+
+pub mod bayer;
+#[cfg(feature = "jpeg")]
+pub mod mjpg;
+pub mod yuyv;
+
+/// Conversion of frames from one pixel format into another
+pub trait Converter: Send + Sync {
+    /// Pixel format of the input frames
+    fn input(&self) -> FourCC;
+
+    /// Pixel format of the output frames
+    fn output(&self) -> FourCC;
+
+    /// Returns the format of the output frames for a given input format
+    ///
+    /// By default, the image size is retained and the output is tightly packed with the given
+    /// number of bytes per pixel.
+    fn output_format(&self, input: &Format) -> Format {
+        let bpp = self.bytes_per_pixel();
+        let mut fmt = Format::new(input.width, input.height, self.output());
+        fmt.stride = input.width * bpp;
+        fmt.size = fmt.stride * input.height;
+        fmt.colorspace = input.colorspace;
+        fmt
+    }
+
+    /// Bytes per pixel of packed output formats, used by the default `output_format`
+    fn bytes_per_pixel(&self) -> u32 {
+        3
+    }
+
+    /// Converts a single frame
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Input frame
+    /// * `fmt` - Format of the input frame
+    /// * `dest` - Output frame, its previous contents are replaced
+    fn convert(&self, src: &[u8], fmt: &Format, dest: &mut Vec<u8>) -> io::Result<()>;
+}
+
+/// Collection of converters to choose from
+#[derive(Clone, Default)]
+pub struct Registry {
+    converters: Vec<Arc<dyn Converter>>,
+}
+
+impl Registry {
+    /// Returns an empty registry
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    /// Returns a registry containing all converters shipped with the crate
+    ///
+    /// These convert YUYV, raw 8-bit bayer data and (with the `jpeg` feature) MJPG to RGB3.
+    pub fn with_builtins() -> Self {
+        let mut registry = Registry::new();
+        registry.register(yuyv::YuyvToRgb);
+        for pattern in &bayer::Pattern::ALL {
+            registry.register(bayer::Debayer::new(*pattern));
+        }
+        #[cfg(feature = "jpeg")]
+        registry.register(mjpg::MjpgToRgb);
+        registry
+    }
+
+    /// Adds a converter
+    ///
+    /// Converters registered later take precedence over earlier ones for the same conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `converter` - Converter to add
+    pub fn register<C: Converter + 'static>(&mut self, converter: C) {
+        self.converters.insert(0, Arc::new(converter));
+    }
+
+    /// Returns the shortest chain of converters from one format to another
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - Format of the input frames
+    /// * `output` - Desired pixel format
+    pub fn chain(&self, input: &Format, output: FourCC) -> io::Result<Chain> {
+        // breadth first search over the pixel formats
+        let mut visited = vec![input.fourcc];
+        let mut queue = VecDeque::new();
+        queue.push_back((input.fourcc, Vec::new()));
+
+        while let Some((fourcc, path)) = queue.pop_front() {
+            if fourcc == output {
+                return Ok(Chain::new(path, input));
+            }
+
+            for converter in &self.converters {
+                if converter.input() == fourcc && !visited.contains(&converter.output()) {
+                    visited.push(converter.output());
+                    let mut path = path.clone();
+                    path.push(Arc::clone(converter));
+                    queue.push_back((converter.output(), path));
+                }
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("no conversion from {} to {}", input.fourcc, output),
+        ))
+    }
+
+    /// Wraps a stream so it yields frames in the desired format
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Frame source, e.g. a capture stream
+    /// * `input` - Format of the frames yielded by the source
+    /// * `output` - Desired pixel format
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::buffer::Type;
+    /// use v4l::convert::Registry;
+    /// use v4l::io::adapters::FrameSource;
+    /// use v4l::prelude::*;
+    /// use v4l::video::Capture;
+    /// use v4l::FourCC;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    ///
+    /// let registry = Registry::with_builtins();
+    /// let mut stream = registry.convert(stream, &fmt, FourCC::new(b"RGB3")).unwrap();
+    /// println!("output format:\n{}", stream.format());
+    /// loop {
+    ///     let (rgb, meta) = stream.next_frame().unwrap();
+    /// }
+    /// ```
+    pub fn convert<S>(
+        &self,
+        source: S,
+        input: &Format,
+        output: FourCC,
+    ) -> io::Result<ConvertedStream<S>>
+    where
+        S: FrameSource<Item = [u8]>,
+    {
+        Ok(ConvertedStream {
+            source,
+            chain: self.chain(input, output)?,
+            meta: Metadata::default(),
+        })
+    }
+}
+
+/// Sequence of converters applied one after another
+pub struct Chain {
+    steps: Vec<(Arc<dyn Converter>, Format)>,
+    output: Format,
+    bufs: [Vec<u8>; 2],
+}
+
+impl Chain {
+    fn new(converters: Vec<Arc<dyn Converter>>, input: &Format) -> Self {
+        let mut fmt = *input;
+        let mut steps = Vec::new();
+        for converter in converters {
+            let next = converter.output_format(&fmt);
+            steps.push((converter, fmt));
+            fmt = next;
+        }
+
+        Chain {
+            steps,
+            output: fmt,
+            bufs: [Vec::new(), Vec::new()],
+        }
+    }
+
+    /// Returns the format of the output frames
+    pub fn format(&self) -> &Format {
+        &self.output
+    }
+
+    /// Converts a frame, returning the output
+    ///
+    /// Intermediate and output buffers are reused across calls. An empty chain returns the input
+    /// unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `src` - Input frame
+    pub fn convert<'a>(&'a mut self, src: &'a [u8]) -> io::Result<&'a [u8]> {
+        let mut current = 0;
+        for (i, (converter, fmt)) in self.steps.iter().enumerate() {
+            let (first, second) = self.bufs.split_at_mut(1);
+            let (input, output) = match (i, current) {
+                (0, _) => (src, &mut first[0]),
+                (_, 0) => (first[0].as_slice(), &mut second[0]),
+                _ => (second[0].as_slice(), &mut first[0]),
+            };
+            converter.convert(input, fmt, output)?;
+            current = if i == 0 { 0 } else { 1 - current };
+        }
+
+        Ok(match self.steps.len() {
+            0 => src,
+            _ => &self.bufs[current],
+        })
+    }
+}
+
+/// Stream adapter converting each frame, see `Registry::convert`
+pub struct ConvertedStream<S> {
+    source: S,
+    chain: Chain,
+    meta: Metadata,
+}
+
+impl<S> ConvertedStream<S> {
+    /// Returns the format of the converted frames
+    pub fn format(&self) -> &Format {
+        self.chain.format()
+    }
+
+    /// Returns the underlying source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S> FrameSource for ConvertedStream<S>
+where
+    S: FrameSource<Item = [u8]>,
+{
+    type Item = [u8];
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        let (buf, meta) = self.source.next_frame()?;
+        self.meta = *meta;
+        // empty buffers (e.g. the last one of a drained decoder) have nothing to convert
+        if meta.bytesused == 0 {
+            return Ok((&[], &self.meta));
+        }
+
+        let out = self.chain.convert(meta.payload(buf))?;
+        self.meta.bytesused = out.len() as u32;
+        Ok((out, &self.meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Converts between two made-up formats by applying a function to each byte
+    struct Map(&'static [u8; 4], &'static [u8; 4], fn(u8) -> u8);
+
+    impl Converter for Map {
+        fn input(&self) -> FourCC {
+            FourCC::new(self.0)
+        }
+
+        fn output(&self) -> FourCC {
+            FourCC::new(self.1)
+        }
+
+        fn convert(&self, src: &[u8], _: &Format, dest: &mut Vec<u8>) -> io::Result<()> {
+            dest.clear();
+            dest.extend(src.iter().map(|byte| (self.2)(*byte)));
+            Ok(())
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+        registry.register(Map(b"AAAA", b"BBBB", |x| x + 1));
+        registry.register(Map(b"BBBB", b"CCCC", |x| x * 2));
+        registry.register(Map(b"CCCC", b"DDDD", |x| x + 3));
+        registry
+    }
+
+    #[test]
+    fn chain_steps() {
+        let input = Format::new(2, 1, FourCC::new(b"AAAA"));
+        for &(output, first, second) in &[
+            (b"AAAA", [1, 2], [3, 4]),
+            (b"BBBB", [2, 3], [4, 5]),
+            (b"CCCC", [4, 6], [8, 10]),
+            (b"DDDD", [7, 9], [11, 13]),
+        ] {
+            let mut chain = registry().chain(&input, FourCC::new(output)).unwrap();
+            assert_eq!(chain.format().fourcc, FourCC::new(output));
+            assert_eq!(chain.convert(&[1, 2]).unwrap(), &first[..]);
+            // buffers are reused, the second frame must not see leftovers of the first
+            assert_eq!(chain.convert(&[3, 4]).unwrap(), &second[..]);
+        }
+    }
+
+    #[test]
+    fn no_chain() {
+        let input = Format::new(2, 1, FourCC::new(b"DDDD"));
+        assert!(registry().chain(&input, FourCC::new(b"AAAA")).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use std::io;
+
+use crate::convert::Converter;
+use crate::format::{Format, FourCC};
+use crate::frame::FrameView;
+
+/// Converts a single pixel from BT.601 limited range YUV to RGB
+pub(crate) fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let c = (y as i32 - 16) * 298;
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+
+    let clamp = |x: i32| ((x + 128) >> 8).clamp(0, 255) as u8;
+    [
+        clamp(c + 409 * e),
+        clamp(c - 100 * d - 208 * e),
+        clamp(c + 516 * d),
+    ]
+}
+
+/// Converts packed YUYV 4:2:2 to RGB3
+#[derive(Debug, Default, Copy, Clone)]
+pub struct YuyvToRgb;
+
+impl Converter for YuyvToRgb {
+    fn input(&self) -> FourCC {
+        FourCC::new(b"YUYV")
+    }
+
+    fn output(&self) -> FourCC {
+        FourCC::new(b"RGB3")
+    }
+
+    fn convert(&self, src: &[u8], fmt: &Format, dest: &mut Vec<u8>) -> io::Result<()> {
+        let frame = FrameView::new(src, fmt)?;
+        dest.clear();
+        dest.reserve(fmt.width as usize * fmt.height as usize * 3);
+
+        for line in frame.planes()[0].lines() {
+            for pixels in line.chunks(4) {
+                let (y0, u, y1, v) = match *pixels {
+                    [y0, u, y1, v] => (y0, u, y1, v),
+                    // odd widths end with a single pixel
+                    [y0, u] => (y0, u, y0, 128),
+                    _ => continue,
+                };
+                dest.extend_from_slice(&yuv_to_rgb(y0, u, v));
+                if pixels.len() == 4 {
+                    dest.extend_from_slice(&yuv_to_rgb(y1, u, v));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use std::{fmt, mem};
+
+use crate::v4l_sys::*;
+
+/// Decoder command
+///
+/// Commands are issued through `Device::decoder_command` and mostly matter for stateful
+/// memory-to-memory decoders. A drain sequence consists of sending `Command::stop()`, dequeueing
+/// capture buffers until one carries the LAST flag and finally sending `Command::start()` to
+/// resume decoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Start or resume decoding after a stop
+    Start {
+        /// Playback speed, 0 and 1000 mean normal speed, negative values play backwards
+        speed: i32,
+        /// Mute audio while decoding at a speed other than normal
+        mute_audio: bool,
+    },
+    /// Stop decoding, which drains all pending buffers unless `immediately` is set
+    Stop {
+        /// Show a black frame after stopping
+        to_black: bool,
+        /// Stop right away instead of draining the pending buffers
+        immediately: bool,
+        /// Stop once this presentation timestamp is reached, 0 to stop at the end of the stream
+        pts: u64,
+    },
+    /// Pause decoding
+    Pause {
+        /// Show a black frame while paused
+        to_black: bool,
+    },
+    /// Resume decoding after a pause
+    Resume,
+    /// Flush held capture buffers, used by stateless decoders
+    Flush,
+}
+
+impl Command {
+    /// Returns a command which starts decoding at normal speed
+    pub fn start() -> Self {
+        Command::Start {
+            speed: 0,
+            mute_audio: false,
+        }
+    }
+
+    /// Returns a command which initiates draining the decoder
+    pub fn stop() -> Self {
+        Command::Stop {
+            to_black: false,
+            immediately: false,
+            pts: 0,
+        }
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Start { .. } => write!(f, "start"),
+            Command::Stop { .. } => write!(f, "stop"),
+            Command::Pause { .. } => write!(f, "pause"),
+            Command::Resume => write!(f, "resume"),
+            Command::Flush => write!(f, "flush"),
+        }
+    }
+}
+
+impl From<Command> for v4l2_decoder_cmd {
+    fn from(command: Command) -> Self {
+        let mut cmd: v4l2_decoder_cmd = unsafe { mem::zeroed() };
+        match command {
+            Command::Start { speed, mute_audio } => {
+                cmd.cmd = V4L2_DEC_CMD_START;
+                if mute_audio {
+                    cmd.flags |= V4L2_DEC_CMD_START_MUTE_AUDIO;
+                }
+                cmd.__bindgen_anon_1.start.speed = speed;
+                cmd.__bindgen_anon_1.start.format = V4L2_DEC_START_FMT_NONE;
+            }
+            Command::Stop {
+                to_black,
+                immediately,
+                pts,
+            } => {
+                cmd.cmd = V4L2_DEC_CMD_STOP;
+                if to_black {
+                    cmd.flags |= V4L2_DEC_CMD_STOP_TO_BLACK;
+                }
+                if immediately {
+                    cmd.flags |= V4L2_DEC_CMD_STOP_IMMEDIATELY;
+                }
+                cmd.__bindgen_anon_1.stop.pts = pts;
+            }
+            Command::Pause { to_black } => {
+                cmd.cmd = V4L2_DEC_CMD_PAUSE;
+                if to_black {
+                    cmd.flags |= V4L2_DEC_CMD_PAUSE_TO_BLACK;
+                }
+            }
+            Command::Resume => cmd.cmd = V4L2_DEC_CMD_RESUME,
+            Command::Flush => cmd.cmd = V4L2_DEC_CMD_FLUSH,
+        }
+        cmd
+    }
+}
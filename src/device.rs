@@ -1,12 +1,27 @@
 use std::convert::TryFrom;
 use std::path::Path;
 use std::sync::Arc;
-use std::{io, mem};
+use std::time::{Duration, Instant};
+use std::{fmt, fs, io, mem};
 
 use crate::control;
+use crate::decoder;
+use crate::encoder;
+use crate::event::{self, Event};
 use crate::v4l2;
 use crate::v4l_sys::*;
-use crate::{capability::Capabilities, control::Control};
+use crate::{
+    buffer,
+    capability::{self, Capabilities},
+    control::{Control, TypedControl},
+    features,
+    format::{Format, FormatMplane, QueueFormat},
+    io::adapters::FrameSource,
+    io::mmap,
+    io::traits::{CaptureStream, Stream},
+    memory::Memory,
+    video::Capture,
+};
 
 /// Linux capture device abstraction
 pub struct Device {
@@ -69,6 +84,31 @@ impl Device {
         })
     }
 
+    /// Returns a capture device by path, opened in non-blocking mode (O_NONBLOCK)
+    ///
+    /// Dequeueing a buffer never blocks on such a device, it fails with WouldBlock instead if no
+    /// buffer is ready. This is what event loops and the timeout APIs expect, the streams wait for
+    /// readiness with poll(2) before dequeueing. See `io::retry::Retry` for how streams react to
+    /// spurious wakeups.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path (e.g. "/dev/video0")
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::device::Device;
+    /// let dev = Device::open_nonblocking("/dev/video0");
+    /// ```
+    pub fn open_nonblocking<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let fd = v4l2::open(&path, libc::O_RDWR | libc::O_NONBLOCK)?;
+
+        Ok(Device {
+            handle: Arc::new(Handle { fd }),
+        })
+    }
+
     /// Returns the raw device handle
     pub fn handle(&self) -> Arc<Handle> {
         self.handle.clone()
@@ -88,6 +128,298 @@ impl Device {
         }
     }
 
+    /// Returns a summary of the device for diagnostics output and bug reports
+    ///
+    /// This combines the capabilities with the current format and the supported I/O methods.
+    /// Memory-to-memory devices are described by their output queue, like in
+    /// `Features::probe()`. Memory types are probed through VIDIOC_CREATE_BUFS with a count of 0,
+    /// which leaves any allocated buffers alone, so this is safe to call while streaming. They
+    /// are not detected if the driver does not implement VIDIOC_CREATE_BUFS or if another file
+    /// descriptor owns the buffers of the queue.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     if let Ok(info) = dev.info() {
+    ///         print!("{}", info);
+    ///     }
+    /// }
+    /// ```
+    pub fn info(&self) -> io::Result<Info> {
+        let caps = self.query_caps()?;
+
+        let mut format = None;
+        let mut memory = Vec::new();
+        if let Some(buf_type) = features::buf_type(caps.capabilities) {
+            unsafe {
+                let mut v4l2_fmt: v4l2_format = mem::zeroed();
+                v4l2_fmt.type_ = buf_type as u32;
+                if v4l2::ioctl(
+                    self.handle.fd(),
+                    v4l2::vidioc::VIDIOC_G_FMT,
+                    &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+                )
+                .is_ok()
+                {
+                    format = Some(if buf_type.is_multiplanar() {
+                        Format::from(v4l2_fmt.fmt.pix_mp)
+                    } else {
+                        Format::from(v4l2_fmt.fmt.pix)
+                    });
+                }
+            }
+
+            if caps.capabilities.contains(capability::Flags::STREAMING) {
+                // Creating zero buffers only validates the memory type and the format, it never
+                // touches buffers which are already allocated
+                for typ in &[Memory::Mmap, Memory::UserPtr, Memory::DmaBuf] {
+                    unsafe {
+                        let mut v4l2_create: v4l2_create_buffers = mem::zeroed();
+                        v4l2_create.count = 0;
+                        v4l2_create.memory = *typ as u32;
+                        v4l2_create.format.type_ = buf_type as u32;
+                        if v4l2::ioctl(
+                            self.handle.fd(),
+                            v4l2::vidioc::VIDIOC_G_FMT,
+                            &mut v4l2_create.format as *mut _ as *mut std::os::raw::c_void,
+                        )
+                        .is_ok()
+                            && v4l2::ioctl(
+                                self.handle.fd(),
+                                v4l2::vidioc::VIDIOC_CREATE_BUFS,
+                                &mut v4l2_create as *mut _ as *mut std::os::raw::c_void,
+                            )
+                            .is_ok()
+                        {
+                            memory.push(*typ);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(Info {
+            read_write: caps.capabilities.contains(capability::Flags::READ_WRITE),
+            kernel: fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|release| release.trim().to_string()),
+            driver: caps.driver,
+            card: caps.card,
+            bus: caps.bus,
+            version: caps.version,
+            capabilities: caps.capabilities,
+            format,
+            memory,
+        })
+    }
+
+    /// Captures for a while and measures what the device actually delivers in a given format
+    ///
+    /// USB cameras frequently advertise formats which the bus cannot sustain at the nominal
+    /// frame rate. Probing each candidate for a short while allows picking the best one which
+    /// does not drop frames. The format is left applied when this function returns.
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - Format to probe, adjusted by the driver as in `Capture::set_format`
+    /// * `duration` - Time to capture for
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use v4l::device::Device;
+    /// use v4l::video::Capture;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let perf = dev.probe_performance(&fmt, Duration::from_secs(2)).unwrap();
+    /// print!("{}", perf);
+    /// ```
+    pub fn probe_performance(&self, fmt: &Format, duration: Duration) -> io::Result<Performance> {
+        let format = Capture::set_format(self, fmt)?;
+        let mut stream = mmap::Stream::new(self, buffer::Type::VideoCapture)?;
+        // give slow devices a moment to deliver the first frame
+        stream.set_timeout(Some(duration.max(Duration::from_secs(2))));
+
+        let mut frames = 0u64;
+        let mut dropped = 0u64;
+        let mut sequence = None;
+        let mut first = None;
+        let start = Instant::now();
+        let mut last = start;
+        let deadline = start + duration;
+
+        while Instant::now() < deadline {
+            let (_, meta) = CaptureStream::next(&mut stream)?;
+            last = Instant::now();
+            if let Some(prev) = sequence {
+                dropped += meta.sequence.wrapping_sub(prev).saturating_sub(1) as u64;
+            }
+            sequence = Some(meta.sequence);
+            first.get_or_insert(last);
+            frames += 1;
+        }
+
+        // the first frame marks the start, since stream startup may take a while
+        let mut elapsed = first.map(|first| last - first).unwrap_or_default();
+        let mut intervals = frames.saturating_sub(1);
+        if elapsed.is_zero() {
+            // all frames were dequeued at once, e.g. from a backlog: count them from the start
+            elapsed = last - start;
+            intervals = frames;
+        }
+        let fps = if intervals == 0 || elapsed.is_zero() {
+            0.0
+        } else {
+            intervals as f64 / elapsed.as_secs_f64()
+        };
+
+        Ok(Performance {
+            format,
+            frames,
+            dropped,
+            duration: elapsed,
+            fps,
+            latency: stream.stats().delivery(50.0),
+            latency_max: stream.stats().delivery(99.0),
+        })
+    }
+
+    /// Captures a single frame in a given format
+    ///
+    /// This takes care of the whole streaming lifecycle: buffers are allocated, a few frames are
+    /// discarded so auto exposure can settle, one frame is copied out and the stream is torn down
+    /// again. It is meant for scripts and tests, use a stream to capture continuously.
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - Format to capture in, adjusted by the driver as in `Capture::set_format`
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::device::Device;
+    /// use v4l::video::Capture;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let (frame, meta) = dev.capture_one(&fmt).unwrap();
+    /// println!("captured {} bytes at {}", frame.len(), meta.timestamp);
+    /// ```
+    pub fn capture_one(&self, fmt: &Format) -> io::Result<(Vec<u8>, buffer::Metadata)> {
+        // enough for the auto exposure of typical webcams to settle
+        const WARM_UP_FRAMES: usize = 5;
+        // frames to look at for a usable one after warming up
+        const MAX_ATTEMPTS: usize = 5;
+        const TIMEOUT: Duration = Duration::from_secs(5);
+
+        Capture::set_format(self, fmt)?;
+        let mut stream = mmap::Stream::new(self, buffer::Type::VideoCapture)?;
+        stream.set_timeout(Some(TIMEOUT));
+        stream.warm_up(WARM_UP_FRAMES)?;
+
+        let mut empty = 0;
+        let mut corrupted = 0;
+        for _ in 0..MAX_ATTEMPTS {
+            let (buf, meta) = CaptureStream::next(&mut stream)?;
+            // skip empty frames and frames the driver flagged as corrupted
+            if meta.bytesused == 0 {
+                empty += 1;
+                continue;
+            }
+            if meta.flags.contains(buffer::Flags::ERROR) {
+                corrupted += 1;
+                continue;
+            }
+
+            return Ok((meta.payload(buf).to_vec(), *meta));
+        }
+
+        let msg = if corrupted == 0 {
+            "no non-empty frame received".to_string()
+        } else if empty == 0 {
+            "the driver flagged all frames as corrupted".to_string()
+        } else {
+            format!(
+                "no usable frame received ({} empty, {} flagged as corrupted)",
+                empty, corrupted
+            )
+        };
+        Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+    }
+
+    /// Returns the current multi-planar format
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_type` - Multi-planar buffer type, e.g. `Type::VideoCaptureMplane`
+    pub fn format_mplane(&self, buf_type: buffer::Type) -> io::Result<FormatMplane> {
+        check_mplane(buf_type)?;
+
+        unsafe {
+            let mut v4l2_fmt: v4l2_format = mem::zeroed();
+            v4l2_fmt.type_ = buf_type as u32;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_G_FMT,
+                &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(FormatMplane::from(v4l2_fmt.fmt.pix_mp))
+        }
+    }
+
+    /// Modifies the multi-planar format and returns the actual format
+    ///
+    /// The driver adjusts the parameters (including the number of planes and their layout) on a
+    /// best effort basis, so check the returned format.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_type` - Multi-planar buffer type, e.g. `Type::VideoCaptureMplane`
+    /// * `fmt` - Desired format
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::buffer::Type;
+    /// use v4l::device::Device;
+    /// use v4l::format::{FormatMplane, FourCC};
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     let fmt = FormatMplane::new(1280, 720, FourCC::new(b"NM12"), 2).unwrap();
+    ///     if let Ok(fmt) = dev.set_format_mplane(Type::VideoCaptureMplane, &fmt) {
+    ///         for plane in fmt.planes() {
+    ///             println!("stride: {}, size: {}", plane.stride, plane.size);
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn set_format_mplane(
+        &self,
+        buf_type: buffer::Type,
+        fmt: &FormatMplane,
+    ) -> io::Result<FormatMplane> {
+        check_mplane(buf_type)?;
+
+        unsafe {
+            let mut v4l2_fmt: v4l2_format = mem::zeroed();
+            v4l2_fmt.type_ = buf_type as u32;
+            v4l2_fmt.fmt.pix_mp = fmt.clone().into();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_S_FMT,
+                &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(FormatMplane::from(v4l2_fmt.fmt.pix_mp))
+        }
+    }
+
     /// Returns the supported controls for a device such as gain, focus, white balance, etc.
     pub fn query_controls(&self) -> io::Result<Vec<control::Description>> {
         let mut controls = Vec::new();
@@ -163,6 +495,85 @@ impl Device {
         Ok(controls)
     }
 
+    /// Resets all writable controls to their default values
+    ///
+    /// Controls are reset in the order reported by the driver, so e.g. automatic exposure is
+    /// restored before the exposure time. Resetting continues if a single control fails, the
+    /// first error is returned afterwards.
+    ///
+    /// # Arguments
+    ///
+    /// * `class` - Only reset controls of this class, None to reset all controls
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::control::Class;
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     let res = dev.reset_controls(Some(Class::Camera));
+    /// }
+    /// ```
+    pub fn reset_controls(&self, class: Option<control::Class>) -> io::Result<()> {
+        let mut res = Ok(());
+
+        for ctrl in self.query_controls()? {
+            if class.is_some() && control::Class::of(ctrl.id) != class {
+                continue;
+            }
+
+            if ctrl.flags.intersects(
+                control::Flags::READ_ONLY | control::Flags::DISABLED | control::Flags::GRABBED,
+            ) {
+                continue;
+            }
+
+            let val = match ctrl.typ {
+                control::Type::Integer
+                | control::Type::Boolean
+                | control::Type::Menu
+                | control::Type::IntegerMenu
+                | control::Type::Bitmask => Control::Value(ctrl.default),
+                // VIDIOC_QUERYCTRL cannot report 64-bit defaults, it leaves them at 0
+                control::Type::Integer64 => match self.default64(ctrl.id) {
+                    Ok(default) => Control::Value64(default),
+                    Err(e) => {
+                        if res.is_ok() {
+                            res = Err(e);
+                        }
+                        continue;
+                    }
+                },
+                // buttons, class headers and compound controls have no default to restore
+                _ => continue,
+            };
+
+            if let Err(e) = self.set_control(ctrl.id, val) {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Returns the default value of a 64-bit control, as reported by VIDIOC_QUERY_EXT_CTRL
+    fn default64(&self, id: u32) -> io::Result<i64> {
+        unsafe {
+            let mut v4l2_ctrl: v4l2_query_ext_ctrl = mem::zeroed();
+            v4l2_ctrl.id = id;
+            v4l2::ioctl(
+                self.handle().fd(),
+                v4l2::vidioc::VIDIOC_QUERY_EXT_CTRL,
+                &mut v4l2_ctrl as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(v4l2_ctrl.default_value)
+        }
+    }
+
     /// Returns the control value for an ID
     ///
     /// # Arguments
@@ -194,6 +605,12 @@ impl Device {
             v4l2_ctrl.id = id;
             match val {
                 Control::Value(val) => v4l2_ctrl.value = val,
+                Control::Value64(val) => {
+                    let mut v4l2_ext_ctrl: v4l2_ext_control = mem::zeroed();
+                    v4l2_ext_ctrl.id = id;
+                    v4l2_ext_ctrl.__bindgen_anon_1.value64 = val;
+                    return self.ext_control(v4l2::vidioc::VIDIOC_S_EXT_CTRLS, &mut v4l2_ext_ctrl);
+                }
                 _ => {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidInput,
@@ -208,6 +625,294 @@ impl Device {
             )
         }
     }
+
+    /// Reads several controls at once through VIDIOC_G_EXT_CTRLS
+    ///
+    /// The variant of each given value selects how the control is read: `Value` and `Value64`
+    /// for 32-bit and 64-bit controls, `Compound` for compound controls, whose vector must have
+    /// the size of the control payload. The values are replaced by the ones read.
+    ///
+    /// # Arguments
+    ///
+    /// * `which` - Current, default or request values
+    /// * `ctrls` - Control identifiers and values
+    pub fn ext_controls(
+        &self,
+        which: control::Which,
+        ctrls: &mut [(u32, Control)],
+    ) -> io::Result<()> {
+        let mut raw = ctrls
+            .iter_mut()
+            .map(|(id, val)| raw_ext_control(*id, val))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.ext_controls_raw(v4l2::vidioc::VIDIOC_G_EXT_CTRLS, which, &mut raw)?;
+
+        for ((_, val), raw) in ctrls.iter_mut().zip(raw.iter()) {
+            unsafe {
+                match val {
+                    Control::Value(val) => *val = raw.__bindgen_anon_1.value,
+                    Control::Value64(val) => *val = raw.__bindgen_anon_1.value64,
+                    // compound payloads were written in place
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes several controls at once through VIDIOC_S_EXT_CTRLS
+    ///
+    /// The controls are applied atomically: if one of them is rejected, none is changed. With
+    /// `Which::Request`, the values are stored in the request and only applied once the request
+    /// is queued, which is how per-frame parameters are passed to stateless codecs.
+    ///
+    /// # Arguments
+    ///
+    /// * `which` - Current or request values
+    /// * `ctrls` - Control identifiers and new values
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::control::{Control, Which};
+    /// use v4l::device::Device;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let request_fd = 42; // allocated through MEDIA_IOC_REQUEST_ALLOC
+    /// let sps = vec![0u8; 1048]; // struct v4l2_ctrl_h264_sps
+    /// dev.set_ext_controls(
+    ///     Which::Request(request_fd),
+    ///     &[(0x00a40ac0 /* V4L2_CID_STATELESS_H264_SPS */, Control::Compound(sps))],
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn set_ext_controls(
+        &self,
+        which: control::Which,
+        ctrls: &[(u32, Control)],
+    ) -> io::Result<()> {
+        // the kernel only reads the payloads, but the bindings want mutable pointers
+        let mut ctrls = ctrls.to_vec();
+        let mut raw = ctrls
+            .iter_mut()
+            .map(|(id, val)| raw_ext_control(*id, val))
+            .collect::<io::Result<Vec<_>>>()?;
+        self.ext_controls_raw(v4l2::vidioc::VIDIOC_S_EXT_CTRLS, which, &mut raw)
+    }
+
+    /// Issues an extended control request for several controls
+    fn ext_controls_raw(
+        &self,
+        request: v4l2::vidioc::_IOC_TYPE,
+        which: control::Which,
+        ctrls: &mut [v4l2_ext_control],
+    ) -> io::Result<()> {
+        let (which, request_fd) = which.as_raw();
+        unsafe {
+            let mut v4l2_ext_ctrls: v4l2_ext_controls = mem::zeroed();
+            v4l2_ext_ctrls.__bindgen_anon_1.which = which;
+            v4l2_ext_ctrls.request_fd = request_fd;
+            v4l2_ext_ctrls.count = ctrls.len() as u32;
+            v4l2_ext_ctrls.controls = ctrls.as_mut_ptr();
+            v4l2::ioctl(
+                self.handle().fd(),
+                request,
+                &mut v4l2_ext_ctrls as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Returns the value of a 64-bit control, which is only accessible through VIDIOC_G_EXT_CTRLS
+    fn control64(&self, id: u32) -> io::Result<Control> {
+        unsafe {
+            let mut v4l2_ext_ctrl: v4l2_ext_control = mem::zeroed();
+            v4l2_ext_ctrl.id = id;
+            self.ext_control(v4l2::vidioc::VIDIOC_G_EXT_CTRLS, &mut v4l2_ext_ctrl)?;
+
+            Ok(Control::Value64(v4l2_ext_ctrl.__bindgen_anon_1.value64))
+        }
+    }
+
+    /// Issues an extended control request for a single control
+    fn ext_control(
+        &self,
+        request: v4l2::vidioc::_IOC_TYPE,
+        ctrl: &mut v4l2_ext_control,
+    ) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_ext_ctrls: v4l2_ext_controls = mem::zeroed();
+            v4l2_ext_ctrls.__bindgen_anon_1.ctrl_class = ctrl.id & 0x0fff_0000;
+            v4l2_ext_ctrls.count = 1;
+            v4l2_ext_ctrls.controls = ctrl;
+            v4l2::ioctl(
+                self.handle().fd(),
+                request,
+                &mut v4l2_ext_ctrls as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Returns the value of a strongly typed control
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::control::codec::BitrateMode;
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     let mode = dev.typed_control::<BitrateMode>();
+    /// }
+    /// ```
+    pub fn typed_control<C: TypedControl>(&self) -> io::Result<C> {
+        let ctrl = match C::TYPE {
+            control::Type::Integer64 => self.control64(C::ID)?,
+            _ => self.control(C::ID)?,
+        };
+
+        C::from_control(ctrl).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid value for control {}", C::ID),
+            )
+        })
+    }
+
+    /// Modifies the value of a strongly typed control
+    ///
+    /// # Arguments
+    ///
+    /// * `ctrl` - New value
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::control::codec::{Bitrate, BitrateMode};
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     let res = dev.set_typed_control(BitrateMode::Cbr);
+    ///     let res = dev.set_typed_control(Bitrate(4_000_000));
+    /// }
+    /// ```
+    pub fn set_typed_control<C: TypedControl>(&self, ctrl: C) -> io::Result<()> {
+        self.set_control(C::ID, ctrl.into_control())
+    }
+
+    /// Subscribes to an event so it can be dequeued later on
+    ///
+    /// # Arguments
+    ///
+    /// * `typ` - Event type
+    /// * `id` - Event source ID, e.g. the control ID for control events, zero otherwise
+    pub fn subscribe_event(&self, typ: event::Type, id: u32) -> io::Result<()> {
+        event::subscribe(&self.handle, typ, id)
+    }
+
+    /// Cancels an event subscription
+    ///
+    /// # Arguments
+    ///
+    /// * `typ` - Event type, `event::Type::All` to cancel all subscriptions
+    /// * `id` - Event source ID
+    pub fn unsubscribe_event(&self, typ: event::Type, id: u32) -> io::Result<()> {
+        event::unsubscribe(&self.handle, typ, id)
+    }
+
+    /// Returns the next pending event
+    ///
+    /// The device signals pending events through POLLPRI.
+    pub fn dequeue_event(&self) -> io::Result<Event> {
+        event::dequeue(&self.handle)
+    }
+
+    /// Sends a command to a decoder
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Decoder command
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::decoder::Command;
+    /// use v4l::device::Device;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     // start draining the decoder
+    ///     let res = dev.decoder_command(Command::stop());
+    /// }
+    /// ```
+    pub fn decoder_command(&self, cmd: decoder::Command) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_cmd: v4l2_decoder_cmd = cmd.into();
+            v4l2::ioctl(
+                self.handle().fd(),
+                v4l2::vidioc::VIDIOC_DECODER_CMD,
+                &mut v4l2_cmd as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Checks whether a decoder supports a command without actually executing it
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Decoder command
+    pub fn try_decoder_command(&self, cmd: decoder::Command) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_cmd: v4l2_decoder_cmd = cmd.into();
+            v4l2::ioctl(
+                self.handle().fd(),
+                v4l2::vidioc::VIDIOC_TRY_DECODER_CMD,
+                &mut v4l2_cmd as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Sends a command to an encoder
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Encoder command
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::device::Device;
+    /// use v4l::encoder::Command;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     // start draining the encoder
+    ///     let res = dev.encoder_command(Command::Stop { at_gop_end: false });
+    /// }
+    /// ```
+    pub fn encoder_command(&self, cmd: encoder::Command) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_cmd: v4l2_encoder_cmd = cmd.into();
+            v4l2::ioctl(
+                self.handle().fd(),
+                v4l2::vidioc::VIDIOC_ENCODER_CMD,
+                &mut v4l2_cmd as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Checks whether an encoder supports a command without actually executing it
+    ///
+    /// # Arguments
+    ///
+    /// * `cmd` - Encoder command
+    pub fn try_encoder_command(&self, cmd: encoder::Command) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_cmd: v4l2_encoder_cmd = cmd.into();
+            v4l2::ioctl(
+                self.handle().fd(),
+                v4l2::vidioc::VIDIOC_TRY_ENCODER_CMD,
+                &mut v4l2_cmd as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
 }
 
 impl io::Read for Device {
@@ -249,6 +954,148 @@ impl io::Write for Device {
     }
 }
 
+/// Rejects buffer types which do not use multi-planar formats
+fn check_mplane(buf_type: buffer::Type) -> io::Result<()> {
+    match buf_type {
+        buffer::Type::VideoCaptureMplane | buffer::Type::VideoOutputMplane => Ok(()),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("not a multi-planar buffer type: {:?}", buf_type),
+        )),
+    }
+}
+
+/// Summary of a device, see `Device::info()`
+#[derive(Debug, Clone)]
+pub struct Info {
+    /// Driver name, e.g. uvcvideo for usb video class devices
+    pub driver: String,
+    /// Card name
+    pub card: String,
+    /// Bus name, e.g. USB or PCI
+    pub bus: String,
+    /// Driver version MAJOR.MINOR.PATCH, which equals the kernel version for in-tree drivers
+    pub version: (u8, u8, u8),
+    /// Release of the running kernel, if available
+    pub kernel: Option<String>,
+    /// Capabilities of the opened device node
+    pub capabilities: capability::Flags,
+    /// Current format of video capture, output or memory-to-memory devices, multi-planar
+    /// formats are summarized as in `Format::from(v4l2_pix_format_mplane)`
+    pub format: Option<Format>,
+    /// Whether frames can be exchanged through read() and write()
+    pub read_write: bool,
+    /// Memory types supported for streaming I/O
+    pub memory: Vec<Memory>,
+}
+
+impl fmt::Display for Info {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Driver       : {}", self.driver)?;
+        writeln!(f, "Card         : {}", self.card)?;
+        writeln!(f, "Bus          : {}", self.bus)?;
+        writeln!(
+            f,
+            "Version      : {}.{}.{}",
+            self.version.0, self.version.1, self.version.2
+        )?;
+        if let Some(kernel) = &self.kernel {
+            writeln!(f, "Kernel       : {}", kernel)?;
+        }
+        writeln!(f, "Capabilities : {}", self.capabilities)?;
+        if let Some(format) = &self.format {
+            writeln!(
+                f,
+                "Format       : {}x{} {}",
+                format.width, format.height, format.fourcc
+            )?;
+        }
+
+        let mut io = Vec::new();
+        if self.read_write {
+            io.push("read/write".to_string());
+        }
+        io.extend(self.memory.iter().map(|mem| mem.to_string()));
+        writeln!(f, "I/O          : {}", io.join(", "))?;
+        Ok(())
+    }
+}
+
+/// Report of a short capture, see `Device::probe_performance()`
+#[derive(Debug, Clone)]
+pub struct Performance {
+    /// Format actually in use
+    pub format: Format,
+    /// Number of frames received
+    pub frames: u64,
+    /// Number of frames dropped by the driver, as told by gaps in the sequence numbers
+    pub dropped: u64,
+    /// Time between the first and the last frame
+    pub duration: Duration,
+    /// Achieved frame rate
+    pub fps: f64,
+    /// Median time between the driver timestamp and the dequeue operation, if the driver uses
+    /// monotonic timestamps
+    pub latency: Option<Duration>,
+    /// 99th percentile of the latency
+    pub latency_max: Option<Duration>,
+}
+
+impl Performance {
+    /// Returns the fraction of frames dropped by the driver, from 0.0 to 1.0
+    pub fn drop_rate(&self) -> f64 {
+        match self.frames + self.dropped {
+            0 => 0.0,
+            total => self.dropped as f64 / total as f64,
+        }
+    }
+}
+
+impl fmt::Display for Performance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Format       : {}x{} {}",
+            self.format.width, self.format.height, self.format.fourcc
+        )?;
+        writeln!(f, "Frames       : {}", self.frames)?;
+        writeln!(f, "Frame rate   : {:.2} fps", self.fps)?;
+        writeln!(
+            f,
+            "Dropped      : {} ({:.1}%)",
+            self.dropped,
+            self.drop_rate() * 100.0
+        )?;
+        if let (Some(latency), Some(latency_max)) = (self.latency, self.latency_max) {
+            writeln!(f, "Latency      : {:?} (p99 {:?})", latency, latency_max)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the raw extended control for a value, pointing into it for strings and compounds
+fn raw_ext_control(id: u32, val: &mut Control) -> io::Result<v4l2_ext_control> {
+    unsafe {
+        let mut ctrl: v4l2_ext_control = mem::zeroed();
+        ctrl.id = id;
+        match val {
+            Control::Value(val) => ctrl.__bindgen_anon_1.value = *val,
+            Control::Value64(val) => ctrl.__bindgen_anon_1.value64 = *val,
+            Control::Compound(data) => {
+                ctrl.size = data.len() as u32;
+                ctrl.__bindgen_anon_1.ptr = data.as_mut_ptr() as *mut std::os::raw::c_void;
+            }
+            Control::String(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "string controls are not supported at the moment",
+                ))
+            }
+        }
+        Ok(ctrl)
+    }
+}
+
 /// Device handle for low-level access.
 ///
 /// Acquiring a handle facilitates (possibly mutating) interactions with the device.
@@ -257,10 +1104,220 @@ pub struct Handle {
 }
 
 impl Handle {
+    /// Takes ownership of a file descriptor, which is closed when the handle is dropped
+    pub(crate) fn from_fd(fd: std::os::raw::c_int) -> Self {
+        Handle { fd }
+    }
+
     /// Returns the raw file descriptor
     pub fn fd(&self) -> std::os::raw::c_int {
         self.fd
     }
+
+    /// Returns the major and minor number of the device node
+    pub(crate) fn devnum(&self) -> io::Result<(u32, u32)> {
+        let rdev = unsafe {
+            let mut stat: libc::stat = mem::zeroed();
+            if libc::fstat(self.fd, &mut stat) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            stat.st_rdev as u64
+        };
+
+        // see gnu_dev_major() and gnu_dev_minor() in glibc
+        let major = ((rdev >> 8) & 0xfff) | ((rdev >> 32) & !0xfff);
+        let minor = (rdev & 0xff) | ((rdev >> 12) & !0xff);
+        Ok((major as u32, minor as u32))
+    }
+
+    /// Returns true if the file descriptor is in non-blocking mode (O_NONBLOCK)
+    pub fn is_nonblocking(&self) -> io::Result<bool> {
+        let flags = unsafe { libc::fcntl(self.fd, libc::F_GETFL) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(flags & libc::O_NONBLOCK != 0)
+    }
+
+    /// Switches the file descriptor between blocking and non-blocking mode
+    ///
+    /// The mode is a property of the open file, so it applies to all users of the handle and the
+    /// negotiated device state (formats, buffers, streaming) is kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `nonblocking` - True to set O_NONBLOCK, false to clear it
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        unsafe {
+            let flags = libc::fcntl(self.fd, libc::F_GETFL);
+            if flags == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let flags = if nonblocking {
+                flags | libc::O_NONBLOCK
+            } else {
+                flags & !libc::O_NONBLOCK
+            };
+            if libc::fcntl(self.fd, libc::F_SETFL, flags) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Waits until a capture buffer can be dequeued (POLLIN)
+    ///
+    /// Returns false if the timeout expired before the device became ready. Errors (POLLERR),
+    /// e.g. because streaming is not enabled, count as ready since dequeueing reports them.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, None blocks indefinitely
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use v4l::buffer::Type;
+    /// use v4l::io::traits::{CaptureStream, Stream};
+    /// use v4l::prelude::*;
+    ///
+    /// let dev = Device::open_nonblocking("/dev/video0").unwrap();
+    /// let handle = dev.handle();
+    /// let mut stream = MmapStream::with_buffers(&dev, Type::VideoCapture, 4).unwrap();
+    /// for index in 0..4 {
+    ///     stream.queue(index).unwrap();
+    /// }
+    /// stream.start().unwrap();
+    ///
+    /// loop {
+    ///     if handle.wait_readable(Some(Duration::from_millis(100))).unwrap() {
+    ///         let index = stream.dequeue().unwrap();
+    ///         // process the buffer, then hand it back
+    ///         stream.queue(index).unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn wait_readable(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        self.poll(libc::POLLIN, timeout)
+    }
+
+    /// Waits until an output buffer can be dequeued (POLLOUT)
+    ///
+    /// Returns false if the timeout expired before the device became ready.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, None blocks indefinitely
+    pub fn wait_writable(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        self.poll(libc::POLLOUT, timeout)
+    }
+
+    /// Applies the timings detected by a DV receiver and returns the current format of a queue
+    ///
+    /// Devices which do not support DV timings (e.g. cameras or decoders) keep their timings.
+    /// Fails with ENOLINK if a receiver detects no signal and ENOLCK if the signal is unstable.
+    /// No buffers may be allocated for the device while the timings are changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf_type` - Buffer type of the queue
+    pub(crate) fn renegotiate(&self, buf_type: buffer::Type) -> io::Result<QueueFormat> {
+        unsafe {
+            let mut v4l2_timings: v4l2_dv_timings = mem::zeroed();
+            let res = v4l2::ioctl(
+                self.fd,
+                v4l2::vidioc::VIDIOC_QUERY_DV_TIMINGS,
+                &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+            );
+            match res.as_ref().map_err(v4l2::errno) {
+                Ok(()) => v4l2::ioctl(
+                    self.fd,
+                    v4l2::vidioc::VIDIOC_S_DV_TIMINGS,
+                    &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+                )?,
+                // ENOTTY: no DV support at all, ENODATA: not supported by the current input
+                Err(Some(libc::ENOTTY)) | Err(Some(libc::ENODATA)) => {}
+                Err(_) => res?,
+            }
+
+            let mut v4l2_fmt: v4l2_format = mem::zeroed();
+            v4l2_fmt.type_ = buf_type as u32;
+            v4l2::ioctl(
+                self.fd,
+                v4l2::vidioc::VIDIOC_G_FMT,
+                &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            if buf_type.is_multiplanar() {
+                Ok(QueueFormat::Multi(FormatMplane::from(v4l2_fmt.fmt.pix_mp)))
+            } else {
+                Ok(QueueFormat::Single(Format::from(v4l2_fmt.fmt.pix)))
+            }
+        }
+    }
+
+    /// Waits until one of the requested poll(2) events occurs
+    ///
+    /// Returns false if the timeout expired before the device became ready.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - Poll events, e.g. POLLIN
+    /// * `timeout` - Maximum time to wait, None blocks indefinitely
+    pub(crate) fn poll(&self, events: i16, timeout: Option<Duration>) -> io::Result<bool> {
+        self.poll_events(events, timeout)
+            .map(|revents| revents != 0)
+    }
+
+    /// Waits like poll(), but returns the events which actually occurred
+    ///
+    /// Returns zero if the timeout expired before the device became ready.
+    ///
+    /// # Arguments
+    ///
+    /// * `events` - Poll events, e.g. POLLIN | POLLPRI
+    /// * `timeout` - Maximum time to wait, None blocks indefinitely
+    pub(crate) fn poll_events(&self, events: i16, timeout: Option<Duration>) -> io::Result<i16> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let mut pollfd = libc::pollfd {
+            fd: self.fd,
+            events,
+            revents: 0,
+        };
+
+        loop {
+            // poll() works with a granularity of milliseconds, so round up to avoid busy looping
+            // on sub-millisecond timeouts
+            let timeout = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    let mut millis = remaining.as_millis();
+                    if remaining.subsec_nanos() % 1_000_000 != 0 {
+                        millis += 1;
+                    }
+                    millis.min(std::os::raw::c_int::MAX as u128) as std::os::raw::c_int
+                }
+                None => -1,
+            };
+
+            let ret = unsafe { libc::poll(&mut pollfd, 1, timeout) };
+            match ret {
+                -1 => {
+                    // interrupted by a signal, try again with the remaining time
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 => return Ok(0),
+                _ => return Ok(pollfd.revents),
+            }
+        }
+    }
 }
 
 impl Drop for Handle {
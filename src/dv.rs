@@ -0,0 +1,161 @@
+//! Digital video timings
+//!
+//! Receivers and transmitters of digital video interfaces such as HDMI or DVI are configured
+//! with the timings of the video signal instead of a video standard. A receiver detects the
+//! timings of the incoming signal and the capture format has to be set to match them.
+
+use std::mem;
+
+use crate::fraction::Fraction;
+use crate::v4l_sys::*;
+
+/// Timing type of BT.656/1120 timings (V4L2_DV_BT_656_1120), the only one defined
+const BT_656_1120: u32 = 0;
+
+/// Sync polarities (V4L2_DV_*SYNC_POS_POL)
+const VSYNC_POS_POL: u32 = 0x0000_0001;
+const HSYNC_POS_POL: u32 = 0x0000_0002;
+
+/// BT.656/1120 timings of a video signal (struct v4l2_bt_timings)
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Timings {
+    /// active width in pixels
+    pub width: u32,
+    /// active height in lines, of both fields for interlaced signals
+    pub height: u32,
+    /// whether the signal is interlaced
+    pub interlaced: bool,
+    /// whether the horizontal sync pulse is positive
+    pub hsync_positive: bool,
+    /// whether the vertical sync pulse is positive
+    pub vsync_positive: bool,
+    /// pixel clock in Hz
+    pub pixelclock: u64,
+
+    /// horizontal front porch in pixels
+    pub hfrontporch: u32,
+    /// horizontal sync length in pixels
+    pub hsync: u32,
+    /// horizontal back porch in pixels
+    pub hbackporch: u32,
+    /// vertical front porch in lines
+    pub vfrontporch: u32,
+    /// vertical sync length in lines
+    pub vsync: u32,
+    /// vertical back porch in lines
+    pub vbackporch: u32,
+    /// vertical front porch of the second field in lines
+    pub il_vfrontporch: u32,
+    /// vertical sync length of the second field in lines
+    pub il_vsync: u32,
+    /// vertical back porch of the second field in lines
+    pub il_vbackporch: u32,
+
+    /// standards the timings belong to (V4L2_DV_BT_STD_*)
+    pub standards: u32,
+    /// flags (V4L2_DV_FL_*)
+    pub flags: u32,
+    /// picture aspect ratio, if V4L2_DV_FL_HAS_PICTURE_ASPECT is set
+    pub picture_aspect: Fraction,
+    /// CEA-861 video identification code, if V4L2_DV_FL_HAS_CEA861_VIC is set
+    pub cea861_vic: u8,
+    /// HDMI video identification code, if V4L2_DV_FL_HAS_HDMI_VIC is set
+    pub hdmi_vic: u8,
+}
+
+impl Timings {
+    /// Returns the total width of a line including blanking, in pixels
+    pub fn total_width(&self) -> u32 {
+        self.width + self.hfrontporch + self.hsync + self.hbackporch
+    }
+
+    /// Returns the total height of a frame including blanking, in lines
+    pub fn total_height(&self) -> u32 {
+        let mut height = self.height + self.vfrontporch + self.vsync + self.vbackporch;
+        if self.interlaced {
+            height += self.il_vfrontporch + self.il_vsync + self.il_vbackporch;
+        }
+        height
+    }
+
+    /// Returns the number of frames per second, or zero if the timings are incomplete
+    pub fn fps(&self) -> f64 {
+        let total = self.total_width() as u64 * self.total_height() as u64;
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.pixelclock as f64 / total as f64
+    }
+}
+
+impl From<v4l2_dv_timings> for Timings {
+    fn from(timings: v4l2_dv_timings) -> Self {
+        let bt = unsafe { timings.__bindgen_anon_1.bt };
+        let polarities = bt.polarities;
+
+        Timings {
+            width: bt.width,
+            height: bt.height,
+            interlaced: bt.interlaced != 0,
+            hsync_positive: polarities & HSYNC_POS_POL != 0,
+            vsync_positive: polarities & VSYNC_POS_POL != 0,
+            pixelclock: bt.pixelclock,
+            hfrontporch: bt.hfrontporch,
+            hsync: bt.hsync,
+            hbackporch: bt.hbackporch,
+            vfrontporch: bt.vfrontporch,
+            vsync: bt.vsync,
+            vbackporch: bt.vbackporch,
+            il_vfrontporch: bt.il_vfrontporch,
+            il_vsync: bt.il_vsync,
+            il_vbackporch: bt.il_vbackporch,
+            standards: bt.standards,
+            flags: bt.flags,
+            picture_aspect: Fraction::from(bt.picture_aspect),
+            cea861_vic: bt.cea861_vic,
+            hdmi_vic: bt.hdmi_vic,
+        }
+    }
+}
+
+impl From<Timings> for v4l2_dv_timings {
+    fn from(timings: Timings) -> Self {
+        let mut v4l2_timings: v4l2_dv_timings;
+        unsafe {
+            v4l2_timings = mem::zeroed();
+        }
+
+        let mut polarities = 0;
+        if timings.hsync_positive {
+            polarities |= HSYNC_POS_POL;
+        }
+        if timings.vsync_positive {
+            polarities |= VSYNC_POS_POL;
+        }
+
+        v4l2_timings.type_ = BT_656_1120;
+        let mut bt: v4l2_bt_timings = unsafe { mem::zeroed() };
+        bt.width = timings.width;
+        bt.height = timings.height;
+        bt.interlaced = timings.interlaced as u32;
+        bt.polarities = polarities;
+        bt.pixelclock = timings.pixelclock;
+        bt.hfrontporch = timings.hfrontporch;
+        bt.hsync = timings.hsync;
+        bt.hbackporch = timings.hbackporch;
+        bt.vfrontporch = timings.vfrontporch;
+        bt.vsync = timings.vsync;
+        bt.vbackporch = timings.vbackporch;
+        bt.il_vfrontporch = timings.il_vfrontporch;
+        bt.il_vsync = timings.il_vsync;
+        bt.il_vbackporch = timings.il_vbackporch;
+        bt.standards = timings.standards;
+        bt.flags = timings.flags;
+        bt.picture_aspect = timings.picture_aspect.into();
+        bt.cea861_vic = timings.cea861_vic;
+        bt.hdmi_vic = timings.hdmi_vic;
+        v4l2_timings.__bindgen_anon_1.bt = bt;
+        v4l2_timings
+    }
+}
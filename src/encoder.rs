@@ -0,0 +1,52 @@
+use std::{fmt, mem};
+
+use crate::v4l_sys::*;
+
+/// Encoder command
+///
+/// Commands are issued through `Device::encoder_command`. To drain a memory-to-memory encoder,
+/// send `Command::Stop` and keep dequeueing capture buffers until one carries the LAST flag,
+/// e.g. by using `CaptureStream::drain`. Send `Command::Start` afterwards to continue encoding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Start or resume encoding after a stop
+    Start,
+    /// Stop encoding, which drains all pending buffers
+    Stop {
+        /// Stop at the end of the current group of pictures instead of right away
+        at_gop_end: bool,
+    },
+    /// Pause encoding
+    Pause,
+    /// Resume encoding after a pause
+    Resume,
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Start => write!(f, "start"),
+            Command::Stop { .. } => write!(f, "stop"),
+            Command::Pause => write!(f, "pause"),
+            Command::Resume => write!(f, "resume"),
+        }
+    }
+}
+
+impl From<Command> for v4l2_encoder_cmd {
+    fn from(command: Command) -> Self {
+        let mut cmd: v4l2_encoder_cmd = unsafe { mem::zeroed() };
+        match command {
+            Command::Start => cmd.cmd = V4L2_ENC_CMD_START,
+            Command::Stop { at_gop_end } => {
+                cmd.cmd = V4L2_ENC_CMD_STOP;
+                if at_gop_end {
+                    cmd.flags |= V4L2_ENC_CMD_STOP_AT_GOP_END;
+                }
+            }
+            Command::Pause => cmd.cmd = V4L2_ENC_CMD_PAUSE,
+            Command::Resume => cmd.cmd = V4L2_ENC_CMD_RESUME,
+        }
+        cmd
+    }
+}
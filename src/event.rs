@@ -0,0 +1,146 @@
+use bitflags::bitflags;
+use std::convert::TryFrom;
+use std::{fmt, io, mem};
+
+use crate::device::Handle;
+use crate::v4l2;
+use crate::v4l_sys::*;
+
+/// Event type
+#[allow(clippy::unreadable_literal)]
+#[rustfmt::skip]
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// All events, only valid for unsubscribing
+    All             = 0,
+    Vsync           = 1,
+    /// End of stream, e.g. after a decoder has been drained
+    Eos             = 2,
+    Ctrl            = 3,
+    FrameSync       = 4,
+    /// The source parameters (e.g. the resolution) changed
+    SourceChange    = 5,
+    MotionDet       = 6,
+}
+
+impl TryFrom<u32> for Type {
+    type Error = ();
+
+    fn try_from(repr: u32) -> Result<Self, Self::Error> {
+        match repr {
+            0 => Ok(Type::All),
+            1 => Ok(Type::Vsync),
+            2 => Ok(Type::Eos),
+            3 => Ok(Type::Ctrl),
+            4 => Ok(Type::FrameSync),
+            5 => Ok(Type::SourceChange),
+            6 => Ok(Type::MotionDet),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+bitflags! {
+    #[allow(clippy::unreadable_literal)]
+    pub struct SourceChanges: u32 {
+        /// The resolution of the source changed
+        const RESOLUTION    = 0x00000001;
+    }
+}
+
+impl From<u32> for SourceChanges {
+    fn from(flags: u32) -> SourceChanges {
+        SourceChanges::from_bits_truncate(flags)
+    }
+}
+
+impl From<SourceChanges> for u32 {
+    fn from(changes: SourceChanges) -> Self {
+        changes.bits()
+    }
+}
+
+impl fmt::Display for SourceChanges {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Event as dequeued from a device
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The last buffer of the stream has been produced
+    EndOfStream,
+    /// The source parameters changed, the format should be queried again
+    SourceChange(SourceChanges),
+    /// Any other event, contains the raw event type
+    Other(u32),
+}
+
+impl From<v4l2_event> for Event {
+    fn from(ev: v4l2_event) -> Self {
+        match Type::try_from(ev.type_) {
+            Ok(Type::Eos) => Event::EndOfStream,
+            Ok(Type::SourceChange) => {
+                Event::SourceChange(SourceChanges::from(unsafe { ev.u.src_change.changes }))
+            }
+            _ => Event::Other(ev.type_),
+        }
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::EndOfStream => write!(f, "end of stream"),
+            Event::SourceChange(changes) => write!(f, "source change ({})", changes),
+            Event::Other(typ) => write!(f, "event {}", typ),
+        }
+    }
+}
+
+pub(crate) fn subscribe(handle: &Handle, typ: Type, id: u32) -> io::Result<()> {
+    unsafe {
+        let mut v4l2_sub: v4l2_event_subscription = mem::zeroed();
+        v4l2_sub.type_ = typ as u32;
+        v4l2_sub.id = id;
+        v4l2::ioctl(
+            handle.fd(),
+            v4l2::vidioc::VIDIOC_SUBSCRIBE_EVENT,
+            &mut v4l2_sub as *mut _ as *mut std::os::raw::c_void,
+        )
+    }
+}
+
+pub(crate) fn unsubscribe(handle: &Handle, typ: Type, id: u32) -> io::Result<()> {
+    unsafe {
+        let mut v4l2_sub: v4l2_event_subscription = mem::zeroed();
+        v4l2_sub.type_ = typ as u32;
+        v4l2_sub.id = id;
+        v4l2::ioctl(
+            handle.fd(),
+            v4l2::vidioc::VIDIOC_UNSUBSCRIBE_EVENT,
+            &mut v4l2_sub as *mut _ as *mut std::os::raw::c_void,
+        )
+    }
+}
+
+pub(crate) fn dequeue(handle: &Handle) -> io::Result<Event> {
+    unsafe {
+        let mut v4l2_event: v4l2_event = mem::zeroed();
+        v4l2::ioctl(
+            handle.fd(),
+            v4l2::vidioc::VIDIOC_DQEVENT,
+            &mut v4l2_event as *mut _ as *mut std::os::raw::c_void,
+        )?;
+
+        Ok(Event::from(v4l2_event))
+    }
+}
@@ -0,0 +1,194 @@
+//! Runtime detection of optional kernel and driver features
+//!
+//! Depending on the kernel version and the driver, some ioctls are not implemented at all and
+//! fail with ENOTTY. Probing for them once up front allows applications to pick a fallback
+//! instead of handling errors on every call.
+
+use std::{io, mem};
+
+use crate::buffer;
+use crate::capability::Flags;
+use crate::device::Device;
+use crate::memory::Memory;
+use crate::v4l2;
+use crate::v4l_sys::*;
+
+/// V4L2_BUF_CAP_SUPPORTS_REQUESTS, reported by VIDIOC_REQBUFS since Linux 4.20
+const BUF_CAP_SUPPORTS_REQUESTS: u32 = 0x0000_0008;
+
+/// Optional features supported by the running kernel and the driver of a device
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Features {
+    /// Cropping and composing through VIDIOC_G_SELECTION and VIDIOC_S_SELECTION
+    pub selection: bool,
+    /// Extended controls through VIDIOC_G_EXT_CTRLS and VIDIOC_S_EXT_CTRLS
+    pub ext_controls: bool,
+    /// Allocating additional buffers through VIDIOC_CREATE_BUFS
+    pub create_bufs: bool,
+    /// Submitting buffers and controls as part of a media request
+    pub requests: bool,
+}
+
+impl Features {
+    /// Probes the features supported by a device
+    ///
+    /// Features which depend on buffers (create_bufs, requests) are only probed for video
+    /// capture, output and memory-to-memory nodes, single- or multi-planar. Probing them calls
+    /// VIDIOC_REQBUFS with a count of 0, which frees any buffers allocated through the same file
+    /// descriptor, so do not probe a device while streaming from it. The format and controls are
+    /// left untouched. Buffer features are not detected while another process is streaming from
+    /// the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Device to probe
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::device::Device;
+    /// use v4l::features::Features;
+    ///
+    /// if let Ok(dev) = Device::new(0) {
+    ///     if let Ok(features) = Features::probe(&dev) {
+    ///         if !features.selection {
+    ///             println!("cropping is not supported");
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn probe(dev: &Device) -> io::Result<Self> {
+        let caps = dev.query_caps()?;
+        let fd = dev.handle().fd();
+        let mut features = Features::default();
+
+        unsafe {
+            let mut v4l2_ctrls: v4l2_ext_controls = mem::zeroed();
+            features.ext_controls = implemented(v4l2::ioctl(
+                fd,
+                v4l2::vidioc::VIDIOC_G_EXT_CTRLS,
+                &mut v4l2_ctrls as *mut _ as *mut std::os::raw::c_void,
+            ));
+        }
+
+        let buf_type = match buf_type(caps.capabilities) {
+            Some(buf_type) => buf_type,
+            None => return Ok(features),
+        };
+
+        unsafe {
+            let mut v4l2_sel: v4l2_selection = mem::zeroed();
+            v4l2_sel.type_ = buf_type as u32;
+            v4l2_sel.target = if buf_type.is_output() {
+                V4L2_SEL_TGT_COMPOSE_BOUNDS
+            } else {
+                V4L2_SEL_TGT_CROP_BOUNDS
+            };
+            // EINVAL merely means the target is not supported by the driver
+            features.selection = implemented(v4l2::ioctl(
+                fd,
+                v4l2::vidioc::VIDIOC_G_SELECTION,
+                &mut v4l2_sel as *mut _ as *mut std::os::raw::c_void,
+            ));
+        }
+
+        if !caps.capabilities.contains(Flags::STREAMING) {
+            return Ok(features);
+        }
+
+        unsafe {
+            let mut v4l2_reqbufs: v4l2_requestbuffers = mem::zeroed();
+            v4l2_reqbufs.type_ = buf_type as u32;
+            v4l2_reqbufs.count = 0;
+            v4l2_reqbufs.memory = Memory::Mmap as u32;
+            if v4l2::ioctl(
+                fd,
+                v4l2::vidioc::VIDIOC_REQBUFS,
+                &mut v4l2_reqbufs as *mut _ as *mut std::os::raw::c_void,
+            )
+            .is_ok()
+            {
+                features.requests = v4l2_reqbufs.capabilities & BUF_CAP_SUPPORTS_REQUESTS != 0;
+            }
+        }
+
+        unsafe {
+            // A count of zero only validates the format without allocating anything
+            let mut v4l2_create: v4l2_create_buffers = mem::zeroed();
+            v4l2_create.count = 0;
+            v4l2_create.memory = Memory::Mmap as u32;
+            v4l2_create.format.type_ = buf_type as u32;
+            features.create_bufs = v4l2::ioctl(
+                fd,
+                v4l2::vidioc::VIDIOC_G_FMT,
+                &mut v4l2_create.format as *mut _ as *mut std::os::raw::c_void,
+            )
+            .is_ok()
+                && implemented(v4l2::ioctl(
+                    fd,
+                    v4l2::vidioc::VIDIOC_CREATE_BUFS,
+                    &mut v4l2_create as *mut _ as *mut std::os::raw::c_void,
+                ));
+        }
+
+        Ok(features)
+    }
+}
+
+/// Returns the buffer type to probe buffer features with, if the device has a video queue
+///
+/// Memory-to-memory devices are probed on their output queue, which is the one taking requests
+/// for stateless codecs.
+pub(crate) fn buf_type(caps: Flags) -> Option<buffer::Type> {
+    if caps.contains(Flags::VIDEO_M2M_MPLANE) {
+        Some(buffer::Type::VideoOutputMplane)
+    } else if caps.contains(Flags::VIDEO_M2M) {
+        Some(buffer::Type::VideoOutput)
+    } else if caps.contains(Flags::VIDEO_CAPTURE_MPLANE) {
+        Some(buffer::Type::VideoCaptureMplane)
+    } else if caps.contains(Flags::VIDEO_CAPTURE) {
+        Some(buffer::Type::VideoCapture)
+    } else if caps.contains(Flags::VIDEO_OUTPUT_MPLANE) {
+        Some(buffer::Type::VideoOutputMplane)
+    } else if caps.contains(Flags::VIDEO_OUTPUT) {
+        Some(buffer::Type::VideoOutput)
+    } else {
+        None
+    }
+}
+
+/// Returns false if an ioctl failed because it is not implemented at all
+fn implemented(res: io::Result<()>) -> bool {
+    match res {
+        Ok(()) => true,
+        Err(e) => v4l2::errno(&e) != Some(libc::ENOTTY),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buf_type_of_caps() {
+        use buffer::Type;
+
+        assert!(matches!(
+            buf_type(Flags::VIDEO_CAPTURE_MPLANE),
+            Some(Type::VideoCaptureMplane)
+        ));
+        assert!(matches!(
+            buf_type(Flags::VIDEO_OUTPUT_MPLANE),
+            Some(Type::VideoOutputMplane)
+        ));
+        assert!(matches!(
+            buf_type(Flags::VIDEO_M2M),
+            Some(Type::VideoOutput)
+        ));
+        assert!(matches!(
+            buf_type(Flags::VIDEO_M2M_MPLANE),
+            Some(Type::VideoOutputMplane)
+        ));
+        assert!(buf_type(Flags::META_CAPTURE).is_none());
+    }
+}
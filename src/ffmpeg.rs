@@ -0,0 +1,172 @@
+//! Interop with ffmpeg-next, e.g. to encode captured frames into mp4 or mkv files
+//!
+//! This module is only available with the `ffmpeg` feature.
+
+use std::io;
+use std::time::Duration;
+
+use ffmpeg_next::format::Pixel;
+use ffmpeg_next::frame::Video;
+use ffmpeg_next::Rational;
+
+use crate::buffer::Metadata;
+use crate::format::{Format, FourCC};
+use crate::fraction::Fraction;
+use crate::frame::FrameView;
+
+/// Returns the ffmpeg pixel format of a fourcc and whether the chroma planes are swapped
+fn pixel(fourcc: &FourCC) -> Option<(Pixel, bool)> {
+    Some(match &fourcc.repr {
+        b"GREY" => (Pixel::GRAY8, false),
+        b"YUYV" => (Pixel::YUYV422, false),
+        b"UYVY" => (Pixel::UYVY422, false),
+        b"YVYU" => (Pixel::YVYU422, false),
+        b"RGBP" => (Pixel::RGB565LE, false),
+        b"RGB3" => (Pixel::RGB24, false),
+        b"BGR3" => (Pixel::BGR24, false),
+        b"AR24" => (Pixel::BGRA, false),
+        b"XR24" => (Pixel::BGRZ, false),
+        b"AB24" => (Pixel::RGBA, false),
+        b"XB24" => (Pixel::RGBZ, false),
+        b"NV12" | b"NM12" => (Pixel::NV12, false),
+        b"NV21" | b"NM21" => (Pixel::NV21, false),
+        b"NV16" | b"NM16" => (Pixel::NV16, false),
+        b"YU12" | b"YM12" => (Pixel::YUV420P, false),
+        b"YV12" | b"YM21" => (Pixel::YUV420P, true),
+        b"422P" | b"YM16" => (Pixel::YUV422P, false),
+        _ => return None,
+    })
+}
+
+/// Converts captured frames into ffmpeg video frames
+///
+/// Frames are copied into newly allocated ffmpeg frames, honoring the line padding on both
+/// sides. Presentation timestamps are derived from the buffer timestamps, relative to the first
+/// frame and expressed in units of the frame interval, so they can be passed to an encoder
+/// configured with `time_base()` as is. Frames dropped by the driver thus leave gaps in the
+/// timestamps instead of shifting all subsequent frames.
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::ffmpeg::FrameAdapter;
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+/// use v4l::video::Capture;
+///
+/// let dev = Device::new(0).unwrap();
+/// let fmt = dev.format().unwrap();
+/// let params = dev.params().unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+///
+/// let mut adapter = FrameAdapter::new(&fmt, params.interval).unwrap();
+/// println!("time base: {}", adapter.time_base());
+/// loop {
+///     let (buf, meta) = stream.next().unwrap();
+///     let frame = adapter.convert(buf, meta).unwrap();
+///     // encoder.send_frame(&frame)
+/// }
+/// ```
+pub struct FrameAdapter {
+    format: Format,
+    pixel: Pixel,
+    swap_chroma: bool,
+    interval: Fraction,
+    start: Option<(Duration, u32)>,
+}
+
+impl FrameAdapter {
+    /// Returns an adapter for a given format
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - Format of the captured frames
+    /// * `interval` - Time per frame as negotiated with the device, see `Capture::params`
+    pub fn new(fmt: &Format, interval: Fraction) -> io::Result<Self> {
+        let (pixel, swap_chroma) = pixel(&fmt.fourcc).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("no ffmpeg pixel format for {}", fmt.fourcc),
+            )
+        })?;
+        if interval.numerator == 0 || interval.denominator == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid frame interval: {}", interval),
+            ));
+        }
+
+        Ok(FrameAdapter {
+            format: *fmt,
+            pixel,
+            swap_chroma,
+            interval,
+            start: None,
+        })
+    }
+
+    /// Returns the ffmpeg pixel format of the frames
+    pub fn pixel(&self) -> Pixel {
+        self.pixel
+    }
+
+    /// Returns the time base of the presentation timestamps, i.e. the frame interval
+    pub fn time_base(&self) -> Rational {
+        Rational::new(
+            self.interval.numerator as i32,
+            self.interval.denominator as i32,
+        )
+    }
+
+    /// Returns the nominal frame rate
+    pub fn frame_rate(&self) -> Rational {
+        self.time_base().invert()
+    }
+
+    /// Forgets the first frame, so the timestamps of the next frame start at zero again
+    pub fn reset(&mut self) {
+        self.start = None;
+    }
+
+    /// Copies a captured frame into a new ffmpeg frame
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Buffer data
+    /// * `meta` - Buffer metadata
+    pub fn convert(&mut self, buf: &[u8], meta: &Metadata) -> io::Result<Video> {
+        let view = FrameView::new(buf, &self.format)?;
+        let mut frame = Video::new(self.pixel, self.format.width, self.format.height);
+
+        for (i, plane) in view.planes().iter().enumerate() {
+            let index = match (self.swap_chroma, i) {
+                (true, 1) => 2,
+                (true, 2) => 1,
+                _ => i,
+            };
+            let stride = frame.stride(index);
+            let data = frame.data_mut(index);
+            for (y, line) in plane.lines().enumerate() {
+                data[y * stride..y * stride + line.len()].copy_from_slice(line);
+            }
+        }
+
+        frame.set_pts(Some(self.pts(meta)));
+        Ok(frame)
+    }
+
+    fn pts(&mut self, meta: &Metadata) -> i64 {
+        let timestamp = Duration::from(meta.timestamp);
+        let (start, sequence) = *self.start.get_or_insert((timestamp, meta.sequence));
+
+        if timestamp == Duration::from_secs(0) {
+            // drivers without timestamps, count frames instead
+            return meta.sequence.wrapping_sub(sequence) as i64;
+        }
+
+        let elapsed = timestamp.checked_sub(start).unwrap_or_default().as_micros();
+        let unit = self.interval.numerator as u128 * 1_000_000;
+        ((elapsed * self.interval.denominator as u128 + unit / 2) / unit) as i64
+    }
+}
@@ -1,5 +1,48 @@
 use std::{fmt, str};
 
+/// Bit set in the last character of big-endian format variants, see v4l2_fourcc_be()
+const BE_FLAG: u8 = 0x80;
+
+/// Formats with 16-bit samples stored in little-endian byte order
+const LITTLE_ENDIAN: &[&[u8; 4]] = &[
+    b"RGBP", b"RGBO", b"AR15", b"XR15", b"AR12", b"XR12", b"Y10 ", b"Y12 ", b"Y14 ", b"Y16 ",
+];
+
+/// Formats with 16-bit samples stored in big-endian byte order without carrying the BE flag
+const BIG_ENDIAN: &[&[u8; 4]] = &[b"RGBR", b"RGBQ"];
+
+/// Byte order of multi-byte samples
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Returns the byte order of the target
+    pub fn native() -> Self {
+        if cfg!(target_endian = "big") {
+            ByteOrder::Big
+        } else {
+            ByteOrder::Little
+        }
+    }
+
+    /// Returns true if samples can be read without swapping bytes on the target
+    pub fn is_native(&self) -> bool {
+        *self == ByteOrder::native()
+    }
+}
+
+impl fmt::Display for ByteOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteOrder::Little => write!(f, "little-endian"),
+            ByteOrder::Big => write!(f, "big-endian"),
+        }
+    }
+}
+
 #[derive(Debug, Default, Copy, Clone, Eq)]
 /// Four character code representing a pixelformat
 pub struct FourCC {
@@ -26,23 +69,90 @@ impl FourCC {
 
     /// Returns the string representation of a four character code
     ///
+    /// This fails for big-endian variants, as their flag makes the last character invalid UTF-8.
+    /// Call `without_be` first, or format the code with `Display`, which appends "-BE".
+    ///
     /// # Example
     ///
     /// ```
     /// use v4l::format::FourCC;
     /// let fourcc = FourCC::new(b"YUYV");
     /// let str = fourcc.str().unwrap();
+    ///
+    /// let fourcc = FourCC::new_be(b"Y16 ");
+    /// assert!(fourcc.str().is_err());
+    /// assert_eq!(fourcc.without_be().str(), Ok("Y16 "));
     /// ```
     pub fn str(&self) -> Result<&str, str::Utf8Error> {
         str::from_utf8(&self.repr)
     }
+
+    /// Returns the big-endian variant of a pixelformat, e.g. "Y16 " for "Y16 -BE"
+    ///
+    /// # Arguments
+    ///
+    /// * `repr` - Four characters as raw bytes
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::format::FourCC;
+    /// let fourcc = FourCC::new_be(b"Y16 ");
+    /// assert_eq!(fourcc.to_string(), "Y16 -BE");
+    /// ```
+    pub fn new_be(repr: &[u8; 4]) -> FourCC {
+        let mut fourcc = FourCC::new(repr);
+        fourcc.repr[3] |= BE_FLAG;
+        fourcc
+    }
+
+    /// Returns true if this is the big-endian variant of a pixelformat (-BE suffix)
+    pub fn is_be(&self) -> bool {
+        self.repr[3] & BE_FLAG != 0
+    }
+
+    /// Returns the pixelformat without the big-endian flag
+    pub fn without_be(&self) -> FourCC {
+        let mut fourcc = *self;
+        fourcc.repr[3] &= !BE_FLAG;
+        fourcc
+    }
+
+    /// Returns the byte order of the samples for known formats with multi-byte samples
+    ///
+    /// This covers formats with the big-endian flag as well as formats which encode the byte
+    /// order in their name, such as RGB565 ("RGBP") and RGB565X ("RGBR"). None is returned for
+    /// formats with 8-bit samples and unknown formats.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::format::fourcc::ByteOrder;
+    /// use v4l::format::FourCC;
+    ///
+    /// assert_eq!(FourCC::new(b"RGBR").byte_order(), Some(ByteOrder::Big));
+    /// assert_eq!(FourCC::new(b"Y16 ").byte_order(), Some(ByteOrder::Little));
+    /// ```
+    pub fn byte_order(&self) -> Option<ByteOrder> {
+        if self.is_be() || BIG_ENDIAN.contains(&&self.repr) {
+            Some(ByteOrder::Big)
+        } else if LITTLE_ENDIAN.contains(&&self.repr) {
+            Some(ByteOrder::Little)
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for FourCC {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let string = str::from_utf8(&self.repr);
+        let base = self.without_be();
+        let string = str::from_utf8(&base.repr);
         if let Ok(string) = string {
             write!(f, "{}", string)?;
+            if self.is_be() {
+                write!(f, "-BE")?;
+            }
         }
         Ok(())
     }
@@ -54,6 +164,8 @@ impl PartialEq for FourCC {
     }
 }
 
+// The first character is always stored in the least significant byte, as done by v4l2_fourcc(),
+// so the conversions do not depend on the byte order of the target.
 impl From<u32> for FourCC {
     fn from(code: u32) -> Self {
         let mut repr: [u8; 4] = [0; 4];
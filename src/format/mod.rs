@@ -15,6 +15,9 @@ pub use field::FieldOrder;
 pub mod fourcc;
 pub use fourcc::FourCC;
 
+pub mod mplane;
+pub use mplane::{FormatMplane, PlaneFormat};
+
 pub mod quantization;
 pub use quantization::Quantization;
 
@@ -105,6 +108,15 @@ impl Format {
     }
 }
 
+/// Format of a single- or multi-planar queue
+#[derive(Debug, Clone)]
+pub enum QueueFormat {
+    /// Format of a single-planar queue, e.g. `Type::VideoCapture`
+    Single(Format),
+    /// Format of a multi-planar queue, e.g. `Type::VideoCaptureMplane`
+    Multi(FormatMplane),
+}
+
 impl fmt::Display for Format {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "width          : {}", self.width)?;
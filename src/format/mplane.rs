@@ -0,0 +1,272 @@
+use std::{convert::TryFrom, fmt, io, mem};
+
+use crate::format::{
+    Colorspace, FieldOrder, Flags, Format, FourCC, Quantization, TransferFunction,
+};
+use crate::v4l_sys::*;
+
+/// Maximum number of planes per buffer, see VIDEO_MAX_PLANES
+pub const MAX_PLANES: usize = 8;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+/// Memory layout of a single plane
+pub struct PlaneFormat {
+    /// bytes per line
+    pub stride: u32,
+    /// maximum number of bytes required to store the plane
+    pub size: u32,
+}
+
+#[derive(Debug, Clone)]
+/// Streaming format (multi-planar)
+///
+/// Formats such as NV12M or YUV420M store each plane in a separate buffer, with a stride and
+/// size of its own.
+pub struct FormatMplane {
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+    /// pixelformat code
+    pub fourcc: FourCC,
+    /// field order for interlacing
+    pub field_order: FieldOrder,
+
+    /// per-plane layout
+    planes: Vec<PlaneFormat>,
+
+    /// flags set by the application or driver
+    pub flags: Flags,
+
+    /// supplements the pixelformat (fourcc) information
+    pub colorspace: Colorspace,
+    /// the way colors are mapped
+    pub quantization: Quantization,
+    /// the transfer function for the colorspace
+    pub transfer: TransferFunction,
+}
+
+impl FormatMplane {
+    /// Returns a multi-planar format
+    ///
+    /// The plane layouts are left at zero so the driver fills them in.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width in pixels
+    /// * `height` - Height in pixels
+    /// * `fourcc` - Four character code (pixelformat)
+    /// * `num_planes` - Number of planes, between 1 and MAX_PLANES
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::format::{FormatMplane, FourCC};
+    /// let fmt = FormatMplane::new(1280, 720, FourCC::new(b"NM12"), 2).unwrap();
+    /// ```
+    pub fn new(width: u32, height: u32, fourcc: FourCC, num_planes: usize) -> io::Result<Self> {
+        let mut fmt = FormatMplane {
+            width,
+            height,
+            fourcc,
+            field_order: FieldOrder::Any,
+            planes: Vec::new(),
+            flags: Flags::empty(),
+            colorspace: Colorspace::Default,
+            quantization: Quantization::Default,
+            transfer: TransferFunction::Default,
+        };
+        fmt.set_planes(vec![PlaneFormat::default(); num_planes])?;
+        Ok(fmt)
+    }
+
+    /// Returns the layout of all planes
+    pub fn planes(&self) -> &[PlaneFormat] {
+        &self.planes
+    }
+
+    /// Returns the layout of a single plane
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index
+    pub fn plane(&self, index: usize) -> Option<&PlaneFormat> {
+        self.planes.get(index)
+    }
+
+    /// Replaces the layout of all planes, which also changes the number of planes
+    ///
+    /// # Arguments
+    ///
+    /// * `planes` - Plane layouts, between 1 and MAX_PLANES
+    pub fn set_planes(&mut self, planes: Vec<PlaneFormat>) -> io::Result<()> {
+        if planes.is_empty() || planes.len() > MAX_PLANES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid number of planes: {}", planes.len()),
+            ));
+        }
+
+        self.planes = planes;
+        Ok(())
+    }
+
+    /// Replaces the layout of a single plane
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index, must be smaller than the number of planes
+    /// * `plane` - Plane layout
+    pub fn set_plane(&mut self, index: usize, plane: PlaneFormat) -> io::Result<()> {
+        let count = self.planes.len();
+        match self.planes.get_mut(index) {
+            Some(slot) => {
+                *slot = plane;
+                Ok(())
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid plane index {} for {} planes", index, count),
+            )),
+        }
+    }
+
+    /// Returns the maximum number of bytes required to store an image across all planes
+    pub fn size(&self) -> u32 {
+        self.planes.iter().map(|plane| plane.size).sum()
+    }
+}
+
+impl fmt::Display for FormatMplane {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "width          : {}", self.width)?;
+        writeln!(f, "height         : {}", self.height)?;
+        writeln!(f, "fourcc         : {}", self.fourcc)?;
+        writeln!(f, "field          : {}", self.field_order)?;
+        for (i, plane) in self.planes.iter().enumerate() {
+            writeln!(
+                f,
+                "plane {}        : stride {}, size {}",
+                i, plane.stride, plane.size
+            )?;
+        }
+        writeln!(f, "colorspace     : {}", self.colorspace)?;
+        writeln!(f, "quantization   : {}", self.quantization)?;
+        writeln!(f, "transfer       : {}", self.transfer)?;
+        Ok(())
+    }
+}
+
+impl From<v4l2_pix_format_mplane> for FormatMplane {
+    fn from(fmt: v4l2_pix_format_mplane) -> Self {
+        // the struct is packed, so copy the array out before accessing its elements
+        let plane_fmt = fmt.plane_fmt;
+        // keep at least one plane, like set_planes() requires
+        let num_planes = (fmt.num_planes as usize).clamp(1, MAX_PLANES);
+
+        FormatMplane {
+            width: fmt.width,
+            height: fmt.height,
+            fourcc: FourCC::from(fmt.pixelformat),
+            field_order: FieldOrder::try_from(fmt.field).expect("Invalid field order"),
+            planes: plane_fmt[..num_planes]
+                .iter()
+                .map(|plane| PlaneFormat {
+                    stride: plane.bytesperline,
+                    size: plane.sizeimage,
+                })
+                .collect(),
+            flags: Flags::from(fmt.flags as u32),
+            colorspace: Colorspace::try_from(fmt.colorspace).expect("Invalid colorspace"),
+            quantization: Quantization::try_from(fmt.quantization as u32)
+                .expect("Invalid quantization"),
+            transfer: TransferFunction::try_from(fmt.xfer_func as u32)
+                .expect("Invalid transfer function"),
+        }
+    }
+}
+
+impl From<v4l2_pix_format_mplane> for Format {
+    /// Summarizes a multi-planar format: the stride is the one of the first plane, the size
+    /// covers all planes
+    fn from(fmt: v4l2_pix_format_mplane) -> Self {
+        let fmt = FormatMplane::from(fmt);
+        Format {
+            width: fmt.width,
+            height: fmt.height,
+            fourcc: fmt.fourcc,
+            field_order: fmt.field_order,
+            stride: fmt.planes[0].stride,
+            size: fmt.planes.iter().map(|plane| plane.size).sum(),
+            flags: fmt.flags,
+            colorspace: fmt.colorspace,
+            quantization: fmt.quantization,
+            transfer: fmt.transfer,
+        }
+    }
+}
+
+impl From<FormatMplane> for v4l2_pix_format_mplane {
+    fn from(format: FormatMplane) -> Self {
+        let mut fmt: v4l2_pix_format_mplane;
+        unsafe {
+            fmt = mem::zeroed();
+        }
+
+        let mut plane_fmt: [v4l2_plane_pix_format; MAX_PLANES] = unsafe { mem::zeroed() };
+        for (dst, src) in plane_fmt.iter_mut().zip(format.planes.iter()) {
+            dst.bytesperline = src.stride;
+            dst.sizeimage = src.size;
+        }
+
+        fmt.width = format.width;
+        fmt.height = format.height;
+        fmt.pixelformat = format.fourcc.into();
+        fmt.field = format.field_order as u32;
+        fmt.colorspace = format.colorspace as u32;
+        fmt.plane_fmt = plane_fmt;
+        fmt.num_planes = format.planes.len() as u8;
+        fmt.flags = Into::<u32>::into(format.flags) as u8;
+        fmt.quantization = format.quantization as u8;
+        fmt.xfer_func = format.transfer as u8;
+        fmt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plane_count_from_driver() {
+        for (num_planes, expected) in &[
+            (0, 1),
+            (1, 1),
+            (2, 2),
+            (MAX_PLANES, MAX_PLANES),
+            (255, MAX_PLANES),
+        ] {
+            let mut raw: v4l2_pix_format_mplane = unsafe { mem::zeroed() };
+            raw.num_planes = *num_planes as u8;
+            let fmt = FormatMplane::from(raw);
+            assert_eq!(fmt.planes().len(), *expected, "{}", num_planes);
+        }
+    }
+
+    #[test]
+    fn single_planar_summary() {
+        let mut raw: v4l2_pix_format_mplane = unsafe { mem::zeroed() };
+        raw.width = 1920;
+        raw.height = 1080;
+        raw.num_planes = 2;
+        raw.plane_fmt[0].bytesperline = 1920;
+        raw.plane_fmt[0].sizeimage = 1920 * 1080;
+        raw.plane_fmt[1].bytesperline = 1920;
+        raw.plane_fmt[1].sizeimage = 1920 * 540;
+
+        let fmt = Format::from(raw);
+        assert_eq!((fmt.width, fmt.height), (1920, 1080));
+        assert_eq!(fmt.stride, 1920);
+        assert_eq!(fmt.size, 1920 * 1080 * 3 / 2);
+    }
+}
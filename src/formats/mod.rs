@@ -0,0 +1,4 @@
+//! Simple container formats to store captured frames without external dependencies
+
+pub mod pnm;
+pub mod y4m;
@@ -0,0 +1,147 @@
+//! Binary PPM/PGM images, handy to inspect single frames while debugging a capture pipeline
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::format::{Format, FourCC};
+
+/// Writes a frame as binary PPM (RGB) or PGM (greyscale) image
+///
+/// Supported pixel formats are RGB3 and BGR3, which result in a PPM image, as well as GREY,
+/// which results in a PGM image. Line padding (stride) is removed.
+///
+/// # Arguments
+///
+/// * `dest` - Destination of the image data
+/// * `buf` - Frame data
+/// * `fmt` - Format of the frame
+///
+/// # Example
+///
+/// ```
+/// use v4l::format::{Format, FourCC};
+/// use v4l::formats::pnm;
+///
+/// let fmt = Format::new(2, 2, FourCC::new(b"GREY"));
+/// let mut image = Vec::new();
+/// pnm::write(&mut image, &[0, 64, 128, 255], &fmt).unwrap();
+/// assert!(image.starts_with(b"P5\n2 2\n255\n"));
+/// ```
+pub fn write<W: Write>(mut dest: W, buf: &[u8], fmt: &Format) -> io::Result<()> {
+    let (magic, bpp) = if fmt.fourcc == FourCC::new(b"RGB3") || fmt.fourcc == FourCC::new(b"BGR3") {
+        ("P6", 3)
+    } else if fmt.fourcc == FourCC::new(b"GREY") {
+        ("P5", 1)
+    } else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("unsupported pixel format for pnm: {}", fmt.fourcc),
+        ));
+    };
+
+    let width = fmt.width as usize * bpp;
+    let stride = (fmt.stride as usize).max(width);
+    let height = fmt.height as usize;
+    if height > 0 && buf.len() < stride * (height - 1) + width {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame too small: {} bytes", buf.len()),
+        ));
+    }
+
+    write!(dest, "{}\n{} {}\n255\n", magic, fmt.width, fmt.height)?;
+
+    let swap = fmt.fourcc == FourCC::new(b"BGR3");
+    let mut line = Vec::with_capacity(width);
+    for row in 0..height {
+        let src = &buf[row * stride..row * stride + width];
+        if swap {
+            line.clear();
+            for px in src.chunks_exact(3) {
+                line.extend_from_slice(&[px[2], px[1], px[0]]);
+            }
+            dest.write_all(&line)?;
+        } else {
+            dest.write_all(src)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes a frame to a PPM/PGM file, see `write` for the supported formats
+///
+/// # Arguments
+///
+/// * `path` - Path of the image file, which is created or truncated
+/// * `buf` - Frame data
+/// * `fmt` - Format of the frame
+pub fn save<P: AsRef<Path>>(path: P, buf: &[u8], fmt: &Format) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    write(&mut file, buf, fmt)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(width: u32, height: u32, fourcc: &[u8; 4], stride: u32) -> Format {
+        let mut fmt = Format::new(width, height, FourCC::new(fourcc));
+        fmt.stride = stride;
+        fmt
+    }
+
+    #[test]
+    fn write_rgb() {
+        // two pixels per line, padded to 8 bytes
+        let buf = [
+            1, 2, 3, 4, 5, 6, 0xee, 0xee, //
+            7, 8, 9, 10, 11, 12, 0xee, 0xee,
+        ];
+        let mut image = Vec::new();
+        write(&mut image, &buf, &format(2, 2, b"RGB3", 8)).unwrap();
+
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn write_bgr() {
+        let buf = [
+            3, 2, 1, 6, 5, 4, 0xee, //
+            9, 8, 7, 12, 11, 10, 0xee,
+        ];
+        let mut image = Vec::new();
+        write(&mut image, &buf, &format(2, 2, b"BGR3", 7)).unwrap();
+
+        let mut expected = b"P6\n2 2\n255\n".to_vec();
+        expected.extend_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn write_grey() {
+        // the last line does not need to be padded
+        let buf = [10, 20, 30, 0xee, 40, 50, 60];
+        let mut image = Vec::new();
+        write(&mut image, &buf, &format(3, 2, b"GREY", 4)).unwrap();
+
+        let mut expected = b"P5\n3 2\n255\n".to_vec();
+        expected.extend_from_slice(&[10, 20, 30, 40, 50, 60]);
+        assert_eq!(image, expected);
+    }
+
+    #[test]
+    fn write_invalid() {
+        let mut image = Vec::new();
+        let err = write(&mut image, &[0; 8], &format(2, 2, b"YUYV", 0)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = write(&mut image, &[0; 11], &format(2, 2, b"RGB3", 0)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(image.is_empty());
+    }
+}
@@ -0,0 +1,530 @@
+//! YUV4MPEG2 (y4m) streams, as understood by ffmpeg, mpv and most other video tools
+
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read, Write};
+use std::str;
+
+use crate::buffer::Metadata;
+use crate::format::{Format, FourCC, Quantization};
+use crate::fraction::Fraction;
+use crate::sink::Sink;
+
+/// Largest width or height accepted when reading a stream header
+const MAX_DIMENSION: u32 = 16384;
+
+/// Longest stream header or frame header line accepted when reading a stream
+const MAX_LINE: u64 = 4096;
+
+/// Chroma subsampling of the frames in a stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Chroma {
+    /// Planar 4:2:0 (I420), V4L2 fourcc YU12
+    C420,
+    /// Planar 4:2:2, V4L2 fourcc 422P
+    C422,
+    /// Luma only, V4L2 fourcc GREY
+    Mono,
+}
+
+impl Chroma {
+    /// Returns the subsampling matching a V4L2 pixel format, if there is one
+    ///
+    /// Only planar formats with the planes in Y, U, V order can be stored in y4m streams.
+    pub fn from_fourcc(fourcc: FourCC) -> Option<Self> {
+        if fourcc == FourCC::new(b"YU12") {
+            Some(Chroma::C420)
+        } else if fourcc == FourCC::new(b"422P") {
+            Some(Chroma::C422)
+        } else if fourcc == FourCC::new(b"GREY") {
+            Some(Chroma::Mono)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the V4L2 pixel format matching this subsampling
+    pub fn fourcc(&self) -> FourCC {
+        match self {
+            Chroma::C420 => FourCC::new(b"YU12"),
+            Chroma::C422 => FourCC::new(b"422P"),
+            Chroma::Mono => FourCC::new(b"GREY"),
+        }
+    }
+
+    /// Returns the width and height of a single chroma plane
+    fn plane_size(&self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Chroma::C420 => (width / 2 + width % 2, height / 2 + height % 2),
+            Chroma::C422 => (width / 2 + width % 2, height),
+            Chroma::Mono => (0, 0),
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        match self {
+            // V4L2 does not tell the chroma siting, so leave it to the default of the reader
+            Chroma::C420 => "420",
+            Chroma::C422 => "422",
+            Chroma::Mono => "mono",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "420" | "420jpeg" | "420paldv" | "420mpeg2" => Some(Chroma::C420),
+            "422" => Some(Chroma::C422),
+            "mono" => Some(Chroma::Mono),
+            _ => None,
+        }
+    }
+}
+
+/// Stream header
+#[derive(Debug, Copy, Clone)]
+pub struct Header {
+    /// Frame width in pixels
+    pub width: u32,
+    /// Frame height in pixels
+    pub height: u32,
+    /// Frame rate in frames per second
+    pub fps: Fraction,
+    /// Chroma subsampling
+    pub chroma: Chroma,
+    /// Quantization range, written as the XCOLORRANGE extension if known
+    pub quantization: Quantization,
+}
+
+impl Header {
+    /// Returns a header matching the frames of a stream
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - Format of the stream, must be YU12, 422P or GREY
+    /// * `interval` - Frame interval as found in the stream parameters, must not be zero
+    ///
+    /// Drivers which do not report a frame interval leave it zeroed. Fails with an error of kind
+    /// `InvalidInput` in that case, the caller has to pick a frame rate instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::format::{Format, FourCC};
+    /// use v4l::formats::y4m::Header;
+    /// use v4l::fraction::Fraction;
+    ///
+    /// let fmt = Format::new(640, 480, FourCC::new(b"YU12"));
+    /// let header = Header::new(&fmt, Fraction::new(1, 30));
+    /// ```
+    pub fn new(fmt: &Format, interval: Fraction) -> io::Result<Self> {
+        let chroma = Chroma::from_fourcc(fmt.fourcc).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported pixel format for y4m: {}", fmt.fourcc),
+            )
+        })?;
+        if interval.numerator == 0 || interval.denominator == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid frame interval for y4m: {}", interval),
+            ));
+        }
+
+        Ok(Header {
+            width: fmt.width,
+            height: fmt.height,
+            fps: Fraction::new(interval.denominator, interval.numerator),
+            chroma,
+            quantization: fmt.quantization,
+        })
+    }
+
+    /// Returns the size of a single tightly packed frame in bytes
+    ///
+    /// Fails if the size does not fit into memory.
+    pub fn frame_size(&self) -> io::Result<usize> {
+        let (width, height) = self.chroma.plane_size(self.width, self.height);
+        let luma = (self.width as u64).checked_mul(self.height as u64);
+        let chroma = (width as u64)
+            .checked_mul(height as u64)
+            .and_then(|size| size.checked_mul(2));
+
+        luma.zip(chroma)
+            .and_then(|(luma, chroma)| luma.checked_add(chroma))
+            .and_then(|size| usize::try_from(size).ok())
+            .ok_or_else(|| invalid_data("frame size overflows"))
+    }
+
+    fn parse(line: &str) -> io::Result<Self> {
+        let mut tokens = line.split_ascii_whitespace();
+        if tokens.next() != Some("YUV4MPEG2") {
+            return Err(invalid_data("missing YUV4MPEG2 signature"));
+        }
+
+        let mut header = Header {
+            width: 0,
+            height: 0,
+            fps: Fraction::new(0, 0),
+            chroma: Chroma::C420,
+            quantization: Quantization::Default,
+        };
+
+        for token in tokens {
+            // the tag may be a multibyte character
+            let mut chars = token.chars();
+            let tag = chars.next();
+            let value = chars.as_str();
+            match tag {
+                Some('W') => header.width = parse_number(value)?,
+                Some('H') => header.height = parse_number(value)?,
+                Some('F') => {
+                    let mut parts = value.splitn(2, ':');
+                    let num = parse_number(parts.next().unwrap_or(""))?;
+                    let denom = parse_number(parts.next().unwrap_or(""))?;
+                    header.fps = Fraction::new(num, denom);
+                }
+                Some('C') => {
+                    header.chroma = Chroma::from_tag(value).ok_or_else(|| {
+                        invalid_data(&format!("unsupported y4m colorspace: {}", value))
+                    })?
+                }
+                Some('X') => match value {
+                    "COLORRANGE=FULL" => header.quantization = Quantization::FullRange,
+                    "COLORRANGE=LIMITED" => header.quantization = Quantization::LimitedRange,
+                    _ => {}
+                },
+                // interlacing, aspect ratio and comments are not interpreted
+                _ => {}
+            }
+        }
+
+        if header.width == 0 || header.height == 0 {
+            return Err(invalid_data("missing frame size"));
+        }
+        if header.width > MAX_DIMENSION || header.height > MAX_DIMENSION {
+            return Err(invalid_data(&format!(
+                "frame size too large: {}x{}",
+                header.width, header.height
+            )));
+        }
+
+        Ok(header)
+    }
+}
+
+/// Writes frames to a y4m stream
+pub struct Writer<W: Write> {
+    inner: W,
+    header: Header,
+    stride: u32,
+}
+
+impl<W: Write> Writer<W> {
+    /// Returns a writer after writing the stream header
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Destination, e.g. a file or the stdin of an ffmpeg process
+    /// * `header` - Stream header, its frame rate must not be zero
+    pub fn new(mut inner: W, header: Header) -> io::Result<Self> {
+        if header.fps.numerator == 0 || header.fps.denominator == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid frame rate for y4m: {}", header.fps),
+            ));
+        }
+
+        write!(
+            inner,
+            "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{}",
+            header.width,
+            header.height,
+            header.fps.numerator,
+            header.fps.denominator,
+            header.chroma.tag()
+        )?;
+        match header.quantization {
+            Quantization::FullRange => write!(inner, " XCOLORRANGE=FULL")?,
+            Quantization::LimitedRange => write!(inner, " XCOLORRANGE=LIMITED")?,
+            Quantization::Default => {}
+        }
+        writeln!(inner)?;
+
+        Ok(Writer {
+            inner,
+            header,
+            stride: header.width,
+        })
+    }
+
+    /// Sets the luma line stride of the frames handed to write_frame()
+    ///
+    /// Drivers may pad lines, in which case the stride (bytesperline) is larger than the width.
+    /// The chroma planes are expected to use half the luma stride.
+    ///
+    /// # Arguments
+    ///
+    /// * `stride` - Luma line stride in bytes
+    pub fn set_stride(&mut self, stride: u32) {
+        self.stride = stride.max(self.header.width);
+    }
+
+    /// Returns the stream header
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Writes a single frame
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Planar frame data
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let (chroma_width, chroma_height) = self
+            .header
+            .chroma
+            .plane_size(self.header.width, self.header.height);
+        let chroma_stride = if self.stride == self.header.width {
+            chroma_width
+        } else {
+            self.stride / 2
+        };
+
+        let planes = [
+            (self.header.width, self.header.height, self.stride),
+            (chroma_width, chroma_height, chroma_stride),
+            (chroma_width, chroma_height, chroma_stride),
+        ];
+        let size: usize = planes
+            .iter()
+            .map(|(_, height, stride)| *height as usize * *stride as usize)
+            .sum();
+        if frame.len() < size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("frame too small: {} < {} bytes", frame.len(), size),
+            ));
+        }
+
+        self.inner.write_all(b"FRAME\n")?;
+
+        let mut offset = 0;
+        for (width, height, stride) in planes.iter() {
+            if width == stride {
+                let len = *width as usize * *height as usize;
+                self.inner.write_all(&frame[offset..offset + len])?;
+                offset += len;
+            } else {
+                for _ in 0..*height {
+                    self.inner
+                        .write_all(&frame[offset..offset + *width as usize])?;
+                    offset += *stride as usize;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the underlying writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Sink for Writer<W> {
+    fn write_frame(&mut self, buf: &[u8], _meta: &Metadata) -> io::Result<()> {
+        Writer::write_frame(self, buf)
+    }
+}
+
+/// Reads frames from a y4m stream
+pub struct Reader<R: BufRead> {
+    inner: R,
+    header: Header,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Returns a reader after parsing the stream header
+    ///
+    /// # Arguments
+    ///
+    /// * `inner` - Source of the stream
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::format::{Format, FourCC};
+    /// use v4l::formats::y4m::{Header, Reader, Writer};
+    /// use v4l::fraction::Fraction;
+    ///
+    /// let fmt = Format::new(4, 2, FourCC::new(b"YU12"));
+    /// let header = Header::new(&fmt, Fraction::new(1, 30)).unwrap();
+    /// let mut writer = Writer::new(Vec::new(), header).unwrap();
+    /// writer.write_frame(&[128; 12]).unwrap();
+    ///
+    /// let data = writer.into_inner();
+    /// let mut reader = Reader::new(&data[..]).unwrap();
+    /// let mut frame = Vec::new();
+    /// assert!(reader.read_frame(&mut frame).unwrap());
+    /// assert_eq!(frame, vec![128; 12]);
+    /// assert!(!reader.read_frame(&mut frame).unwrap());
+    /// ```
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut line = String::new();
+        (&mut inner).take(MAX_LINE).read_line(&mut line)?;
+        let header = Header::parse(&line)?;
+
+        Ok(Reader { inner, header })
+    }
+
+    /// Returns the stream header
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Reads the next frame into the buffer
+    ///
+    /// Returns false if the end of the stream was reached.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Destination, resized to the frame size
+    pub fn read_frame(&mut self, buf: &mut Vec<u8>) -> io::Result<bool> {
+        let mut line = Vec::new();
+        if (&mut self.inner)
+            .take(MAX_LINE)
+            .read_until(b'\n', &mut line)?
+            == 0
+        {
+            return Ok(false);
+        }
+        if !line.starts_with(b"FRAME") || !line.ends_with(b"\n") {
+            return Err(invalid_data("missing FRAME marker"));
+        }
+
+        let size = self.header.frame_size()?;
+        buf.clear();
+        buf.try_reserve_exact(size)
+            .map_err(|_| io::Error::new(io::ErrorKind::OutOfMemory, "frame too large"))?;
+        buf.resize(size, 0);
+        self.inner.read_exact(buf)?;
+        Ok(true)
+    }
+}
+
+fn parse_number(value: &str) -> io::Result<u32> {
+    value
+        .parse()
+        .map_err(|_| invalid_data(&format!("invalid number in y4m header: {}", value)))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_header() {
+        let header =
+            Header::parse("YUV4MPEG2 W640 H480 F30000:1001 Ip A1:1 C422 XCOLORRANGE=FULL\n")
+                .unwrap();
+        assert_eq!(header.width, 640);
+        assert_eq!(header.height, 480);
+        assert_eq!(header.fps.numerator, 30000);
+        assert_eq!(header.fps.denominator, 1001);
+        assert_eq!(header.chroma, Chroma::C422);
+        assert_eq!(header.frame_size().unwrap(), 640 * 480 * 2);
+    }
+
+    #[test]
+    fn write_header() {
+        let mut fmt = Format::new(4, 2, FourCC::new(b"YU12"));
+        fmt.quantization = Quantization::LimitedRange;
+        let header = Header::new(&fmt, Fraction::new(1001, 30000)).unwrap();
+        let data = Writer::new(Vec::new(), header).unwrap().into_inner();
+        assert_eq!(
+            data,
+            b"YUV4MPEG2 W4 H2 F30000:1001 Ip A1:1 C420 XCOLORRANGE=LIMITED\n"
+        );
+
+        let header = Header::parse(str::from_utf8(&data).unwrap()).unwrap();
+        assert_eq!(header.chroma, Chroma::C420);
+        assert_eq!(header.fps.numerator, 30000);
+    }
+
+    #[test]
+    fn write_zero_interval() {
+        let fmt = Format::new(4, 2, FourCC::new(b"YU12"));
+        for interval in &[
+            Fraction::new(0, 0),
+            Fraction::new(0, 30),
+            Fraction::new(1, 0),
+        ] {
+            let err = Header::new(&fmt, *interval).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+
+        // headers built by hand are checked as well
+        let mut header = Header::new(&fmt, Fraction::new(1, 30)).unwrap();
+        header.fps = Fraction::new(0, 1);
+        let err = Writer::new(Vec::new(), header).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn parse_malformed_header() {
+        for line in &[
+            "",
+            "YUV4MPEG W640 H480",
+            "YUV4MPEG2 W640",
+            "YUV4MPEG2 W-1 H480",
+            "YUV4MPEG2 W640 H480 F30",
+            "YUV4MPEG2 W640 H480 Cfoo",
+            "YUV4MPEG2 W4294967295 H4294967295",
+        ] {
+            assert!(Header::parse(line).is_err(), "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn parse_multibyte_tags() {
+        // unknown tags are ignored, whatever their first character
+        let header = Header::parse("YUV4MPEG2 W640 H480 \u{e9}1 \u{1f600}\n").unwrap();
+        assert_eq!(header.width, 640);
+    }
+
+    #[test]
+    fn frame_size_overflow() {
+        let header = Header {
+            width: u32::MAX,
+            height: u32::MAX,
+            fps: Fraction::new(30, 1),
+            chroma: Chroma::C420,
+            quantization: Quantization::Default,
+        };
+        assert!(header.frame_size().is_err());
+    }
+
+    #[test]
+    fn read_malformed_frames() {
+        let header = b"YUV4MPEG2 W4 H2 F30:1 Cmono\n";
+
+        let data = [&header[..], b"FRAME\n\x00\x01"].concat();
+        let mut reader = Reader::new(&data[..]).unwrap();
+        let err = reader.read_frame(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let data = [&header[..], b"FRAMES\x00\x01"].concat();
+        let mut reader = Reader::new(&data[..]).unwrap();
+        let err = reader.read_frame(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let data = [&header[..], b"JUNK\n01234567"].concat();
+        let mut reader = Reader::new(&data[..]).unwrap();
+        let err = reader.read_frame(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
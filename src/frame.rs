@@ -0,0 +1,452 @@
+//! Stride-aware access to the planes of a frame
+//!
+//! Drivers frequently pad image lines, e.g. to a multiple of 64 bytes, so the stride (bytes per
+//! line) of a buffer can be larger than the visible line. Treating such a buffer as tightly packed
+//! skews the image. A `FrameView` splits a buffer into its planes and exposes the visible part of
+//! each line.
+
+use std::io;
+
+use crate::format::{Format, FormatMplane, FourCC};
+
+/// Layout of a plane relative to the image size
+#[derive(Copy, Clone)]
+struct PlaneLayout {
+    /// horizontal subsampling
+    hsub: u32,
+    /// vertical subsampling
+    vsub: u32,
+    /// bytes per (subsampled) pixel
+    bytes: u32,
+}
+
+const fn plane(hsub: u32, vsub: u32, bytes: u32) -> PlaneLayout {
+    PlaneLayout { hsub, vsub, bytes }
+}
+
+/// Returns the plane layouts of the uncompressed formats known to the crate
+fn layout(fourcc: &FourCC) -> Option<&'static [PlaneLayout]> {
+    const PACKED_8: &[PlaneLayout] = &[plane(1, 1, 1)];
+    const PACKED_16: &[PlaneLayout] = &[plane(1, 1, 2)];
+    const PACKED_24: &[PlaneLayout] = &[plane(1, 1, 3)];
+    const PACKED_32: &[PlaneLayout] = &[plane(1, 1, 4)];
+    const SEMI_PLANAR_420: &[PlaneLayout] = &[plane(1, 1, 1), plane(2, 2, 2)];
+    const SEMI_PLANAR_422: &[PlaneLayout] = &[plane(1, 1, 1), plane(2, 1, 2)];
+    const PLANAR_420: &[PlaneLayout] = &[plane(1, 1, 1), plane(2, 2, 1), plane(2, 2, 1)];
+    const PLANAR_422: &[PlaneLayout] = &[plane(1, 1, 1), plane(2, 1, 1), plane(2, 1, 1)];
+
+    Some(match &fourcc.repr {
+        b"GREY" => PACKED_8,
+        b"YUYV" | b"YVYU" | b"UYVY" | b"VYUY" | b"RGBP" | b"RGBR" | b"RGBO" | b"RGBQ" | b"Y16 " => {
+            PACKED_16
+        }
+        b"RGB3" | b"BGR3" => PACKED_24,
+        b"RGB4" | b"BGR4" | b"AR24" | b"XR24" | b"AB24" | b"XB24" | b"BA24" | b"RA24" => PACKED_32,
+        b"NV12" | b"NV21" | b"NM12" | b"NM21" => SEMI_PLANAR_420,
+        b"NV16" | b"NV61" | b"NM16" | b"NM61" => SEMI_PLANAR_422,
+        b"YU12" | b"YV12" | b"YM12" | b"YM21" => PLANAR_420,
+        b"422P" | b"YM16" | b"YM61" => PLANAR_422,
+        _ => return None,
+    })
+}
+
+fn unsupported(fourcc: &FourCC) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unsupported format: {}", fourcc),
+    )
+}
+
+/// View of a single image plane
+#[derive(Debug, Copy, Clone)]
+pub struct PlaneView<'a> {
+    /// width in pixels (after subsampling)
+    pub width: u32,
+    /// height in lines (after subsampling)
+    pub height: u32,
+    /// bytes per line, including padding
+    pub stride: usize,
+    /// bytes per line, excluding padding
+    pub line_size: usize,
+    /// plane data, starting at the first line
+    pub data: &'a [u8],
+}
+
+impl<'a> PlaneView<'a> {
+    fn new(
+        data: &'a [u8],
+        width: u32,
+        height: u32,
+        layout: PlaneLayout,
+        stride: usize,
+    ) -> io::Result<Self> {
+        let width = width / layout.hsub + width % layout.hsub;
+        let height = height / layout.vsub + height % layout.vsub;
+        let line_size = width as usize * layout.bytes as usize;
+        // Some drivers do not report a stride for tightly packed planes
+        let stride = stride.max(line_size);
+
+        let len = match height as usize {
+            0 => 0,
+            height => stride * (height - 1) + line_size,
+        };
+        if data.len() < len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("plane too small: {} < {} bytes", data.len(), len),
+            ));
+        }
+
+        Ok(PlaneView {
+            width,
+            height,
+            stride,
+            line_size,
+            data,
+        })
+    }
+
+    /// Returns the visible part of a line
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - Line index
+    pub fn line(&self, y: u32) -> Option<&'a [u8]> {
+        if y >= self.height {
+            return None;
+        }
+
+        let start = y as usize * self.stride;
+        Some(&self.data[start..start + self.line_size])
+    }
+
+    /// Returns an iterator over the visible part of all lines
+    pub fn lines(&self) -> impl Iterator<Item = &'a [u8]> + '_ {
+        (0..self.height).filter_map(move |y| self.line(y))
+    }
+
+    /// Appends the visible part of all lines to a buffer, without padding
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - Destination buffer
+    pub fn copy_packed_into(&self, dest: &mut Vec<u8>) {
+        if self.stride == self.line_size {
+            // nothing to strip, copy the plane at once
+            dest.extend_from_slice(&self.data[..self.line_size * self.height as usize]);
+        } else {
+            for line in self.lines() {
+                dest.extend_from_slice(line);
+            }
+        }
+    }
+}
+
+/// View of a frame, split into its planes
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::frame::FrameView;
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+/// use v4l::video::Capture;
+///
+/// let dev = Device::new(0).unwrap();
+/// let fmt = dev.format().unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+///
+/// let (buf, _) = stream.next().unwrap();
+/// let frame = FrameView::new(buf, &fmt).unwrap();
+/// for line in frame.plane(0).unwrap().lines() {
+///     // process the visible pixels of each line
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FrameView<'a> {
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+    /// pixelformat code
+    pub fourcc: FourCC,
+    planes: Vec<PlaneView<'a>>,
+}
+
+impl<'a> FrameView<'a> {
+    /// Returns a view of a single-planar buffer
+    ///
+    /// Planar formats stored in a single buffer (e.g. NV12 or YU12) are split into their planes,
+    /// with chroma strides derived from the luma stride as the kernel does.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Buffer data
+    /// * `fmt` - Format of the buffer
+    pub fn new(buf: &'a [u8], fmt: &Format) -> io::Result<Self> {
+        let layouts = layout(&fmt.fourcc).ok_or_else(|| unsupported(&fmt.fourcc))?;
+        let luma = layouts[0];
+
+        let mut planes = Vec::new();
+        let mut offset = 0;
+        for layout in layouts {
+            let stride = fmt.stride as usize * (layout.bytes * luma.hsub) as usize
+                / (layout.hsub * luma.bytes) as usize;
+            let plane = PlaneView::new(
+                buf.get(offset..).unwrap_or(&[]),
+                fmt.width,
+                fmt.height,
+                *layout,
+                stride,
+            )?;
+            offset += plane.stride * plane.height as usize;
+            planes.push(plane);
+        }
+
+        Ok(FrameView {
+            width: fmt.width,
+            height: fmt.height,
+            fourcc: fmt.fourcc,
+            planes,
+        })
+    }
+
+    /// Returns a view of a multi-planar buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `bufs` - Data of each plane, e.g. as returned by the plane accessors of a stream
+    /// * `fmt` - Format of the buffer
+    pub fn with_planes(bufs: &[&'a [u8]], fmt: &FormatMplane) -> io::Result<Self> {
+        let layouts = layout(&fmt.fourcc).ok_or_else(|| unsupported(&fmt.fourcc))?;
+        if bufs.len() != layouts.len() || fmt.planes().len() != layouts.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} needs {} planes, got {} buffers and {} plane formats",
+                    fmt.fourcc,
+                    layouts.len(),
+                    bufs.len(),
+                    fmt.planes().len()
+                ),
+            ));
+        }
+
+        let planes = bufs
+            .iter()
+            .zip(layouts)
+            .zip(fmt.planes())
+            .map(|((buf, layout), plane)| {
+                PlaneView::new(buf, fmt.width, fmt.height, *layout, plane.stride as usize)
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+
+        Ok(FrameView {
+            width: fmt.width,
+            height: fmt.height,
+            fourcc: fmt.fourcc,
+            planes,
+        })
+    }
+
+    /// Copies all planes into a buffer, stripping any line padding
+    ///
+    /// The planes are stored one after another, each line being exactly as long as its visible
+    /// part, which is the layout most image processing libraries expect. The previous contents of
+    /// the destination are replaced, but its allocation is reused.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - Destination buffer
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::buffer::Type;
+    /// use v4l::frame::FrameView;
+    /// use v4l::io::traits::CaptureStream;
+    /// use v4l::prelude::*;
+    /// use v4l::video::Capture;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    /// let mut packed = Vec::new();
+    ///
+    /// loop {
+    ///     let (buf, _) = stream.next().unwrap();
+    ///     FrameView::new(buf, &fmt).unwrap().copy_packed(&mut packed);
+    /// }
+    /// ```
+    pub fn copy_packed(&self, dest: &mut Vec<u8>) {
+        dest.clear();
+        dest.reserve(self.packed_size());
+        for plane in &self.planes {
+            plane.copy_packed_into(dest);
+        }
+    }
+
+    /// Returns the size of the frame without line padding in bytes
+    pub fn packed_size(&self) -> usize {
+        self.planes
+            .iter()
+            .map(|plane| plane.line_size * plane.height as usize)
+            .sum()
+    }
+
+    /// Returns all planes
+    pub fn planes(&self) -> &[PlaneView<'a>] {
+        &self.planes
+    }
+
+    /// Returns a single plane
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Plane index
+    pub fn plane(&self, index: usize) -> Option<&PlaneView<'a>> {
+        self.planes.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::PlaneFormat;
+
+    fn format(width: u32, height: u32, fourcc: &[u8; 4], stride: u32) -> Format {
+        Format {
+            stride,
+            ..Format::new(width, height, FourCC::new(fourcc))
+        }
+    }
+
+    #[test]
+    fn plane_layouts() {
+        // (format, minimum buffer size, (width, height, stride, line size) of each plane)
+        #[allow(clippy::type_complexity)]
+        let cases: &[(Format, usize, &[(u32, u32, usize, usize)])] = &[
+            (format(4, 2, b"GREY", 0), 8, &[(4, 2, 4, 4)]),
+            (format(4, 2, b"GREY", 8), 12, &[(4, 2, 8, 4)]),
+            (format(4, 2, b"YUYV", 12), 20, &[(4, 2, 12, 8)]),
+            (format(4, 2, b"RGB3", 0), 24, &[(4, 2, 12, 12)]),
+            (format(4, 4, b"NV12", 8), 44, &[(4, 4, 8, 4), (2, 2, 8, 4)]),
+            (
+                format(4, 2, b"YU12", 8),
+                22,
+                &[(4, 2, 8, 4), (2, 1, 4, 2), (2, 1, 4, 2)],
+            ),
+            (
+                format(4, 2, b"422P", 0),
+                16,
+                &[(4, 2, 4, 4), (2, 2, 2, 2), (2, 2, 2, 2)],
+            ),
+            // odd sizes round the chroma planes up
+            (
+                format(5, 3, b"NV12", 0),
+                15 + 12,
+                &[(5, 3, 5, 5), (3, 2, 6, 6)],
+            ),
+        ];
+
+        for (fmt, size, planes) in cases {
+            let buf = vec![0; *size];
+            let view = FrameView::new(&buf, fmt).unwrap();
+            let layouts: Vec<_> = view
+                .planes()
+                .iter()
+                .map(|plane| (plane.width, plane.height, plane.stride, plane.line_size))
+                .collect();
+            assert_eq!(layouts, *planes, "{}", fmt.fourcc);
+
+            // the last line of the last plane does not need padding, but must be complete
+            assert!(
+                FrameView::new(&buf[..size - 1], fmt).is_err(),
+                "{}",
+                fmt.fourcc
+            );
+        }
+    }
+
+    #[test]
+    fn lines() {
+        let buf: Vec<u8> = (0..16).collect();
+        let view = FrameView::new(&buf, &format(2, 3, b"YUYV", 6)).unwrap();
+        let plane = view.plane(0).unwrap();
+
+        let lines: Vec<&[u8]> = plane.lines().collect();
+        assert_eq!(lines, [&[0, 1, 2, 3][..], &[6, 7, 8, 9], &[12, 13, 14, 15]]);
+        assert_eq!(plane.line(1), Some(&[6, 7, 8, 9][..]));
+        assert_eq!(plane.line(3), None);
+        assert!(view.plane(1).is_none());
+    }
+
+    #[test]
+    fn with_planes() {
+        let mut fmt = FormatMplane::new(4, 2, FourCC::new(b"NM12"), 2).unwrap();
+        fmt.set_planes(vec![
+            PlaneFormat {
+                stride: 8,
+                size: 16,
+            },
+            PlaneFormat { stride: 0, size: 4 },
+        ])
+        .unwrap();
+
+        let luma: Vec<u8> = (0..12).collect();
+        let chroma = [20, 21, 22, 23];
+        let view = FrameView::with_planes(&[&luma, &chroma], &fmt).unwrap();
+        assert_eq!(view.plane(0).unwrap().line(1), Some(&[8, 9, 10, 11][..]));
+        assert_eq!(view.plane(1).unwrap().line(0), Some(&chroma[..]));
+
+        assert!(FrameView::with_planes(&[&luma], &fmt).is_err());
+        assert!(FrameView::with_planes(&[&luma, &chroma[..2]], &fmt).is_err());
+    }
+
+    #[test]
+    fn copy_packed() {
+        // (format, buffer, packed frame)
+        let cases: &[(Format, &[u8], &[u8])] = &[
+            // already packed, copied at once
+            (format(2, 2, b"GREY", 0), &[1, 2, 3, 4], &[1, 2, 3, 4]),
+            (format(2, 2, b"GREY", 3), &[1, 2, 0, 3, 4], &[1, 2, 3, 4]),
+            (
+                format(1, 2, b"YUYV", 4),
+                &[1, 2, 0, 0, 3, 4, 0, 0],
+                &[1, 2, 3, 4],
+            ),
+            (
+                format(2, 2, b"NV12", 4),
+                &[1, 2, 0, 0, 3, 4, 0, 0, 5, 6],
+                &[1, 2, 3, 4, 5, 6],
+            ),
+            (
+                format(2, 2, b"YU12", 4),
+                &[1, 2, 0, 0, 3, 4, 0, 0, 5, 0, 6],
+                &[1, 2, 3, 4, 5, 6],
+            ),
+        ];
+
+        for (fmt, buf, packed) in cases {
+            let view = FrameView::new(buf, fmt).unwrap();
+            assert_eq!(view.packed_size(), packed.len(), "{}", fmt.fourcc);
+
+            // previous contents are replaced
+            let mut dest = vec![0xff; 3];
+            view.copy_packed(&mut dest);
+            assert_eq!(dest, *packed, "{}", fmt.fourcc);
+
+            // a plane is appended to what is already there
+            let mut dest = vec![0xff];
+            view.plane(0).unwrap().copy_packed_into(&mut dest);
+            assert_eq!(dest[0], 0xff);
+            assert_eq!(dest[1..], packed[..dest.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn unsupported_format() {
+        assert!(FrameView::new(&[0; 16], &format(4, 2, b"MJPG", 0)).is_err());
+    }
+}
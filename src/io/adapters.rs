@@ -0,0 +1,421 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::buffer::Metadata;
+use crate::io::assembler::{Assemble, Codec};
+use crate::io::traits::{CaptureStream, Stream as StreamTrait};
+use crate::sink::Sink;
+
+/// Source of frames which can be composed with adapters
+///
+/// This is implemented for all capture streams as well as for the adapters themselves, so
+/// pipelines such as "convert, show a preview and record" can be layered on top of a stream.
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::io::adapters::FrameSource;
+/// use v4l::prelude::*;
+/// use v4l::sink::RawFileWriter;
+///
+/// let dev = Device::new(0).unwrap();
+/// let stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// let writer = RawFileWriter::new("/tmp", "capture").unwrap();
+///
+/// let mut pipeline = stream
+///     .inspect(|_, meta| println!("frame {}", meta.sequence))
+///     .tee(writer);
+/// loop {
+///     let (buf, meta) = pipeline.next_frame().unwrap();
+/// }
+/// ```
+pub trait FrameSource {
+    type Item: ?Sized;
+
+    /// Fetch the next frame
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)>;
+
+    /// Captures and discards a number of frames
+    ///
+    /// Auto exposure and white balance usually need a few frames to settle after streaming has
+    /// started, so the first frames tend to be too dark or have a color cast.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - Number of frames to discard
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::buffer::Type;
+    /// use v4l::io::adapters::FrameSource;
+    /// use v4l::prelude::*;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    ///
+    /// stream.warm_up(10).unwrap();
+    /// let (buf, meta) = stream.next_frame().unwrap();
+    /// ```
+    fn warm_up(&mut self, frames: usize) -> io::Result<()> {
+        for _ in 0..frames {
+            self.next_frame()?;
+        }
+        Ok(())
+    }
+
+    /// Captures and discards frames for a given time, returning the number of discarded frames
+    ///
+    /// This is the time based variant of `warm_up`, which does not depend on the frame rate.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - Time to discard frames for
+    fn warm_up_for(&mut self, duration: Duration) -> io::Result<usize> {
+        let deadline = Instant::now() + duration;
+        let mut frames = 0;
+        while Instant::now() < deadline {
+            self.next_frame()?;
+            frames += 1;
+        }
+        Ok(frames)
+    }
+
+    /// Calls a closure on each frame before passing it on unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure receiving each frame
+    fn inspect<F>(self, f: F) -> Inspect<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item, &Metadata),
+    {
+        Inspect { source: self, f }
+    }
+
+    /// Converts each frame into a buffer owned by the adapter
+    ///
+    /// The output buffer is reused for all frames, so no allocations happen once it has grown to
+    /// the final frame size. The bytesused field of the passed on metadata reflects the size of
+    /// the converted frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure converting a frame into the output buffer
+    fn map_frame<F>(self, f: F) -> MapFrame<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Self::Item, &Metadata, &mut Vec<u8>) -> io::Result<()>,
+    {
+        MapFrame {
+            source: self,
+            f,
+            buf: Vec::new(),
+            meta: Metadata::default(),
+        }
+    }
+
+    /// Writes each frame into a sink before passing it on unchanged
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - Secondary consumer, e.g. a file writer
+    fn tee<K>(self, sink: K) -> Tee<Self, K>
+    where
+        Self: Sized + FrameSource<Item = [u8]>,
+        K: Sink,
+    {
+        Tee { source: self, sink }
+    }
+
+    /// Reassembles encoded frames which are spread across multiple buffers
+    ///
+    /// See `assembler::Assembler` for how access unit boundaries are detected.
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - Codec of the byte stream, None to rely on timestamps only
+    fn assemble(self, codec: Option<Codec>) -> Assemble<Self>
+    where
+        Self: Sized + FrameSource<Item = [u8]>,
+    {
+        Assemble::new(self, codec)
+    }
+
+    /// Delivers at most the given number of frames per second and skips the others
+    ///
+    /// Skipped frames are fetched from the source and dropped right away, so a capture stream
+    /// hands their buffers back to the driver and the device keeps running at its configured
+    /// frame rate. Frames are selected based on their driver timestamps, or on their time of
+    /// arrival if the driver leaves the timestamps zero.
+    ///
+    /// Fails with InvalidInput if `fps` is zero.
+    ///
+    /// # Arguments
+    ///
+    /// * `fps` - Maximum number of frames per second to deliver
+    fn throttle(self, fps: u32) -> io::Result<Throttle<Self>>
+    where
+        Self: Sized,
+    {
+        if fps == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot throttle to 0 frames per second",
+            ));
+        }
+
+        Ok(Throttle {
+            source: self,
+            interval: Duration::from_secs(1) / fps,
+            due: None,
+            skipped: 0,
+            epoch: Instant::now(),
+        })
+    }
+}
+
+impl<S> FrameSource for S
+where
+    S: for<'b> CaptureStream<'b>,
+{
+    type Item = <S as StreamTrait>::Item;
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        self.next()
+    }
+}
+
+/// Adapter calling a closure on each frame, see `FrameSource::inspect`
+pub struct Inspect<S, F> {
+    source: S,
+    f: F,
+}
+
+impl<S, F> Inspect<S, F> {
+    /// Returns the underlying source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S, F> FrameSource for Inspect<S, F>
+where
+    S: FrameSource,
+    F: FnMut(&S::Item, &Metadata),
+{
+    type Item = S::Item;
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        let (buf, meta) = self.source.next_frame()?;
+        (self.f)(buf, meta);
+        Ok((buf, meta))
+    }
+}
+
+/// Adapter converting each frame, see `FrameSource::map_frame`
+pub struct MapFrame<S, F> {
+    source: S,
+    f: F,
+    buf: Vec<u8>,
+    meta: Metadata,
+}
+
+impl<S, F> MapFrame<S, F> {
+    /// Returns the underlying source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S, F> FrameSource for MapFrame<S, F>
+where
+    S: FrameSource,
+    F: FnMut(&S::Item, &Metadata, &mut Vec<u8>) -> io::Result<()>,
+{
+    type Item = [u8];
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        let (buf, meta) = self.source.next_frame()?;
+        self.buf.clear();
+        (self.f)(buf, meta, &mut self.buf)?;
+
+        self.meta = *meta;
+        self.meta.bytesused = self.buf.len() as u32;
+        Ok((&self.buf, &self.meta))
+    }
+}
+
+/// Adapter writing each frame into a sink, see `FrameSource::tee`
+pub struct Tee<S, K> {
+    source: S,
+    sink: K,
+}
+
+impl<S, K> Tee<S, K> {
+    /// Returns the sink
+    pub fn sink(&self) -> &K {
+        &self.sink
+    }
+
+    /// Returns the underlying source and the sink
+    pub fn into_inner(self) -> (S, K) {
+        (self.source, self.sink)
+    }
+}
+
+impl<S, K> FrameSource for Tee<S, K>
+where
+    S: FrameSource<Item = [u8]>,
+    K: Sink,
+{
+    type Item = [u8];
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        let (buf, meta) = self.source.next_frame()?;
+        self.sink.write_frame(buf, meta)?;
+        Ok((buf, meta))
+    }
+}
+
+/// Adapter limiting the frame rate, see `FrameSource::throttle`
+pub struct Throttle<S> {
+    source: S,
+    interval: Duration,
+    due: Option<Duration>,
+    skipped: u64,
+    epoch: Instant,
+}
+
+impl<S> Throttle<S> {
+    /// Returns the number of frames skipped so far
+    pub fn skipped(&self) -> u64 {
+        self.skipped
+    }
+
+    /// Returns the underlying source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+
+    /// Decides whether a frame is delivered and advances the schedule if so
+    fn admit(&mut self, meta: &Metadata) -> bool {
+        // Tolerate some timestamp jitter so e.g. every 6th frame of a 30 fps camera is delivered
+        // for 5 fps instead of every 7th one whenever a frame arrives slightly early.
+        let slack = self.interval / 10;
+
+        // Some drivers do not timestamp their buffers, fall back to the time of arrival
+        let timestamp = match Duration::from(meta.timestamp) {
+            timestamp if timestamp.is_zero() => self.epoch.elapsed(),
+            timestamp => timestamp,
+        };
+
+        if let Some(due) = self.due {
+            if timestamp + slack < due {
+                return false;
+            }
+        }
+
+        // Keep a fixed schedule unless we fell behind by more than a whole interval
+        self.due = Some(match self.due {
+            Some(due) if timestamp < due + self.interval => due + self.interval,
+            _ => timestamp + self.interval,
+        });
+        true
+    }
+}
+
+impl<S> FrameSource for Throttle<S>
+where
+    S: FrameSource,
+{
+    type Item = S::Item;
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        loop {
+            // The borrow checker cannot tell that the source is no longer borrowed when a frame
+            // is skipped, so detach the frame from the borrow. It stays valid until the source
+            // is used again, just like the frame returned by the source itself.
+            let (buf, meta): (*const S::Item, *const Metadata) = {
+                let (buf, meta) = self.source.next_frame()?;
+                (buf, meta)
+            };
+            if self.admit(unsafe { &*meta }) {
+                return unsafe { Ok((&*buf, &*meta)) };
+            }
+            self.skipped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Delivers frames holding their sequence number at a fixed rate
+    struct Frames {
+        interval: Duration,
+        sequence: u32,
+        buf: [u8; 1],
+        meta: Metadata,
+    }
+
+    impl FrameSource for Frames {
+        type Item = [u8];
+
+        fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+            self.sequence += 1;
+            self.buf = [self.sequence as u8];
+            self.meta = Metadata {
+                sequence: self.sequence,
+                timestamp: (self.interval * self.sequence).into(),
+                ..Metadata::default()
+            };
+            Ok((&self.buf, &self.meta))
+        }
+    }
+
+    fn frames(fps: u32) -> Frames {
+        Frames {
+            interval: Duration::from_secs(1) / fps,
+            sequence: 0,
+            buf: [0],
+            meta: Metadata::default(),
+        }
+    }
+
+    #[test]
+    fn throttle_skips_frames() {
+        let mut throttle = frames(30).throttle(5).unwrap();
+        let delivered: Vec<_> = (0..4)
+            .map(|_| {
+                let (buf, meta) = throttle.next_frame().unwrap();
+                assert_eq!(buf, [meta.sequence as u8]);
+                meta.sequence
+            })
+            .collect();
+        assert_eq!(delivered, [1, 7, 13, 19]);
+        assert_eq!(throttle.skipped(), 15);
+    }
+
+    #[test]
+    fn throttle_composes() {
+        let mut inspected = 0;
+        let mut throttle = frames(10)
+            .inspect(|_, _| inspected += 1)
+            .throttle(10)
+            .unwrap();
+        throttle.next_frame().unwrap();
+        throttle.next_frame().unwrap();
+        assert_eq!(throttle.skipped(), 0);
+        assert_eq!(inspected, 2);
+    }
+
+    #[test]
+    fn throttle_zero_fps() {
+        let err = frames(30).throttle(0).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
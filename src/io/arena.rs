@@ -1,5 +1,7 @@
 use std::io;
 
+use crate::memory::{Backing, Usage};
+
 /// Manage buffers for a device
 pub trait Arena {
     type Buffer: ?Sized;
@@ -23,11 +25,61 @@ pub trait Arena {
     fn get_mut(&mut self, index: usize) -> Option<&mut Self::Buffer>;
 
     /// Access a single buffer without bounds checking
+    ///
+    /// # Safety
+    ///
+    /// The index must be smaller than `len()`, e.g. because it was returned by the driver on
+    /// dequeue. Calling this with an out of bounds index is undefined behavior.
     unsafe fn get_unchecked(&self, index: usize) -> &Self::Buffer;
 
     /// Access a single buffer without bounds checking
+    ///
+    /// # Safety
+    ///
+    /// The index must be smaller than `len()`, see `get_unchecked`.
     unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut Self::Buffer;
 
     /// Number of buffers
     fn len(&self) -> usize;
+
+    /// Returns true if no buffers are allocated
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of planes per buffer
+    ///
+    /// Single-planar buffers (the default) have exactly one plane.
+    fn plane_count(&self) -> usize {
+        1
+    }
+
+    /// Access a single plane of a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `plane` - Plane index, 0 for single-planar buffers
+    fn plane(&self, index: usize, plane: usize) -> Option<&Self::Buffer> {
+        if plane == 0 {
+            self.get(index)
+        } else {
+            None
+        }
+    }
+
+    /// Resource backing a single plane of a buffer
+    ///
+    /// The default implementation reports no backing resource.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `plane` - Plane index, 0 for single-planar buffers
+    fn backing(&self, _index: usize, _plane: usize) -> Option<Backing> {
+        None
+    }
+
+    /// Memory occupied by the buffers
+    fn memory_usage(&self) -> Usage;
 }
@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::io;
+
+use crate::buffer::{Flags, Metadata};
+use crate::io::adapters::FrameSource;
+
+/// Compressed byte stream formats understood by the assembler
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Codec {
+    H264,
+    Hevc,
+}
+
+impl Codec {
+    /// Returns true if the data starts with an access unit delimiter NAL unit
+    fn starts_access_unit(&self, data: &[u8]) -> bool {
+        let header = if data.starts_with(&[0, 0, 0, 1]) {
+            data.get(4)
+        } else if data.starts_with(&[0, 0, 1]) {
+            data.get(3)
+        } else {
+            None
+        };
+
+        match (self, header) {
+            (Codec::H264, Some(header)) => header & 0x1f == 9,
+            (Codec::Hevc, Some(header)) => (header >> 1) & 0x3f == 35,
+            _ => false,
+        }
+    }
+}
+
+/// Reassembles encoded frames (access units) which are spread across multiple buffers
+///
+/// Buffers are concatenated until the next access unit begins, which is detected by a change of
+/// the buffer timestamp or, if the codec is known, by an access unit delimiter at the start of a
+/// buffer. A buffer with the LAST flag completes the current access unit right away.
+///
+/// The metadata of an access unit is the one of its first buffer, with the flags of all buffers
+/// combined and bytesused covering the whole unit.
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::io::assembler::{Assembler, Codec};
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// let mut assembler = Assembler::new(Some(Codec::H264));
+///
+/// loop {
+///     let (buf, meta) = stream.next().unwrap();
+///     assembler.push(buf, meta);
+///     while let Some((unit, meta)) = assembler.pop() {
+///         println!("access unit {}: {} bytes", meta.sequence, unit.len());
+///     }
+/// }
+/// ```
+pub struct Assembler {
+    codec: Option<Codec>,
+    pending: Vec<u8>,
+    meta: Option<Metadata>,
+    ready: VecDeque<(Vec<u8>, Metadata)>,
+}
+
+impl Assembler {
+    /// Returns a new assembler
+    ///
+    /// # Arguments
+    ///
+    /// * `codec` - Codec of the byte stream, None to rely on timestamps only
+    pub fn new(codec: Option<Codec>) -> Self {
+        Assembler {
+            codec,
+            pending: Vec::new(),
+            meta: None,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Appends the payload of a buffer, as indicated by bytesused
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Buffer data
+    /// * `meta` - Buffer metadata
+    pub fn push(&mut self, buf: &[u8], meta: &Metadata) {
+        let data = meta.payload(buf);
+        if data.is_empty() {
+            // an empty buffer carries no data, only its LAST flag matters
+            if meta.flags.contains(Flags::LAST) {
+                self.complete();
+            }
+            return;
+        }
+
+        let boundary = match &self.meta {
+            Some(pending) => {
+                pending.timestamp.sec != meta.timestamp.sec
+                    || pending.timestamp.usec != meta.timestamp.usec
+                    || self
+                        .codec
+                        .map(|codec| codec.starts_access_unit(data))
+                        .unwrap_or(false)
+            }
+            None => false,
+        };
+        if boundary {
+            self.complete();
+        }
+
+        match &mut self.meta {
+            Some(pending) => pending.flags |= meta.flags,
+            None => self.meta = Some(*meta),
+        }
+        self.pending.extend_from_slice(data);
+
+        if meta.flags.contains(Flags::LAST) {
+            self.complete();
+        }
+    }
+
+    /// Returns the next complete access unit, if any
+    pub fn pop(&mut self) -> Option<(Vec<u8>, Metadata)> {
+        self.ready.pop_front()
+    }
+
+    /// Completes the pending access unit, e.g. once the stream has ended
+    pub fn flush(&mut self) {
+        self.complete();
+    }
+
+    /// Discards all pending and complete access units, e.g. after seeking
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.meta = None;
+        self.ready.clear();
+    }
+
+    fn complete(&mut self) {
+        if let Some(mut meta) = self.meta.take() {
+            meta.bytesused = self.pending.len() as u32;
+            self.ready
+                .push_back((std::mem::take(&mut self.pending), meta));
+        }
+    }
+}
+
+/// Adapter yielding complete access units, see `FrameSource::assemble`
+pub struct Assemble<S> {
+    source: S,
+    assembler: Assembler,
+    buf: Vec<u8>,
+    meta: Metadata,
+}
+
+impl<S> Assemble<S> {
+    pub(crate) fn new(source: S, codec: Option<Codec>) -> Self {
+        Assemble {
+            source,
+            assembler: Assembler::new(codec),
+            buf: Vec::new(),
+            meta: Metadata::default(),
+        }
+    }
+
+    /// Returns the underlying source
+    pub fn into_inner(self) -> S {
+        self.source
+    }
+}
+
+impl<S> FrameSource for Assemble<S>
+where
+    S: FrameSource<Item = [u8]>,
+{
+    type Item = [u8];
+
+    fn next_frame(&mut self) -> io::Result<(&Self::Item, &Metadata)> {
+        loop {
+            if let Some((buf, meta)) = self.assembler.pop() {
+                self.buf = buf;
+                self.meta = meta;
+                return Ok((&self.buf, &self.meta));
+            }
+
+            let (buf, meta) = self.source.next_frame()?;
+            self.assembler.push(buf, meta);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::Timestamp;
+
+    fn meta(usec: i64, flags: Flags, len: usize) -> Metadata {
+        Metadata {
+            bytesused: len as u32,
+            flags,
+            timestamp: Timestamp::new(0, usec),
+            ..Metadata::default()
+        }
+    }
+
+    #[test]
+    fn assemble_units() {
+        let none = Flags::empty();
+        let last = Flags::LAST;
+        let h264_aud: &[u8] = &[0, 0, 0, 1, 9, 0xf0];
+        let hevc_aud: &[u8] = &[0, 0, 1, 35 << 1, 1];
+
+        #[allow(clippy::type_complexity)]
+        let cases: &[(Option<Codec>, &[(&[u8], i64, Flags)], &[&[u8]])] = &[
+            // one buffer per timestamp
+            (None, &[(&[1, 2], 0, none), (&[3], 1, none)], &[&[1, 2]]),
+            // buffers sharing a timestamp are concatenated
+            (
+                None,
+                &[(&[1], 0, none), (&[2], 0, none), (&[3], 1, none)],
+                &[&[1, 2]],
+            ),
+            // the LAST flag completes the unit right away
+            (None, &[(&[1], 0, none), (&[2], 0, last)], &[&[1, 2]]),
+            // an empty buffer only carries its LAST flag
+            (None, &[(&[1], 0, none), (&[], 0, last)], &[&[1]]),
+            (None, &[(&[], 0, last)], &[]),
+            // access unit delimiters split buffers sharing a timestamp
+            (
+                Some(Codec::H264),
+                &[(&[1], 0, none), (h264_aud, 0, none), (&[2], 0, last)],
+                &[&[1], &[0, 0, 0, 1, 9, 0xf0, 2]],
+            ),
+            (
+                Some(Codec::Hevc),
+                &[(&[1], 0, none), (hevc_aud, 0, none), (&[2], 0, last)],
+                &[&[1], &[0, 0, 1, 35 << 1, 1, 2]],
+            ),
+            // delimiters of the other codec are plain data
+            (
+                Some(Codec::Hevc),
+                &[(&[1], 0, none), (h264_aud, 0, last)],
+                &[&[1, 0, 0, 0, 1, 9, 0xf0]],
+            ),
+        ];
+
+        for (codec, buffers, units) in cases {
+            let mut assembler = Assembler::new(*codec);
+            for (data, usec, flags) in buffers.iter() {
+                assembler.push(data, &meta(*usec, *flags, data.len()));
+            }
+
+            for (i, unit) in units.iter().enumerate() {
+                let (buf, meta) = assembler.pop().unwrap();
+                assert_eq!(buf, *unit, "{:?} unit {}", buffers, i);
+                assert_eq!(meta.bytesused as usize, unit.len());
+            }
+            assert!(assembler.pop().is_none(), "{:?}", buffers);
+        }
+    }
+
+    #[test]
+    fn payload_and_flags() {
+        let mut assembler = Assembler::new(None);
+        // only bytesused bytes of a buffer belong to the unit
+        assembler.push(&[1, 2, 3, 4], &meta(5, Flags::KEYFRAME, 2));
+        assembler.push(&[3, 4], &meta(5, Flags::ERROR, 2));
+        assembler.flush();
+
+        let (buf, meta) = assembler.pop().unwrap();
+        assert_eq!(buf, [1, 2, 3, 4]);
+        assert_eq!(meta.timestamp.usec, 5);
+        assert_eq!(meta.flags, Flags::KEYFRAME | Flags::ERROR);
+        assert!(assembler.pop().is_none());
+    }
+
+    #[test]
+    fn reset() {
+        let mut assembler = Assembler::new(None);
+        assembler.push(&[1], &meta(0, Flags::LAST, 1));
+        assembler.push(&[2], &meta(1, Flags::empty(), 1));
+        assembler.reset();
+        assembler.flush();
+        assert!(assembler.pop().is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! Runtime validation of driver behavior
+//!
+//! Drivers occasionally violate the V4L2 specification in subtle ways, e.g. by repeating
+//! sequence numbers or reporting timestamps from the future. Such bugs tend to surface as odd
+//! behavior far away from their cause. A `Validator` checks each dequeued buffer against the
+//! rules of the specification and reports every violation, with enough detail to file a bug
+//! report against the driver.
+
+use std::time::Duration;
+use std::{fmt, io};
+
+use crate::buffer::{Clock, Flags, Metadata};
+use crate::io::stats;
+use crate::timestamp::Timestamp;
+
+/// Tolerance for timestamps ahead of the current time, to allow for clock granularity
+const CLOCK_SLACK: Duration = Duration::from_millis(1);
+
+/// V4L2_FIELD_TOP and V4L2_FIELD_BOTTOM, where both fields of a frame share a sequence number
+const FIELD_TOP: u32 = 2;
+const FIELD_BOTTOM: u32 = 3;
+
+/// Pattern written into poisoned buffers, repeated over the whole buffer
+const POISON: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+/// Fills a buffer with the poison pattern
+pub(crate) fn poison(buf: &mut [u8]) {
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = POISON[i % POISON.len()];
+    }
+}
+
+/// Verifies that the driver did not touch a poisoned buffer beyond the reported payload
+pub(crate) fn check_poison(buf: &[u8], index: usize, bytesused: u32) -> io::Result<()> {
+    let payload = bytesused as usize;
+    if payload > buf.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "buffer {}: bytesused {} exceeds the buffer length {}",
+                index,
+                payload,
+                buf.len()
+            ),
+        ));
+    }
+
+    let overwritten = buf[payload..]
+        .iter()
+        .enumerate()
+        .position(|(i, byte)| *byte != POISON[(payload + i) % POISON.len()]);
+    if let Some(offset) = overwritten {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "buffer {}: driver wrote at offset {} beyond bytesused {}",
+                index,
+                payload + offset,
+                payload
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Receives each violation together with the metadata of the offending buffer
+type Callback = Box<dyn FnMut(&Violation, &Metadata) + Send>;
+
+/// Rule of the specification a driver broke
+#[derive(Debug, Clone)]
+pub enum Violation {
+    /// The sequence number did not increase
+    Sequence { previous: u32, current: u32 },
+    /// The timestamp went backwards
+    TimestampDecreased {
+        previous: Timestamp,
+        current: Timestamp,
+    },
+    /// A monotonic timestamp lies in the future
+    TimestampInFuture { timestamp: Timestamp, now: Duration },
+    /// The microseconds component is out of range
+    TimestampInvalid { timestamp: Timestamp },
+    /// More bytes were reported as used than the buffer can hold
+    Bytesused { bytesused: u32, length: usize },
+    /// The buffer flags contradict each other or the buffer state
+    Flags { flags: Flags, reason: &'static str },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Sequence { previous, current } => write!(
+                f,
+                "sequence number did not increase: {} after {}",
+                current, previous
+            ),
+            Violation::TimestampDecreased { previous, current } => write!(
+                f,
+                "timestamp went backwards: {}.{:06} after {}.{:06}",
+                current.sec, current.usec, previous.sec, previous.usec
+            ),
+            Violation::TimestampInFuture { timestamp, now } => write!(
+                f,
+                "monotonic timestamp {}.{:06} is ahead of the clock ({:?})",
+                timestamp.sec, timestamp.usec, now
+            ),
+            Violation::TimestampInvalid { timestamp } => write!(
+                f,
+                "invalid timestamp: {} s, {} us",
+                timestamp.sec, timestamp.usec
+            ),
+            Violation::Bytesused { bytesused, length } => write!(
+                f,
+                "bytesused {} exceeds the buffer length {}",
+                bytesused, length
+            ),
+            Violation::Flags { flags, reason } => write!(f, "{} (flags: {})", reason, flags),
+        }
+    }
+}
+
+/// Checks dequeued buffers against the V4L2 specification
+///
+/// Each violation is passed to a callback, the buffers themselves are delivered as usual. Mmap
+/// and userptr streams accept a validator through `set_validator()`.
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::io::conformance::Validator;
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// stream.set_validator(Some(Validator::new(|violation, meta| {
+///     eprintln!("driver bug in frame {}: {}", meta.sequence, violation);
+/// })));
+///
+/// loop {
+///     let (buf, meta) = stream.next().unwrap();
+/// }
+/// ```
+pub struct Validator {
+    callback: Callback,
+    previous: Option<Metadata>,
+    violations: u64,
+}
+
+impl Validator {
+    /// Returns a validator
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - Called with each violation and the metadata of the offending buffer
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: FnMut(&Violation, &Metadata) + Send + 'static,
+    {
+        Validator {
+            callback: Box::new(callback),
+            previous: None,
+            violations: 0,
+        }
+    }
+
+    /// Returns the number of violations reported so far
+    pub fn violations(&self) -> u64 {
+        self.violations
+    }
+
+    /// Forgets the previous buffer, e.g. when streaming is restarted
+    pub fn reset(&mut self) {
+        self.previous = None;
+    }
+
+    /// Checks a dequeued buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `meta` - Metadata of the buffer
+    /// * `length` - Size of the buffer in bytes
+    pub fn check(&mut self, meta: &Metadata, length: usize) {
+        let mut violations = Vec::new();
+
+        if meta.bytesused as usize > length {
+            violations.push(Violation::Bytesused {
+                bytesused: meta.bytesused,
+                length,
+            });
+        }
+
+        if let Some(reason) = flags_violation(meta.flags) {
+            violations.push(Violation::Flags {
+                flags: meta.flags,
+                reason,
+            });
+        }
+
+        let timestamp = meta.timestamp;
+        if timestamp.sec < 0 || !(0..1_000_000).contains(&timestamp.usec) {
+            violations.push(Violation::TimestampInvalid { timestamp });
+        } else if meta.clock() == Clock::Monotonic {
+            if let Ok(now) = stats::monotonic_now() {
+                if Duration::from(timestamp) > now + CLOCK_SLACK {
+                    violations.push(Violation::TimestampInFuture { timestamp, now });
+                }
+            }
+        }
+
+        if let Some(previous) = &self.previous {
+            // both fields of an interlaced frame are delivered with the same sequence number
+            let same_frame = matches!(meta.field, FIELD_TOP | FIELD_BOTTOM)
+                && previous.field != meta.field
+                && previous.sequence == meta.sequence;
+            if !same_frame && meta.sequence.wrapping_sub(previous.sequence) as i32 <= 0 {
+                violations.push(Violation::Sequence {
+                    previous: previous.sequence,
+                    current: meta.sequence,
+                });
+            }
+
+            let prev = previous.timestamp;
+            if (timestamp.sec, timestamp.usec) < (prev.sec, prev.usec)
+                && meta.clock() != Clock::Copy
+            {
+                violations.push(Violation::TimestampDecreased {
+                    previous: prev,
+                    current: timestamp,
+                });
+            }
+        }
+
+        for violation in &violations {
+            (self.callback)(violation, meta);
+        }
+        self.violations += violations.len() as u64;
+        self.previous = Some(*meta);
+    }
+}
+
+/// Returns why a combination of flags is invalid for a dequeued buffer, if it is
+fn flags_violation(flags: Flags) -> Option<&'static str> {
+    let frame_types = [Flags::KEYFRAME, Flags::PFRAME, Flags::BFRAME]
+        .iter()
+        .filter(|flag| flags.contains(**flag))
+        .count();
+
+    // DONE is not checked: vb2 fills in the buffer before marking it as dequeued, so dequeued
+    // buffers legitimately carry it
+    if flags.contains(Flags::QUEUED) {
+        Some("QUEUED set after dequeueing")
+    } else if flags.clock().is_none() {
+        Some("unknown timestamp type")
+    } else if flags.timestamp_source().is_none() {
+        Some("unknown timestamp source")
+    } else if frame_types > 1 {
+        Some("more than one of KEYFRAME, PFRAME and BFRAME set")
+    } else {
+        None
+    }
+}
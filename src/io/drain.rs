@@ -0,0 +1,55 @@
+//! Stopping capture streams without dropping frames the hardware has already filled
+
+use std::io;
+use std::time::Duration;
+
+use crate::buffer::Metadata;
+use crate::device::Handle;
+use crate::io::traits::CaptureStream;
+
+/// Time to wait for each buffer if the stream has no timeout configured
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Dequeues the buffers still owned by the driver and hands them to a closure, then stops
+/// streaming
+///
+/// Streaming is stopped even if dequeueing fails or times out, the first error is returned.
+///
+/// # Arguments
+///
+/// * `stream` - Capture stream
+/// * `handle` - Device handle of the stream
+/// * `pending` - Number of buffers owned by the driver
+/// * `timeout` - Maximum time to wait for each buffer, None for the default of one second
+/// * `f` - Closure receiving each drained buffer along with its metadata
+pub(crate) fn stop_draining<'a, S, F>(
+    stream: &mut S,
+    handle: &Handle,
+    pending: usize,
+    timeout: Option<Duration>,
+    mut f: F,
+) -> io::Result<()>
+where
+    S: CaptureStream<'a>,
+    F: FnMut(&S::Item, &Metadata),
+{
+    let timeout = timeout.unwrap_or(TIMEOUT);
+    let mut drain = || -> io::Result<()> {
+        for _ in 0..pending {
+            if !handle.poll(libc::POLLIN, Some(timeout))? {
+                return Err(crate::io::timed_out());
+            }
+
+            let index = stream.dequeue()?;
+            if let (Some(buf), Some(meta)) = (stream.get(index), stream.get_meta(index)) {
+                f(buf, meta);
+            }
+        }
+        Ok(())
+    };
+    let drained = drain();
+
+    // VIDIOC_STREAMOFF also returns the buffers which could not be dequeued
+    let stopped = stream.stop();
+    drained.and(stopped)
+}
@@ -0,0 +1,183 @@
+use std::io;
+
+use crate::buffer::{Flags, Metadata};
+use crate::io::traits::CaptureStream;
+use crate::v4l2;
+
+/// Progress of an iteration over the frames of a stream
+#[derive(Debug, Default, Copy, Clone)]
+struct Cursor {
+    /// At least one frame has been dequeued, so the stream was running
+    streaming: bool,
+    /// The iteration is over, no more frames are fetched
+    done: bool,
+}
+
+/// Fetches the next frame and decides whether the iteration is over
+///
+/// The iteration ends (and stays over) once the stream reaches its end, the device disappears or
+/// an error other than a timeout is returned. That error is handed out once.
+fn advance<'s, S>(
+    stream: &'s mut S,
+    cursor: &mut Cursor,
+) -> Option<io::Result<(&'s [u8], &'s Metadata)>>
+where
+    S: for<'b> CaptureStream<'b, Item = [u8]>,
+{
+    if cursor.done {
+        return None;
+    }
+
+    match stream.next() {
+        Ok((buf, meta)) => {
+            cursor.streaming = true;
+            if meta.flags.contains(Flags::LAST) {
+                cursor.done = true;
+                // drivers may signal the end of the stream with an empty buffer
+                if meta.bytesused == 0 {
+                    return None;
+                }
+            }
+
+            Some(Ok((meta.payload(buf), meta)))
+        }
+        Err(e) => {
+            match e.kind() {
+                // no frame in time, the caller may keep going
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => return Some(Err(e)),
+                _ => cursor.done = true,
+            }
+
+            match v4l2::errno(&e) {
+                // EPIPE: the stream has been drained, ENODEV: the device is gone
+                Some(libc::EPIPE) | Some(libc::ENODEV) => None,
+                // EINVAL: streaming was turned off underneath us (VIDIOC_STREAMOFF)
+                Some(libc::EINVAL) if cursor.streaming => None,
+                _ => Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Frames of a capture stream, taking ownership of the stream
+///
+/// This is a lending iterator: each frame borrows the stream buffer and is valid until the next
+/// call to `next()`, so no frame is copied. The iteration ends once the driver signals the end of
+/// the stream (e.g. after a decoder has been drained), streaming is turned off or the device
+/// disappears. Timeouts are returned as errors and the iteration may be continued afterwards,
+/// any other error is returned once and ends the iteration.
+pub struct IntoFrames<S> {
+    stream: S,
+    cursor: Cursor,
+}
+
+impl<S> IntoFrames<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        IntoFrames {
+            stream,
+            cursor: Cursor::default(),
+        }
+    }
+
+    /// Returns the underlying stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> IntoFrames<S>
+where
+    S: for<'b> CaptureStream<'b, Item = [u8]>,
+{
+    /// Fetches the next frame, returns None once the iteration is over
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<io::Result<(&[u8], &Metadata)>> {
+        advance(&mut self.stream, &mut self.cursor)
+    }
+}
+
+/// Frames of a borrowed capture stream, see `IntoFrames`
+pub struct Frames<'s, S> {
+    stream: &'s mut S,
+    cursor: Cursor,
+}
+
+impl<'s, S> Frames<'s, S> {
+    pub(crate) fn new(stream: &'s mut S) -> Self {
+        Frames {
+            stream,
+            cursor: Cursor::default(),
+        }
+    }
+}
+
+impl<'s, S> Frames<'s, S>
+where
+    S: for<'b> CaptureStream<'b, Item = [u8]>,
+{
+    /// Fetches the next frame, returns None once the iteration is over
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<io::Result<(&[u8], &Metadata)>> {
+        advance(self.stream, &mut self.cursor)
+    }
+}
+
+/// Owned frames of a capture stream, taking ownership of the stream
+///
+/// Unlike `IntoFrames`, this is a regular `Iterator`, so streams can be used in `for` loops and
+/// with iterator adapters. Each frame is copied out of the stream buffer, which is handed back to
+/// the driver on the next iteration. The iteration ends under the same conditions as the one of
+/// `IntoFrames`.
+pub struct IntoIter<S> {
+    frames: IntoFrames<S>,
+}
+
+impl<S> IntoIter<S> {
+    pub(crate) fn new(stream: S) -> Self {
+        IntoIter {
+            frames: IntoFrames::new(stream),
+        }
+    }
+
+    /// Returns the underlying stream
+    pub fn into_inner(self) -> S {
+        self.frames.into_inner()
+    }
+}
+
+impl<S> Iterator for IntoIter<S>
+where
+    S: for<'b> CaptureStream<'b, Item = [u8]>,
+{
+    type Item = io::Result<(Vec<u8>, Metadata)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+        Some(frame.map(|(buf, meta)| (buf.to_vec(), *meta)))
+    }
+}
+
+/// Owned frames of a borrowed capture stream, see `IntoIter`
+pub struct Iter<'s, S> {
+    frames: Frames<'s, S>,
+}
+
+impl<'s, S> Iter<'s, S> {
+    pub(crate) fn new(stream: &'s mut S) -> Self {
+        Iter {
+            frames: Frames::new(stream),
+        }
+    }
+}
+
+impl<'s, S> Iterator for Iter<'s, S>
+where
+    S: for<'b> CaptureStream<'b, Item = [u8]>,
+{
+    type Item = io::Result<(Vec<u8>, Metadata)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+        Some(frame.map(|(buf, meta)| (buf.to_vec(), *meta)))
+    }
+}
@@ -3,7 +3,8 @@ use std::{io, mem, ptr, slice, sync::Arc};
 use crate::buffer;
 use crate::device::Handle;
 use crate::io::arena::Arena as ArenaTrait;
-use crate::memory::Memory;
+use crate::io::mmap::Options;
+use crate::memory::{Backing, Memory, Usage};
 use crate::v4l2;
 use crate::v4l_sys::*;
 
@@ -14,7 +15,9 @@ use crate::v4l_sys::*;
 pub struct Arena<'a> {
     handle: Arc<Handle>,
     bufs: Vec<&'a mut [u8]>,
+    offsets: Vec<u32>,
     buf_type: buffer::Type,
+    options: Options,
 }
 
 impl<'a> Arena<'a> {
@@ -27,24 +30,112 @@ impl<'a> Arena<'a> {
     ///
     /// * `handle` - Device handle to get its file descriptor
     /// * `buf_type` - Type of the buffers
-    pub fn new(handle: Arc<Handle>, buf_type: buffer::Type) -> Self {
+    /// * `options` - Mapping options
+    pub fn new(handle: Arc<Handle>, buf_type: buffer::Type, options: Options) -> Self {
         Arena {
             handle,
             bufs: Vec::new(),
+            offsets: Vec::new(),
             buf_type,
+            options,
         }
     }
+
+    /// Queries a single buffer from the driver and maps it
+    fn map(&mut self, index: u32) -> io::Result<()> {
+        let mut v4l2_buf: v4l2_buffer;
+        unsafe {
+            v4l2_buf = mem::zeroed();
+            v4l2_buf.type_ = self.buf_type as u32;
+            v4l2_buf.memory = Memory::Mmap as u32;
+            v4l2_buf.index = index;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_QUERYBUF,
+                &mut v4l2_buf as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            let prot = if self.options.read_only {
+                libc::PROT_READ
+            } else {
+                libc::PROT_READ | libc::PROT_WRITE
+            };
+            let flags = if self.options.populate {
+                libc::MAP_SHARED | libc::MAP_POPULATE
+            } else {
+                libc::MAP_SHARED
+            };
+            let ptr = v4l2::mmap(
+                ptr::null_mut(),
+                v4l2_buf.length as usize,
+                prot,
+                flags,
+                self.handle.fd(),
+                v4l2_buf.m.offset as libc::off_t,
+            )?;
+
+            let slice = slice::from_raw_parts_mut::<u8>(ptr as *mut u8, v4l2_buf.length as usize);
+            self.bufs.push(slice);
+            self.offsets.push(v4l2_buf.m.offset);
+
+            if let Some(advice) = self.options.advice {
+                if libc::madvise(ptr, v4l2_buf.length as usize, advice.as_raw()) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            if self.options.lock && libc::mlock(ptr, v4l2_buf.length as usize) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Allocates additional buffers while keeping the existing ones, see VIDIOC_CREATE_BUFS
+    ///
+    /// Returns the number of buffers which were actually added, the new buffers are appended
+    /// after the existing ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Desired number of additional buffers
+    pub(crate) fn grow(&mut self, count: u32) -> io::Result<u32> {
+        let mut v4l2_create: v4l2_create_buffers;
+        unsafe {
+            v4l2_create = mem::zeroed();
+            v4l2_create.count = count;
+            v4l2_create.memory = Memory::Mmap as u32;
+            v4l2_create.format.type_ = self.buf_type as u32;
+            // the new buffers are sized for the current format
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_G_FMT,
+                &mut v4l2_create.format as *mut _ as *mut std::os::raw::c_void,
+            )?;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_CREATE_BUFS,
+                &mut v4l2_create as *mut _ as *mut std::os::raw::c_void,
+            )?;
+        }
+
+        for index in v4l2_create.index..v4l2_create.index + v4l2_create.count {
+            self.map(index)?;
+        }
+
+        Ok(v4l2_create.count)
+    }
 }
 
 impl<'a> Drop for Arena<'a> {
     fn drop(&mut self) {
-        if self.bufs.is_empty() {
+        if self.is_empty() {
             // nothing to do
             return;
         }
 
         if let Err(e) = self.release() {
-            if let Some(code) = e.raw_os_error() {
+            if let Some(code) = v4l2::errno(&e) {
                 // ENODEV means the file descriptor wrapped in the handle became invalid, most
                 // likely because the device was unplugged or the connection (USB, PCI, ..)
                 // broke down. Handle this case gracefully by ignoring it.
@@ -76,32 +167,8 @@ impl<'a> ArenaTrait for Arena<'a> {
             )?;
         }
 
-        for i in 0..v4l2_reqbufs.count {
-            let mut v4l2_buf: v4l2_buffer;
-            unsafe {
-                v4l2_buf = mem::zeroed();
-                v4l2_buf.type_ = self.buf_type as u32;
-                v4l2_buf.memory = Memory::Mmap as u32;
-                v4l2_buf.index = i;
-                v4l2::ioctl(
-                    self.handle.fd(),
-                    v4l2::vidioc::VIDIOC_QUERYBUF,
-                    &mut v4l2_buf as *mut _ as *mut std::os::raw::c_void,
-                )?;
-
-                let ptr = v4l2::mmap(
-                    ptr::null_mut(),
-                    v4l2_buf.length as usize,
-                    libc::PROT_READ | libc::PROT_WRITE,
-                    libc::MAP_SHARED,
-                    self.handle.fd(),
-                    v4l2_buf.m.offset as libc::off_t,
-                )?;
-
-                let slice =
-                    slice::from_raw_parts_mut::<u8>(ptr as *mut u8, v4l2_buf.length as usize);
-                self.bufs.push(slice);
-            }
+        for index in 0..v4l2_reqbufs.count {
+            self.map(index)?;
         }
 
         Ok(v4l2_reqbufs.count)
@@ -129,6 +196,7 @@ impl<'a> ArenaTrait for Arena<'a> {
         }
 
         self.bufs.clear();
+        self.offsets.clear();
         Ok(())
     }
 
@@ -137,6 +205,10 @@ impl<'a> ArenaTrait for Arena<'a> {
     }
 
     fn get_mut(&mut self, index: usize) -> Option<&mut Self::Buffer> {
+        if self.options.read_only {
+            return None;
+        }
+
         Some(self.bufs.get_mut(index)?)
     }
 
@@ -144,11 +216,31 @@ impl<'a> ArenaTrait for Arena<'a> {
         self.bufs.get_unchecked(index)
     }
 
+    /// # Safety
+    ///
+    /// Besides the index being in bounds, the buffers must not be mapped read-only: writing
+    /// through the returned slice would fault. Streams never create read-only output arenas.
     unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut Self::Buffer {
+        debug_assert!(!self.options.read_only);
         self.bufs.get_unchecked_mut(index)
     }
 
     fn len(&self) -> usize {
         self.bufs.len()
     }
+
+    fn backing(&self, index: usize, plane: usize) -> Option<Backing> {
+        if plane != 0 {
+            return None;
+        }
+
+        Some(Backing::Offset(*self.offsets.get(index)?))
+    }
+
+    fn memory_usage(&self) -> Usage {
+        Usage {
+            memory: Memory::Mmap,
+            buffers: self.bufs.iter().map(|buf| vec![buf.len()]).collect(),
+        }
+    }
 }
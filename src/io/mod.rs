@@ -1,5 +1,21 @@
+pub mod adapters;
 pub(crate) mod arena;
+pub mod assembler;
+pub mod conformance;
+pub(crate) mod drain;
+pub mod iter;
+pub mod retry;
+pub mod shared;
+pub mod stats;
 pub mod traits;
 
 pub mod mmap;
 pub mod userptr;
+
+/// Error returned when no buffer became available within the configured timeout
+pub(crate) fn timed_out() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        "timed out waiting for a buffer",
+    )
+}
@@ -0,0 +1,155 @@
+//! Retry behavior of calls which would block
+//!
+//! Calls interrupted by a signal (EINTR) are always restarted by `v4l2::ioctl`. Devices opened
+//! with O_NONBLOCK additionally fail with EAGAIN whenever no buffer is ready, even after poll(2)
+//! reported readiness, e.g. when an event rather than a buffer woke the caller up. The policy
+//! defined here decides what streams do in that case.
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::device::Handle;
+
+/// What to do when a call on a non-blocking device fails with EAGAIN
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Retry {
+    /// Return the WouldBlock error to the caller right away
+    Fail,
+    /// Wait for the device to become ready and try again until the call succeeds
+    Poll,
+    /// Wait for the device to become ready and try again, at most the given number of times
+    Limit(u32),
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Retry::Fail
+    }
+}
+
+impl Retry {
+    /// Runs a call, retrying it according to the policy
+    ///
+    /// Between attempts, the device is polled for the given events. Once the deadline passes,
+    /// an error of kind `TimedOut` is returned no matter how many attempts the policy has left.
+    ///
+    /// # Arguments
+    ///
+    /// * `handle` - Device the call operates on
+    /// * `events` - Poll events signaling readiness, e.g. POLLIN
+    /// * `deadline` - Point in time after which no more attempts are made, None waits forever
+    /// * `f` - The call
+    pub(crate) fn run<T, F>(
+        &self,
+        handle: &Handle,
+        events: i16,
+        deadline: Option<Instant>,
+        mut f: F,
+    ) -> io::Result<T>
+    where
+        F: FnMut() -> io::Result<T>,
+    {
+        let mut attempts = 0;
+        loop {
+            let err = match f() {
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => e,
+                res => return res,
+            };
+
+            match *self {
+                Retry::Fail => return Err(err),
+                Retry::Limit(limit) if attempts >= limit => return Err(err),
+                _ => attempts += 1,
+            }
+
+            let timeout = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if remaining > Duration::ZERO => Some(remaining),
+                    _ => return Err(crate::io::timed_out()),
+                },
+                None => None,
+            };
+            if !handle.poll(events, timeout)? {
+                return Err(crate::io::timed_out());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns both ends of a pipe, the read end only becomes ready once data was written
+    fn pipe() -> (Handle, Handle) {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        (Handle::from_fd(fds[0]), Handle::from_fd(fds[1]))
+    }
+
+    fn would_block<T>() -> io::Result<T> {
+        Err(io::Error::from(io::ErrorKind::WouldBlock))
+    }
+
+    #[test]
+    fn deadline_bounds_retries() {
+        let (rx, _tx) = pipe();
+
+        for retry in &[Retry::Poll, Retry::Limit(1000)] {
+            let start = Instant::now();
+            let deadline = start + Duration::from_millis(20);
+            let err = retry
+                .run(&rx, libc::POLLIN, Some(deadline), would_block::<()>)
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(start.elapsed() >= Duration::from_millis(20));
+        }
+
+        // an expired deadline still allows the first attempt
+        let mut attempts = 0;
+        let err = Retry::Poll
+            .run(&rx, libc::POLLIN, Some(Instant::now()), || {
+                attempts += 1;
+                would_block::<()>()
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn policy_limits_attempts() {
+        let (rx, tx) = pipe();
+        // keep the read end ready so that retries do not wait
+        assert_eq!(
+            unsafe { libc::write(tx.fd(), [0u8].as_ptr() as *const _, 1) },
+            1
+        );
+
+        let mut attempts = 0;
+        let err = Retry::Limit(2)
+            .run(&rx, libc::POLLIN, None, || {
+                attempts += 1;
+                would_block::<()>()
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert_eq!(attempts, 3);
+
+        let err = Retry::Fail
+            .run(&rx, libc::POLLIN, None, would_block::<()>)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+
+        let mut attempts = 0;
+        let res = Retry::Poll.run(&rx, libc::POLLIN, None, || {
+            attempts += 1;
+            if attempts < 5 {
+                would_block()
+            } else {
+                Ok(attempts)
+            }
+        });
+        assert_eq!(res.unwrap(), 5);
+    }
+}
@@ -0,0 +1,643 @@
+//! Mapped buffers shared between multiple consumers
+//!
+//! A buffer dequeued from a shared stream is wrapped in a reference counted `Frame`. The frame
+//! can be cloned and sent to other threads, and the buffer is only handed back to the driver once
+//! the last clone has been dropped. This way, e.g. a preview and an encoder thread can look at the
+//! same frame without copying it and without racing against the driver refilling the buffer.
+//!
+//! If consumers hold on to all buffers, the driver has nothing left to fill. What happens then is
+//! decided by the `Backpressure` policy of the stream.
+
+use std::collections::VecDeque;
+use std::ops::Deref;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant};
+use std::{io, slice};
+
+use crate::buffer::{Metadata, Type};
+use crate::device::{Device, Handle};
+use crate::io::mmap;
+use crate::io::traits::{CaptureStream, Stream as StreamTrait};
+
+/// What to do when consumers hold all buffers and the driver queue runs dry
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Wait in next() until a frame is dropped
+    Block,
+    /// Copy a frame out of its buffer and requeue the buffer right away whenever holding on to
+    /// it would leave the driver without buffers
+    ///
+    /// Buffers are never taken away from frames still held by consumers, only the newest frame is
+    /// copied. Use `Frame::is_copy()` to tell such frames apart.
+    Copy,
+    /// Allocate additional buffers (VIDIOC_CREATE_BUFS) until there are `max` buffers in total,
+    /// then block
+    Grow { max: usize },
+}
+
+impl Default for Backpressure {
+    fn default() -> Self {
+        Backpressure::Block
+    }
+}
+
+/// Number of times the backpressure policy had to step in
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Pressure {
+    /// next() had to wait for a frame to be dropped
+    pub blocked: u64,
+    /// A frame was copied out of its buffer
+    pub copied: u64,
+    /// Buffers allocated in addition to the initial ones
+    pub grown: u64,
+}
+
+/// Device queue operations the shared stream builds on
+///
+/// Keeps the buffer lifecycle and the backpressure policies independent of an actual device.
+trait Driver: Send {
+    fn start(&mut self) -> io::Result<()>;
+    fn queue(&mut self, index: usize) -> io::Result<()>;
+    fn dequeue(&mut self) -> io::Result<usize>;
+    fn buffer(&self, index: usize) -> Option<(&[u8], &Metadata)>;
+    /// Allocates additional buffers, returns how many were added
+    fn grow(&mut self, count: u32) -> io::Result<usize>;
+}
+
+impl<'a> Driver for mmap::Stream<'a> {
+    fn start(&mut self) -> io::Result<()> {
+        StreamTrait::start(self)
+    }
+
+    fn queue(&mut self, index: usize) -> io::Result<()> {
+        CaptureStream::queue(self, index)
+    }
+
+    fn dequeue(&mut self) -> io::Result<usize> {
+        CaptureStream::dequeue(self)
+    }
+
+    fn buffer(&self, index: usize) -> Option<(&[u8], &Metadata)> {
+        Some((
+            CaptureStream::get(self, index)?,
+            CaptureStream::get_meta(self, index)?,
+        ))
+    }
+
+    fn grow(&mut self, count: u32) -> io::Result<usize> {
+        mmap::Stream::grow(self, count)
+    }
+}
+
+/// Waits for a buffer to become ready for dequeueing without holding the queue lock
+trait Ready: Send + Sync {
+    fn wait(&self, timeout: Option<Duration>) -> io::Result<bool>;
+}
+
+impl Ready for Handle {
+    fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        self.poll(libc::POLLIN, timeout)
+    }
+}
+
+struct Queue<'a> {
+    stream: Box<dyn Driver + 'a>,
+    started: bool,
+    count: usize,
+    /// Buffers held by consumers, oldest first
+    held: VecDeque<(usize, Weak<FrameInner<'a>>)>,
+    policy: Backpressure,
+    pressure: Pressure,
+}
+
+struct Shared<'a> {
+    ready: Arc<dyn Ready>,
+    queue: Mutex<Queue<'a>>,
+    /// Signalled whenever a buffer is handed back to the driver
+    freed: Condvar,
+}
+
+impl<'a> Shared<'a> {
+    fn lock(&self) -> MutexGuard<'_, Queue<'a>> {
+        // A panicking consumer does not leave the queue in an inconsistent state, so keep going
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Makes sure at least one buffer is queued, applying the backpressure policy if needed
+    fn relieve<'g>(
+        &'g self,
+        mut queue: MutexGuard<'g, Queue<'a>>,
+        deadline: Option<Instant>,
+    ) -> io::Result<MutexGuard<'g, Queue<'a>>> {
+        if queue.held.len() < queue.count {
+            return Ok(queue);
+        }
+
+        if let Backpressure::Grow { max } = queue.policy {
+            if queue.count < max {
+                let added = queue.stream.grow(1)?;
+                for index in queue.count..queue.count + added {
+                    queue.stream.queue(index)?;
+                }
+                queue.count += added;
+                queue.pressure.grown += added as u64;
+                if added > 0 {
+                    return Ok(queue);
+                }
+            }
+        }
+
+        queue.pressure.blocked += 1;
+        while queue.held.len() >= queue.count {
+            queue = match deadline {
+                None => self.freed.wait(queue).unwrap_or_else(|e| e.into_inner()),
+                Some(deadline) => {
+                    let timeout = deadline.saturating_duration_since(Instant::now());
+                    if timeout == Duration::from_secs(0) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            "timed out waiting for a frame to be dropped",
+                        ));
+                    }
+
+                    self.freed
+                        .wait_timeout(queue, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+            };
+        }
+
+        Ok(queue)
+    }
+}
+
+/// Capture stream whose buffers are handed out as reference counted frames
+///
+/// Cloning the stream is cheap, all clones refer to the same device queue.
+#[derive(Clone)]
+pub struct Stream<'a> {
+    shared: Arc<Shared<'a>>,
+    timeout: Option<Duration>,
+}
+
+impl<'a> Stream<'a> {
+    /// Returns a shared stream for frame capturing
+    ///
+    /// Consumers keep buffers away from the driver for as long as they hold on to frames, so
+    /// allocate enough buffers to cover all frames which may be in flight at the same time or
+    /// configure a different policy through set_backpressure().
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Capture device ref to get its file descriptor
+    /// * `buf_type` - Type of the buffers
+    /// * `buf_count` - Desired number of buffers
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::thread;
+    /// use v4l::buffer::Type;
+    /// use v4l::device::Device;
+    /// use v4l::io::shared::Stream;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let stream = Stream::new(&dev, Type::VideoCapture, 8).unwrap();
+    ///
+    /// loop {
+    ///     let frame = stream.next().unwrap();
+    ///     let preview = frame.clone();
+    ///     thread::spawn(move || println!("preview: {} bytes", preview.len()));
+    ///     println!("encode: {} bytes", frame.len());
+    /// }
+    /// ```
+    pub fn new(dev: &Device, buf_type: Type, buf_count: u32) -> io::Result<Self> {
+        let stream = mmap::Stream::with_buffers(dev, buf_type, buf_count)?;
+        let count = StreamTrait::len(&stream);
+
+        Ok(Stream::with_driver(Box::new(stream), dev.handle(), count))
+    }
+
+    fn with_driver(stream: Box<dyn Driver + 'a>, ready: Arc<dyn Ready>, count: usize) -> Self {
+        Stream {
+            shared: Arc::new(Shared {
+                ready,
+                queue: Mutex::new(Queue {
+                    stream,
+                    started: false,
+                    count,
+                    held: VecDeque::new(),
+                    policy: Backpressure::default(),
+                    pressure: Pressure::default(),
+                }),
+                freed: Condvar::new(),
+            }),
+            timeout: None,
+        }
+    }
+
+    /// Sets the policy applied when consumers hold all buffers, `Backpressure::Block` by default
+    ///
+    /// The policy applies to all clones of the stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - Backpressure policy
+    pub fn set_backpressure(&self, policy: Backpressure) {
+        self.shared.lock().policy = policy;
+    }
+
+    /// Returns how often the backpressure policy had to step in so far
+    pub fn pressure(&self) -> Pressure {
+        self.shared.lock().pressure
+    }
+
+    /// Set the time to wait for a buffer in next() before giving up
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, None to wait indefinitely
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Waits for the next frame
+    ///
+    /// Streaming is started on first use. The wait happens without holding the internal lock,
+    /// so frames may be dropped (and their buffers requeued) from other threads in the meantime.
+    /// The buffer is only dequeued once the device is ready with the lock held, so clones of the
+    /// stream calling next() concurrently never block each other in the driver.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&self) -> io::Result<Frame<'a>> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+
+        let mut queue = loop {
+            let mut queue = self.shared.lock();
+            if !queue.started {
+                for index in 0..queue.count {
+                    queue.stream.queue(index)?;
+                }
+                queue.stream.start()?;
+                queue.started = true;
+            }
+            let queue = self.shared.relieve(queue, deadline)?;
+
+            // Another clone may have dequeued the buffer the last wait was woken up for, so check
+            // again: only lock holders dequeue, so DQBUF does not block once the device is ready.
+            if self.shared.ready.wait(Some(Duration::from_secs(0)))? {
+                break queue;
+            }
+            drop(queue);
+
+            let timeout =
+                deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if !self.shared.ready.wait(timeout)? {
+                return Err(crate::io::timed_out());
+            }
+        };
+
+        let index = queue.stream.dequeue()?;
+        let (buf, meta) = match queue.stream.buffer(index) {
+            Some((buf, meta)) => (buf, *meta),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("driver returned invalid buffer index {}", index),
+                ))
+            }
+        };
+
+        // Copy the frame instead of handing out the buffer if this was the last one left to the
+        // driver. Nobody else refers to the buffer yet, so it can be requeued right away.
+        if queue.policy == Backpressure::Copy && queue.held.len() + 1 >= queue.count {
+            let data = Data::Copied(Box::from(buf));
+            queue.stream.queue(index)?;
+            queue.pressure.copied += 1;
+
+            return Ok(Frame {
+                inner: Arc::new(FrameInner {
+                    shared: Arc::clone(&self.shared),
+                    index,
+                    data,
+                    meta,
+                }),
+            });
+        }
+
+        let inner = Arc::new(FrameInner {
+            shared: Arc::clone(&self.shared),
+            index,
+            data: Data::Mapped(Mapped {
+                ptr: buf.as_ptr(),
+                len: buf.len(),
+            }),
+            meta,
+        });
+        queue.held.push_back((index, Arc::downgrade(&inner)));
+
+        Ok(Frame { inner })
+    }
+}
+
+/// Buffer memory of a frame still owned by the frame
+struct Mapped {
+    ptr: *const u8,
+    len: usize,
+}
+
+// The pointer refers to a buffer mapped by the stream, which the frame keeps alive. The buffer is
+// only queued again once the frame has been dropped, so neither the driver nor anyone else writes
+// to it while it is read through the frame, from whichever thread.
+unsafe impl Send for Mapped {}
+unsafe impl Sync for Mapped {}
+
+/// Contents of a frame
+enum Data {
+    /// The frame owns the buffer it was captured to
+    Mapped(Mapped),
+    /// The buffer was requeued right away, see `Backpressure::Copy`
+    Copied(Box<[u8]>),
+}
+
+struct FrameInner<'a> {
+    shared: Arc<Shared<'a>>,
+    index: usize,
+    data: Data,
+    meta: Metadata,
+}
+
+impl<'a> Drop for FrameInner<'a> {
+    fn drop(&mut self) {
+        // The buffer of a copied frame has already been handed back to the driver
+        if let Data::Copied(_) = self.data {
+            return;
+        }
+
+        let mut queue = self.shared.lock();
+        let index = self.index;
+        queue.held.retain(|(i, _)| *i != index);
+        // There is no one left to report an error to, e.g. if the device has been unplugged
+        let _ = queue.stream.queue(index);
+        self.shared.freed.notify_all();
+    }
+}
+
+/// Reference counted handle to a captured frame
+///
+/// The buffer is handed back to the driver once the last clone of the frame is dropped.
+#[derive(Clone)]
+pub struct Frame<'a> {
+    inner: Arc<FrameInner<'a>>,
+}
+
+impl<'a> Frame<'a> {
+    /// Returns the index of the underlying buffer
+    pub fn index(&self) -> usize {
+        self.inner.index
+    }
+
+    /// Returns the frame metadata
+    pub fn meta(&self) -> &Metadata {
+        &self.inner.meta
+    }
+
+    /// Returns true if the frame was copied out of its buffer by `Backpressure::Copy`
+    pub fn is_copy(&self) -> bool {
+        matches!(self.inner.data, Data::Copied(_))
+    }
+}
+
+impl<'a> Deref for Frame<'a> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match &self.inner.data {
+            Data::Mapped(mapped) => unsafe { slice::from_raw_parts(mapped.ptr, mapped.len) },
+            Data::Copied(data) => data,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// State of the fake device queue, shared with the readiness check
+    #[derive(Default)]
+    struct Fake {
+        /// Indices of the buffers queued to the driver, in order
+        queued: VecDeque<usize>,
+        /// Every QBUF seen so far
+        log: Vec<usize>,
+        /// Index to return from the next DQBUF instead of a queued one
+        bogus: Option<usize>,
+    }
+
+    struct FakeDriver {
+        state: Arc<Mutex<Fake>>,
+        bufs: Vec<(Vec<u8>, Metadata)>,
+    }
+
+    impl Driver for FakeDriver {
+        fn start(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn queue(&mut self, index: usize) -> io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.queued.push_back(index);
+            state.log.push(index);
+            Ok(())
+        }
+
+        fn dequeue(&mut self) -> io::Result<usize> {
+            let mut state = self.state.lock().unwrap();
+            if let Some(index) = state.bogus.take() {
+                return Ok(index);
+            }
+            Ok(state.queued.pop_front().expect("DQBUF with an empty queue"))
+        }
+
+        fn buffer(&self, index: usize) -> Option<(&[u8], &Metadata)> {
+            let (buf, meta) = self.bufs.get(index)?;
+            Some((buf, meta))
+        }
+
+        fn grow(&mut self, count: u32) -> io::Result<usize> {
+            for _ in 0..count {
+                let index = self.bufs.len();
+                self.bufs.push((vec![index as u8; 4], Metadata::default()));
+            }
+            Ok(count as usize)
+        }
+    }
+
+    struct FakeReady(Arc<Mutex<Fake>>);
+
+    impl Ready for FakeReady {
+        fn wait(&self, _timeout: Option<Duration>) -> io::Result<bool> {
+            // Frames are captured instantly, so there is no point in waiting
+            let state = self.0.lock().unwrap();
+            Ok(!state.queued.is_empty() || state.bogus.is_some())
+        }
+    }
+
+    fn fake(count: usize) -> (Stream<'static>, Arc<Mutex<Fake>>) {
+        let state = Arc::new(Mutex::new(Fake::default()));
+        let mut driver = FakeDriver {
+            state: Arc::clone(&state),
+            bufs: Vec::new(),
+        };
+        driver.grow(count as u32).unwrap();
+
+        let ready = Arc::new(FakeReady(Arc::clone(&state)));
+        let mut stream = Stream::with_driver(Box::new(driver), ready, count);
+        stream.set_timeout(Some(Duration::from_millis(10)));
+        (stream, state)
+    }
+
+    #[test]
+    fn requeue_after_last_clone() {
+        let (stream, state) = fake(2);
+
+        let frame = stream.next().unwrap();
+        assert_eq!(frame.index(), 0);
+        assert_eq!(&*frame, &[0; 4]);
+        assert!(!frame.is_copy());
+        let clone = frame.clone();
+        assert_eq!(state.lock().unwrap().log, [0, 1]);
+
+        drop(frame);
+        assert_eq!(state.lock().unwrap().log, [0, 1]);
+        assert_eq!(stream.shared.lock().held.len(), 1);
+
+        drop(clone);
+        assert_eq!(state.lock().unwrap().log, [0, 1, 0]);
+        assert_eq!(stream.shared.lock().held.len(), 0);
+    }
+
+    #[test]
+    fn block_times_out() {
+        let (stream, _) = fake(2);
+        stream.set_backpressure(Backpressure::Block);
+
+        let _first = stream.next().unwrap();
+        let _second = stream.next().unwrap();
+        let err = stream.next().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            stream.pressure(),
+            Pressure {
+                blocked: 1,
+                ..Pressure::default()
+            }
+        );
+    }
+
+    #[test]
+    fn block_resumes_after_drop() {
+        let (mut stream, _) = fake(1);
+        stream.set_timeout(None);
+
+        let first = stream.next().unwrap();
+        let waiter = {
+            let stream = stream.clone();
+            thread::spawn(move || stream.next().map(|frame| frame.index()))
+        };
+        // Give the waiter a chance to block, then hand the buffer back
+        thread::sleep(Duration::from_millis(10));
+        drop(first);
+
+        assert_eq!(waiter.join().unwrap().unwrap(), 0);
+    }
+
+    #[test]
+    fn copy_requeues_last_buffer() {
+        let (stream, state) = fake(2);
+        stream.set_backpressure(Backpressure::Copy);
+
+        let first = stream.next().unwrap();
+        assert!(!first.is_copy());
+        let second = stream.next().unwrap();
+        assert!(second.is_copy());
+        assert_eq!(second.index(), 1);
+        assert_eq!(&*second, &[1; 4]);
+        // The buffer went straight back to the driver
+        assert_eq!(state.lock().unwrap().log, [0, 1, 1]);
+        assert_eq!(stream.shared.lock().held.len(), 1);
+
+        // Dropping a copy must not queue the buffer a second time
+        drop(second);
+        assert_eq!(state.lock().unwrap().log, [0, 1, 1]);
+        assert_eq!(stream.pressure().copied, 1);
+        drop(first);
+    }
+
+    #[test]
+    fn grow_up_to_max() {
+        let (stream, _) = fake(1);
+        stream.set_backpressure(Backpressure::Grow { max: 2 });
+
+        let first = stream.next().unwrap();
+        let second = stream.next().unwrap();
+        assert_eq!(second.index(), 1);
+        assert_eq!(&*second, &[1; 4]);
+        assert_eq!(stream.pressure().grown, 1);
+
+        // Blocks once the limit is reached
+        let err = stream.next().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            stream.pressure(),
+            Pressure {
+                blocked: 1,
+                copied: 0,
+                grown: 1,
+            }
+        );
+        drop((first, second));
+    }
+
+    #[test]
+    fn invalid_index() {
+        let (stream, state) = fake(2);
+        state.lock().unwrap().bogus = Some(7);
+
+        let err = stream.next().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn next_from_two_threads() {
+        // needs a capture device such as vivid, e.g. V4L_TEST_DEVICE=/dev/video0
+        let path = match std::env::var("V4L_TEST_DEVICE") {
+            Ok(path) => path,
+            Err(_) => return,
+        };
+
+        let dev = Device::with_path(path).unwrap();
+        // fewer buffers than frames in flight, so both threads compete for them
+        let mut stream = Stream::new(&dev, Type::VideoCapture, 2).unwrap();
+        stream.set_timeout(Some(Duration::from_secs(5)));
+
+        let threads: Vec<_> = (0..2)
+            .map(|_| {
+                let stream = stream.clone();
+                thread::spawn(move || {
+                    for _ in 0..30 {
+                        let frame = stream.next().unwrap();
+                        assert!(!frame.is_empty());
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(stream.shared.lock().held.len(), 0);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use std::{io, mem};
+
+use crate::buffer::{Clock, Latency, Metadata};
+
+/// Number of samples kept for computing percentiles
+const WINDOW: usize = 512;
+
+/// Latency statistics of a stream
+///
+/// Percentiles are computed over the most recent frames only, so they follow changes in the
+/// system load instead of averaging over the whole lifetime of the stream.
+#[derive(Debug, Default, Clone)]
+pub struct Stats {
+    frames: u64,
+    queued: VecDeque<Duration>,
+    delivery: VecDeque<Duration>,
+}
+
+impl Stats {
+    /// Returns the number of frames dequeued so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Returns a percentile of the time buffers spent in the driver (VIDIOC_QBUF to VIDIOC_DQBUF)
+    ///
+    /// Returns None if no frame was dequeued yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `percentile` - Percentile from 0.0 to 100.0, e.g. 50.0 for the median
+    pub fn queued(&self, percentile: f64) -> Option<Duration> {
+        Self::percentile(&self.queued, percentile)
+    }
+
+    /// Returns a percentile of the time between the driver timestamp and the dequeue operation
+    ///
+    /// Only buffers with monotonic timestamps are taken into account. Returns None if there
+    /// were none.
+    ///
+    /// # Arguments
+    ///
+    /// * `percentile` - Percentile from 0.0 to 100.0, e.g. 99.0 to look at outliers
+    pub fn delivery(&self, percentile: f64) -> Option<Duration> {
+        Self::percentile(&self.delivery, percentile)
+    }
+
+    /// Discards all samples
+    pub fn reset(&mut self) {
+        *self = Stats::default();
+    }
+
+    pub(crate) fn record(&mut self, latency: &Latency) {
+        self.frames += 1;
+        if let Some(queued) = latency.queued {
+            Self::push(&mut self.queued, queued);
+        }
+        if let Some(delivery) = latency.delivery {
+            Self::push(&mut self.delivery, delivery);
+        }
+    }
+
+    fn push(samples: &mut VecDeque<Duration>, sample: Duration) {
+        if samples.len() == WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    fn percentile(samples: &VecDeque<Duration>, percentile: f64) -> Option<Duration> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted: Vec<Duration> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = (percentile.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64).round();
+        Some(sorted[rank as usize])
+    }
+}
+
+/// Measures the latency of a buffer which has just been dequeued
+///
+/// # Arguments
+///
+/// * `queued_at` - Time the buffer was queued, if known
+/// * `meta` - Metadata of the dequeued buffer
+pub(crate) fn measure(queued_at: Option<Instant>, meta: &Metadata) -> Latency {
+    let queued = queued_at.map(|instant| instant.elapsed());
+
+    let delivery = if meta.clock() == Clock::Monotonic {
+        monotonic_now()
+            .ok()
+            .and_then(|now| now.checked_sub(Duration::from(meta.timestamp)))
+    } else {
+        None
+    };
+
+    Latency { queued, delivery }
+}
+
+pub(crate) fn monotonic_now() -> io::Result<Duration> {
+    unsafe {
+        let mut ts: libc::timespec = mem::zeroed();
+        if libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+    }
+}
@@ -1,6 +1,30 @@
 use std::io;
+use std::time::{Duration, Instant};
 
-use crate::buffer::Metadata;
+use crate::buffer::{Flags, Metadata};
+use crate::event::Event;
+use crate::v4l2;
+
+/// Result of dequeueing a buffer while watching for the end of a stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StreamEvent {
+    /// A regular buffer was dequeued, contains the buffer index
+    Buffer(usize),
+    /// The driver signalled the end of the stream
+    ///
+    /// Contains the index of the buffer carrying V4L2_BUF_FLAG_LAST if there was one. Drivers may
+    /// return an empty last buffer (bytesused == 0), so check its metadata before processing it.
+    /// If the last buffer was already dequeued earlier, the index is None.
+    EndOfStream(Option<usize>),
+}
+
+/// Result of waiting on a stream which reports events inline
+pub enum Next<'a, T: ?Sized> {
+    /// A frame was captured
+    Frame(&'a T, &'a Metadata),
+    /// An event was dequeued before a frame became available
+    Event(Event),
+}
 
 /// Streaming I/O
 pub trait Stream {
@@ -11,6 +35,29 @@ pub trait Stream {
 
     /// Stop streaming, frees all buffers
     fn stop(&mut self) -> io::Result<()>;
+
+    /// Set the time to wait for a buffer in next() before giving up
+    ///
+    /// If no timeout is set (the default), next() blocks until a buffer becomes available.
+    /// Streams which cannot time out ignore the setting, which is what the default
+    /// implementation does.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, None to wait indefinitely
+    fn set_timeout(&mut self, _timeout: Option<Duration>) {}
+
+    /// Returns the number of buffers allocated for the stream
+    ///
+    /// The default implementation reports no buffers.
+    fn len(&self) -> usize {
+        0
+    }
+
+    /// Returns true if the stream has no buffers
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 }
 
 pub trait CaptureStream<'a>: Stream {
@@ -20,6 +67,74 @@ pub trait CaptureStream<'a>: Stream {
     /// Remove a buffer from the drivers' outgoing queue
     fn dequeue(&mut self) -> io::Result<usize>;
 
+    /// Remove a buffer from the drivers' outgoing queue, waiting at most until the deadline
+    ///
+    /// Returns an error of kind `TimedOut` if no buffer became available in time. Unlike
+    /// next_before(), this leaves queueing to the caller, e.g. to hold on to several buffers.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - Point in time by which the buffer must have been filled
+    ///
+    /// The default implementation returns an error of kind `Unsupported`.
+    fn dequeue_before(&mut self, _deadline: Instant) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "stream does not support deadlines",
+        ))
+    }
+
+    /// Remove a buffer from the drivers' outgoing queue and check for the end of the stream
+    ///
+    /// Memory-to-memory decoders mark the final buffer of a drained stream with
+    /// V4L2_BUF_FLAG_LAST and fail any further dequeue attempts with EPIPE. Both cases are
+    /// reported as `StreamEvent::EndOfStream` so drain loops know when to terminate.
+    fn dequeue_event(&mut self) -> io::Result<StreamEvent> {
+        match self.dequeue() {
+            Ok(index) => match self.get_meta(index) {
+                Some(meta) if meta.flags.contains(Flags::LAST) => {
+                    Ok(StreamEvent::EndOfStream(Some(index)))
+                }
+                _ => Ok(StreamEvent::Buffer(index)),
+            },
+            Err(e) if v4l2::errno(&e) == Some(libc::EPIPE) => Ok(StreamEvent::EndOfStream(None)),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Hand out buffers until the driver signals the end of a drained stream
+    ///
+    /// Call this after sending a stop command to an encoder or decoder. Every dequeued buffer is
+    /// handed to the closure and queued again, until the buffer carrying V4L2_BUF_FLAG_LAST has
+    /// been dequeued. An empty last buffer is not handed to the closure.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure receiving each buffer along with its metadata
+    fn drain<F>(&mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&Self::Item, &Metadata),
+    {
+        loop {
+            let (index, last) = match self.dequeue_event()? {
+                StreamEvent::Buffer(index) => (index, false),
+                StreamEvent::EndOfStream(Some(index)) => (index, true),
+                StreamEvent::EndOfStream(None) => return Ok(()),
+            };
+
+            if let (Some(buf), Some(meta)) = (self.get(index), self.get_meta(index)) {
+                if !last || meta.bytesused > 0 {
+                    f(buf, meta);
+                }
+            }
+
+            if last {
+                return Ok(());
+            }
+            self.queue(index)?;
+        }
+    }
+
     /// Get the buffer at the specified index
     fn get(&self, index: usize) -> Option<&Self::Item>;
 
@@ -29,6 +144,84 @@ pub trait CaptureStream<'a>: Stream {
     /// Fetch a new frame by first queueing and then dequeueing.
     /// First time initialization is performed if necessary.
     fn next(&'a mut self) -> io::Result<(&Self::Item, &Metadata)>;
+
+    /// Fetch a new frame like next(), but wait at most for the given amount of time
+    ///
+    /// Returns an error of kind `TimedOut` if no frame became available in time, e.g. because
+    /// the camera silently stopped delivering frames.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, overrides the stream default for this call
+    ///
+    /// The default implementation returns an error of kind `Unsupported`.
+    fn next_timeout(
+        &'a mut self,
+        _timeout: Duration,
+    ) -> io::Result<(&'a Self::Item, &'a Metadata)> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "stream does not support timeouts",
+        ))
+    }
+
+    /// Fetch a new frame if one becomes available before the deadline
+    ///
+    /// Returns `Ok(None)` if the deadline passed (or the device would block) without a frame
+    /// becoming available, so schedulers can tell a missing frame apart from an actual error.
+    /// A deadline in the past still picks up a frame which is already waiting.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - Point in time by which the frame must have arrived
+    ///
+    /// The default implementation returns an error of kind `Unsupported`.
+    fn next_before(
+        &'a mut self,
+        _deadline: Instant,
+    ) -> io::Result<Option<(&'a Self::Item, &'a Metadata)>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "stream does not support deadlines",
+        ))
+    }
+
+    /// Fetch a new frame like next(), but report subscribed events as well
+    ///
+    /// Events are only reported after subscribing to them on the stream. Otherwise, this behaves
+    /// exactly like next(). An EPIPE error from a drained decoder is reported as
+    /// `Event::EndOfStream`.
+    ///
+    /// The default implementation never reports events and only wraps next().
+    fn next_event(&'a mut self) -> io::Result<Next<'a, Self::Item>> {
+        let (buf, meta) = self.next()?;
+        Ok(Next::Frame(buf, meta))
+    }
+
+    /// Stop streaming after draining all buffers which are still owned by the driver
+    ///
+    /// Buffers that are queued at the time of calling are dequeued one by one and handed to the
+    /// closure before VIDIOC_STREAMOFF is issued. This way, frames which the hardware has already
+    /// filled are delivered instead of being dropped.
+    ///
+    /// Each buffer is waited for at most as long as the stream timeout, or one second if there is
+    /// none. Streaming is stopped even if draining fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure receiving each drained buffer along with its metadata
+    ///
+    /// The default implementation returns an error of kind `Unsupported` and leaves the stream
+    /// running.
+    fn stop_draining<F>(&mut self, _f: F) -> io::Result<()>
+    where
+        F: FnMut(&Self::Item, &Metadata),
+    {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "stream does not support draining",
+        ))
+    }
 }
 
 pub trait OutputStream<'a>: Stream {
@@ -1,19 +1,63 @@
-use std::{io, mem, sync::Arc};
+use std::alloc::{self, Layout};
+use std::ptr::NonNull;
+use std::{cmp, io, mem, slice, sync::Arc};
 
 use crate::buffer;
 use crate::device::Handle;
 use crate::io::arena::Arena as ArenaTrait;
-use crate::memory::Memory;
+use crate::io::userptr::Options;
+use crate::memory::{Backing, Memory, Usage};
 use crate::v4l2;
 use crate::v4l_sys::*;
 
+/// Heap allocation with a custom alignment
+struct Buffer {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+// The buffer exclusively owns its allocation, just like a Vec<u8> would.
+unsafe impl Send for Buffer {}
+unsafe impl Sync for Buffer {}
+
+impl Buffer {
+    fn new(size: usize, align: usize) -> io::Result<Self> {
+        let layout = Layout::from_size_align(size, align)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        match NonNull::new(unsafe { alloc::alloc_zeroed(layout) }) {
+            Some(ptr) => Ok(Buffer { ptr, layout }),
+            None => Err(io::Error::new(
+                io::ErrorKind::OutOfMemory,
+                format!("failed to allocate {} bytes", size),
+            )),
+        }
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
 /// Manage user allocated buffers
 ///
-/// All buffers are released in the Drop impl.
+/// Buffers start at page boundaries by default, which is what most drivers doing DMA into user
+/// memory require. All buffers are released in the Drop impl.
 pub struct Arena {
     handle: Arc<Handle>,
-    bufs: Vec<Vec<u8>>,
+    bufs: Vec<Buffer>,
     buf_type: buffer::Type,
+    options: Options,
 }
 
 impl Arena {
@@ -26,24 +70,46 @@ impl Arena {
     ///
     /// * `dev` - Device handle to get its file descriptor
     /// * `buf_type` - Type of the buffers
-    pub fn new(handle: Arc<Handle>, buf_type: buffer::Type) -> Self {
+    /// * `options` - Allocation options
+    pub fn new(handle: Arc<Handle>, buf_type: buffer::Type, options: Options) -> Self {
         Arena {
             handle,
             bufs: Vec::new(),
             buf_type,
+            options,
         }
     }
+
+    /// Returns the size of a single buffer for the given format, honoring the options
+    fn buffer_size(&self, pix: &v4l2_pix_format) -> io::Result<usize> {
+        // Some drivers report a sizeimage which is too small for the negotiated stride
+        let size = cmp::max(
+            pix.sizeimage as usize,
+            pix.bytesperline as usize * pix.height as usize,
+        );
+        if size == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "driver reported a buffer size of zero",
+            ));
+        }
+
+        Ok(match self.options.size_multiple {
+            Some(multiple) if multiple > 1 => size + (multiple - size % multiple) % multiple,
+            _ => size,
+        })
+    }
 }
 
 impl Drop for Arena {
     fn drop(&mut self) {
-        if self.bufs.is_empty() {
+        if self.is_empty() {
             // nothing to do
             return;
         }
 
         if let Err(e) = self.release() {
-            if let Some(code) = e.raw_os_error() {
+            if let Some(code) = v4l2::errno(&e) {
                 // ENODEV means the file descriptor wrapped in the handle became invalid, most
                 // likely because the device was unplugged or the connection (USB, PCI, ..)
                 // broke down. Handle this case gracefully by ignoring it.
@@ -95,12 +161,14 @@ impl ArenaTrait for Arena {
         }
 
         // allocate the new user buffers
-        self.bufs.resize(v4l2_reqbufs.count as usize, Vec::new());
-        for i in 0..v4l2_reqbufs.count {
-            let buf = &mut self.bufs[i as usize];
-            unsafe {
-                buf.resize(v4l2_fmt.fmt.pix.sizeimage as usize, 0);
-            }
+        let size = self.buffer_size(unsafe { &v4l2_fmt.fmt.pix })?;
+        let align = match self.options.alignment {
+            Some(align) => align,
+            None => unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize },
+        };
+        self.bufs.clear();
+        for _ in 0..v4l2_reqbufs.count {
+            self.bufs.push(Buffer::new(size, align)?);
         }
 
         Ok(v4l2_reqbufs.count)
@@ -123,22 +191,41 @@ impl ArenaTrait for Arena {
     }
 
     fn get(&self, index: usize) -> Option<&Self::Buffer> {
-        Some(self.bufs.get(index)?)
+        Some(self.bufs.get(index)?.as_slice())
     }
 
     fn get_mut(&mut self, index: usize) -> Option<&mut Self::Buffer> {
-        Some(self.bufs.get_mut(index)?)
+        Some(self.bufs.get_mut(index)?.as_mut_slice())
     }
 
     unsafe fn get_unchecked(&self, index: usize) -> &Self::Buffer {
-        self.bufs.get_unchecked(index)
+        self.bufs.get_unchecked(index).as_slice()
     }
 
     unsafe fn get_unchecked_mut(&mut self, index: usize) -> &mut Self::Buffer {
-        self.bufs.get_unchecked_mut(index)
+        self.bufs.get_unchecked_mut(index).as_mut_slice()
     }
 
     fn len(&self) -> usize {
         self.bufs.len()
     }
+
+    fn backing(&self, index: usize, plane: usize) -> Option<Backing> {
+        if plane != 0 {
+            return None;
+        }
+
+        Some(Backing::UserPtr(self.bufs.get(index)?.ptr.as_ptr() as usize))
+    }
+
+    fn memory_usage(&self) -> Usage {
+        Usage {
+            memory: Memory::UserPtr,
+            buffers: self
+                .bufs
+                .iter()
+                .map(|buf| vec![buf.layout.size()])
+                .collect(),
+        }
+    }
 }
@@ -1,23 +1,60 @@
+use std::time::{Duration, Instant};
 use std::{io, mem, sync::Arc};
 
-use crate::buffer::{Metadata, Type};
+use crate::buffer::{Flags, Latency, Metadata, State, StateError, Type};
 use crate::device::{Device, Handle};
+use crate::event::{self, Event};
+use crate::format::QueueFormat;
 use crate::io::arena::Arena as ArenaTrait;
-use crate::io::traits::{CaptureStream, Stream as StreamTrait};
+use crate::io::conformance::{self, Validator};
+use crate::io::drain;
+use crate::io::iter::{Frames, IntoFrames, IntoIter, Iter};
+use crate::io::retry::Retry;
+use crate::io::stats::{self, Stats};
+use crate::io::traits::{CaptureStream, Next, Stream as StreamTrait};
 use crate::io::userptr::arena::Arena;
-use crate::memory::Memory;
+use crate::memory::{Backing, Memory, Usage};
 use crate::v4l2;
 use crate::v4l_sys::*;
 
+/// Options for allocating the buffers of a stream
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Options {
+    /// Alignment of the buffer start addresses in bytes, must be a power of two
+    ///
+    /// Defaults to the page size, which satisfies the DMA requirements of most drivers.
+    pub alignment: Option<usize>,
+    /// Round the buffer size up to a multiple of this many bytes, e.g. the cache line size
+    pub size_multiple: Option<usize>,
+    /// Fill capture buffers with a pattern before queueing them and check it after dequeueing
+    ///
+    /// This is a debugging aid for driver development, see `mmap::Options::poison`.
+    pub poison: bool,
+}
+
 /// Stream of user buffers
 ///
 /// An arena instance is used internally for buffer handling.
-pub struct Stream {
+pub struct Stream<T = ()> {
     handle: Arc<Handle>,
     arena: Arena,
     arena_index: usize,
     buf_type: Type,
     buf_meta: Vec<Metadata>,
+    buf_state: Vec<State>,
+    buf_cookies: Vec<Option<T>>,
+    /// Cookie of the buffer dequeued last
+    dequeued_cookie: Option<T>,
+    /// The buffer dequeued last carried V4L2_BUF_FLAG_LAST
+    drained: bool,
+    queue_flags: Vec<Flags>,
+    queued_at: Vec<Option<Instant>>,
+    stats: Stats,
+    timeout: Option<Duration>,
+    retry: Retry,
+    events: bool,
+    poison: bool,
+    validator: Option<Validator>,
 
     active: bool,
 }
@@ -47,10 +84,84 @@ impl Stream {
     }
 
     pub fn with_buffers(dev: &Device, buf_type: Type, buf_count: u32) -> io::Result<Self> {
-        let mut arena = Arena::new(dev.handle(), buf_type);
+        Stream::with_cookies(dev, buf_type, buf_count)
+    }
+}
+
+impl<T> Stream<T> {
+    /// Returns a stream which can carry a user value (cookie) along with each buffer
+    ///
+    /// Cookies are attached to buffers before they are queued and retrieved through
+    /// dequeued_cookie() once the buffers have been dequeued again, e.g. to match the settings
+    /// used for a frame with the result.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Device ref to get its file descriptor
+    /// * `buf_type` - Type of the buffers
+    /// * `buf_count` - Desired number of buffers
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::buffer::Type;
+    /// use v4l::device::Device;
+    /// use v4l::io::userptr::Stream;
+    ///
+    /// let dev = Device::new(0);
+    /// if let Ok(dev) = dev {
+    ///     let stream = Stream::<u64>::with_cookies(&dev, Type::VideoCapture, 4);
+    /// }
+    /// ```
+    pub fn with_cookies(dev: &Device, buf_type: Type, buf_count: u32) -> io::Result<Self> {
+        Stream::with_options(dev, buf_type, buf_count, Options::default())
+    }
+
+    /// Returns a stream whose buffers are allocated according to the options
+    ///
+    /// The buffer size is derived from the format negotiated with the driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Device ref to get its file descriptor
+    /// * `buf_type` - Type of the buffers
+    /// * `buf_count` - Desired number of buffers
+    /// * `options` - Allocation options
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::buffer::Type;
+    /// use v4l::device::Device;
+    /// use v4l::io::userptr::{Options, Stream};
+    ///
+    /// let dev = Device::new(0);
+    /// if let Ok(dev) = dev {
+    ///     let options = Options {
+    ///         size_multiple: Some(64),
+    ///         ..Options::default()
+    ///     };
+    ///     let stream = Stream::<()>::with_options(&dev, Type::VideoCapture, 4, options);
+    /// }
+    /// ```
+    pub fn with_options(
+        dev: &Device,
+        buf_type: Type,
+        buf_count: u32,
+        options: Options,
+    ) -> io::Result<Self> {
+        let mut arena = Arena::new(dev.handle(), buf_type, options);
         let count = arena.allocate(buf_count)?;
         let mut buf_meta = Vec::new();
         buf_meta.resize(count as usize, Metadata::default());
+        let mut buf_state = Vec::new();
+        buf_state.resize(count as usize, State::Free);
+        let mut buf_cookies = Vec::new();
+        buf_cookies.resize_with(count as usize, || None);
+        let mut queue_flags = Vec::new();
+        queue_flags.resize(count as usize, Flags::empty());
+        let mut queued_at = Vec::new();
+        queued_at.resize(count as usize, None);
 
         Ok(Stream {
             handle: dev.handle(),
@@ -58,15 +169,343 @@ impl Stream {
             arena_index: 0,
             buf_type,
             buf_meta,
+            buf_state,
+            buf_cookies,
+            dequeued_cookie: None,
+            drained: false,
+            queue_flags,
+            queued_at,
+            stats: Stats::default(),
+            timeout: None,
+            retry: Retry::default(),
+            events: false,
+            poison: options.poison && !buf_type.is_output(),
+            validator: None,
             active: false,
         })
     }
+
+    /// Attaches a user value to a buffer, replacing and returning any previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `cookie` - User value
+    pub fn set_cookie(&mut self, index: usize, cookie: T) -> io::Result<Option<T>> {
+        match self.buf_cookies.get_mut(index) {
+            Some(slot) => Ok(slot.replace(cookie)),
+            None => Err(StateError::InvalidIndex(index).into()),
+        }
+    }
+
+    /// Returns the user value attached to a buffer which has not been dequeued yet
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    pub fn cookie(&self, index: usize) -> Option<&T> {
+        self.buf_cookies.get(index)?.as_ref()
+    }
+
+    /// Detaches the user value from a buffer and returns it
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    pub fn take_cookie(&mut self, index: usize) -> Option<T> {
+        self.buf_cookies.get_mut(index)?.take()
+    }
+
+    /// Returns the index of the buffer dequeued last, e.g. the one returned by next()
+    pub fn index(&self) -> usize {
+        self.arena_index
+    }
+
+    /// Returns the user value the buffer dequeued last was queued with
+    ///
+    /// Dequeueing a buffer detaches its cookie, so a buffer which is queued again does not carry
+    /// a stale value along.
+    pub fn dequeued_cookie(&self) -> Option<&T> {
+        self.dequeued_cookie.as_ref()
+    }
+
+    /// Takes the user value the buffer dequeued last was queued with, see dequeued_cookie()
+    pub fn take_dequeued_cookie(&mut self) -> Option<T> {
+        self.dequeued_cookie.take()
+    }
+
+    /// Sets the flags to pass to the driver the next time a buffer is queued
+    ///
+    /// The flags only apply to the next successful queue operation and are cleared afterwards.
+    /// Only flags contained in `Flags::queueable` may be set, e.g. to mark an output buffer as
+    /// keyframe.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `flags` - Flags to set on queue
+    pub fn set_queue_flags(&mut self, index: usize, flags: Flags) -> io::Result<()> {
+        let queueable = Flags::queueable(self.buf_type);
+        if !queueable.contains(flags) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("buffer flags cannot be set on queue: {}", flags - queueable),
+            ));
+        }
+
+        match self.queue_flags.get_mut(index) {
+            Some(slot) => {
+                *slot = flags;
+                Ok(())
+            }
+            None => Err(StateError::InvalidIndex(index).into()),
+        }
+    }
+
+    /// Returns the memory occupied by the buffers of the stream
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::buffer::Type;
+    /// use v4l::device::Device;
+    /// use v4l::io::userptr::Stream;
+    ///
+    /// let dev = Device::new(0);
+    /// if let Ok(dev) = dev {
+    ///     if let Ok(stream) = Stream::new(&dev, Type::VideoCapture) {
+    ///         println!("{}", stream.memory_usage());
+    ///     }
+    /// }
+    /// ```
+    pub fn memory_usage(&self) -> Usage {
+        self.arena.memory_usage()
+    }
+
+    /// Returns the number of planes per buffer
+    ///
+    /// The stream uses single-planar buffer types, so every buffer has exactly one plane.
+    pub fn plane_count(&self) -> usize {
+        self.arena.plane_count()
+    }
+
+    /// Returns a single plane of a buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `plane` - Plane index, 0 for single-planar buffers
+    pub fn plane(&self, index: usize, plane: usize) -> Option<&[u8]> {
+        self.arena.plane(index, plane)
+    }
+
+    /// Returns the resource backing a buffer plane, e.g. to import it into another API
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `plane` - Plane index, 0 for single-planar buffers
+    pub fn backing(&self, index: usize, plane: usize) -> Option<Backing> {
+        self.arena.backing(index, plane)
+    }
+
+    /// Returns the latency statistics of the stream
+    ///
+    /// The latency of each individual buffer is available through its metadata.
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Sets what dequeueing does when a non-blocking device has no buffer ready (default: fail)
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - Retry policy
+    pub fn set_retry(&mut self, retry: Retry) {
+        self.retry = retry;
+    }
+
+    /// Enables strict mode, validating every dequeued buffer against the V4L2 specification
+    ///
+    /// Violations are reported to the validator, the buffers are delivered as usual. Pass None
+    /// to disable strict mode again. Returns the previous validator, if any.
+    ///
+    /// # Arguments
+    ///
+    /// * `validator` - Validator to use
+    pub fn set_validator(&mut self, validator: Option<Validator>) -> Option<Validator> {
+        mem::replace(&mut self.validator, validator)
+    }
+
+    /// Returns the validator used in strict mode
+    pub fn validator(&self) -> Option<&Validator> {
+        self.validator.as_ref()
+    }
+
+    fn check_queue(&self, index: usize) -> io::Result<()> {
+        match self.buf_state.get(index) {
+            None => Err(StateError::InvalidIndex(index).into()),
+            Some(State::Queued) => Err(StateError::AlreadyQueued(index).into()),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn check_dequeue(&self) -> io::Result<()> {
+        if self.buf_state.contains(&State::Queued) {
+            Ok(())
+        } else if self.drained {
+            // the driver fails any dequeue after the last buffer, whether buffers are queued or not
+            Err(io::Error::from_raw_os_error(libc::EPIPE))
+        } else {
+            Err(StateError::NothingQueued.into())
+        }
+    }
+
+    /// Subscribes to events which are then reported by next_event()
+    ///
+    /// # Arguments
+    ///
+    /// * `types` - Event types, e.g. `event::Type::Eos` and `event::Type::SourceChange`
+    pub fn subscribe_events(&mut self, types: &[event::Type]) -> io::Result<()> {
+        for typ in types {
+            event::subscribe(&self.handle, *typ, 0)?;
+        }
+
+        self.events = true;
+        Ok(())
+    }
+
+    /// Reallocates all buffers after the source parameters changed
+    ///
+    /// Streaming is stopped, the new format is queried from the driver and the buffers are
+    /// reallocated to match it before streaming is started again. Call this after receiving
+    /// `Event::SourceChange` from next_event(). Any cookies and queue flags are discarded.
+    ///
+    /// The timings detected by DV receivers (e.g. HDMI capture devices) are applied before the
+    /// format is queried, which fails with ENOLINK if the signal is lost.
+    ///
+    /// Returns the new format, which is multi-planar for multi-planar buffer types.
+    pub fn handle_source_change(&mut self) -> io::Result<QueueFormat> {
+        let count = self.arena.len() as u32;
+        self.stop()?;
+        self.arena.release()?;
+
+        let fmt = self.handle.renegotiate(self.buf_type)?;
+
+        let count = self.arena.allocate(count)? as usize;
+        self.buf_meta.clear();
+        self.buf_meta.resize(count, Metadata::default());
+        self.buf_state.clear();
+        self.buf_state.resize(count, State::Free);
+        self.buf_cookies.clear();
+        self.buf_cookies.resize_with(count, || None);
+        self.dequeued_cookie = None;
+        self.queue_flags.clear();
+        self.queue_flags.resize(count, Flags::empty());
+        self.queued_at.clear();
+        self.queued_at.resize(count, None);
+        self.arena_index = 0;
+
+        self.prepare()?;
+        Ok(fmt)
+    }
+
+    /// Hands the last buffer back to the driver and waits for the next one to be filled
+    ///
+    /// Streaming is started on first use. Returns false if the deadline passed before a buffer
+    /// became available, also while retrying a dequeue which would block.
+    fn capture(&mut self, deadline: Option<Instant>) -> io::Result<bool> {
+        self.prepare()?;
+
+        if let Some(deadline) = deadline {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !self.handle.poll(libc::POLLIN, Some(timeout))? {
+                return Ok(false);
+            }
+        }
+
+        match self.dequeue_until(deadline) {
+            Ok(index) => self.arena_index = index,
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => return Ok(false),
+            Err(e) => return Err(e),
+        }
+        Ok(true)
+    }
+
+    /// Dequeues a filled buffer, retrying according to the retry policy until the deadline
+    fn dequeue_until(&mut self, deadline: Option<Instant>) -> io::Result<usize> {
+        self.check_dequeue()?;
+
+        let v4l2_buf = self
+            .retry
+            .run(&self.handle, libc::POLLIN, deadline, || unsafe {
+                let mut v4l2_buf: v4l2_buffer = mem::zeroed();
+                v4l2_buf.type_ = self.buf_type as u32;
+                v4l2_buf.memory = Memory::UserPtr as u32;
+                v4l2::ioctl(
+                    self.handle.fd(),
+                    v4l2::vidioc::VIDIOC_DQBUF,
+                    &mut v4l2_buf as *mut _ as *mut std::os::raw::c_void,
+                )?;
+                Ok(v4l2_buf)
+            })?;
+        self.arena_index = v4l2_buf.index as usize;
+        self.buf_state[self.arena_index] = State::Dequeued;
+        self.dequeued_cookie = self.buf_cookies[self.arena_index].take();
+        self.drained = Flags::from(v4l2_buf.flags).contains(Flags::LAST);
+
+        self.buf_meta[self.arena_index] = Metadata {
+            bytesused: v4l2_buf.bytesused,
+            flags: v4l2_buf.flags.into(),
+            field: v4l2_buf.field,
+            timestamp: v4l2_buf.timestamp.into(),
+            sequence: v4l2_buf.sequence,
+            latency: Latency::default(),
+        };
+        let index = self.arena_index;
+        self.buf_meta[index].latency =
+            stats::measure(self.queued_at[index].take(), &self.buf_meta[index]);
+        self.stats.record(&self.buf_meta[index].latency);
+
+        if let Some(validator) = &mut self.validator {
+            let length = self.arena.get(index).map_or(0, |buf| buf.len());
+            validator.check(&self.buf_meta[index], length);
+        }
+
+        if self.poison {
+            conformance::check_poison(
+                self.arena.get(index).unwrap_or(&[]),
+                index,
+                v4l2_buf.bytesused,
+            )?;
+        }
+
+        Ok(self.arena_index)
+    }
+
+    /// Starts streaming on first use or hands the last buffer back to the driver otherwise
+    fn prepare(&mut self) -> io::Result<()> {
+        if !self.active {
+            // Enqueue all buffers once on stream start
+            for index in 0..self.arena.len() {
+                if self.buf_state[index] != State::Queued {
+                    self.queue(index)?;
+                }
+            }
+
+            self.start()?;
+        } else if self.buf_state[self.arena_index] == State::Dequeued {
+            self.queue(self.arena_index)?;
+        }
+
+        Ok(())
+    }
 }
 
-impl Drop for Stream {
+impl<T> Drop for Stream<T> {
     fn drop(&mut self) {
         if let Err(e) = self.stop() {
-            if let Some(code) = e.raw_os_error() {
+            if let Some(code) = v4l2::errno(&e) {
                 // ENODEV means the file descriptor wrapped in the handle became invalid, most
                 // likely because the device was unplugged or the connection (USB, PCI, ..)
                 // broke down. Handle this case gracefully by ignoring it.
@@ -81,10 +520,44 @@ impl Drop for Stream {
     }
 }
 
-impl StreamTrait for Stream {
+impl<T> Stream<T> {
+    /// Iterate over the captured frames without copying them
+    pub fn frames(&mut self) -> Frames<'_, Self> {
+        Frames::new(self)
+    }
+
+    /// Iterate over the captured frames, taking ownership of the stream
+    pub fn into_frames(self) -> IntoFrames<Self> {
+        IntoFrames::new(self)
+    }
+}
+
+impl<T> IntoIterator for Stream<T> {
+    type Item = io::Result<(Vec<u8>, Metadata)>;
+    type IntoIter = IntoIter<Self>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+impl<'s, T> IntoIterator for &'s mut Stream<T> {
+    type Item = io::Result<(Vec<u8>, Metadata)>;
+    type IntoIter = Iter<'s, Stream<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Iter::new(self)
+    }
+}
+
+impl<T> StreamTrait for Stream<T> {
     type Item = [u8];
 
     fn start(&mut self) -> io::Result<()> {
+        if self.active {
+            return Ok(());
+        }
+
         unsafe {
             let mut typ = self.buf_type as u32;
             v4l2::ioctl(
@@ -108,13 +581,37 @@ impl StreamTrait for Stream {
             )?;
         }
 
+        // VIDIOC_STREAMOFF removes all buffers from the incoming and outgoing queues and
+        // resets the end of stream state
+        for state in self.buf_state.iter_mut() {
+            *state = State::Free;
+        }
+        self.drained = false;
+        if let Some(validator) = &mut self.validator {
+            validator.reset();
+        }
         self.active = false;
         Ok(())
     }
+
+    fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    fn len(&self) -> usize {
+        self.arena.len()
+    }
 }
 
-impl<'a> CaptureStream<'a> for Stream {
+impl<'a, T> CaptureStream<'a> for Stream<T> {
     fn queue(&mut self, index: usize) -> io::Result<()> {
+        self.check_queue(index)?;
+        if self.poison {
+            if let Some(buf) = self.arena.get_mut(index) {
+                conformance::poison(buf);
+            }
+        }
+
         let mut v4l2_buf: v4l2_buffer;
         let buf = unsafe { &mut self.arena.get_unchecked(index) };
         unsafe {
@@ -122,6 +619,7 @@ impl<'a> CaptureStream<'a> for Stream {
             v4l2_buf.type_ = self.buf_type as u32;
             v4l2_buf.memory = Memory::UserPtr as u32;
             v4l2_buf.index = index as u32;
+            v4l2_buf.flags = self.queue_flags[index].into();
             v4l2_buf.m.userptr = buf.as_ptr() as std::os::raw::c_ulong;
             v4l2_buf.length = buf.len() as u32;
             v4l2::ioctl(
@@ -131,32 +629,24 @@ impl<'a> CaptureStream<'a> for Stream {
             )?;
         }
 
+        self.queue_flags[index] = Flags::empty();
+        self.buf_state[index] = State::Queued;
+        self.queued_at[index] = Some(Instant::now());
         Ok(())
     }
 
     fn dequeue(&mut self) -> io::Result<usize> {
-        let mut v4l2_buf: v4l2_buffer;
-        unsafe {
-            v4l2_buf = mem::zeroed();
-            v4l2_buf.type_ = self.buf_type as u32;
-            v4l2_buf.memory = Memory::UserPtr as u32;
-            v4l2::ioctl(
-                self.handle.fd(),
-                v4l2::vidioc::VIDIOC_DQBUF,
-                &mut v4l2_buf as *mut _ as *mut std::os::raw::c_void,
-            )?;
-        }
-        self.arena_index = v4l2_buf.index as usize;
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        self.dequeue_until(deadline)
+    }
 
-        self.buf_meta[self.arena_index] = Metadata {
-            bytesused: v4l2_buf.bytesused,
-            flags: v4l2_buf.flags.into(),
-            field: v4l2_buf.field,
-            timestamp: v4l2_buf.timestamp.into(),
-            sequence: v4l2_buf.sequence,
-        };
+    fn dequeue_before(&mut self, deadline: Instant) -> io::Result<usize> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if !self.handle.poll(libc::POLLIN, Some(timeout))? {
+            return Err(crate::io::timed_out());
+        }
 
-        Ok(self.arena_index)
+        self.dequeue_until(Some(deadline))
     }
 
     fn get(&self, index: usize) -> Option<&Self::Item> {
@@ -168,18 +658,24 @@ impl<'a> CaptureStream<'a> for Stream {
     }
 
     fn next(&'a mut self) -> io::Result<(&Self::Item, &Metadata)> {
-        if !self.active {
-            // Enqueue all buffers once on stream start
-            for index in 0..self.arena.len() {
-                self.queue(index)?;
-            }
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        if !self.capture(deadline)? {
+            return Err(crate::io::timed_out());
+        }
 
-            self.start()?;
-        } else {
-            self.queue(self.arena_index)?;
+        // The index used to access the buffer elements is given to us by v4l2, so we assume it
+        // will always be valid.
+        unsafe {
+            let bytes = self.arena.get_unchecked(self.arena_index);
+            let meta = self.buf_meta.get_unchecked(self.arena_index);
+            Ok((bytes, meta))
         }
+    }
 
-        self.arena_index = self.dequeue()?;
+    fn next_timeout(&'a mut self, timeout: Duration) -> io::Result<(&'a Self::Item, &'a Metadata)> {
+        if !self.capture(Some(Instant::now() + timeout))? {
+            return Err(crate::io::timed_out());
+        }
 
         // The index used to access the buffer elements is given to us by v4l2, so we assume it
         // will always be valid.
@@ -189,4 +685,79 @@ impl<'a> CaptureStream<'a> for Stream {
             Ok((bytes, meta))
         }
     }
+
+    fn next_before(
+        &'a mut self,
+        deadline: Instant,
+    ) -> io::Result<Option<(&'a Self::Item, &'a Metadata)>> {
+        match self.capture(Some(deadline)) {
+            Ok(true) => {}
+            Ok(false) => return Ok(None),
+            // nonblocking file descriptors may report readiness without a buffer being available
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        // The index used to access the buffer elements is given to us by v4l2, so we assume it
+        // will always be valid.
+        unsafe {
+            let bytes = self.arena.get_unchecked(self.arena_index);
+            let meta = self.buf_meta.get_unchecked(self.arena_index);
+            Ok(Some((bytes, meta)))
+        }
+    }
+
+    fn next_event(&'a mut self) -> io::Result<Next<'a, Self::Item>> {
+        if !self.events {
+            let (bytes, meta) = CaptureStream::next(self)?;
+            return Ok(Next::Frame(bytes, meta));
+        }
+
+        self.prepare()?;
+
+        // Pending events are signalled through POLLPRI
+        let revents = self
+            .handle
+            .poll_events(libc::POLLIN | libc::POLLPRI, self.timeout)?;
+        if revents == 0 {
+            return Err(crate::io::timed_out());
+        }
+        if revents & libc::POLLPRI != 0 {
+            return Ok(Next::Event(event::dequeue(&self.handle)?));
+        }
+
+        match self.dequeue() {
+            Ok(index) => self.arena_index = index,
+            Err(e) if v4l2::errno(&e) == Some(libc::EPIPE) => {
+                return Ok(Next::Event(Event::EndOfStream))
+            }
+            Err(e) => return Err(e),
+        }
+
+        // The index used to access the buffer elements is given to us by v4l2, so we assume it
+        // will always be valid.
+        unsafe {
+            let bytes = self.arena.get_unchecked(self.arena_index);
+            let meta = self.buf_meta.get_unchecked(self.arena_index);
+            Ok(Next::Frame(bytes, meta))
+        }
+    }
+
+    fn stop_draining<F>(&mut self, f: F) -> io::Result<()>
+    where
+        F: FnMut(&Self::Item, &Metadata),
+    {
+        let pending = if self.active {
+            self.buf_state
+                .iter()
+                .filter(|state| **state == State::Queued)
+                .count()
+        } else {
+            0
+        };
+        let handle = self.handle.clone();
+        let timeout = self.timeout;
+
+        drain::stop_draining(self, &handle, pending, timeout, f)
+    }
 }
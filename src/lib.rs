@@ -75,19 +75,40 @@ pub use v4l2_sys as v4l_sys;
 
 pub mod v4l2;
 
+#[cfg(feature = "analysis")]
+pub mod analysis;
 pub mod buffer;
 pub mod capability;
 pub mod context;
 pub mod control;
+pub mod convert;
+pub mod decoder;
 pub mod device;
+pub mod dv;
+pub mod encoder;
+pub mod event;
+pub mod features;
+#[cfg(feature = "ffmpeg")]
+pub mod ffmpeg;
 pub mod format;
+pub mod formats;
 pub mod fraction;
+pub mod frame;
 pub mod frameinterval;
 pub mod framesize;
+pub mod media;
 pub mod memory;
 pub mod parameters;
+pub mod share;
+pub mod sink;
+pub mod subdev;
+pub mod sync;
 pub mod timestamp;
+pub mod uvc;
+#[cfg(feature = "vaapi")]
+pub mod vaapi;
 pub mod video;
+pub mod vivid;
 
 pub mod io;
 
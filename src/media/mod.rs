@@ -0,0 +1,186 @@
+//! Media controller devices
+//!
+//! Complex devices such as stateless codecs and the camera pipelines of embedded platforms
+//! register a media device (/dev/mediaX) next to their video nodes. It describes how the parts of
+//! the hardware are connected and allocates the requests which bind controls and buffers to a
+//! single frame.
+
+use std::path::Path;
+use std::sync::Arc;
+use std::{io, mem};
+
+use crate::device::Handle;
+use crate::v4l2;
+use crate::v4l_sys::*;
+
+pub mod pipeline;
+pub use pipeline::Pipeline;
+
+pub mod request;
+pub use request::Request;
+
+pub mod topology;
+pub use topology::{Pad, Topology};
+
+/// Media controller device
+pub struct MediaDevice {
+    /// Raw handle
+    handle: Arc<Handle>,
+}
+
+impl MediaDevice {
+    /// Returns a media device by index
+    ///
+    /// Devices are usually enumerated by the system.
+    /// An index of zero thus represents the first device the system got to know about.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index (0: first, 1: second, ..)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::media::MediaDevice;
+    /// let dev = MediaDevice::new(0);
+    /// ```
+    pub fn new(index: usize) -> io::Result<Self> {
+        Self::with_path(format!("{}{}", "/dev/media", index))
+    }
+
+    /// Returns a media device by path
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path (e.g. "/dev/media0")
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::media::MediaDevice;
+    /// let dev = MediaDevice::with_path("/dev/media0");
+    /// ```
+    pub fn with_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let fd = v4l2::open(&path, libc::O_RDWR)?;
+
+        Ok(MediaDevice {
+            handle: Arc::new(Handle::from_fd(fd)),
+        })
+    }
+
+    /// Returns the raw device handle
+    pub fn handle(&self) -> Arc<Handle> {
+        self.handle.clone()
+    }
+
+    /// Returns the graph of entities, pads, links and interfaces of the device
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::media::MediaDevice;
+    ///
+    /// let dev = MediaDevice::new(0).unwrap();
+    /// let topology = dev.topology().unwrap();
+    /// std::fs::write("media0.dot", topology.to_dot()).unwrap();
+    /// ```
+    pub fn topology(&self) -> io::Result<Topology> {
+        loop {
+            // the first call returns the number of objects, the second one fills the arrays
+            let mut v4l2_topo: media_v2_topology = unsafe { mem::zeroed() };
+            self.g_topology(&mut v4l2_topo)?;
+            let version = v4l2_topo.topology_version;
+
+            let mut entities: Vec<media_v2_entity> =
+                vec![unsafe { mem::zeroed() }; v4l2_topo.num_entities as usize];
+            let mut interfaces: Vec<media_v2_interface> =
+                vec![unsafe { mem::zeroed() }; v4l2_topo.num_interfaces as usize];
+            let mut pads: Vec<media_v2_pad> =
+                vec![unsafe { mem::zeroed() }; v4l2_topo.num_pads as usize];
+            let mut links: Vec<media_v2_link> =
+                vec![unsafe { mem::zeroed() }; v4l2_topo.num_links as usize];
+            v4l2_topo.ptr_entities = entities.as_mut_ptr() as u64;
+            v4l2_topo.ptr_interfaces = interfaces.as_mut_ptr() as u64;
+            v4l2_topo.ptr_pads = pads.as_mut_ptr() as u64;
+            v4l2_topo.ptr_links = links.as_mut_ptr() as u64;
+
+            match self.g_topology(&mut v4l2_topo) {
+                // the graph grew in between, start over
+                Err(e) if v4l2::errno(&e) == Some(libc::ENOSPC) => continue,
+                res => res?,
+            }
+            if v4l2_topo.topology_version != version {
+                continue;
+            }
+
+            entities.truncate(v4l2_topo.num_entities as usize);
+            interfaces.truncate(v4l2_topo.num_interfaces as usize);
+            pads.truncate(v4l2_topo.num_pads as usize);
+            links.truncate(v4l2_topo.num_links as usize);
+
+            return Ok(Topology {
+                version,
+                entities: entities.into_iter().map(Into::into).collect(),
+                interfaces: interfaces.into_iter().map(Into::into).collect(),
+                pads: pads.into_iter().map(Into::into).collect(),
+                links: links.into_iter().map(Into::into).collect(),
+            });
+        }
+    }
+
+    fn g_topology(&self, v4l2_topo: &mut media_v2_topology) -> io::Result<()> {
+        unsafe {
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::MEDIA_IOC_G_TOPOLOGY,
+                v4l2_topo as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Enables or disables a data link between two pads
+    ///
+    /// Immutable links can not be changed and links can usually not be changed while the
+    /// pipeline is streaming.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Source pad of the link
+    /// * `sink` - Sink pad of the link
+    /// * `enabled` - True to enable the link
+    pub fn setup_link(&self, source: &Pad, sink: &Pad, enabled: bool) -> io::Result<()> {
+        unsafe {
+            let mut v4l2_link: media_link_desc = mem::zeroed();
+            v4l2_link.source.entity = source.entity;
+            v4l2_link.source.index = source.index as u16;
+            v4l2_link.source.flags = source.flags;
+            v4l2_link.sink.entity = sink.entity;
+            v4l2_link.sink.index = sink.index as u16;
+            v4l2_link.sink.flags = sink.flags;
+            // MEDIA_LNK_FL_ENABLED
+            v4l2_link.flags = enabled as u32;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::MEDIA_IOC_SETUP_LINK,
+                &mut v4l2_link as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Allocates a new request
+    ///
+    /// Fails with ENOTTY if the device does not support requests, see
+    /// `features::Features::requests`.
+    pub fn alloc_request(&self) -> io::Result<Request> {
+        unsafe {
+            let mut fd: std::os::raw::c_int = mem::zeroed();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::MEDIA_IOC_REQUEST_ALLOC,
+                &mut fd as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Request::new(fd))
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Camera pipeline configuration
+//!
+//! Before a video node of an embedded camera pipeline delivers frames, the links between the
+//! sensor and the video node have to be enabled and the media bus format has to be set on every
+//! pad along the way, so that each block agrees with its neighbours. This is usually done with
+//! `media-ctl --links` and `media-ctl --set-v4l2` on platforms such as the Raspberry Pi (unicam),
+//! i.MX or Rockchip (rkisp1).
+
+use std::collections::VecDeque;
+use std::io;
+
+use crate::device::Device;
+use crate::media::topology::{Entity, Link, LinkKind, Pad, Topology};
+use crate::media::MediaDevice;
+use crate::subdev::{MbusFormat, Subdev, Which};
+
+/// Path of data links from a sensor to a video node
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::media::{MediaDevice, Pipeline};
+/// use v4l::prelude::*;
+/// use v4l::subdev::MbusFormat;
+/// use v4l::video::Capture;
+/// use v4l::FourCC;
+///
+/// let media = MediaDevice::new(0).unwrap();
+/// let dev = Device::new(0).unwrap();
+///
+/// let pipeline = Pipeline::new(&media, "imx219 10-0010", &dev).unwrap();
+/// // MEDIA_BUS_FMT_SRGGB10_1X10
+/// let bus = pipeline
+///     .configure(&media, &MbusFormat::new(1640, 1232, 0x300f))
+///     .unwrap();
+///
+/// let mut fmt = dev.format().unwrap();
+/// fmt.width = bus.width;
+/// fmt.height = bus.height;
+/// fmt.fourcc = FourCC::new(b"pRAA");
+/// dev.set_format(&fmt).unwrap();
+/// ```
+pub struct Pipeline {
+    topology: Topology,
+    /// Data links from the sensor to the video node, in the order data flows through them
+    links: Vec<Link>,
+}
+
+impl Pipeline {
+    /// Finds the path from a sensor to a video node
+    ///
+    /// Links are considered whether they are enabled or not. If several paths exist, the one
+    /// with the fewest links is chosen.
+    ///
+    /// # Arguments
+    ///
+    /// * `media` - Media device the entities belong to
+    /// * `sensor` - Name of the sensor entity
+    /// * `video` - Video node at the end of the pipeline
+    pub fn new(media: &MediaDevice, sensor: &str, video: &Device) -> io::Result<Self> {
+        let topology = media.topology()?;
+
+        let sensor = topology
+            .entity_by_name(sensor)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "sensor entity not found"))?
+            .id;
+
+        let (major, minor) = video.handle().devnum()?;
+        let video = topology
+            .interfaces
+            .iter()
+            .filter(|intf| intf.major == major && intf.minor == minor)
+            .find_map(|intf| {
+                topology
+                    .links
+                    .iter()
+                    .find(|link| link.kind() == LinkKind::Interface && link.source == intf.id)
+            })
+            .map(|link| link.sink)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "video node is not part of the media device",
+                )
+            })?;
+
+        let links = shortest_path(&topology, sensor, video).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no path from the sensor to the video node",
+            )
+        })?;
+
+        Ok(Pipeline { topology, links })
+    }
+
+    /// Returns the topology the path was found in
+    pub fn topology(&self) -> &Topology {
+        &self.topology
+    }
+
+    /// Returns the data links of the path, from the sensor to the video node
+    pub fn links(&self) -> &[Link] {
+        &self.links
+    }
+
+    /// Returns the entities of the path, from the sensor to the video node
+    pub fn entities(&self) -> Vec<&Entity> {
+        let mut entities = Vec::new();
+        for link in &self.links {
+            if entities.is_empty() {
+                entities.extend(self.pad(link.source).and_then(|pad| self.entity(pad)));
+            }
+            entities.extend(self.pad(link.sink).and_then(|pad| self.entity(pad)));
+        }
+        entities
+    }
+
+    /// Enables the links of the path
+    ///
+    /// Other links feeding the sink pads of the path are disabled first, as most receivers only
+    /// accept one active source.
+    ///
+    /// # Arguments
+    ///
+    /// * `media` - Media device the entities belong to
+    pub fn enable(&self, media: &MediaDevice) -> io::Result<()> {
+        for link in &self.links {
+            let conflicts = self.topology.links.iter().filter(|other| {
+                other.kind() == LinkKind::Data
+                    && other.sink == link.sink
+                    && other.id != link.id
+                    && other.is_enabled()
+                    && !other.is_immutable()
+            });
+            for other in conflicts {
+                let (source, sink) = self.pads(other)?;
+                media.setup_link(source, sink, false)?;
+            }
+
+            if !link.is_immutable() {
+                let (source, sink) = self.pads(link)?;
+                media.setup_link(source, sink, true)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sets the format of the sensor and propagates it along the path
+    ///
+    /// The sensor source pad gets the desired format. Every following sink pad gets the format of
+    /// the source pad it is linked to, every following source pad the size of the sink pad of its
+    /// entity, keeping the format code the driver chose, since entities such as ISPs convert
+    /// between codes. Returns the format arriving at the video node, whose width and height
+    /// should then be set as the format of the video node.
+    ///
+    /// # Arguments
+    ///
+    /// * `fmt` - Desired sensor format
+    pub fn set_format(&self, fmt: &MbusFormat) -> io::Result<MbusFormat> {
+        let mut fmt = *fmt;
+
+        for (i, link) in self.links.iter().enumerate() {
+            let (source, sink) = self.pads(link)?;
+
+            if i == 0 {
+                fmt = self
+                    .subdev(source)?
+                    .set_format(source.index, Which::Active, &fmt)?;
+            }
+
+            // the last sink pad belongs to the video node, which is configured by the caller
+            if i + 1 == self.links.len() {
+                break;
+            }
+
+            let subdev = self.subdev(sink)?;
+            fmt = subdev.set_format(sink.index, Which::Active, &fmt)?;
+
+            let (next, _) = self.pads(&self.links[i + 1])?;
+            let mut source_fmt = subdev.format(next.index, Which::Active)?;
+            source_fmt.width = fmt.width;
+            source_fmt.height = fmt.height;
+            fmt = subdev.set_format(next.index, Which::Active, &source_fmt)?;
+        }
+
+        Ok(fmt)
+    }
+
+    /// Enables the links of the path and propagates the format along it
+    ///
+    /// See `enable` and `set_format`.
+    ///
+    /// # Arguments
+    ///
+    /// * `media` - Media device the entities belong to
+    /// * `fmt` - Desired sensor format
+    pub fn configure(&self, media: &MediaDevice, fmt: &MbusFormat) -> io::Result<MbusFormat> {
+        self.enable(media)?;
+        self.set_format(fmt)
+    }
+
+    fn pad(&self, id: u32) -> Option<&Pad> {
+        self.topology.pad(id)
+    }
+
+    fn entity(&self, pad: &Pad) -> Option<&Entity> {
+        self.topology.entity(pad.entity)
+    }
+
+    fn pads(&self, link: &Link) -> io::Result<(&Pad, &Pad)> {
+        match (self.pad(link.source), self.pad(link.sink)) {
+            (Some(source), Some(sink)) => Ok((source, sink)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "link refers to an unknown pad",
+            )),
+        }
+    }
+
+    /// Opens the subdevice node of the entity a pad belongs to
+    fn subdev(&self, pad: &Pad) -> io::Result<Subdev> {
+        let intf = self.topology.interface_of(pad.entity).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "entity has no subdevice node")
+        })?;
+
+        Subdev::with_path(intf.path()?)
+    }
+}
+
+/// Returns the data links of the shortest path between two entities (breadth-first search)
+fn shortest_path(topology: &Topology, from: u32, to: u32) -> Option<Vec<Link>> {
+    // link through which each visited entity was reached
+    let mut reached: Vec<(u32, Option<Link>)> = vec![(from, None)];
+    let mut queue = VecDeque::from(vec![from]);
+
+    while let Some(entity) = queue.pop_front() {
+        if entity == to {
+            let mut path = Vec::new();
+            let mut current = to;
+            while let Some((_, Some(link))) = reached.iter().find(|(id, _)| *id == current) {
+                path.push(*link);
+                current = topology.pad(link.source)?.entity;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        let outgoing = topology.links.iter().filter(|link| {
+            link.kind() == LinkKind::Data
+                && topology
+                    .pad(link.source)
+                    .filter(|pad| pad.entity == entity)
+                    .is_some()
+        });
+        for link in outgoing {
+            let next = topology.pad(link.sink)?.entity;
+            if reached.iter().all(|(id, _)| *id != next) {
+                reached.push((next, Some(*link)));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,222 @@
+//! Media requests
+//!
+//! A request collects control values and buffers which the driver applies together, e.g. the
+//! bitstream buffer of a frame and the codec parameters needed to decode it. Controls are bound
+//! to a request with `control::Which::Request`, buffers by queueing them with the request fd.
+//! Once queued, the request completes after the driver processed all of its objects, which is
+//! signaled by POLLPRI on the request fd. The values of the controls at that point can then be
+//! read back from the request, the buffers are dequeued as usual.
+
+use std::io;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use crate::buffer::{Latency, Metadata, PlaneMetadata, Type};
+use crate::control::{Control, Which};
+use crate::device::{Device, Handle};
+use crate::format::mplane::MAX_PLANES;
+use crate::memory::Memory;
+use crate::v4l2;
+use crate::v4l_sys::*;
+
+/// Media request
+///
+/// The request is freed when dropped, after the driver released it if it is still queued.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use v4l::control::{Control, Which};
+/// use v4l::device::Device;
+/// use v4l::media::MediaDevice;
+///
+/// let media = MediaDevice::new(0).unwrap();
+/// let dev = Device::new(0).unwrap();
+///
+/// let request = media.alloc_request().unwrap();
+/// dev.set_ext_controls(
+///     Which::Request(request.fd()),
+///     &[(0x009a0900 /* V4L2_CID_TEST_PATTERN */, Control::Value(1))],
+/// )
+/// .unwrap();
+/// // queue the buffers of the frame with the request fd here
+/// request.queue().unwrap();
+///
+/// if request.wait(Some(Duration::from_secs(1))).unwrap() {
+///     let mut ctrls = [(0x009a0900, Control::Value(0))];
+///     request.controls(&dev, &mut ctrls).unwrap();
+///     request.reinit().unwrap();
+/// }
+/// ```
+pub struct Request {
+    /// Request fd, as returned by MEDIA_IOC_REQUEST_ALLOC
+    handle: Handle,
+}
+
+impl Request {
+    /// Takes ownership of a request fd
+    pub(crate) fn new(fd: std::os::raw::c_int) -> Self {
+        Request {
+            handle: Handle::from_fd(fd),
+        }
+    }
+
+    /// Returns the request fd
+    ///
+    /// This is the value to pass to `control::Which::Request` and to set in the request_fd field
+    /// of buffers.
+    pub fn fd(&self) -> std::os::raw::c_int {
+        self.handle.fd()
+    }
+
+    /// Queues the request, the driver processes it once all of its buffers are queued
+    ///
+    /// Fails with ENOENT if the request contains no buffer.
+    pub fn queue(&self) -> io::Result<()> {
+        unsafe {
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::MEDIA_REQUEST_IOC_QUEUE,
+                std::ptr::null_mut(),
+            )
+        }
+    }
+
+    /// Resets a completed request, so it can be filled and queued again
+    ///
+    /// This avoids allocating a new request for every frame. Fails with EBUSY if the request is
+    /// still queued.
+    pub fn reinit(&self) -> io::Result<()> {
+        unsafe {
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::MEDIA_REQUEST_IOC_REINIT,
+                std::ptr::null_mut(),
+            )
+        }
+    }
+
+    /// Returns true if the request completed, without blocking
+    pub fn is_complete(&self) -> io::Result<bool> {
+        self.wait(Some(Duration::ZERO))
+    }
+
+    /// Waits until the request completed (POLLPRI)
+    ///
+    /// Returns false if the timeout expired first. A request stays complete until it is
+    /// reinitialized, so waiting again returns immediately.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait, None blocks indefinitely
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let revents = self.handle.poll_events(libc::POLLPRI, timeout)?;
+        if revents & libc::POLLERR != 0 {
+            // the request was not queued, so it will never complete
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "request is not queued",
+            ));
+        }
+
+        Ok(revents & libc::POLLPRI != 0)
+    }
+
+    /// Reads the values of controls as they were when the request completed
+    ///
+    /// See `Device::ext_controls` for how the values are read.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Device the controls belong to
+    /// * `ctrls` - Control identifiers and values
+    pub fn controls(&self, dev: &Device, ctrls: &mut [(u32, Control)]) -> io::Result<()> {
+        dev.ext_controls(Which::Request(self.fd()), ctrls)
+    }
+
+    /// Returns the state of a buffer of a device
+    ///
+    /// After completion, this tells whether the buffer was processed successfully (see
+    /// `Flags::ERROR`) and carries its timestamp and sequence number, without dequeueing it.
+    /// For multi-planar buffer types, the state of each plane is returned as well and bytesused
+    /// of the buffer is the sum of all planes. The plane list is empty for single-planar types.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Device owning the buffer
+    /// * `buf_type` - Type of the buffer queue
+    /// * `memory` - Memory type of the buffer queue
+    /// * `index` - Index of the buffer
+    pub fn buffer(
+        &self,
+        dev: &Device,
+        buf_type: Type,
+        memory: Memory,
+        index: u32,
+    ) -> io::Result<(Metadata, Vec<PlaneMetadata>)> {
+        unsafe {
+            let mut v4l2_planes: [v4l2_plane; MAX_PLANES] = mem::zeroed();
+            let mut v4l2_buf: v4l2_buffer = mem::zeroed();
+            v4l2_buf.type_ = buf_type as u32;
+            v4l2_buf.memory = memory as u32;
+            v4l2_buf.index = index;
+            if buf_type.is_multiplanar() {
+                // the driver fills in at most length planes
+                v4l2_buf.m.planes = v4l2_planes.as_mut_ptr();
+                v4l2_buf.length = MAX_PLANES as u32;
+            }
+            v4l2::ioctl(
+                dev.handle().fd(),
+                v4l2::vidioc::VIDIOC_QUERYBUF,
+                &mut v4l2_buf as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            let mut meta = Metadata {
+                bytesused: v4l2_buf.bytesused,
+                flags: v4l2_buf.flags.into(),
+                field: v4l2_buf.field,
+                timestamp: v4l2_buf.timestamp.into(),
+                sequence: v4l2_buf.sequence,
+                latency: Latency::default(),
+            };
+
+            let mut planes = Vec::new();
+            if buf_type.is_multiplanar() {
+                let count = (v4l2_buf.length as usize).min(MAX_PLANES);
+                planes.extend(v4l2_planes[..count].iter().map(|plane| PlaneMetadata {
+                    bytesused: plane.bytesused,
+                    length: plane.length,
+                    data_offset: plane.data_offset,
+                }));
+                meta.bytesused = planes.iter().map(|plane| plane.bytesused).sum();
+            }
+
+            Ok((meta, planes))
+        }
+    }
+}
+
+/// Waits until all of the given requests completed
+///
+/// Returns false if the timeout expired before. Completed requests are not reset, so the caller
+/// can inspect each of them afterwards.
+///
+/// # Arguments
+///
+/// * `requests` - Queued requests
+/// * `timeout` - Maximum time to wait for all of them, None blocks indefinitely
+pub fn wait_all(requests: &[&Request], timeout: Option<Duration>) -> io::Result<bool> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    // completion is level triggered, so waiting for the requests one after the other returns as
+    // soon as the last one completed
+    for request in requests {
+        let remaining = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if !request.wait(remaining)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
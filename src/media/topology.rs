@@ -0,0 +1,458 @@
+//! Media graph topology
+//!
+//! The topology describes the hardware as a graph: entities (sensors, ISPs, DMA engines, ...)
+//! have pads, which are connected by data links. Interfaces are the device nodes through which
+//! userspace controls the entities, attached to them by interface links.
+
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::{fs, io};
+
+use crate::v4l_sys::*;
+
+/// Pad flags (MEDIA_PAD_FL_*)
+const PAD_FL_SINK: u32 = 1 << 0;
+const PAD_FL_SOURCE: u32 = 1 << 1;
+
+/// Link flags (MEDIA_LNK_FL_*)
+const LNK_FL_ENABLED: u32 = 1 << 0;
+const LNK_FL_IMMUTABLE: u32 = 1 << 1;
+const LNK_FL_DYNAMIC: u32 = 1 << 2;
+const LNK_FL_LINK_TYPE: u32 = 0xf << 28;
+const LNK_FL_DATA_LINK: u32 = 0;
+const LNK_FL_INTERFACE_LINK: u32 = 1 << 28;
+const LNK_FL_ANCILLARY_LINK: u32 = 2 << 28;
+
+/// Interface types (MEDIA_INTF_T_*)
+const INTF_T_V4L_VIDEO: u32 = 0x200;
+const INTF_T_V4L_SUBDEV: u32 = 0x203;
+
+/// Hardware block of a media device, e.g. a sensor or a DMA engine
+#[derive(Debug, Clone)]
+pub struct Entity {
+    /// Unique ID within the graph
+    pub id: u32,
+    /// Name, unique within the media device
+    pub name: String,
+    /// Main function (MEDIA_ENT_F_*)
+    pub function: u32,
+    /// Flags (MEDIA_ENT_FL_*)
+    pub flags: u32,
+}
+
+impl From<media_v2_entity> for Entity {
+    fn from(entity: media_v2_entity) -> Self {
+        let name = entity.name;
+        let name: Vec<u8> = name
+            .iter()
+            .map(|&c| c as u8)
+            .take_while(|&c| c != 0)
+            .collect();
+
+        Entity {
+            id: entity.id,
+            name: String::from_utf8_lossy(&name).into_owned(),
+            function: entity.function,
+            flags: entity.flags,
+        }
+    }
+}
+
+/// Device node through which an entity is controlled
+#[derive(Debug, Copy, Clone)]
+pub struct Interface {
+    /// Unique ID within the graph
+    pub id: u32,
+    /// Type (MEDIA_INTF_T_*)
+    pub kind: u32,
+    /// Major number of the device node
+    pub major: u32,
+    /// Minor number of the device node
+    pub minor: u32,
+}
+
+impl Interface {
+    /// Returns true for video nodes (/dev/videoX)
+    pub fn is_video(&self) -> bool {
+        self.kind == INTF_T_V4L_VIDEO
+    }
+
+    /// Returns true for subdevice nodes (/dev/v4l-subdevX)
+    pub fn is_subdev(&self) -> bool {
+        self.kind == INTF_T_V4L_SUBDEV
+    }
+
+    /// Returns the path of the device node, as reported by the kernel through sysfs
+    pub fn path(&self) -> io::Result<PathBuf> {
+        let uevent = fs::read_to_string(format!(
+            "/sys/dev/char/{}:{}/uevent",
+            self.major, self.minor
+        ))?;
+
+        uevent
+            .lines()
+            .find_map(|line| line.strip_prefix("DEVNAME="))
+            .map(|name| PathBuf::from("/dev").join(name))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "device node has no name"))
+    }
+}
+
+impl From<media_v2_interface> for Interface {
+    fn from(intf: media_v2_interface) -> Self {
+        let devnode = unsafe { intf.__bindgen_anon_1.devnode };
+
+        Interface {
+            id: intf.id,
+            kind: intf.intf_type,
+            major: devnode.major,
+            minor: devnode.minor,
+        }
+    }
+}
+
+/// Connection point of an entity
+#[derive(Debug, Copy, Clone)]
+pub struct Pad {
+    /// Unique ID within the graph
+    pub id: u32,
+    /// ID of the entity the pad belongs to
+    pub entity: u32,
+    /// Index of the pad within its entity, as used by subdevice calls
+    pub index: u32,
+    /// Flags (MEDIA_PAD_FL_*)
+    pub flags: u32,
+}
+
+impl Pad {
+    /// Returns true if data flows into the entity through this pad
+    pub fn is_sink(&self) -> bool {
+        self.flags & PAD_FL_SINK != 0
+    }
+
+    /// Returns true if data flows out of the entity through this pad
+    pub fn is_source(&self) -> bool {
+        self.flags & PAD_FL_SOURCE != 0
+    }
+}
+
+impl From<media_v2_pad> for Pad {
+    fn from(pad: media_v2_pad) -> Self {
+        Pad {
+            id: pad.id,
+            entity: pad.entity_id,
+            index: pad.index,
+            flags: pad.flags,
+        }
+    }
+}
+
+/// Kind of a link
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LinkKind {
+    /// Data flows from a source pad to a sink pad
+    Data,
+    /// Connects an interface (source) to the entity it controls (sink)
+    Interface,
+    /// Connects two entities which work together, e.g. a sensor and its lens
+    Ancillary,
+    /// A kind not listed here, with its raw value
+    Other(u32),
+}
+
+/// Connection in the graph
+#[derive(Debug, Copy, Clone)]
+pub struct Link {
+    /// Unique ID within the graph
+    pub id: u32,
+    /// ID of the source: a pad for data links, an interface or entity for the other kinds
+    pub source: u32,
+    /// ID of the sink: a pad for data links, an entity for the other kinds
+    pub sink: u32,
+    /// Flags (MEDIA_LNK_FL_*)
+    pub flags: u32,
+}
+
+impl Link {
+    /// Returns the kind of the link
+    pub fn kind(&self) -> LinkKind {
+        match self.flags & LNK_FL_LINK_TYPE {
+            LNK_FL_DATA_LINK => LinkKind::Data,
+            LNK_FL_INTERFACE_LINK => LinkKind::Interface,
+            LNK_FL_ANCILLARY_LINK => LinkKind::Ancillary,
+            other => LinkKind::Other(other),
+        }
+    }
+
+    /// Returns true if the link is active
+    pub fn is_enabled(&self) -> bool {
+        self.flags & LNK_FL_ENABLED != 0
+    }
+
+    /// Returns true if the link can not be disabled
+    pub fn is_immutable(&self) -> bool {
+        self.flags & LNK_FL_IMMUTABLE != 0
+    }
+
+    /// Returns true if the link can be changed while streaming
+    pub fn is_dynamic(&self) -> bool {
+        self.flags & LNK_FL_DYNAMIC != 0
+    }
+}
+
+impl From<media_v2_link> for Link {
+    fn from(link: media_v2_link) -> Self {
+        Link {
+            id: link.id,
+            source: link.source_id,
+            sink: link.sink_id,
+            flags: link.flags,
+        }
+    }
+}
+
+/// Graph of a media device, see `MediaDevice::topology`
+#[derive(Debug, Clone, Default)]
+pub struct Topology {
+    /// Version of the graph, which changes whenever entities or links are added or removed
+    pub version: u64,
+    /// Hardware blocks
+    pub entities: Vec<Entity>,
+    /// Device nodes
+    pub interfaces: Vec<Interface>,
+    /// Connection points of the entities
+    pub pads: Vec<Pad>,
+    /// Data, interface and ancillary links
+    pub links: Vec<Link>,
+}
+
+impl Topology {
+    /// Returns an entity by ID
+    pub fn entity(&self, id: u32) -> Option<&Entity> {
+        self.entities.iter().find(|entity| entity.id == id)
+    }
+
+    /// Returns an entity by name
+    pub fn entity_by_name(&self, name: &str) -> Option<&Entity> {
+        self.entities.iter().find(|entity| entity.name == name)
+    }
+
+    /// Returns a pad by ID
+    pub fn pad(&self, id: u32) -> Option<&Pad> {
+        self.pads.iter().find(|pad| pad.id == id)
+    }
+
+    /// Returns the pads of an entity, ordered by index
+    pub fn pads_of(&self, entity: u32) -> Vec<&Pad> {
+        let mut pads: Vec<&Pad> = self
+            .pads
+            .iter()
+            .filter(|pad| pad.entity == entity)
+            .collect();
+        pads.sort_by_key(|pad| pad.index);
+        pads
+    }
+
+    /// Returns the interface controlling an entity, e.g. its video or subdevice node
+    pub fn interface_of(&self, entity: u32) -> Option<&Interface> {
+        self.links
+            .iter()
+            .filter(|link| link.kind() == LinkKind::Interface && link.sink == entity)
+            .find_map(|link| self.interfaces.iter().find(|intf| intf.id == link.source))
+    }
+
+    /// Returns the data links connected to a pad, in either direction
+    pub fn links_of(&self, pad: u32) -> Vec<&Link> {
+        self.links
+            .iter()
+            .filter(|link| link.kind() == LinkKind::Data)
+            .filter(|link| link.source == pad || link.sink == pad)
+            .collect()
+    }
+
+    /// Serializes the graph in the Graphviz DOT language
+    ///
+    /// Entities are drawn with their sink pads on top and source pads at the bottom, like
+    /// `media-ctl --print-dot` does. Disabled links are dashed, immutable links bold. The output
+    /// can be rendered with e.g. `dot -Tsvg`.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        dot.push_str("digraph board {\n");
+        dot.push_str("\trankdir=TB\n");
+
+        for intf in &self.interfaces {
+            let label = intf
+                .path()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|_| format!("{}:{}", intf.major, intf.minor));
+            let _ = writeln!(
+                dot,
+                "\tn{:08x} [label=\"{}\", shape=box, style=filled, fillcolor=yellow]",
+                intf.id,
+                escape(&label)
+            );
+        }
+
+        for entity in &self.entities {
+            let pads = self.pads_of(entity.id);
+            let ports = |sink: bool| {
+                pads.iter()
+                    .filter(|pad| if sink { pad.is_sink() } else { pad.is_source() })
+                    .map(|pad| format!("<port{}> {}", pad.index, pad.index))
+                    .collect::<Vec<_>>()
+                    .join(" | ")
+            };
+
+            let _ = writeln!(
+                dot,
+                "\tn{:08x} [label=\"{{{{{}}} | {} | {{{}}}}}\", shape=Mrecord, style=filled, fillcolor=green]",
+                entity.id,
+                ports(true),
+                escape(&entity.name),
+                ports(false)
+            );
+        }
+
+        for link in &self.links {
+            let style = if link.is_immutable() {
+                "bold"
+            } else if link.is_enabled() {
+                "solid"
+            } else {
+                "dashed"
+            };
+
+            match link.kind() {
+                LinkKind::Data => {
+                    let (source, sink) = match (self.pad(link.source), self.pad(link.sink)) {
+                        (Some(source), Some(sink)) => (source, sink),
+                        _ => continue,
+                    };
+                    let _ = writeln!(
+                        dot,
+                        "\tn{:08x}:port{} -> n{:08x}:port{} [style={}]",
+                        source.entity, source.index, sink.entity, sink.index, style
+                    );
+                }
+                LinkKind::Interface | LinkKind::Ancillary => {
+                    let _ = writeln!(
+                        dot,
+                        "\tn{:08x} -> n{:08x} [style=dotted, dir=none]",
+                        link.source, link.sink
+                    );
+                }
+                LinkKind::Other(_) => {}
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Escapes the characters with a meaning in DOT record labels
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topology() -> Topology {
+        let entity = |id, name: &str| Entity {
+            id,
+            name: name.to_string(),
+            function: 0,
+            flags: 0,
+        };
+        let pad = |id, entity, index, flags| Pad {
+            id,
+            entity,
+            index,
+            flags,
+        };
+        let link = |id, source, sink, flags| Link {
+            id,
+            source,
+            sink,
+            flags,
+        };
+
+        Topology {
+            version: 1,
+            entities: vec![entity(1, "sensor {0}"), entity(2, "isp")],
+            interfaces: vec![Interface {
+                id: 3,
+                kind: INTF_T_V4L_SUBDEV,
+                major: 0,
+                minor: 0,
+            }],
+            pads: vec![
+                pad(12, 2, 1, PAD_FL_SOURCE),
+                pad(10, 1, 0, PAD_FL_SOURCE),
+                pad(11, 2, 0, PAD_FL_SINK),
+            ],
+            links: vec![
+                link(20, 10, 11, LNK_FL_ENABLED | LNK_FL_IMMUTABLE),
+                link(21, 12, 11, 0),
+                link(22, 3, 1, LNK_FL_INTERFACE_LINK | LNK_FL_ENABLED),
+                link(23, 1, 2, LNK_FL_ANCILLARY_LINK),
+                link(24, 10, 99, LNK_FL_ENABLED),
+                link(25, 1, 2, 3 << 28),
+            ],
+        }
+    }
+
+    #[test]
+    fn escape_labels() {
+        for (s, escaped) in &[
+            ("isp", "isp"),
+            ("a \"b\"", "a \\\"b\\\""),
+            ("{a|b}", "\\{a\\|b\\}"),
+            ("<a> \\", "\\<a\\> \\\\"),
+        ] {
+            assert_eq!(escape(s), *escaped);
+        }
+    }
+
+    #[test]
+    fn to_dot() {
+        let dot = topology().to_dot();
+        let lines: Vec<&str> = dot.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "digraph board {",
+                "\trankdir=TB",
+                "\tn00000003 [label=\"0:0\", shape=box, style=filled, fillcolor=yellow]",
+                "\tn00000001 [label=\"{{} | sensor \\{0\\} | {<port0> 0}}\", shape=Mrecord, style=filled, fillcolor=green]",
+                "\tn00000002 [label=\"{{<port0> 0} | isp | {<port1> 1}}\", shape=Mrecord, style=filled, fillcolor=green]",
+                // links to unknown pads and of unknown kinds are left out
+                "\tn00000001:port0 -> n00000002:port0 [style=bold]",
+                "\tn00000002:port1 -> n00000002:port0 [style=dashed]",
+                "\tn00000003 -> n00000001 [style=dotted, dir=none]",
+                "\tn00000001 -> n00000002 [style=dotted, dir=none]",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn lookups() {
+        let topology = topology();
+        assert_eq!(topology.entity_by_name("isp").unwrap().id, 2);
+        let pads: Vec<u32> = topology.pads_of(2).iter().map(|pad| pad.id).collect();
+        assert_eq!(pads, [11, 12]);
+        assert_eq!(topology.interface_of(1).unwrap().id, 3);
+        assert!(topology.interface_of(2).is_none());
+        let links: Vec<u32> = topology.links_of(11).iter().map(|link| link.id).collect();
+        assert_eq!(links, [20, 21]);
+    }
+}
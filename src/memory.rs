@@ -1,9 +1,11 @@
 use std::fmt;
+use std::os::unix::io::RawFd;
 
 /// Memory used for buffer exchange
 #[allow(clippy::unreadable_literal)]
 #[rustfmt::skip]
 #[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Memory {
     Mmap        = 1,
     UserPtr     = 2,
@@ -21,3 +23,62 @@ impl fmt::Display for Memory {
         }
     }
 }
+
+/// Resource backing a buffer plane
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Backing {
+    /// Driver allocated memory, identified by the offset to pass to mmap
+    Offset(u32),
+    /// Application allocated memory at the given address
+    UserPtr(usize),
+    /// Memory shared through a dmabuf file descriptor, for buffers imported with
+    /// `Memory::DmaBuf`
+    DmaBuf(RawFd),
+}
+
+impl fmt::Display for Backing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Backing::Offset(offset) => write!(f, "offset {:#x}", offset),
+            Backing::UserPtr(ptr) => write!(f, "address {:#x}", ptr),
+            Backing::DmaBuf(fd) => write!(f, "dmabuf fd {}", fd),
+        }
+    }
+}
+
+/// Memory occupied by the buffers of a stream
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Usage {
+    /// Memory type of the buffers
+    pub memory: Memory,
+    /// Size of each plane in bytes, indexed by buffer and plane
+    pub buffers: Vec<Vec<usize>>,
+}
+
+impl Usage {
+    /// Returns the total size of all buffers in bytes
+    pub fn total(&self) -> usize {
+        self.buffers.iter().flatten().sum()
+    }
+
+    /// Returns the size of a single buffer (all of its planes) in bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    pub fn buffer(&self, index: usize) -> Option<usize> {
+        Some(self.buffers.get(index)?.iter().sum())
+    }
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} bytes in {} {} buffers",
+            self.total(),
+            self.buffers.len(),
+            self.memory
+        )
+    }
+}
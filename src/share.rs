@@ -0,0 +1,363 @@
+use std::convert::TryInto;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+use std::{io, mem, ptr, slice};
+
+use crate::buffer::{Flags, Latency, Metadata};
+use crate::timestamp::Timestamp;
+
+/// Size of the serialized frame description sent along with each file descriptor
+const HEADER_SIZE: usize = 40;
+
+/// Buffer exported as dmabuf file descriptor
+///
+/// The file descriptor is closed on drop. The underlying memory stays alive as long as any
+/// process holds a descriptor or mapping of it, even if the buffers of the stream are released.
+pub struct DmaBuf {
+    fd: RawFd,
+    len: usize,
+}
+
+impl DmaBuf {
+    /// Takes ownership of a dmabuf file descriptor
+    ///
+    /// # Safety
+    ///
+    /// The file descriptor must be valid and must not be closed by anyone else.
+    ///
+    /// # Arguments
+    ///
+    /// * `fd` - Dmabuf file descriptor
+    /// * `len` - Size of the buffer in bytes
+    pub unsafe fn from_raw_fd(fd: RawFd, len: usize) -> Self {
+        DmaBuf { fd, len }
+    }
+
+    /// Returns the size of the buffer in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the buffer has a size of zero
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl AsRawFd for DmaBuf {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for DmaBuf {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Frame received from another process, mapped read-only
+///
+/// The mapping is removed and the file descriptor closed on drop.
+pub struct SharedFrame {
+    buf: DmaBuf,
+    ptr: *mut libc::c_void,
+    meta: Metadata,
+}
+
+impl SharedFrame {
+    /// Returns the frame data
+    ///
+    /// Only the first `meta().bytesused` bytes carry payload for most formats.
+    pub fn data(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.buf.len) }
+    }
+
+    /// Returns the frame metadata as sent by the producer
+    pub fn meta(&self) -> &Metadata {
+        &self.meta
+    }
+
+    /// Returns the underlying dmabuf, e.g. to import it into a GPU API
+    pub fn dmabuf(&self) -> &DmaBuf {
+        &self.buf
+    }
+}
+
+impl Drop for SharedFrame {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.buf.len);
+        }
+    }
+}
+
+/// Sends an exported buffer along with its metadata over a Unix socket
+///
+/// The file descriptor is passed as SCM_RIGHTS ancillary data, so the receiving process gets its
+/// own descriptor referring to the same memory and no frame data is copied. The buffer may be
+/// queued again once the receiver signalled (through whatever protocol is in use) that it no
+/// longer reads from it.
+///
+/// # Arguments
+///
+/// * `socket` - Connected Unix stream socket
+/// * `buf` - Exported buffer, see `mmap::Stream::export`
+/// * `meta` - Metadata of the frame in the buffer
+///
+/// # Example
+///
+/// ```no_run
+/// use std::os::unix::net::UnixStream;
+/// use v4l::buffer::Type;
+/// use v4l::io::traits::{CaptureStream, Stream};
+/// use v4l::prelude::*;
+/// use v4l::share;
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// let socket = UnixStream::connect("/run/camera.sock").unwrap();
+///
+/// for index in 0..4 {
+///     CaptureStream::queue(&mut stream, index).unwrap();
+/// }
+/// stream.start().unwrap();
+///
+/// loop {
+///     let index = CaptureStream::dequeue(&mut stream).unwrap();
+///     let buf = stream.export(index).unwrap();
+///     share::send(&socket, &buf, CaptureStream::get_meta(&stream, index).unwrap()).unwrap();
+///     // wait for the consumer to release the frame before handing the buffer back
+///     CaptureStream::queue(&mut stream, index).unwrap();
+/// }
+/// ```
+pub fn send(socket: &UnixStream, buf: &DmaBuf, meta: &Metadata) -> io::Result<()> {
+    let mut header = [0u8; HEADER_SIZE];
+    let timestamp = Duration::from(meta.timestamp);
+    header[0..8].copy_from_slice(&(buf.len as u64).to_ne_bytes());
+    header[8..12].copy_from_slice(&meta.bytesused.to_ne_bytes());
+    header[12..16].copy_from_slice(&meta.flags.bits().to_ne_bytes());
+    header[16..20].copy_from_slice(&meta.field.to_ne_bytes());
+    header[20..24].copy_from_slice(&meta.sequence.to_ne_bytes());
+    header[24..32].copy_from_slice(&timestamp.as_secs().to_ne_bytes());
+    header[32..36].copy_from_slice(&timestamp.subsec_micros().to_ne_bytes());
+
+    unsafe {
+        let mut iov = libc::iovec {
+            iov_base: header.as_mut_ptr() as *mut libc::c_void,
+            iov_len: header.len(),
+        };
+        let mut control = vec![0u8; libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as usize];
+
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<RawFd>() as u32) as _;
+        ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut RawFd, buf.fd);
+
+        if libc::sendmsg(socket.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(())
+}
+
+/// Receives a frame sent by `send` and maps it read-only
+///
+/// # Arguments
+///
+/// * `socket` - Connected Unix stream socket
+pub fn receive(socket: &UnixStream) -> io::Result<SharedFrame> {
+    let mut header = [0u8; HEADER_SIZE];
+    // Every descriptor received is owned here, so the ones which are not used are closed
+    let mut fds = Vec::new();
+    let received;
+    let truncated;
+
+    unsafe {
+        let mut iov = libc::iovec {
+            iov_base: header.as_mut_ptr() as *mut libc::c_void,
+            iov_len: header.len(),
+        };
+        let mut control = vec![0u8; libc::CMSG_SPACE(mem::size_of::<RawFd>() as u32) as usize];
+
+        let mut msg: libc::msghdr = mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = control.len() as _;
+
+        let ret = libc::recvmsg(
+            socket.as_raw_fd(),
+            &mut msg,
+            libc::MSG_CMSG_CLOEXEC | libc::MSG_WAITALL,
+        );
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        received = ret as usize;
+        truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let size = (*cmsg).cmsg_len as usize - (data as usize - cmsg as usize);
+                for i in 0..size / mem::size_of::<RawFd>() {
+                    let fd = ptr::read_unaligned((data as *const RawFd).add(i));
+                    fds.push(DmaBuf::from_raw_fd(fd, 0));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    if received == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "connection closed by the sender",
+        ));
+    }
+    if truncated {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message carries more file descriptors than expected",
+        ));
+    }
+    if fds.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "message does not carry a file descriptor",
+        ));
+    }
+    let mut buf = fds.swap_remove(0);
+    drop(fds);
+
+    if received < HEADER_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "truncated frame description",
+        ));
+    }
+
+    let read_u32 =
+        |offset: usize| u32::from_ne_bytes(header[offset..offset + 4].try_into().unwrap());
+    let read_u64 =
+        |offset: usize| u64::from_ne_bytes(header[offset..offset + 8].try_into().unwrap());
+    let len = read_u64(0) as usize;
+    let bytesused = read_u32(8);
+    let flags = read_u32(12);
+    let field = read_u32(16);
+    let sequence = read_u32(20);
+    let sec = read_u64(24);
+    let usec = read_u32(32);
+    let nsec = match usec.checked_mul(1000) {
+        Some(nsec) if usec < 1_000_000 => nsec,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid timestamp in frame description",
+            ))
+        }
+    };
+
+    // mapping beyond the end of the buffer would fault on first access
+    let size = unsafe { libc::lseek(buf.fd, 0, libc::SEEK_END) };
+    if size < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    if len as u64 > size as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "frame length {} exceeds the buffer size of {} bytes",
+                len, size
+            ),
+        ));
+    }
+
+    buf.len = len;
+    let ptr = unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            buf.fd,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(SharedFrame {
+        buf,
+        ptr,
+        meta: Metadata {
+            bytesused,
+            flags: Flags::from(flags),
+            field,
+            timestamp: Timestamp::from(Duration::new(sec, nsec)),
+            sequence,
+            latency: Latency::default(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+    use std::os::unix::io::IntoRawFd;
+
+    /// Returns a buffer of the given size backed by an unlinked temporary file
+    fn buffer(size: u64, len: usize) -> DmaBuf {
+        let path =
+            std::env::temp_dir().join(format!("v4l-share-{}-{}-{}", std::process::id(), size, len));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        file.set_len(size).unwrap();
+        unsafe { DmaBuf::from_raw_fd(file.into_raw_fd(), len) }
+    }
+
+    #[test]
+    fn receive_frame() {
+        let (tx, rx) = UnixStream::pair().unwrap();
+        let meta = Metadata {
+            bytesused: 100,
+            sequence: 7,
+            ..Metadata::default()
+        };
+
+        send(&tx, &buffer(4096, 4096), &meta).unwrap();
+        let frame = receive(&rx).unwrap();
+        assert_eq!(frame.meta.sequence, 7);
+        assert_eq!(frame.meta.bytesused, 100);
+    }
+
+    #[test]
+    fn reject_length_beyond_buffer() {
+        let (tx, rx) = UnixStream::pair().unwrap();
+
+        send(&tx, &buffer(4096, 8192), &Metadata::default()).unwrap();
+        let err = receive(&rx).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,308 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::buffer::Metadata;
+
+/// Consumer of frames, e.g. a file writer
+pub trait Sink {
+    /// Consume a single frame
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Frame data
+    /// * `meta` - Frame metadata
+    fn write_frame(&mut self, buf: &[u8], meta: &Metadata) -> io::Result<()>;
+}
+
+/// Writes raw frames to disk, split into segments of limited size or duration
+///
+/// Each segment consists of a data file (`<prefix>-<n>.raw`) holding the frames back to back and
+/// an index file (`<prefix>-<n>.idx`) with one line per frame:
+/// `<offset> <bytesused> <sec> <usec> <sequence>`. This allows capturing now and analyzing the
+/// frames later without having to encode them first.
+pub struct RawFileWriter {
+    dir: PathBuf,
+    prefix: String,
+    max_size: Option<u64>,
+    max_duration: Option<Duration>,
+
+    segment: u32,
+    data: BufWriter<File>,
+    index: BufWriter<File>,
+    offset: u64,
+    start: Option<Duration>,
+}
+
+impl RawFileWriter {
+    /// Returns a writer which creates its segments in the given directory
+    ///
+    /// Segments are not rotated by default, see `set_max_size` and `set_max_duration`.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory to place the files in, must exist
+    /// * `prefix` - File name prefix of all segments
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use v4l::buffer::Type;
+    /// use v4l::io::traits::CaptureStream;
+    /// use v4l::prelude::*;
+    /// use v4l::sink::RawFileWriter;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    /// let mut writer = RawFileWriter::new("/tmp", "capture").unwrap();
+    /// writer.set_max_duration(Some(Duration::from_secs(60)));
+    ///
+    /// loop {
+    ///     let (buf, meta) = stream.next().unwrap();
+    ///     writer.write(buf, meta).unwrap();
+    /// }
+    /// ```
+    pub fn new<P: AsRef<Path>>(dir: P, prefix: &str) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        let (data, index) = Self::open_segment(&dir, prefix, 0)?;
+
+        Ok(RawFileWriter {
+            dir,
+            prefix: prefix.to_string(),
+            max_size: None,
+            max_duration: None,
+            segment: 0,
+            data,
+            index,
+            offset: 0,
+            start: None,
+        })
+    }
+
+    /// Starts a new segment once the data file would grow beyond the given size
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum segment size in bytes, None to disable size based rotation
+    pub fn set_max_size(&mut self, size: Option<u64>) {
+        self.max_size = size;
+    }
+
+    /// Starts a new segment once the frames of the current one span the given duration
+    ///
+    /// The duration is measured using the frame timestamps as reported by the driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - Maximum segment duration, None to disable time based rotation
+    pub fn set_max_duration(&mut self, duration: Option<Duration>) {
+        self.max_duration = duration;
+    }
+
+    /// Returns the index of the segment which is currently being written
+    pub fn segment(&self) -> u32 {
+        self.segment
+    }
+
+    /// Appends a frame to the current segment, rotating segments as necessary
+    ///
+    /// Only the first `meta.bytesused` bytes of the buffer are written, see `Metadata::payload`.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Frame data
+    /// * `meta` - Frame metadata
+    pub fn write(&mut self, buf: &[u8], meta: &Metadata) -> io::Result<()> {
+        let len = meta.payload(buf).len();
+        let timestamp = Duration::from(meta.timestamp);
+
+        if self.offset > 0 && self.needs_rotation(len as u64, timestamp) {
+            self.rotate()?;
+        }
+        if self.start.is_none() {
+            self.start = Some(timestamp);
+        }
+
+        self.data.write_all(&buf[..len])?;
+        writeln!(
+            self.index,
+            "{} {} {} {} {}",
+            self.offset, len, meta.timestamp.sec, meta.timestamp.usec, meta.sequence
+        )?;
+        self.offset += len as u64;
+
+        Ok(())
+    }
+
+    /// Flushes all buffered data to disk
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.data.flush()?;
+        self.index.flush()
+    }
+
+    fn needs_rotation(&self, len: u64, timestamp: Duration) -> bool {
+        if let Some(max_size) = self.max_size {
+            if self.offset + len > max_size {
+                return true;
+            }
+        }
+
+        match (self.max_duration, self.start) {
+            (Some(max_duration), Some(start)) => {
+                timestamp.checked_sub(start).unwrap_or_default() >= max_duration
+            }
+            _ => false,
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.flush()?;
+
+        let (data, index) = Self::open_segment(&self.dir, &self.prefix, self.segment + 1)?;
+        self.segment += 1;
+        self.data = data;
+        self.index = index;
+        self.offset = 0;
+        self.start = None;
+
+        Ok(())
+    }
+
+    fn open_segment(
+        dir: &Path,
+        prefix: &str,
+        segment: u32,
+    ) -> io::Result<(BufWriter<File>, BufWriter<File>)> {
+        let data = File::create(dir.join(format!("{}-{:05}.raw", prefix, segment)))?;
+        let index = File::create(dir.join(format!("{}-{:05}.idx", prefix, segment)))?;
+
+        Ok((BufWriter::new(data), BufWriter::new(index)))
+    }
+}
+
+impl Sink for RawFileWriter {
+    fn write_frame(&mut self, buf: &[u8], meta: &Metadata) -> io::Result<()> {
+        self.write(buf, meta)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::timestamp::Timestamp;
+
+    /// Creates an empty directory which is unique to the calling test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("v4l-sink-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn meta(bytesused: u32, sequence: u32) -> Metadata {
+        Metadata {
+            bytesused,
+            sequence,
+            timestamp: Timestamp::new(1, sequence as i64 * 1000),
+            ..Metadata::default()
+        }
+    }
+
+    /// Parses an index file into (offset, length, sec, usec, sequence) entries
+    fn index(path: &Path) -> Vec<[u64; 5]> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let mut entry = [0; 5];
+                for (field, value) in entry.iter_mut().zip(line.split(' ')) {
+                    *field = value.parse().unwrap();
+                }
+                entry
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_matches_data() {
+        let dir = temp_dir("index");
+        let mut writer = RawFileWriter::new(&dir, "cap").unwrap();
+
+        // only bytesused bytes of each buffer end up on disk
+        writer.write(&[1; 16], &meta(10, 0)).unwrap();
+        writer.write(&[2; 16], &meta(16, 1)).unwrap();
+        writer.write(&[3; 16], &meta(4, 2)).unwrap();
+        writer.flush().unwrap();
+
+        let data = fs::read(dir.join("cap-00000.raw")).unwrap();
+        let entries = index(&dir.join("cap-00000.idx"));
+        assert_eq!(data.len(), 30);
+        assert_eq!(
+            entries,
+            [[0, 10, 1, 0, 0], [10, 16, 1, 1000, 1], [26, 4, 1, 2000, 2]]
+        );
+        for (i, entry) in entries.iter().enumerate() {
+            let frame = &data[entry[0] as usize..(entry[0] + entry[1]) as usize];
+            assert!(frame.iter().all(|&b| b == i as u8 + 1));
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_at_max_size() {
+        let dir = temp_dir("rotate");
+        let mut writer = RawFileWriter::new(&dir, "cap").unwrap();
+        writer.set_max_size(Some(20));
+
+        // 8 + 8 fits, the third frame would exceed the limit and starts a new segment
+        for sequence in 0..3 {
+            writer
+                .write(&[sequence as u8; 8], &meta(8, sequence))
+                .unwrap();
+        }
+        assert_eq!(writer.segment(), 1);
+        // frames larger than the limit still get a segment of their own
+        writer.write(&[9; 32], &meta(32, 3)).unwrap();
+        assert_eq!(writer.segment(), 2);
+        writer.flush().unwrap();
+
+        let mut names = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "cap-00000.idx",
+                "cap-00000.raw",
+                "cap-00001.idx",
+                "cap-00001.raw",
+                "cap-00002.idx",
+                "cap-00002.raw"
+            ]
+        );
+
+        let sizes = (0..3)
+            .map(|n| {
+                fs::metadata(dir.join(format!("cap-{:05}.raw", n)))
+                    .unwrap()
+                    .len()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sizes, [16, 8, 32]);
+
+        // offsets restart at zero in every segment
+        let entries = index(&dir.join("cap-00001.idx"));
+        assert_eq!(entries, [[0, 8, 1, 2000, 2]]);
+        let entries = index(&dir.join("cap-00002.idx"));
+        assert_eq!(entries, [[0, 32, 1, 3000, 3]]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
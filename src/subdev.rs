@@ -0,0 +1,572 @@
+//! V4L2 subdevices
+//!
+//! On embedded platforms, the blocks of a camera pipeline (sensor, CSI-2 receiver, ISP, ...) are
+//! configured individually through subdevice nodes (/dev/v4l-subdevX). Their formats are set
+//! per pad and describe the data on the media bus between two blocks rather than in memory.
+
+use bitflags::bitflags;
+use std::convert::TryFrom;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fmt, io, mem};
+
+use crate::device::Handle;
+use crate::dv::Timings;
+use crate::format::{Colorspace, FieldOrder, Quantization, TransferFunction};
+use crate::v4l2;
+use crate::v4l_sys::*;
+
+/// Configuration a call operates on (enum v4l2_subdev_format_whence)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Which {
+    /// Scratch configuration of the file handle, used to negotiate without touching the device
+    Try = 0,
+    /// Configuration applied to the device
+    Active = 1,
+}
+
+/// Selection rectangle (struct v4l2_rect)
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Rect {
+    /// horizontal offset of the top left corner
+    pub left: i32,
+    /// vertical offset of the top left corner
+    pub top: i32,
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+}
+
+impl Rect {
+    /// Returns a rectangle
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Horizontal offset of the top left corner
+    /// * `top` - Vertical offset of the top left corner
+    /// * `width` - Width in pixels
+    /// * `height` - Height in pixels
+    pub const fn new(left: i32, top: i32, width: u32, height: u32) -> Self {
+        Rect {
+            left,
+            top,
+            width,
+            height,
+        }
+    }
+}
+
+impl From<v4l2_rect> for Rect {
+    fn from(rect: v4l2_rect) -> Self {
+        Rect {
+            left: rect.left,
+            top: rect.top,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+impl From<Rect> for v4l2_rect {
+    fn from(rect: Rect) -> Self {
+        v4l2_rect {
+            left: rect.left,
+            top: rect.top,
+            width: rect.width,
+            height: rect.height,
+        }
+    }
+}
+
+/// Selection target (V4L2_SEL_TGT_*)
+///
+/// On the sink pad of a sensor or scaler, the crop rectangle selects the part of the input which
+/// is processed and the compose rectangle the size it is scaled to, e.g. to configure binning.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Target {
+    /// Current cropping area
+    Crop = 0x0000,
+    /// Default cropping area
+    CropDefault = 0x0001,
+    /// Bounds of the cropping area
+    CropBounds = 0x0002,
+    /// Native size of the device, e.g. the pixel array of a sensor
+    NativeSize = 0x0003,
+    /// Current composing area
+    Compose = 0x0100,
+    /// Default composing area
+    ComposeDefault = 0x0101,
+    /// Bounds of the composing area
+    ComposeBounds = 0x0102,
+    /// Composing area including the padding written by the hardware
+    ComposePadded = 0x0103,
+}
+
+bitflags! {
+    /// Constraints of a selection request (V4L2_SEL_FLAG_*)
+    #[allow(clippy::unreadable_literal)]
+    pub struct SelectionFlags: u32 {
+        /// The rectangle may be adjusted to a larger one, but not a smaller one
+        const GE            = 0x00000001;
+        /// The rectangle may be adjusted to a smaller one, but not a larger one
+        const LE            = 0x00000002;
+        /// Do not propagate the change to the other rectangles and formats of the entity
+        const KEEP_CONFIG   = 0x00000004;
+    }
+}
+
+impl From<u32> for SelectionFlags {
+    fn from(flags: u32) -> Self {
+        SelectionFlags::from_bits_truncate(flags)
+    }
+}
+
+impl From<SelectionFlags> for u32 {
+    fn from(flags: SelectionFlags) -> Self {
+        flags.bits()
+    }
+}
+
+/// Media bus format of a pad (struct v4l2_mbus_framefmt)
+#[derive(Debug, Copy, Clone)]
+pub struct MbusFormat {
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+    /// media bus format code (MEDIA_BUS_FMT_*)
+    pub code: u32,
+    /// field order for interlacing
+    pub field_order: FieldOrder,
+
+    /// supplements the format code information
+    pub colorspace: Colorspace,
+    /// the way colors are mapped
+    pub quantization: Quantization,
+    /// the transfer function for the colorspace
+    pub transfer: TransferFunction,
+}
+
+impl MbusFormat {
+    /// Returns a media bus format
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - Width in pixels
+    /// * `height` - Height in pixels
+    /// * `code` - Media bus format code, e.g. 0x300f for MEDIA_BUS_FMT_SRGGB10_1X10
+    pub const fn new(width: u32, height: u32, code: u32) -> Self {
+        MbusFormat {
+            width,
+            height,
+            code,
+            field_order: FieldOrder::Any,
+            colorspace: Colorspace::Default,
+            quantization: Quantization::Default,
+            transfer: TransferFunction::Default,
+        }
+    }
+}
+
+impl fmt::Display for MbusFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "width          : {}", self.width)?;
+        writeln!(f, "height         : {}", self.height)?;
+        writeln!(f, "code           : {:#06x}", self.code)?;
+        writeln!(f, "field          : {}", self.field_order)?;
+        writeln!(f, "colorspace     : {}", self.colorspace)?;
+        writeln!(f, "quantization   : {}", self.quantization)?;
+        writeln!(f, "transfer       : {}", self.transfer)?;
+        Ok(())
+    }
+}
+
+impl From<v4l2_mbus_framefmt> for MbusFormat {
+    fn from(fmt: v4l2_mbus_framefmt) -> Self {
+        MbusFormat {
+            width: fmt.width,
+            height: fmt.height,
+            code: fmt.code,
+            field_order: FieldOrder::try_from(fmt.field).expect("Invalid field order"),
+            colorspace: Colorspace::try_from(fmt.colorspace).expect("Invalid colorspace"),
+            quantization: Quantization::try_from(fmt.quantization as u32)
+                .expect("Invalid quantization"),
+            transfer: TransferFunction::try_from(fmt.xfer_func as u32)
+                .expect("Invalid transfer function"),
+        }
+    }
+}
+
+impl From<MbusFormat> for v4l2_mbus_framefmt {
+    fn from(format: MbusFormat) -> Self {
+        let mut fmt: v4l2_mbus_framefmt;
+        unsafe {
+            fmt = mem::zeroed();
+        }
+
+        fmt.width = format.width;
+        fmt.height = format.height;
+        fmt.code = format.code;
+        fmt.field = format.field_order as u32;
+        fmt.colorspace = format.colorspace as u32;
+        fmt.quantization = format.quantization as u16;
+        fmt.xfer_func = format.transfer as u16;
+        fmt
+    }
+}
+
+/// Subdevice node
+pub struct Subdev {
+    /// Raw handle
+    handle: Arc<Handle>,
+}
+
+impl Subdev {
+    /// Returns a subdevice by index
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index (0: first, 1: second, ..)
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::subdev::Subdev;
+    /// let dev = Subdev::new(0);
+    /// ```
+    pub fn new(index: usize) -> io::Result<Self> {
+        Self::with_path(format!("{}{}", "/dev/v4l-subdev", index))
+    }
+
+    /// Returns a subdevice by path
+    ///
+    /// The node of an entity is found through `media::Topology::interface_of`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path (e.g. "/dev/v4l-subdev0")
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use v4l::subdev::Subdev;
+    /// let dev = Subdev::with_path("/dev/v4l-subdev0");
+    /// ```
+    pub fn with_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let fd = v4l2::open(&path, libc::O_RDWR)?;
+
+        Ok(Subdev {
+            handle: Arc::new(Handle::from_fd(fd)),
+        })
+    }
+
+    /// Returns the raw device handle
+    pub fn handle(&self) -> Arc<Handle> {
+        self.handle.clone()
+    }
+
+    /// Returns the format of a pad
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity
+    /// * `which` - Try or active format
+    pub fn format(&self, pad: u32, which: Which) -> io::Result<MbusFormat> {
+        unsafe {
+            let mut v4l2_fmt: v4l2_subdev_format = mem::zeroed();
+            v4l2_fmt.which = which as u32;
+            v4l2_fmt.pad = pad;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_G_FMT,
+                &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(MbusFormat::from(v4l2_fmt.format))
+        }
+    }
+
+    /// Modifies the format of a pad and returns the actual format
+    ///
+    /// Like for video nodes, the driver adjusts unsupported values. Setting the format of a sink
+    /// pad usually propagates it to the source pads of the entity.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity
+    /// * `which` - Try or active format
+    /// * `fmt` - Desired format
+    pub fn set_format(&self, pad: u32, which: Which, fmt: &MbusFormat) -> io::Result<MbusFormat> {
+        unsafe {
+            let mut v4l2_fmt: v4l2_subdev_format = mem::zeroed();
+            v4l2_fmt.which = which as u32;
+            v4l2_fmt.pad = pad;
+            v4l2_fmt.format = (*fmt).into();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_S_FMT,
+                &mut v4l2_fmt as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(MbusFormat::from(v4l2_fmt.format))
+        }
+    }
+
+    /// Returns a selection rectangle of a pad
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity
+    /// * `which` - Try or active selection
+    /// * `target` - Rectangle to return
+    pub fn selection(&self, pad: u32, which: Which, target: Target) -> io::Result<Rect> {
+        unsafe {
+            let mut v4l2_sel: v4l2_subdev_selection = mem::zeroed();
+            v4l2_sel.which = which as u32;
+            v4l2_sel.pad = pad;
+            v4l2_sel.target = target as u32;
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_G_SELECTION,
+                &mut v4l2_sel as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Rect::from(v4l2_sel.r))
+        }
+    }
+
+    /// Modifies a selection rectangle of a pad and returns the actual rectangle
+    ///
+    /// Only the crop and compose targets can be set, the driver adjusts the rectangle to the
+    /// hardware constraints within the limits given by the flags.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity
+    /// * `which` - Try or active selection
+    /// * `target` - Rectangle to modify
+    /// * `rect` - Desired rectangle
+    /// * `flags` - Constraints on the adjustments
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::subdev::{Rect, SelectionFlags, Subdev, Target, Which};
+    ///
+    /// let sensor = Subdev::new(0).unwrap();
+    /// let bounds = sensor.selection(0, Which::Active, Target::CropBounds).unwrap();
+    ///
+    /// // centered 1920x1080 window
+    /// let crop = Rect::new(
+    ///     bounds.left + (bounds.width as i32 - 1920) / 2,
+    ///     bounds.top + (bounds.height as i32 - 1080) / 2,
+    ///     1920,
+    ///     1080,
+    /// );
+    /// let crop = sensor
+    ///     .set_selection(0, Which::Active, Target::Crop, &crop, SelectionFlags::empty())
+    ///     .unwrap();
+    /// println!("cropped to {:?}", crop);
+    /// ```
+    pub fn set_selection(
+        &self,
+        pad: u32,
+        which: Which,
+        target: Target,
+        rect: &Rect,
+        flags: SelectionFlags,
+    ) -> io::Result<Rect> {
+        unsafe {
+            let mut v4l2_sel: v4l2_subdev_selection = mem::zeroed();
+            v4l2_sel.which = which as u32;
+            v4l2_sel.pad = pad;
+            v4l2_sel.target = target as u32;
+            v4l2_sel.flags = flags.into();
+            v4l2_sel.r = (*rect).into();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_S_SELECTION,
+                &mut v4l2_sel as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Rect::from(v4l2_sel.r))
+        }
+    }
+
+    /// Returns the DV timings currently configured on the receiver or transmitter
+    pub fn dv_timings(&self) -> io::Result<Timings> {
+        unsafe {
+            let mut v4l2_timings: v4l2_dv_timings = mem::zeroed();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_G_DV_TIMINGS,
+                &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Timings::from(v4l2_timings))
+        }
+    }
+
+    /// Configures DV timings and returns the actual timings
+    ///
+    /// Fails with EBUSY while streaming. The format of the source pad and of the video node
+    /// have to be updated afterwards to match the new size.
+    ///
+    /// # Arguments
+    ///
+    /// * `timings` - Desired timings, usually the ones returned by `query_dv_timings`
+    pub fn set_dv_timings(&self, timings: &Timings) -> io::Result<Timings> {
+        unsafe {
+            let mut v4l2_timings: v4l2_dv_timings = (*timings).into();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_S_DV_TIMINGS,
+                &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Timings::from(v4l2_timings))
+        }
+    }
+
+    /// Returns the timings of the signal detected by a receiver
+    ///
+    /// The detected timings are not applied, see `set_dv_timings`. Fails with ENOLINK if there
+    /// is no signal, ENOLCK if the signal is unstable and ERANGE if the timings are not supported.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::subdev::{Subdev, Which};
+    ///
+    /// // e.g. a tc358743 HDMI to CSI-2 bridge
+    /// let bridge = Subdev::new(0).unwrap();
+    /// let timings = bridge.query_dv_timings().unwrap();
+    /// let timings = bridge.set_dv_timings(&timings).unwrap();
+    /// println!(
+    ///     "{}x{} at {:.2} fps",
+    ///     timings.width,
+    ///     timings.height,
+    ///     timings.fps()
+    /// );
+    ///
+    /// let fmt = bridge.format(0, Which::Active).unwrap();
+    /// ```
+    pub fn query_dv_timings(&self) -> io::Result<Timings> {
+        unsafe {
+            let mut v4l2_timings: v4l2_dv_timings = mem::zeroed();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_QUERY_DV_TIMINGS,
+                &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            Ok(Timings::from(v4l2_timings))
+        }
+    }
+
+    /// Returns the DV timings supported on a pad
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity
+    pub fn enum_dv_timings(&self, pad: u32) -> io::Result<Vec<Timings>> {
+        let mut timings = Vec::new();
+        let mut v4l2_timings: v4l2_enum_dv_timings;
+        unsafe {
+            v4l2_timings = mem::zeroed();
+        }
+
+        loop {
+            v4l2_timings.index = timings.len() as u32;
+            v4l2_timings.pad = pad;
+            let ret = unsafe {
+                v4l2::ioctl(
+                    self.handle.fd(),
+                    v4l2::vidioc::VIDIOC_SUBDEV_ENUM_DV_TIMINGS,
+                    &mut v4l2_timings as *mut _ as *mut std::os::raw::c_void,
+                )
+            };
+
+            match ret {
+                Ok(()) => timings.push(Timings::from(v4l2_timings.timings)),
+                Err(e) if v4l2::errno(&e) == Some(libc::EINVAL) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(timings)
+    }
+
+    /// Returns the EDID a receiver presents to the source on a pad
+    ///
+    /// The EDID consists of blocks of 128 bytes. An empty vector is returned if no EDID is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity, usually the input pad of the receiver
+    pub fn edid(&self, pad: u32) -> io::Result<Vec<u8>> {
+        unsafe {
+            // with zero blocks, the driver returns the number of available blocks
+            let mut v4l2_edid: v4l2_edid = mem::zeroed();
+            v4l2_edid.pad = pad;
+            match v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_G_EDID,
+                &mut v4l2_edid as *mut _ as *mut std::os::raw::c_void,
+            ) {
+                Err(e) if v4l2::errno(&e) == Some(libc::ENODATA) => return Ok(Vec::new()),
+                res => res?,
+            }
+
+            let mut edid = vec![0; v4l2_edid.blocks as usize * 128];
+            if edid.is_empty() {
+                return Ok(edid);
+            }
+
+            v4l2_edid.start_block = 0;
+            v4l2_edid.edid = edid.as_mut_ptr();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_G_EDID,
+                &mut v4l2_edid as *mut _ as *mut std::os::raw::c_void,
+            )?;
+
+            edid.truncate(v4l2_edid.blocks as usize * 128);
+            Ok(edid)
+        }
+    }
+
+    /// Sets the EDID a receiver presents to the source on a pad
+    ///
+    /// Most receivers pull the hotplug detect line low while no EDID is set, so the source only
+    /// starts transmitting once this is done. An empty EDID clears it.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Pad index within the entity, usually the input pad of the receiver
+    /// * `edid` - EDID, a multiple of 128 bytes
+    pub fn set_edid(&self, pad: u32, edid: &[u8]) -> io::Result<()> {
+        if edid.len() % 128 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "EDID size must be a multiple of 128 bytes",
+            ));
+        }
+
+        let mut edid = edid.to_vec();
+        unsafe {
+            let mut v4l2_edid: v4l2_edid = mem::zeroed();
+            v4l2_edid.pad = pad;
+            v4l2_edid.blocks = (edid.len() / 128) as u32;
+            v4l2_edid.edid = edid.as_mut_ptr();
+            v4l2::ioctl(
+                self.handle.fd(),
+                v4l2::vidioc::VIDIOC_SUBDEV_S_EDID,
+                &mut v4l2_edid as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+}
@@ -0,0 +1,609 @@
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+
+use crate::buffer::{Metadata, StateError};
+use crate::device::Device;
+use crate::io::traits::CaptureStream;
+use crate::video::Capture;
+
+/// Frames captured by a group of streams at roughly the same time
+pub struct FrameSet<'a, T: ?Sized> {
+    /// One frame per stream, in the order the streams were added to the group
+    pub frames: Vec<(&'a T, &'a Metadata)>,
+}
+
+impl<'a, T: ?Sized> FrameSet<'a, T> {
+    /// Returns the time between the oldest and the newest frame of the set
+    pub fn skew(&self) -> Duration {
+        let timestamps = self
+            .frames
+            .iter()
+            .map(|(_, meta)| Duration::from(meta.timestamp));
+        match (timestamps.clone().min(), timestamps.max()) {
+            (Some(min), Some(max)) => max - min,
+            _ => Duration::default(),
+        }
+    }
+}
+
+/// Error returned when a stream of a group did not deliver a frame in time
+///
+/// Groups report it wrapped in an `io::Error` of kind `TimedOut`. Use `io::Error::get_ref()` and
+/// downcast to find out which stream stalled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stalled {
+    /// Index of the stream in the group
+    pub stream: usize,
+    /// Name of the stream, e.g. "stream 1" or "right camera"
+    pub name: String,
+}
+
+impl fmt::Display for Stalled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out waiting for a frame from the {}", self.name)
+    }
+}
+
+impl std::error::Error for Stalled {}
+
+impl From<Stalled> for io::Error {
+    fn from(err: Stalled) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    }
+}
+
+/// Captures from several streams at once and matches their frames by timestamp
+///
+/// Frames are considered to belong together if their driver timestamps are no further apart
+/// than the configured tolerance. Streams lagging behind have their older frames dropped until
+/// all frames line up. This requires all devices to timestamp their buffers using the same clock,
+/// which is CLOCK_MONOTONIC for the vast majority of drivers.
+///
+/// Each stream holds on to one buffer while its frame is part of the current set, so streams
+/// should have at least three buffers allocated.
+pub struct CaptureGroup<S> {
+    streams: Vec<S>,
+    held: Vec<Option<usize>>,
+    tolerance: Duration,
+    dropped: u64,
+
+    active: bool,
+}
+
+impl<S> CaptureGroup<S>
+where
+    S: for<'b> CaptureStream<'b>,
+{
+    /// Returns a group capturing from the given streams
+    ///
+    /// # Arguments
+    ///
+    /// * `streams` - Capture streams, which must not have been started yet
+    /// * `tolerance` - Maximum difference between the timestamps of matched frames
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use v4l::buffer::Type;
+    /// use v4l::prelude::*;
+    /// use v4l::sync::CaptureGroup;
+    ///
+    /// let left = Device::new(0).unwrap();
+    /// let right = Device::new(1).unwrap();
+    /// let streams = vec![
+    ///     MmapStream::new(&left, Type::VideoCapture).unwrap(),
+    ///     MmapStream::new(&right, Type::VideoCapture).unwrap(),
+    /// ];
+    ///
+    /// let mut group = CaptureGroup::new(streams, Duration::from_millis(5));
+    /// loop {
+    ///     let set = group.next().unwrap();
+    ///     println!("{} frames, skew: {:?}", set.frames.len(), set.skew());
+    /// }
+    /// ```
+    pub fn new(streams: Vec<S>, tolerance: Duration) -> Self {
+        let held = streams.iter().map(|_| None).collect();
+        CaptureGroup {
+            streams,
+            held,
+            tolerance,
+            dropped: 0,
+            active: false,
+        }
+    }
+
+    /// Returns the streams of the group
+    pub fn streams(&self) -> &[S] {
+        &self.streams
+    }
+
+    /// Returns the maximum difference between the timestamps of matched frames
+    pub fn tolerance(&self) -> Duration {
+        self.tolerance
+    }
+
+    /// Sets the maximum difference between the timestamps of matched frames
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - Maximum timestamp difference
+    pub fn set_tolerance(&mut self, tolerance: Duration) {
+        self.tolerance = tolerance;
+    }
+
+    /// Returns the number of frames dropped so far to keep the streams aligned
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Starts streaming on all devices
+    ///
+    /// This is done implicitly by the first call to next().
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.active {
+            return Ok(());
+        }
+
+        for stream in self.streams.iter_mut() {
+            // Enqueue all buffers once on stream start
+            for index in 0..stream.len() {
+                stream.queue(index)?;
+            }
+            stream.start()?;
+        }
+
+        self.active = true;
+        Ok(())
+    }
+
+    /// Stops streaming on all devices
+    pub fn stop(&mut self) -> io::Result<()> {
+        for stream in self.streams.iter_mut() {
+            stream.stop()?;
+        }
+        for held in self.held.iter_mut() {
+            *held = None;
+        }
+
+        self.active = false;
+        Ok(())
+    }
+
+    /// Waits for a matching set of frames
+    ///
+    /// The buffers of the previous set are handed back to the drivers first. Blocks until every
+    /// stream delivered a frame and all of them lie within the tolerance.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> io::Result<FrameSet<'_, S::Item>> {
+        self.collect(None)
+    }
+
+    /// Waits for a matching set of frames like next(), but at most for the given amount of time
+    ///
+    /// If a stream stalls, an error of kind `TimedOut` wrapping `Stalled` is returned, which tells
+    /// the stream apart. The frames held by the other streams are handed back to the drivers by
+    /// the next call.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for the whole set, including dropped frames
+    pub fn next_timeout(&mut self, timeout: Duration) -> io::Result<FrameSet<'_, S::Item>> {
+        self.collect(Some(Instant::now() + timeout))
+    }
+
+    fn collect(&mut self, deadline: Option<Instant>) -> io::Result<FrameSet<'_, S::Item>> {
+        self.start()?;
+
+        for i in 0..self.streams.len() {
+            self.advance(i, deadline)?;
+        }
+
+        loop {
+            let timestamps = self.timestamps();
+            let newest = match timestamps.iter().max() {
+                Some(newest) => *newest,
+                None => break,
+            };
+
+            let mut aligned = true;
+            for (i, timestamp) in timestamps.iter().enumerate() {
+                if newest - *timestamp > self.tolerance {
+                    // This frame is too old to ever be matched, replace it by a newer one
+                    self.advance(i, deadline)?;
+                    self.dropped += 1;
+                    aligned = false;
+                }
+            }
+
+            if aligned {
+                break;
+            }
+        }
+
+        let mut frames = Vec::with_capacity(self.streams.len());
+        for (stream, held) in self.streams.iter().zip(self.held.iter()) {
+            let index = held.expect("every stream holds a buffer");
+            match (stream.get(index), stream.get_meta(index)) {
+                (Some(buf), Some(meta)) => frames.push((buf, meta)),
+                _ => return Err(StateError::InvalidIndex(index).into()),
+            }
+        }
+
+        Ok(FrameSet { frames })
+    }
+
+    /// Hands the buffer held for a stream back to the driver and waits for the next one
+    fn advance(&mut self, i: usize, deadline: Option<Instant>) -> io::Result<()> {
+        let stream = &mut self.streams[i];
+        if let Some(index) = self.held[i].take() {
+            stream.queue(index)?;
+        }
+
+        let res = match deadline {
+            Some(deadline) => stream.dequeue_before(deadline),
+            None => stream.dequeue(),
+        };
+        match res {
+            Ok(index) => self.held[i] = Some(index),
+            Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                return Err(Stalled {
+                    stream: i,
+                    name: format!("stream {}", i),
+                }
+                .into())
+            }
+            Err(e) => return Err(e),
+        }
+        Ok(())
+    }
+
+    /// Returns the streams, stopping them first
+    pub fn into_inner(mut self) -> io::Result<Vec<S>> {
+        self.stop()?;
+        Ok(self.streams)
+    }
+
+    fn timestamps(&self) -> Vec<Duration> {
+        self.streams
+            .iter()
+            .zip(self.held.iter())
+            .map(
+                |(stream, held)| match held.and_then(|index| stream.get_meta(index)) {
+                    Some(meta) => Duration::from(meta.timestamp),
+                    None => Duration::default(),
+                },
+            )
+            .collect()
+    }
+}
+
+/// Frames captured by the two cameras of a stereo rig
+pub struct StereoFrame<'a, T: ?Sized> {
+    /// Frame of the left camera
+    pub left: (&'a T, &'a Metadata),
+    /// Frame of the right camera
+    pub right: (&'a T, &'a Metadata),
+}
+
+impl<'a, T: ?Sized> StereoFrame<'a, T> {
+    /// Returns the timestamp of the right frame minus the one of the left frame in microseconds
+    pub fn delta(&self) -> i64 {
+        let left = Duration::from(self.left.1.timestamp).as_micros() as i64;
+        let right = Duration::from(self.right.1.timestamp).as_micros() as i64;
+        right - left
+    }
+}
+
+/// Captures synchronized frames from the left and right camera of a stereo rig
+pub struct StereoPair<S> {
+    group: CaptureGroup<S>,
+}
+
+impl<S> StereoPair<S>
+where
+    S: for<'b> CaptureStream<'b>,
+{
+    /// Returns a pair capturing from the given streams
+    ///
+    /// Both devices must be configured for the same format (size and pixel format), otherwise an
+    /// error of kind `InvalidInput` is returned.
+    ///
+    /// # Arguments
+    ///
+    /// * `left` - Left camera device and its capture stream
+    /// * `right` - Right camera device and its capture stream
+    /// * `tolerance` - Maximum difference between the timestamps of matched frames
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use v4l::buffer::Type;
+    /// use v4l::prelude::*;
+    /// use v4l::sync::StereoPair;
+    ///
+    /// let a = Device::new(0).unwrap();
+    /// let b = Device::new(1).unwrap();
+    /// let (left, right) = StereoPair::<MmapStream>::identify(&a, &b, None).unwrap();
+    ///
+    /// let mut pair = StereoPair::new(
+    ///     (left, MmapStream::new(left, Type::VideoCapture).unwrap()),
+    ///     (right, MmapStream::new(right, Type::VideoCapture).unwrap()),
+    ///     Duration::from_millis(5),
+    /// )
+    /// .unwrap();
+    /// loop {
+    ///     let frame = pair.next().unwrap();
+    ///     println!("delta: {} us", frame.delta());
+    /// }
+    /// ```
+    pub fn new(left: (&Device, S), right: (&Device, S), tolerance: Duration) -> io::Result<Self> {
+        let left_fmt = left.0.format()?;
+        let right_fmt = right.0.format()?;
+        if left_fmt.width != right_fmt.width
+            || left_fmt.height != right_fmt.height
+            || left_fmt.fourcc != right_fmt.fourcc
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "stereo formats differ: left {}x{} {}, right {}x{} {}",
+                    left_fmt.width,
+                    left_fmt.height,
+                    left_fmt.fourcc,
+                    right_fmt.width,
+                    right_fmt.height,
+                    right_fmt.fourcc
+                ),
+            ));
+        }
+
+        Ok(StereoPair {
+            group: CaptureGroup::new(vec![left.1, right.1], tolerance),
+        })
+    }
+
+    /// Tells the left and the right camera apart
+    ///
+    /// If the bus info of the left camera is given, the device matching it is considered to be
+    /// the left one. Otherwise, the devices are ordered by their bus info, so the camera plugged
+    /// into the lower port (e.g. usb-0000:00:14.0-2 vs. usb-0000:00:14.0-10) is the left one.
+    /// The components of USB port paths are compared as numbers.
+    ///
+    /// # Arguments
+    ///
+    /// * `a` - First device
+    /// * `b` - Second device
+    /// * `left_bus` - Bus info of the left camera as reported by the capabilities
+    pub fn identify<'d>(
+        a: &'d Device,
+        b: &'d Device,
+        left_bus: Option<&str>,
+    ) -> io::Result<(&'d Device, &'d Device)> {
+        let bus_a = a.query_caps()?.bus;
+        let bus_b = b.query_caps()?.bus;
+
+        match left_bus {
+            Some(left_bus) if bus_a == left_bus => Ok((a, b)),
+            Some(left_bus) if bus_b == left_bus => Ok((b, a)),
+            Some(left_bus) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no device on bus {}", left_bus),
+            )),
+            None => match bus_key(&bus_a).cmp(&bus_key(&bus_b)) {
+                Ordering::Less => Ok((a, b)),
+                Ordering::Greater => Ok((b, a)),
+                Ordering::Equal => Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("devices share the same bus info: {}", bus_a),
+                )),
+            },
+        }
+    }
+
+    /// Returns the underlying capture group
+    pub fn group(&self) -> &CaptureGroup<S> {
+        &self.group
+    }
+
+    /// Waits for a matching pair of frames, see `CaptureGroup::next`
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> io::Result<StereoFrame<'_, S::Item>> {
+        let set = self.group.next().map_err(name_camera)?;
+        Ok(StereoFrame {
+            left: set.frames[0],
+            right: set.frames[1],
+        })
+    }
+
+    /// Waits for a matching pair of frames, see `CaptureGroup::next_timeout`
+    ///
+    /// A stalled camera is reported as "left camera" or "right camera".
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait for the pair, including dropped frames
+    pub fn next_timeout(&mut self, timeout: Duration) -> io::Result<StereoFrame<'_, S::Item>> {
+        let set = self.group.next_timeout(timeout).map_err(name_camera)?;
+        Ok(StereoFrame {
+            left: set.frames[0],
+            right: set.frames[1],
+        })
+    }
+}
+
+/// Names the stalled stream of a stereo pair after its camera
+fn name_camera(err: io::Error) -> io::Error {
+    let stream = match err.get_ref().and_then(|e| e.downcast_ref::<Stalled>()) {
+        Some(stalled) => stalled.stream,
+        None => return err,
+    };
+
+    let name = if stream == 0 { "left" } else { "right" };
+    Stalled {
+        stream,
+        name: format!("{} camera", name),
+    }
+    .into()
+}
+
+/// Returns the sort key of a bus info string
+///
+/// USB bus info consists of the controller and the port path, e.g. usb-0000:00:14.0-1.10. The
+/// port path is split into its numeric components so port 10 sorts after port 2.
+fn bus_key(bus: &str) -> (&str, Vec<u32>) {
+    let ports = bus.rsplit_once('-').and_then(|(controller, path)| {
+        let ports: Result<Vec<u32>, _> = path.split('.').map(str::parse).collect();
+        ports.ok().map(|ports| (controller, ports))
+    });
+
+    ports.unwrap_or((bus, Vec::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::traits::Stream;
+
+    /// Delivers a frame every 10ms, or nothing at all once stalled
+    struct Camera {
+        stalled: bool,
+        sequence: u32,
+        buf: [u8; 1],
+        meta: [Metadata; 3],
+    }
+
+    impl Camera {
+        fn new(stalled: bool) -> Self {
+            Camera {
+                stalled,
+                sequence: 0,
+                buf: [0],
+                meta: [Metadata::default(); 3],
+            }
+        }
+    }
+
+    impl Stream for Camera {
+        type Item = [u8];
+
+        fn start(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn stop(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn len(&self) -> usize {
+            self.meta.len()
+        }
+    }
+
+    impl<'a> CaptureStream<'a> for Camera {
+        fn queue(&mut self, _index: usize) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn dequeue(&mut self) -> io::Result<usize> {
+            let index = self.sequence as usize % self.meta.len();
+            self.meta[index] = Metadata {
+                sequence: self.sequence,
+                timestamp: (Duration::from_millis(10) * self.sequence).into(),
+                ..Metadata::default()
+            };
+            self.sequence += 1;
+            Ok(index)
+        }
+
+        fn dequeue_before(&mut self, deadline: Instant) -> io::Result<usize> {
+            if self.stalled {
+                std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                return Err(crate::io::timed_out());
+            }
+            self.dequeue()
+        }
+
+        fn get(&self, index: usize) -> Option<&Self::Item> {
+            self.meta.get(index).map(|_| &self.buf[..])
+        }
+
+        fn get_meta(&self, index: usize) -> Option<&Metadata> {
+            self.meta.get(index)
+        }
+
+        fn next(&'a mut self) -> io::Result<(&'a Self::Item, &'a Metadata)> {
+            let index = self.dequeue()?;
+            Ok((&self.buf, &self.meta[index]))
+        }
+    }
+
+    #[test]
+    fn group_timeout() {
+        let timeout = Duration::from_millis(10);
+        let mut group = CaptureGroup::new(
+            vec![Camera::new(false), Camera::new(false)],
+            Duration::from_millis(1),
+        );
+        let set = group.next_timeout(timeout).unwrap();
+        assert_eq!(set.frames.len(), 2);
+        assert_eq!(set.skew(), Duration::default());
+
+        let mut group = CaptureGroup::new(
+            vec![Camera::new(false), Camera::new(true), Camera::new(false)],
+            Duration::from_millis(1),
+        );
+        let start = Instant::now();
+        let err = group.next_timeout(timeout).err().unwrap();
+        assert!(start.elapsed() >= timeout);
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        let stalled = err.get_ref().unwrap().downcast_ref::<Stalled>().unwrap();
+        assert_eq!(stalled.stream, 1);
+        assert_eq!(
+            err.to_string(),
+            "timed out waiting for a frame from the stream 1"
+        );
+    }
+
+    #[test]
+    fn camera_names() {
+        let err = name_camera(
+            Stalled {
+                stream: 1,
+                name: "stream 1".to_string(),
+            }
+            .into(),
+        );
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(
+            err.to_string(),
+            "timed out waiting for a frame from the right camera"
+        );
+
+        let err = name_camera(io::Error::from(io::ErrorKind::BrokenPipe));
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn bus_order() {
+        let ordered = [
+            "platform:vivid-000",
+            "usb-0000:00:14.0-2",
+            "usb-0000:00:14.0-2.1",
+            "usb-0000:00:14.0-2.10",
+            "usb-0000:00:14.0-10",
+            "usb-0000:00:1a.0-1",
+        ];
+        for pair in ordered.windows(2) {
+            assert_eq!(
+                bus_key(pair[0]).cmp(&bus_key(pair[1])),
+                Ordering::Less,
+                "{} < {}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
+}
@@ -4,9 +4,13 @@ use crate::v4l_sys::*;
 
 #[derive(Debug, Default, Clone, Copy)]
 /// Timestamp consisting of a seconds and a microseconds component
+///
+/// Both components are 64-bit wide independent of the size of time_t on the target, so the
+/// timestamps of 32-bit (e.g. armv7) builds using a 64-bit time_t remain correct past 2038. The
+/// buffer ioctl codes are picked to match the time_t of the bindings, see `vidioc::TIME64`.
 pub struct Timestamp {
-    pub sec: time_t,
-    pub usec: time_t,
+    pub sec: i64,
+    pub usec: i64,
 }
 
 impl Timestamp {
@@ -23,7 +27,7 @@ impl Timestamp {
     /// use v4l::timestamp::Timestamp;
     /// let ts = Timestamp::new(5, 5);
     /// ```
-    pub fn new(sec: time_t, usec: time_t) -> Self {
+    pub fn new(sec: i64, usec: i64) -> Self {
         Timestamp { sec, usec }
     }
 }
@@ -36,10 +40,12 @@ impl fmt::Display for Timestamp {
 }
 
 impl From<timeval> for Timestamp {
+    // time_t is only 32-bit wide on some targets
+    #[allow(clippy::unnecessary_cast)]
     fn from(tv: timeval) -> Self {
         Timestamp {
-            sec: tv.tv_sec as time_t,
-            usec: tv.tv_usec as time_t,
+            sec: tv.tv_sec as i64,
+            usec: tv.tv_usec as i64,
         }
     }
 }
@@ -51,8 +57,9 @@ impl Into<timeval> for Timestamp {
             tv = mem::zeroed();
         }
 
-        tv.tv_sec = self.sec as time_t;
-        tv.tv_usec = self.usec as time_t;
+        // Truncates on targets with a 32-bit time_t, the kernel cannot represent more anyway
+        tv.tv_sec = self.sec as _;
+        tv.tv_usec = self.usec as _;
         tv
     }
 }
@@ -60,14 +67,17 @@ impl Into<timeval> for Timestamp {
 impl From<time::Duration> for Timestamp {
     fn from(duration: time::Duration) -> Self {
         Timestamp::new(
-            duration.as_secs() as time_t,
-            duration.subsec_micros() as time_t,
+            duration.as_secs() as i64,
+            i64::from(duration.subsec_micros()),
         )
     }
 }
 
 impl From<Timestamp> for time::Duration {
     fn from(ts: Timestamp) -> Self {
-        time::Duration::new(ts.sec as u64, (ts.usec * 1000) as u32)
+        // Timestamps before the epoch (or the boot time for monotonic ones) cannot be represented
+        let sec = ts.sec.max(0) as u64;
+        let usec = ts.usec.clamp(0, 999_999) as u32;
+        time::Duration::new(sec, usec * 1000)
     }
 }
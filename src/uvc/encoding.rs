@@ -0,0 +1,355 @@
+//! H.264 encoder configuration of UVC 1.5 cameras
+//!
+//! UVC 1.5 standardized the on-board encoders of cameras as encoding units (EU), which sit
+//! between the camera sensor and the video streaming interface. The encoder parameters (profile,
+//! resolution, rate control, bitrate, ...) are controls of the encoding unit, while the format
+//! itself is negotiated through the probe/commit sequence of the video streaming interface. The
+//! uvcvideo driver runs that sequence on its own: it probes when setting the format and commits
+//! when starting the stream. The encoding unit thus has to be configured in between:
+//!
+//! 1. Set the H264 format on the video device.
+//! 2. Write the desired configuration with `Encoder::probe`, the camera adjusts unsupported
+//!    values and the accepted configuration is read back.
+//! 3. Start the stream, which commits the configuration.
+//!
+//! Controls the camera lists in its bmControlsRuntime may also be changed while streaming.
+//! Queries are sent through UVCIOC_CTRL_QUERY, addressed by the unit ID like those of extension
+//! units. Drivers which restrict that ioctl to extension units fail them with an error of kind
+//! `NotFound`, see `logitech_h264` for the vendor interface of UVC 1.1 cameras.
+
+use std::io;
+
+use crate::device::Device;
+use crate::uvc::xu::{self, Request, Unit};
+
+pub use crate::uvc::logitech_h264::{Profile, RateControl};
+
+/// Encoding unit descriptor subtype (VC_ENCODING_UNIT)
+const VC_ENCODING_UNIT: u8 = 0x07;
+
+/// Control selectors of the encoding unit, see the UVC 1.5 specification A.9.9
+const PROFILE_TOOLSET_CONTROL: u8 = 0x02;
+const VIDEO_RESOLUTION_CONTROL: u8 = 0x03;
+const MIN_FRAME_INTERVAL_CONTROL: u8 = 0x04;
+const RATE_CONTROL_MODE_CONTROL: u8 = 0x06;
+const AVERAGE_BITRATE_CONTROL: u8 = 0x07;
+const CPB_SIZE_CONTROL: u8 = 0x08;
+const PEAK_BIT_RATE_CONTROL: u8 = 0x09;
+const QUANTIZATION_PARAMS_CONTROL: u8 = 0x0a;
+const LEVEL_IDC_LIMIT_CONTROL: u8 = 0x0f;
+
+/// Encoding unit descriptor
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Descriptor {
+    /// Unit ID (bUnitID)
+    id: u8,
+    /// Controls available during initialization (bmControls), bit n for selector n + 1
+    controls: u32,
+    /// Controls available while streaming (bmControlsRuntime)
+    runtime: u32,
+}
+
+impl Descriptor {
+    fn parse(desc: &[u8]) -> Option<Self> {
+        let size = *desc.get(6)? as usize;
+        let bitmap = |offset: usize| -> Option<u32> {
+            let bytes = desc.get(offset..offset + size)?;
+            Some(
+                bytes
+                    .iter()
+                    .take(4)
+                    .rev()
+                    .fold(0, |bits, byte| bits << 8 | *byte as u32),
+            )
+        };
+
+        Some(Descriptor {
+            id: desc[3],
+            controls: bitmap(7)?,
+            runtime: bitmap(7 + size)?,
+        })
+    }
+}
+
+/// Encoder configuration
+///
+/// Fields are `None` for controls the camera does not implement, those are skipped when probing.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Config {
+    /// H.264 profile (wProfile of the profile and toolset control)
+    pub profile: Option<Profile>,
+    /// Width and height in pixels
+    pub resolution: Option<(u16, u16)>,
+    /// Minimum frame interval in 100ns units, e.g. 333333 for 30 fps
+    pub frame_interval: Option<u32>,
+    /// Rate control mode
+    pub rate_control: Option<RateControl>,
+    /// Average bitrate in bits per second
+    pub bitrate: Option<u32>,
+    /// Peak bitrate in bits per second
+    pub peak_bitrate: Option<u32>,
+    /// Size of the coded picture buffer in bits
+    pub cpb_size: Option<u32>,
+    /// Quantization parameters of I, P and B frames
+    pub qp: Option<[u16; 3]>,
+    /// Maximum level_idc of the stream, e.g. 40 for level 4.0
+    pub level_idc: Option<u8>,
+
+    /// wConstrainedToolset and bmSettings of the profile and toolset control
+    toolset: [u8; 3],
+}
+
+/// Encoding unit of a UVC 1.5 camera
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::prelude::*;
+/// use v4l::uvc::encoding::Encoder;
+/// use v4l::video::Capture;
+/// use v4l::FourCC;
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut fmt = dev.format().unwrap();
+/// fmt.fourcc = FourCC::new(b"H264");
+/// let fmt = dev.set_format(&fmt).unwrap();
+///
+/// let encoder = Encoder::new(&dev).unwrap();
+/// let mut config = encoder.current().unwrap();
+/// config.resolution = Some((fmt.width as u16, fmt.height as u16));
+/// config.bitrate = Some(3_000_000);
+/// let config = encoder.probe(&config).unwrap();
+/// println!("negotiated {:?} bps", config.bitrate);
+/// // starting a stream on dev commits the configuration
+/// ```
+pub struct Encoder {
+    unit: Unit,
+    desc: Descriptor,
+}
+
+impl Encoder {
+    /// Returns the encoding unit of a camera
+    ///
+    /// Returns an error of kind `NotFound` if the camera has none, e.g. because it implements
+    /// a UVC version older than 1.5.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Video device of the camera
+    pub fn new(dev: &Device) -> io::Result<Self> {
+        let descriptors = xu::descriptors(dev)?;
+        let desc = xu::find_unit(&descriptors, VC_ENCODING_UNIT, |desc| {
+            Descriptor::parse(desc).is_some()
+        })
+        .and_then(Descriptor::parse)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "encoding unit not found in the USB descriptors",
+            )
+        })?;
+
+        Ok(Encoder {
+            unit: Unit::new(dev, desc.id),
+            desc,
+        })
+    }
+
+    /// Returns the unit
+    pub fn unit(&self) -> &Unit {
+        &self.unit
+    }
+
+    /// Returns the current configuration
+    pub fn current(&self) -> io::Result<Config> {
+        self.read(Request::GetCur)
+    }
+
+    /// Returns the default configuration of the camera
+    pub fn default_config(&self) -> io::Result<Config> {
+        self.read(Request::GetDef)
+    }
+
+    /// Returns the lower limits of the configuration fields
+    pub fn min(&self) -> io::Result<Config> {
+        self.read(Request::GetMin)
+    }
+
+    /// Returns the upper limits of the configuration fields
+    pub fn max(&self) -> io::Result<Config> {
+        self.read(Request::GetMax)
+    }
+
+    /// Proposes a configuration and returns the one the camera accepted
+    ///
+    /// Only the fields which are set and implemented by the camera are written.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Desired configuration
+    pub fn probe(&self, config: &Config) -> io::Result<Config> {
+        for (selector, data) in encode(config) {
+            if self.supports(selector) {
+                self.unit.set(selector, &data)?;
+            }
+        }
+        self.current()
+    }
+
+    fn supports(&self, selector: u8) -> bool {
+        bit(self.desc.controls | self.desc.runtime, selector)
+    }
+
+    fn read(&self, request: Request) -> io::Result<Config> {
+        let mut config = Config::default();
+        for &(selector, len) in &[
+            (PROFILE_TOOLSET_CONTROL, 5),
+            (VIDEO_RESOLUTION_CONTROL, 4),
+            (MIN_FRAME_INTERVAL_CONTROL, 4),
+            (RATE_CONTROL_MODE_CONTROL, 1),
+            (AVERAGE_BITRATE_CONTROL, 4),
+            (CPB_SIZE_CONTROL, 4),
+            (PEAK_BIT_RATE_CONTROL, 4),
+            (QUANTIZATION_PARAMS_CONTROL, 6),
+            (LEVEL_IDC_LIMIT_CONTROL, 1),
+        ] {
+            if self.supports(selector) {
+                let mut data = vec![0; len];
+                self.unit.query(selector, request, &mut data)?;
+                decode(&mut config, selector, &data);
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn bit(bitmap: u32, selector: u8) -> bool {
+    (1..=32).contains(&selector) && bitmap & 1 << (selector - 1) != 0
+}
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+/// Stores the value of a control in the matching configuration field
+fn decode(config: &mut Config, selector: u8, data: &[u8]) {
+    match selector {
+        PROFILE_TOOLSET_CONTROL => {
+            config.profile = Some(Profile::from(u16_at(data, 0)));
+            config.toolset.copy_from_slice(&data[2..5]);
+        }
+        VIDEO_RESOLUTION_CONTROL => {
+            config.resolution = Some((u16_at(data, 0), u16_at(data, 2)));
+        }
+        MIN_FRAME_INTERVAL_CONTROL => config.frame_interval = Some(u32_at(data, 0)),
+        RATE_CONTROL_MODE_CONTROL => config.rate_control = Some(RateControl::from(data[0])),
+        AVERAGE_BITRATE_CONTROL => config.bitrate = Some(u32_at(data, 0)),
+        CPB_SIZE_CONTROL => config.cpb_size = Some(u32_at(data, 0)),
+        PEAK_BIT_RATE_CONTROL => config.peak_bitrate = Some(u32_at(data, 0)),
+        QUANTIZATION_PARAMS_CONTROL => {
+            config.qp = Some([u16_at(data, 0), u16_at(data, 2), u16_at(data, 4)]);
+        }
+        LEVEL_IDC_LIMIT_CONTROL => config.level_idc = Some(data[0]),
+        _ => {}
+    }
+}
+
+/// Returns the control values of the fields which are set
+fn encode(config: &Config) -> Vec<(u8, Vec<u8>)> {
+    let mut controls = Vec::new();
+    if let Some(profile) = config.profile {
+        let mut data = u16::from(profile).to_le_bytes().to_vec();
+        data.extend_from_slice(&config.toolset);
+        controls.push((PROFILE_TOOLSET_CONTROL, data));
+    }
+    if let Some((width, height)) = config.resolution {
+        let mut data = width.to_le_bytes().to_vec();
+        data.extend_from_slice(&height.to_le_bytes());
+        controls.push((VIDEO_RESOLUTION_CONTROL, data));
+    }
+    if let Some(interval) = config.frame_interval {
+        controls.push((MIN_FRAME_INTERVAL_CONTROL, interval.to_le_bytes().to_vec()));
+    }
+    if let Some(mode) = config.rate_control {
+        controls.push((RATE_CONTROL_MODE_CONTROL, vec![mode.into()]));
+    }
+    if let Some(bitrate) = config.bitrate {
+        controls.push((AVERAGE_BITRATE_CONTROL, bitrate.to_le_bytes().to_vec()));
+    }
+    if let Some(size) = config.cpb_size {
+        controls.push((CPB_SIZE_CONTROL, size.to_le_bytes().to_vec()));
+    }
+    if let Some(bitrate) = config.peak_bitrate {
+        controls.push((PEAK_BIT_RATE_CONTROL, bitrate.to_le_bytes().to_vec()));
+    }
+    if let Some(qp) = config.qp {
+        let data = qp.iter().flat_map(|qp| qp.to_le_bytes().to_vec()).collect();
+        controls.push((QUANTIZATION_PARAMS_CONTROL, data));
+    }
+    if let Some(level) = config.level_idc {
+        controls.push((LEVEL_IDC_LIMIT_CONTROL, vec![level]));
+    }
+    controls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_descriptor() {
+        // profile, resolution, rate control and bitrate during initialization, bitrate and
+        // level at runtime
+        let eu = [
+            13, 0x24, 0x07, 5, 4, 0, 3, 0x66, 0x00, 0x00, 0x40, 0x40, 0x00,
+        ];
+        let descriptors = [&[9, 0x24, 0x06, 3, 0, 0, 0, 0, 0][..], &eu[..]].concat();
+
+        let found = xu::find_unit(&descriptors, VC_ENCODING_UNIT, |_| true).unwrap();
+        let desc = Descriptor::parse(found).unwrap();
+        assert_eq!(desc.id, 5);
+        assert!(bit(desc.controls, PROFILE_TOOLSET_CONTROL));
+        assert!(bit(desc.controls, VIDEO_RESOLUTION_CONTROL));
+        assert!(!bit(desc.controls, MIN_FRAME_INTERVAL_CONTROL));
+        assert!(bit(desc.controls, RATE_CONTROL_MODE_CONTROL));
+        assert!(bit(desc.controls, AVERAGE_BITRATE_CONTROL));
+        assert!(bit(desc.runtime, AVERAGE_BITRATE_CONTROL));
+        assert!(bit(desc.runtime, LEVEL_IDC_LIMIT_CONTROL));
+        assert!(!bit(desc.runtime, PROFILE_TOOLSET_CONTROL));
+
+        // truncated bitmaps
+        assert_eq!(Descriptor::parse(&eu[..10]), None);
+    }
+
+    #[test]
+    fn encode_decode() {
+        let config = Config {
+            profile: Some(Profile::High),
+            resolution: Some((1920, 1080)),
+            rate_control: Some(RateControl::Vbr),
+            bitrate: Some(3_000_000),
+            qp: Some([26, 28, 30]),
+            level_idc: Some(40),
+            toolset: [1, 0, 2],
+            ..Config::default()
+        };
+
+        let mut decoded = Config::default();
+        for (selector, data) in encode(&config) {
+            decode(&mut decoded, selector, &data);
+        }
+        assert_eq!(encode(&decoded), encode(&config));
+        assert_eq!(decoded.resolution, Some((1920, 1080)));
+        assert_eq!(decoded.frame_interval, None);
+        assert_eq!(decoded.toolset, [1, 0, 2]);
+    }
+}
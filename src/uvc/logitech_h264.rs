@@ -0,0 +1,278 @@
+//! H.264 encoder configuration of Logitech UVC 1.1 cameras
+//!
+//! Before UVC 1.5 standardized encoding units, Logitech (e.g. C920, C930e) shipped UVC 1.1
+//! cameras with an on-board H.264 encoder configured through a vendor extension unit, the
+//! "UVCX" H.264 XU. Such cameras deliver hardware-encoded video through a regular H264 capture
+//! format. The encoder parameters (bitrate, profile, key frame period, ...) are not covered by
+//! V4L2 controls, instead they are negotiated through a probe/commit sequence on the extension
+//! unit, similar to the stream negotiation of the video streaming interface:
+//!
+//! 1. Write the desired configuration to the probe control.
+//! 2. Read back the probe control, the camera adjusts unsupported values.
+//! 3. Write the accepted configuration to the commit control.
+//!
+//! The configuration has to be committed after setting the H264 format and before starting the
+//! stream. UVC 1.5 cameras are configured through their encoding unit instead, see `encoding`.
+//! `Encoder::new` fails with `NotFound` for cameras lacking the Logitech extension unit.
+
+use std::io;
+
+use crate::device::Device;
+use crate::uvc::xu::{Request, Unit};
+
+/// GUID of the Logitech H.264 extension unit, A29E7641-DE04-47E3-8B2B-F4341AFF003B
+const GUID: [u8; 16] = [
+    0x41, 0x76, 0x9e, 0xa2, 0x04, 0xde, 0xe3, 0x47, 0x8b, 0x2b, 0xf4, 0x34, 0x1a, 0xff, 0x00, 0x3b,
+];
+
+/// Control selectors of the Logitech H.264 extension unit
+const VIDEO_CONFIG_PROBE: u8 = 0x01;
+const VIDEO_CONFIG_COMMIT: u8 = 0x02;
+const PICTURE_TYPE_CONTROL: u8 = 0x09;
+
+/// Size of uvcx_video_config_probe_commit_t
+const CONFIG_SIZE: usize = 46;
+
+/// H.264 profile (wProfile)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Profile {
+    ConstrainedBaseline,
+    Baseline,
+    Main,
+    ConstrainedHigh,
+    High,
+    /// A profile not listed here, with its raw value
+    Other(u16),
+}
+
+impl From<u16> for Profile {
+    fn from(profile: u16) -> Self {
+        match profile {
+            0x4240 => Profile::ConstrainedBaseline,
+            0x4200 => Profile::Baseline,
+            0x4d00 => Profile::Main,
+            0x640c => Profile::ConstrainedHigh,
+            0x6400 => Profile::High,
+            other => Profile::Other(other),
+        }
+    }
+}
+
+impl From<Profile> for u16 {
+    fn from(profile: Profile) -> Self {
+        match profile {
+            Profile::ConstrainedBaseline => 0x4240,
+            Profile::Baseline => 0x4200,
+            Profile::Main => 0x4d00,
+            Profile::ConstrainedHigh => 0x640c,
+            Profile::High => 0x6400,
+            Profile::Other(other) => other,
+        }
+    }
+}
+
+/// Rate control mode (bRateControlMode)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RateControl {
+    /// Constant bitrate
+    Cbr,
+    /// Variable bitrate
+    Vbr,
+    /// Constant QP
+    ConstantQp,
+    /// A mode not listed here, with its raw value
+    Other(u8),
+}
+
+impl From<u8> for RateControl {
+    fn from(mode: u8) -> Self {
+        match mode {
+            1 => RateControl::Cbr,
+            2 => RateControl::Vbr,
+            3 => RateControl::ConstantQp,
+            other => RateControl::Other(other),
+        }
+    }
+}
+
+impl From<RateControl> for u8 {
+    fn from(mode: RateControl) -> Self {
+        match mode {
+            RateControl::Cbr => 1,
+            RateControl::Vbr => 2,
+            RateControl::ConstantQp => 3,
+            RateControl::Other(other) => other,
+        }
+    }
+}
+
+/// Encoder configuration (uvcx_video_config_probe_commit_t)
+///
+/// Fields which are not exposed individually are kept as they were read from the camera.
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Frame interval in 100ns units, e.g. 333333 for 30 fps
+    pub frame_interval: u32,
+    /// Average bitrate in bits per second
+    pub bitrate: u32,
+    /// Bitmask of the fields the camera should keep fixed while negotiating (bmHints)
+    pub hints: u16,
+    /// Width in pixels
+    pub width: u16,
+    /// Height in pixels
+    pub height: u16,
+    /// H.264 profile
+    pub profile: Profile,
+    /// Time between key frames in milliseconds, 0 for only the first frame
+    pub iframe_period: u16,
+    /// Rate control mode
+    pub rate_control: RateControl,
+    /// Entropy coding, true for CABAC, false for CAVLC
+    pub cabac: bool,
+
+    raw: [u8; CONFIG_SIZE],
+}
+
+impl Config {
+    fn from_bytes(raw: [u8; CONFIG_SIZE]) -> Self {
+        let u16_at = |offset: usize| u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                raw[offset],
+                raw[offset + 1],
+                raw[offset + 2],
+                raw[offset + 3],
+            ])
+        };
+
+        Config {
+            frame_interval: u32_at(0),
+            bitrate: u32_at(4),
+            hints: u16_at(8),
+            width: u16_at(12),
+            height: u16_at(14),
+            profile: Profile::from(u16_at(20)),
+            iframe_period: u16_at(22),
+            rate_control: RateControl::from(raw[29]),
+            cabac: raw[35] == 1,
+            raw,
+        }
+    }
+
+    fn to_bytes(self) -> [u8; CONFIG_SIZE] {
+        let mut raw = self.raw;
+        raw[0..4].copy_from_slice(&self.frame_interval.to_le_bytes());
+        raw[4..8].copy_from_slice(&self.bitrate.to_le_bytes());
+        raw[8..10].copy_from_slice(&self.hints.to_le_bytes());
+        raw[12..14].copy_from_slice(&self.width.to_le_bytes());
+        raw[14..16].copy_from_slice(&self.height.to_le_bytes());
+        raw[20..22].copy_from_slice(&u16::from(self.profile).to_le_bytes());
+        raw[22..24].copy_from_slice(&self.iframe_period.to_le_bytes());
+        raw[29] = self.rate_control.into();
+        raw[35] = self.cabac as u8;
+        // bStreamMuxOption: plain H.264 frames, not muxed into another container
+        raw[33] = 0;
+        raw
+    }
+}
+
+/// Logitech H.264 extension unit of a UVC 1.1 camera
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::prelude::*;
+/// use v4l::uvc::logitech_h264::Encoder;
+/// use v4l::video::Capture;
+/// use v4l::FourCC;
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut fmt = dev.format().unwrap();
+/// fmt.fourcc = FourCC::new(b"H264");
+/// let fmt = dev.set_format(&fmt).unwrap();
+///
+/// let encoder = Encoder::new(&dev).unwrap();
+/// let mut config = encoder.current().unwrap();
+/// config.width = fmt.width as u16;
+/// config.height = fmt.height as u16;
+/// config.bitrate = 3_000_000;
+/// config.iframe_period = 1000;
+/// let config = encoder.probe(&config).unwrap();
+/// encoder.commit(&config).unwrap();
+/// println!("negotiated {} bps", config.bitrate);
+/// ```
+pub struct Encoder {
+    unit: Unit,
+}
+
+impl Encoder {
+    /// Returns the H.264 extension unit of a camera
+    ///
+    /// Returns an error of kind `NotFound` if the camera does not implement it.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Video device of the camera
+    pub fn new(dev: &Device) -> io::Result<Self> {
+        Ok(Encoder {
+            unit: Unit::find(dev, &GUID)?,
+        })
+    }
+
+    /// Returns the extension unit
+    pub fn unit(&self) -> &Unit {
+        &self.unit
+    }
+
+    /// Returns the configuration of the last probe
+    pub fn current(&self) -> io::Result<Config> {
+        self.read(VIDEO_CONFIG_PROBE, Request::GetCur)
+    }
+
+    /// Returns the default configuration of the camera
+    pub fn default_config(&self) -> io::Result<Config> {
+        self.read(VIDEO_CONFIG_PROBE, Request::GetDef)
+    }
+
+    /// Returns the lower limits of the configuration fields
+    pub fn min(&self) -> io::Result<Config> {
+        self.read(VIDEO_CONFIG_PROBE, Request::GetMin)
+    }
+
+    /// Returns the upper limits of the configuration fields
+    pub fn max(&self) -> io::Result<Config> {
+        self.read(VIDEO_CONFIG_PROBE, Request::GetMax)
+    }
+
+    /// Proposes a configuration and returns the one the camera accepted
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Desired configuration
+    pub fn probe(&self, config: &Config) -> io::Result<Config> {
+        self.unit.set(VIDEO_CONFIG_PROBE, &config.to_bytes())?;
+        self.current()
+    }
+
+    /// Applies a configuration previously returned by `probe`
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Negotiated configuration
+    pub fn commit(&self, config: &Config) -> io::Result<()> {
+        self.unit.set(VIDEO_CONFIG_COMMIT, &config.to_bytes())
+    }
+
+    /// Asks the encoder to emit an IDR frame as soon as possible, e.g. after packet loss
+    pub fn request_idr(&self) -> io::Result<()> {
+        // wLayerOrViewID 0 (all layers), wPicType 1 (IDR frame)
+        self.unit
+            .set(PICTURE_TYPE_CONTROL, &[0x00, 0x00, 0x01, 0x00])
+    }
+
+    fn read(&self, selector: u8, request: Request) -> io::Result<Config> {
+        let mut raw = [0; CONFIG_SIZE];
+        self.unit.query(selector, request, &mut raw)?;
+        Ok(Config::from_bytes(raw))
+    }
+}
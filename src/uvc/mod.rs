@@ -0,0 +1,642 @@
+//! Video and metadata capture from USB video class (UVC) cameras
+//!
+//! The uvcvideo driver exposes a second device node per camera which delivers the UVC payload
+//! headers of each frame: the presentation timestamp (PTS) and source clock reference (SCR) set
+//! by the camera, along with the host time at which the frame started. These allow correlating
+//! frames with the device clock, e.g. to compute precise capture times. Each metadata buffer
+//! carries the sequence number and timestamp of the video frame it belongs to.
+//!
+//! Vendor extension units and the H.264 encoder configuration of UVC 1.5 cameras and of
+//! Logitech UVC 1.1 cameras are available through the `xu`, `encoding` and `logitech_h264`
+//! submodules.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::buffer::{Metadata, StateError, Type};
+use crate::capability;
+use crate::context;
+use crate::device::Device;
+use crate::io::mmap;
+use crate::io::traits::{CaptureStream, Stream};
+
+pub mod encoding;
+pub mod logitech_h264;
+pub mod xu;
+
+/// Size of the fixed part of struct uvc_meta_buf: host timestamp (ns) and USB frame number
+const RECORD_HEADER: usize = 10;
+
+/// Default time to wait for the metadata of a frame, see `Capture::set_meta_timeout`
+const META_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Payload header flags, see the UVC specification 2.4.3.3
+const FLAG_PTS: u8 = 0x04;
+const FLAG_SCR: u8 = 0x08;
+
+/// One UVC payload header of a frame
+#[derive(Debug, Copy, Clone)]
+pub struct Record<'a> {
+    /// Host time (CLOCK_MONOTONIC) at which the header was received
+    pub timestamp: Duration,
+    /// USB frame number at which the header was received
+    pub sof: u16,
+    /// Raw payload header, starting with its length and flags bytes
+    pub header: &'a [u8],
+}
+
+impl<'a> Record<'a> {
+    /// Returns the payload header flags (bmHeaderInfo)
+    pub fn flags(&self) -> u8 {
+        self.header[1]
+    }
+
+    /// Returns the presentation timestamp in units of the device clock, if present
+    pub fn pts(&self) -> Option<u32> {
+        if self.flags() & FLAG_PTS == 0 {
+            return None;
+        }
+        self.u32_at(2)
+    }
+
+    /// Returns the source clock reference (device clock, USB frame number), if present
+    pub fn scr(&self) -> Option<(u32, u16)> {
+        if self.flags() & FLAG_SCR == 0 {
+            return None;
+        }
+
+        let offset = if self.flags() & FLAG_PTS != 0 { 6 } else { 2 };
+        let stc = self.u32_at(offset)?;
+        let sof = self.header.get(offset + 4..offset + 6)?;
+        Some((stc, u16::from_le_bytes([sof[0], sof[1]])))
+    }
+
+    fn u32_at(&self, offset: usize) -> Option<u32> {
+        let bytes = self.header.get(offset..offset + 4)?;
+        Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Iterator over the records of a metadata buffer
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Records<'a> {
+    /// Returns the records of a metadata buffer in the UVCH format
+    ///
+    /// Truncated records at the end of the buffer are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Metadata buffer, limited to its bytesused value
+    pub fn new(buf: &'a [u8]) -> Self {
+        Records { data: buf }
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Record<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.data;
+        let length = *data.get(RECORD_HEADER)? as usize;
+        // the header always contains its length and flags bytes
+        if length < 2 || data.len() < RECORD_HEADER + length {
+            self.data = &[];
+            return None;
+        }
+
+        let mut ns = [0; 8];
+        ns.copy_from_slice(&data[..8]);
+        self.data = &data[RECORD_HEADER + length..];
+
+        Some(Record {
+            timestamp: Duration::from_nanos(u64::from_ne_bytes(ns)),
+            sof: u16::from_ne_bytes([data[8], data[9]]),
+            header: &data[RECORD_HEADER..RECORD_HEADER + length],
+        })
+    }
+}
+
+/// Returns the path of the metadata node belonging to a video capture device
+///
+/// All nodes of a physical camera share the same driver and bus info, the metadata node is the
+/// one which supports metadata capture. Returns an error of kind `NotFound` if there is none,
+/// e.g. for cameras handled by drivers other than uvcvideo or kernels older than 4.16.
+///
+/// # Arguments
+///
+/// * `dev` - Video capture device
+pub fn metadata_node(dev: &Device) -> io::Result<PathBuf> {
+    let caps = dev.query_caps()?;
+
+    let mut nodes = context::enum_devices();
+    nodes.sort_by_key(|node| node.index());
+    for node in nodes {
+        let other = match Device::with_path(node.path()).and_then(|dev| dev.query_caps()) {
+            Ok(caps) => caps,
+            Err(_) => continue,
+        };
+
+        if other.driver == caps.driver
+            && other.bus == caps.bus
+            && other.capabilities.contains(capability::Flags::META_CAPTURE)
+        {
+            return Ok(node.path().to_path_buf());
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no metadata node for {} on {}", caps.card, caps.bus),
+    ))
+}
+
+/// Position of a metadata buffer relative to a video frame
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Order {
+    Older,
+    Same,
+    Newer,
+}
+
+/// Metadata buffer waiting to be paired with a video frame
+#[derive(Debug, Copy, Clone)]
+struct Pending {
+    index: usize,
+    sequence: u32,
+    timestamp: Duration,
+    host: Option<Duration>,
+}
+
+/// Outcome of pairing a video frame with the pending metadata buffers
+#[derive(Debug, Clone, Default)]
+pub struct Pairing {
+    /// Index of the metadata buffer belonging to the frame, if it was captured
+    pub meta: Option<usize>,
+    /// Indices of metadata buffers which belong to earlier frames and can be queued again
+    pub stale: Vec<usize>,
+}
+
+/// Pairs video frames with their metadata buffers
+///
+/// Both streams are dequeued independently, so a naive one-to-one pairing goes wrong as soon as
+/// either of them drops a buffer. Older kernels also count the metadata sequence numbers on their
+/// own, leaving them off by one (or more) from the video ones. Buffers are therefore associated
+/// by the following rules, in order:
+///
+/// 1. Identical buffer timestamps, which uvcvideo copies from the video to the metadata buffer.
+/// 2. The difference of the sequence numbers, learned from the last match by rule 1 or 3.
+/// 3. The host time of the first payload header lying within a tolerance of the video
+///    timestamp, as both are sampled when the first packet of the frame arrives.
+///
+/// Metadata buffers older than the current video frame are reported as stale, video frames for
+/// which the metadata was dropped are paired with nothing.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    pending: Vec<Pending>,
+    offset: Option<u32>,
+    tolerance: Duration,
+    matched: u64,
+    missing: u64,
+}
+
+impl Default for Matcher {
+    fn default() -> Self {
+        Matcher {
+            pending: Vec::new(),
+            offset: None,
+            tolerance: Duration::from_millis(1),
+            matched: 0,
+            missing: 0,
+        }
+    }
+}
+
+impl Matcher {
+    /// Returns a matcher
+    pub fn new() -> Self {
+        Matcher::default()
+    }
+
+    /// Sets the tolerance for comparing header host times with video timestamps (default: 1ms)
+    ///
+    /// # Arguments
+    ///
+    /// * `tolerance` - Maximum time difference
+    pub fn set_tolerance(&mut self, tolerance: Duration) {
+        self.tolerance = tolerance;
+    }
+
+    /// Returns the number of video frames paired with metadata so far
+    pub fn matched(&self) -> u64 {
+        self.matched
+    }
+
+    /// Returns the number of video frames for which no metadata was found so far
+    pub fn missing(&self) -> u64 {
+        self.missing
+    }
+
+    /// Forgets all pending buffers and the learned sequence offset, e.g. after restarting
+    ///
+    /// Returns the indices of the pending metadata buffers.
+    pub fn reset(&mut self) -> Vec<usize> {
+        self.offset = None;
+        self.pending
+            .drain(..)
+            .map(|pending| pending.index)
+            .collect()
+    }
+
+    /// Adds a dequeued metadata buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Buffer index
+    /// * `meta` - Buffer metadata
+    /// * `records` - Payload headers of the buffer
+    pub fn push(&mut self, index: usize, meta: &Metadata, mut records: Records) {
+        self.pending.push(Pending {
+            index,
+            sequence: meta.sequence,
+            timestamp: Duration::from(meta.timestamp),
+            host: records.next().map(|record| record.timestamp),
+        });
+    }
+
+    /// Returns true if a further metadata buffer is needed to decide on a video frame
+    ///
+    /// The metadata of a frame is completed before the frame itself, so once a video frame was
+    /// dequeued, its metadata buffer (or a newer one, if it was dropped) is available as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `video` - Metadata of the video frame
+    pub fn needs_more(&self, video: &Metadata) -> bool {
+        self.pending
+            .iter()
+            .all(|pending| self.order(pending, video) == Order::Older)
+    }
+
+    /// Pairs a video frame with a pending metadata buffer
+    ///
+    /// # Arguments
+    ///
+    /// * `video` - Metadata of the video frame
+    pub fn pair(&mut self, video: &Metadata) -> Pairing {
+        let mut pairing = Pairing::default();
+        let mut keep = Vec::with_capacity(self.pending.len());
+
+        for pending in std::mem::take(&mut self.pending) {
+            match self.order(&pending, video) {
+                Order::Older => pairing.stale.push(pending.index),
+                Order::Same if pairing.meta.is_none() => {
+                    self.offset = Some(pending.sequence.wrapping_sub(video.sequence));
+                    pairing.meta = Some(pending.index);
+                }
+                _ => keep.push(pending),
+            }
+        }
+        self.pending = keep;
+
+        if pairing.meta.is_some() {
+            self.matched += 1;
+        } else {
+            self.missing += 1;
+        }
+        pairing
+    }
+
+    fn order(&self, pending: &Pending, video: &Metadata) -> Order {
+        let timestamp = Duration::from(video.timestamp);
+        if pending.timestamp == timestamp {
+            return Order::Same;
+        }
+
+        if let Some(offset) = self.offset {
+            let sequence = pending.sequence.wrapping_sub(offset);
+            return match sequence.wrapping_sub(video.sequence) as i32 {
+                diff if diff < 0 => Order::Older,
+                0 => Order::Same,
+                _ => Order::Newer,
+            };
+        }
+
+        match pending.host {
+            Some(host) if host.max(timestamp) - host.min(timestamp) <= self.tolerance => {
+                Order::Same
+            }
+            _ if pending.timestamp < timestamp => Order::Older,
+            _ => Order::Newer,
+        }
+    }
+}
+
+/// A video frame along with the metadata captured for it
+pub struct Frame<'a> {
+    /// Video frame
+    pub video: (&'a [u8], &'a Metadata),
+    /// Metadata buffer of the frame, None if the driver dropped it
+    pub meta: Option<(&'a [u8], &'a Metadata)>,
+}
+
+impl<'a> Frame<'a> {
+    /// Returns the UVC payload headers received for the frame
+    pub fn records(&self) -> Records<'a> {
+        match self.meta {
+            Some((buf, meta)) => Records::new(&buf[..(meta.bytesused as usize).min(buf.len())]),
+            None => Records::new(&[]),
+        }
+    }
+}
+
+/// Captures video frames together with their UVC metadata
+///
+/// The metadata buffers are associated with the video frames by a `Matcher`. Video frames are
+/// never dropped for lack of metadata, they are delivered without it instead, also if the
+/// metadata stream stalls.
+pub struct Capture<S> {
+    video: S,
+    meta: S,
+    matcher: Matcher,
+    held: Option<usize>,
+    held_meta: Option<usize>,
+
+    active: bool,
+}
+
+impl<'a> Capture<mmap::Stream<'a>> {
+    /// Returns a capture for a video device, opening its metadata node
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Video capture device
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::prelude::*;
+    /// use v4l::uvc;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let mut capture = uvc::Capture::new(&dev).unwrap();
+    /// loop {
+    ///     let frame = capture.next().unwrap();
+    ///     for record in frame.records() {
+    ///         println!("pts: {:?}, scr: {:?}", record.pts(), record.scr());
+    ///     }
+    /// }
+    /// ```
+    pub fn new(dev: &Device) -> io::Result<Self> {
+        let meta_dev = Device::with_path(metadata_node(dev)?)?;
+        let video = mmap::Stream::new(dev, Type::VideoCapture)?;
+        let meta = mmap::Stream::new(&meta_dev, Type::MetaCapture)?;
+        Ok(Capture::with_streams(video, meta))
+    }
+}
+
+impl<S> Capture<S>
+where
+    S: for<'b> CaptureStream<'b> + Stream<Item = [u8]>,
+{
+    /// Returns a capture running the given streams
+    ///
+    /// # Arguments
+    ///
+    /// * `video` - Video capture stream, which must not have been started yet
+    /// * `meta` - Metadata capture stream of the same camera, which must not have been started
+    ///   yet
+    pub fn with_streams(video: S, mut meta: S) -> Self {
+        meta.set_timeout(Some(META_TIMEOUT));
+        Capture {
+            video,
+            meta,
+            matcher: Matcher::new(),
+            held: None,
+            held_meta: None,
+            active: false,
+        }
+    }
+
+    /// Returns the matcher associating the buffers, e.g. to query its statistics
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    /// Sets how long to wait for the metadata of a frame before delivering it without (default:
+    /// 100ms)
+    ///
+    /// The metadata of a frame is completed before the frame itself, so this only expires if the
+    /// metadata stream stalls or the driver dropped its buffers.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time to wait
+    pub fn set_meta_timeout(&mut self, timeout: Duration) {
+        self.meta.set_timeout(Some(timeout));
+    }
+
+    /// Starts streaming on both devices
+    ///
+    /// This is done implicitly by the first call to next().
+    pub fn start(&mut self) -> io::Result<()> {
+        if self.active {
+            return Ok(());
+        }
+
+        for stream in [&mut self.video, &mut self.meta].iter_mut() {
+            // Enqueue all buffers once on stream start
+            for index in 0..stream.len() {
+                stream.queue(index)?;
+            }
+            stream.start()?;
+        }
+
+        self.active = true;
+        Ok(())
+    }
+
+    /// Stops streaming on both devices
+    pub fn stop(&mut self) -> io::Result<()> {
+        self.video.stop()?;
+        self.meta.stop()?;
+        self.matcher.reset();
+        self.held = None;
+        self.held_meta = None;
+
+        self.active = false;
+        Ok(())
+    }
+
+    /// Waits for the next video frame and pairs it with its metadata
+    ///
+    /// The buffers of the previous frame are handed back to the drivers first.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> io::Result<Frame<'_>> {
+        self.start()?;
+
+        if let Some(index) = self.held.take() {
+            self.video.queue(index)?;
+        }
+        if let Some(index) = self.held_meta.take() {
+            self.meta.queue(index)?;
+        }
+
+        let index = self.video.dequeue()?;
+        self.held = Some(index);
+        let video = *self
+            .video
+            .get_meta(index)
+            .ok_or(StateError::InvalidIndex(index))?;
+
+        while self.matcher.needs_more(&video) {
+            let index = match self.meta.dequeue() {
+                Ok(index) => index,
+                // deliver the frame without metadata rather than blocking the video stream
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    break
+                }
+                Err(e) => return Err(e),
+            };
+            match (self.meta.get(index), self.meta.get_meta(index)) {
+                (Some(buf), Some(meta)) => {
+                    let len = (meta.bytesused as usize).min(buf.len());
+                    self.matcher.push(index, meta, Records::new(&buf[..len]))
+                }
+                _ => return Err(StateError::InvalidIndex(index).into()),
+            }
+        }
+
+        let pairing = self.matcher.pair(&video);
+        for index in pairing.stale {
+            self.meta.queue(index)?;
+        }
+        self.held_meta = pairing.meta;
+
+        let video = match (self.video.get(index), self.video.get_meta(index)) {
+            (Some(buf), Some(meta)) => (buf, meta),
+            _ => return Err(StateError::InvalidIndex(index).into()),
+        };
+        let meta = match pairing.meta {
+            Some(index) => match (self.meta.get(index), self.meta.get_meta(index)) {
+                (Some(buf), Some(meta)) => Some((buf, meta)),
+                _ => return Err(StateError::InvalidIndex(index).into()),
+            },
+            None => None,
+        };
+
+        Ok(Frame { video, meta })
+    }
+
+    /// Returns the video and the metadata stream, stopping them first
+    pub fn into_inner(mut self) -> io::Result<(S, S)> {
+        self.stop()?;
+        Ok((self.video, self.meta))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp::Timestamp;
+
+    fn meta(sequence: u32, usec: i64) -> Metadata {
+        Metadata {
+            sequence,
+            timestamp: Timestamp::new(0, usec),
+            ..Metadata::default()
+        }
+    }
+
+    /// Returns a metadata buffer holding a single payload header received at the given host time
+    fn record(host: Duration) -> Vec<u8> {
+        let mut buf = (host.as_nanos() as u64).to_ne_bytes().to_vec();
+        buf.extend_from_slice(&[0, 0, 2, 0]);
+        buf
+    }
+
+    #[test]
+    fn pair_by_timestamp() {
+        let mut matcher = Matcher::new();
+        for sequence in 0..3 {
+            let video = meta(sequence, 1000 * i64::from(sequence));
+            assert!(matcher.needs_more(&video));
+            matcher.push(sequence as usize, &video, Records::new(&[]));
+            assert!(!matcher.needs_more(&video));
+
+            let pairing = matcher.pair(&video);
+            assert_eq!(pairing.meta, Some(sequence as usize));
+            assert!(pairing.stale.is_empty());
+        }
+        assert_eq!((matcher.matched(), matcher.missing()), (3, 0));
+    }
+
+    #[test]
+    fn dropped_metadata() {
+        let mut matcher = Matcher::new();
+        matcher.push(0, &meta(0, 0), Records::new(&[]));
+        assert_eq!(matcher.pair(&meta(0, 0)).meta, Some(0));
+
+        // the metadata of frame 1 was dropped, the one of frame 2 is already there
+        matcher.push(1, &meta(2, 2000), Records::new(&[]));
+        let video = meta(1, 1000);
+        assert!(!matcher.needs_more(&video));
+        let pairing = matcher.pair(&video);
+        assert_eq!(pairing.meta, None);
+        assert!(pairing.stale.is_empty());
+
+        assert_eq!(matcher.pair(&meta(2, 2000)).meta, Some(1));
+        assert_eq!((matcher.matched(), matcher.missing()), (2, 1));
+    }
+
+    #[test]
+    fn dropped_video() {
+        let mut matcher = Matcher::new();
+        matcher.push(0, &meta(0, 0), Records::new(&[]));
+        matcher.push(1, &meta(1, 1000), Records::new(&[]));
+
+        // frame 0 was dropped, its metadata is stale
+        let pairing = matcher.pair(&meta(1, 1000));
+        assert_eq!(pairing.meta, Some(1));
+        assert_eq!(pairing.stale, vec![0]);
+    }
+
+    #[test]
+    fn reordered_metadata() {
+        let mut matcher = Matcher::new();
+        matcher.push(3, &meta(3, 3000), Records::new(&[]));
+        matcher.push(2, &meta(2, 2000), Records::new(&[]));
+
+        assert_eq!(matcher.pair(&meta(2, 2000)).meta, Some(2));
+        assert_eq!(matcher.pair(&meta(3, 3000)).meta, Some(3));
+    }
+
+    #[test]
+    fn sequence_wrap() {
+        let mut matcher = Matcher::new();
+
+        // the timestamps differ, so the first match is made by the host time of the header and
+        // teaches the matcher that the metadata sequence is one ahead
+        let video = meta(u32::MAX - 1, 500);
+        let buf = record(Duration::from_micros(500));
+        matcher.push(0, &meta(u32::MAX, 0), Records::new(&buf));
+        assert_eq!(matcher.pair(&video).meta, Some(0));
+
+        // from now on, the sequence numbers are compared across the wrap
+        matcher.push(1, &meta(0, 1), Records::new(&[]));
+        matcher.push(2, &meta(1, 2), Records::new(&[]));
+        let pairing = matcher.pair(&meta(u32::MAX, 1500));
+        assert_eq!(pairing.meta, Some(1));
+        assert!(pairing.stale.is_empty());
+        assert_eq!(matcher.pair(&meta(0, 2500)).meta, Some(2));
+    }
+}
@@ -0,0 +1,185 @@
+//! Access to UVC extension units (XU)
+//!
+//! Vendors expose functionality beyond the UVC class specification through extension units,
+//! identified by a GUID. The uvcvideo driver passes queries to them through the
+//! UVCIOC_CTRL_QUERY ioctl, addressed by the unit ID the camera assigned to the unit. That ID
+//! differs between camera models and is looked up in the USB descriptors of the device.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::{fs, io};
+
+use crate::device::{Device, Handle};
+use crate::v4l2;
+use crate::v4l2::vidioc::{uvc_xu_control_query, UVCIOC_CTRL_QUERY};
+
+/// Class-specific interface descriptor (CS_INTERFACE)
+const CS_INTERFACE: u8 = 0x24;
+/// Extension unit descriptor subtype (VC_EXTENSION_UNIT)
+const VC_EXTENSION_UNIT: u8 = 0x06;
+
+/// Request codes of UVC control queries, see the UVC specification A.8
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Request {
+    SetCur = 0x01,
+    GetCur = 0x81,
+    GetMin = 0x82,
+    GetMax = 0x83,
+    GetRes = 0x84,
+    GetLen = 0x85,
+    GetInfo = 0x86,
+    GetDef = 0x87,
+}
+
+/// Extension unit of a UVC camera
+///
+/// Other units, e.g. the encoding unit of UVC 1.5 cameras, are addressed the same way by ID.
+pub struct Unit {
+    handle: Arc<Handle>,
+    id: u8,
+}
+
+impl Unit {
+    /// Returns the extension unit with a given GUID
+    ///
+    /// The GUID is given in the byte order of the USB descriptor, i.e. the first three fields are
+    /// little endian. Returns an error of kind `NotFound` if the camera has no such unit.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Video device of the camera
+    /// * `guid` - GUID of the extension unit (guidExtensionCode)
+    pub fn find(dev: &Device, guid: &[u8; 16]) -> io::Result<Self> {
+        let descriptors = descriptors(dev)?;
+        match find_unit(&descriptors, VC_EXTENSION_UNIT, |desc| {
+            desc.len() >= 20 && &desc[4..20] == guid
+        }) {
+            Some(desc) => Ok(Unit::new(dev, desc[3])),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "extension unit not found in the USB descriptors",
+            )),
+        }
+    }
+
+    /// Returns an extension unit by its ID
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - Video device of the camera
+    /// * `id` - Unit ID (bUnitID)
+    pub fn new(dev: &Device, id: u8) -> Self {
+        Unit {
+            handle: dev.handle(),
+            id,
+        }
+    }
+
+    /// Returns the unit ID
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Queries a control of the unit
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - Control selector
+    /// * `request` - Request code
+    /// * `data` - Control value, read or written depending on the request
+    pub fn query(&self, selector: u8, request: Request, data: &mut [u8]) -> io::Result<()> {
+        let mut query = uvc_xu_control_query {
+            unit: self.id,
+            selector,
+            query: request as u8,
+            size: data.len() as u16,
+            data: data.as_mut_ptr(),
+        };
+
+        unsafe {
+            v4l2::ioctl(
+                self.handle.fd(),
+                UVCIOC_CTRL_QUERY,
+                &mut query as *mut _ as *mut std::os::raw::c_void,
+            )
+        }
+    }
+
+    /// Returns the size of a control in bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - Control selector
+    pub fn len(&self, selector: u8) -> io::Result<usize> {
+        let mut data = [0; 2];
+        self.query(selector, Request::GetLen, &mut data)?;
+        Ok(u16::from_le_bytes(data) as usize)
+    }
+
+    /// Reads the current value of a control
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - Control selector
+    /// * `data` - Buffer for the value, see `len`
+    pub fn get(&self, selector: u8, data: &mut [u8]) -> io::Result<()> {
+        self.query(selector, Request::GetCur, data)
+    }
+
+    /// Writes the value of a control
+    ///
+    /// # Arguments
+    ///
+    /// * `selector` - Control selector
+    /// * `data` - New value
+    pub fn set(&self, selector: u8, data: &[u8]) -> io::Result<()> {
+        let mut data = data.to_vec();
+        self.query(selector, Request::SetCur, &mut data)
+    }
+}
+
+/// Returns the raw USB descriptors of the camera a video node belongs to
+pub(crate) fn descriptors(dev: &Device) -> io::Result<Vec<u8>> {
+    fs::read(usb_device(dev)?.join("descriptors"))
+}
+
+/// Returns the first video control unit descriptor of a given subtype accepted by `matches`
+///
+/// # Arguments
+///
+/// * `descriptors` - Raw USB descriptors, see `descriptors`
+/// * `subtype` - Descriptor subtype (bDescriptorSubtype)
+/// * `matches` - Filter applied to the complete descriptor
+pub(crate) fn find_unit<F>(descriptors: &[u8], subtype: u8, matches: F) -> Option<&[u8]>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    let mut rest = descriptors;
+    while rest.len() >= 2 {
+        let len = rest[0] as usize;
+        if len < 2 || len > rest.len() {
+            break;
+        }
+
+        let desc = &rest[..len];
+        if len >= 4 && desc[1] == CS_INTERFACE && desc[2] == subtype && matches(desc) {
+            return Some(desc);
+        }
+        rest = &rest[len..];
+    }
+
+    None
+}
+
+/// Returns the sysfs directory of the USB device a video node belongs to
+fn usb_device(dev: &Device) -> io::Result<PathBuf> {
+    let (major, minor) = dev.handle().devnum()?;
+
+    // the device link of the video node points to the video control interface
+    let interface = fs::canonicalize(format!("/sys/dev/char/{}:{}/device", major, minor))?;
+    interface
+        .parent()
+        .map(|path| path.to_path_buf())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not a USB device"))
+}
@@ -1,6 +1,7 @@
 use std::ffi::CString;
 use std::os::unix::ffi::OsStrExt;
-use std::{io, path::Path};
+use std::path::{Path, PathBuf};
+use std::{fmt, io};
 
 use crate::v4l2::vidioc;
 
@@ -20,7 +21,8 @@ mod detail {
         request: vidioc::_IOC_TYPE,
         argp: *mut std::os::raw::c_void,
     ) -> std::os::raw::c_int {
-        v4l2_ioctl(fd, request, argp)
+        // the request type of the ioctl wrapper differs between C libraries
+        v4l2_ioctl(fd, request as _, argp)
     }
     pub unsafe fn mmap(
         start: *mut std::os::raw::c_void,
@@ -28,12 +30,13 @@ mod detail {
         prot: std::os::raw::c_int,
         flags: std::os::raw::c_int,
         fd: std::os::raw::c_int,
-        offset: i64,
+        offset: libc::off_t,
     ) -> *mut std::os::raw::c_void {
-        v4l2_mmap(start, length as u64, prot, flags, fd, offset)
+        // off_t and size_t are only 32-bit wide on some targets
+        v4l2_mmap(start, length as _, prot, flags, fd, offset as _)
     }
     pub unsafe fn munmap(start: *mut std::os::raw::c_void, length: usize) -> std::os::raw::c_int {
-        v4l2_munmap(start, length as u64)
+        v4l2_munmap(start, length as _)
     }
 }
 
@@ -58,6 +61,10 @@ mod detail {
          * hack around this without conditional compilation, use syscall()
          * instead as a drop-in replacement. Details:
          * https://github.com/rust-lang/libc/issues/1036
+         *
+         * This also covers musl and bionic (Android), which declare the request
+         * as int instead of unsigned long. Both set errno on failure, just like
+         * glibc does.
          */
         libc::syscall(libc::SYS_ioctl, fd, request, argp) as std::os::raw::c_int
     }
@@ -145,7 +152,10 @@ pub fn close(fd: std::os::raw::c_int) -> io::Result<()> {
 
 /// A convenience wrapper around v4l2_ioctl.
 ///
-/// In case of errors, the last OS error will be reported, aka errno on Linux.
+/// In case of errors, the last OS error will be reported, aka errno on Linux, wrapped in an
+/// [`IoctlError`] which names the request and the device. Use [`errno`] to get the error code.
+/// Calls interrupted by a signal (EINTR) are restarted. EAGAIN, which non-blocking devices return
+/// whenever no buffer is ready, is reported as a plain OS error without context.
 ///
 /// # Arguments
 ///
@@ -187,15 +197,154 @@ pub unsafe fn ioctl(
     request: vidioc::_IOC_TYPE,
     argp: *mut std::os::raw::c_void,
 ) -> io::Result<()> {
-    let ret = detail::ioctl(fd, request, argp);
+    loop {
+        if detail::ioctl(fd, request, argp) != -1 {
+            return Ok(());
+        }
 
-    if ret == -1 {
-        Err(io::Error::last_os_error())
-    } else {
-        Ok(())
+        // errno must be read right away, before any other call may overwrite it
+        let err = io::Error::last_os_error();
+        return Err(match err.raw_os_error() {
+            // blocking ioctls (e.g. VIDIOC_DQBUF) may be interrupted by a signal, just try again
+            Some(libc::EINTR) => continue,
+            // non-blocking VIDIOC_DQBUF fails like this on every empty poll, keep it cheap
+            Some(libc::EAGAIN) => err,
+            _ => io::Error::new(
+                err.kind(),
+                IoctlError {
+                    request,
+                    path: fd_path(fd),
+                    source: err,
+                },
+            ),
+        });
     }
 }
 
+/// Returns the symbolic name of an errno value commonly returned by V4L2 drivers, e.g. "EINVAL"
+///
+/// # Arguments
+///
+/// * `code` - Raw OS error code
+pub fn errno_name(code: i32) -> Option<&'static str> {
+    Some(match code {
+        libc::EPERM => "EPERM",
+        libc::ENOENT => "ENOENT",
+        libc::EINTR => "EINTR",
+        libc::EIO => "EIO",
+        libc::ENXIO => "ENXIO",
+        libc::EBADF => "EBADF",
+        libc::EAGAIN => "EAGAIN",
+        libc::ENOMEM => "ENOMEM",
+        libc::EACCES => "EACCES",
+        libc::EFAULT => "EFAULT",
+        libc::EBUSY => "EBUSY",
+        libc::ENODEV => "ENODEV",
+        libc::EINVAL => "EINVAL",
+        libc::ENOSPC => "ENOSPC",
+        libc::ENOTTY => "ENOTTY",
+        libc::EPIPE => "EPIPE",
+        libc::ERANGE => "ERANGE",
+        libc::ENOSYS => "ENOSYS",
+        libc::ENOLINK => "ENOLINK",
+        libc::EOVERFLOW => "EOVERFLOW",
+        libc::EOPNOTSUPP => "EOPNOTSUPP",
+        libc::ETIMEDOUT => "ETIMEDOUT",
+        _ => return None,
+    })
+}
+
+/// Context of a failed `ioctl`, carried by the errors it returns
+///
+/// The io::Error returned by `ioctl` keeps the kind of the original OS error, its message names
+/// the request, the errno and the device node, e.g.
+/// "VIDIOC_REQBUFS on /dev/video0 failed: EINVAL: Invalid argument (os error 22)".
+/// Since the error is wrapped, `raw_os_error` returns None; use [`errno`] to get the OS error code.
+#[derive(Debug)]
+pub struct IoctlError {
+    /// IO control code which failed
+    pub request: vidioc::_IOC_TYPE,
+    /// Device node the file descriptor refers to, if it could be resolved
+    pub path: Option<PathBuf>,
+    source: io::Error,
+}
+
+impl IoctlError {
+    /// Returns the OS error code reported by the driver
+    pub fn errno(&self) -> Option<i32> {
+        self.source.raw_os_error()
+    }
+
+    /// Returns the symbolic name of the request, e.g. "VIDIOC_REQBUFS"
+    pub fn name(&self) -> Option<&'static str> {
+        vidioc::name(self.request)
+    }
+}
+
+impl fmt::Display for IoctlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "ioctl {:#x}", self.request)?,
+        }
+        if let Some(path) = &self.path {
+            write!(f, " on {}", path.display())?;
+        }
+        match self.errno().and_then(errno_name) {
+            Some(errno) => write!(f, " failed: {}: {}", errno, self.source),
+            None => write!(f, " failed: {}", self.source),
+        }
+    }
+}
+
+impl std::error::Error for IoctlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Returns the OS error code of an error, looking through the context added by `ioctl`
+///
+/// # Arguments
+///
+/// * `err` - Error returned by one of the wrappers in this module or a wrapper built on top of them
+///
+/// # Example
+///
+/// ```no_run
+/// use std::mem;
+///
+/// use v4l::v4l_sys::*;
+/// use v4l::v4l2;
+///
+/// let fd = v4l2::open("/dev/video0", libc::O_RDWR).unwrap();
+/// let mut v4l2_reqbufs: v4l2_requestbuffers = unsafe { mem::zeroed() };
+/// let res = unsafe {
+///     v4l2::ioctl(
+///         fd,
+///         v4l2::vidioc::VIDIOC_REQBUFS,
+///         &mut v4l2_reqbufs as *mut _ as *mut std::os::raw::c_void,
+///     )
+/// };
+/// if let Err(e) = res {
+///     if v4l2::errno(&e) == Some(libc::EINVAL) {
+///         eprintln!("memory type not supported: {}", e);
+///     }
+/// }
+/// ```
+pub fn errno(err: &io::Error) -> Option<i32> {
+    err.raw_os_error().or_else(|| {
+        err.get_ref()?
+            .downcast_ref::<IoctlError>()
+            .and_then(IoctlError::errno)
+    })
+}
+
+/// Resolves the device node a file descriptor refers to
+fn fd_path(fd: std::os::raw::c_int) -> Option<PathBuf> {
+    std::fs::read_link(format!("/proc/self/fd/{}", fd)).ok()
+}
+
 /// A convenience wrapper around v4l2_mmap.
 ///
 /// In case of errors, the last OS error will be reported, aka errno on Linux.
@@ -244,7 +393,7 @@ pub unsafe fn mmap(
     offset: libc::off_t,
 ) -> io::Result<*mut std::os::raw::c_void> {
     let ret = detail::mmap(start, length, prot, flags, fd, offset);
-    if ret as usize == std::usize::MAX {
+    if ret == libc::MAP_FAILED {
         Err(io::Error::last_os_error())
     } else {
         Ok(ret)
@@ -297,3 +446,29 @@ pub unsafe fn munmap(start: *mut std::os::raw::c_void, length: usize) -> io::Res
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ioctl_error_context() {
+        let err = unsafe { ioctl(-1, vidioc::VIDIOC_QUERYCAP, std::ptr::null_mut()) }.unwrap_err();
+        assert_eq!(errno(&err), Some(libc::EBADF));
+        assert!(err.raw_os_error().is_none());
+
+        let msg = err.to_string();
+        assert!(
+            msg.starts_with("VIDIOC_QUERYCAP failed: EBADF: "),
+            "{}",
+            msg
+        );
+    }
+
+    #[test]
+    fn errno_of_plain_error() {
+        let err = io::Error::from_raw_os_error(libc::EPIPE);
+        assert_eq!(errno(&err), Some(libc::EPIPE));
+        assert_eq!(errno(&io::Error::from(io::ErrorKind::TimedOut)), None);
+    }
+}
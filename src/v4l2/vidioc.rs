@@ -76,19 +76,104 @@ macro_rules! _IOWR {
     };
 }
 
+// The size of struct v4l2_buffer, and thus the buffer ioctl codes, depend on the size of time_t.
+// 32-bit kernels accept both variants: the TIME32 codes for the old 32-bit timeval and the native
+// codes for a 64-bit time_t, which is also the only layout on 64-bit targets. The structures below
+// spell out both layouts, the codes without suffix pick the one matching the bindings.
+
+/// Timestamp of the VIDIOC_*_TIME32 buffer ioctls, see old_timeval32 in the kernel
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct v4l2_timeval32 {
+    pub tv_sec: i32,
+    pub tv_usec: i32,
+}
+
+/// Timestamp of the buffer ioctls with a 64-bit time_t, see __kernel_v4l2_timeval in the kernel
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Default, Copy, Clone)]
+pub struct v4l2_timeval64 {
+    pub tv_sec: i64,
+    pub tv_usec: i64,
+}
+
+/// struct v4l2_buffer with a 32-bit timestamp, see struct v4l2_buffer_time32 in the kernel
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct v4l2_buffer_time32 {
+    pub index: __u32,
+    pub type_: __u32,
+    pub bytesused: __u32,
+    pub flags: __u32,
+    pub field: __u32,
+    pub timestamp: v4l2_timeval32,
+    pub timecode: v4l2_timecode,
+    pub sequence: __u32,
+    pub memory: __u32,
+    pub m: v4l2_buffer__bindgen_ty_1,
+    pub length: __u32,
+    pub reserved2: __u32,
+    pub __bindgen_anon_1: v4l2_buffer__bindgen_ty_2,
+}
+
+/// struct v4l2_buffer with a 64-bit timestamp
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct v4l2_buffer_time64 {
+    pub index: __u32,
+    pub type_: __u32,
+    pub bytesused: __u32,
+    pub flags: __u32,
+    pub field: __u32,
+    pub timestamp: v4l2_timeval64,
+    pub timecode: v4l2_timecode,
+    pub sequence: __u32,
+    pub memory: __u32,
+    pub m: v4l2_buffer__bindgen_ty_1,
+    pub length: __u32,
+    pub reserved2: __u32,
+    pub __bindgen_anon_1: v4l2_buffer__bindgen_ty_2,
+}
+
+/// True if the timeval of the bindings, and thus their v4l2_buffer, uses a 64-bit time_t
+pub const TIME64: bool = std::mem::size_of::<timeval>() == std::mem::size_of::<v4l2_timeval64>();
+
+macro_rules! time_abi {
+    ($time64:expr, $time32:expr) => {
+        if TIME64 {
+            $time64
+        } else {
+            $time32
+        }
+    };
+}
+
+pub const VIDIOC_QUERYBUF_TIME32: _IOC_TYPE = _IOWR!(b'V', 9, v4l2_buffer_time32);
+pub const VIDIOC_QUERYBUF_TIME64: _IOC_TYPE = _IOWR!(b'V', 9, v4l2_buffer_time64);
+pub const VIDIOC_QBUF_TIME32: _IOC_TYPE = _IOWR!(b'V', 15, v4l2_buffer_time32);
+pub const VIDIOC_QBUF_TIME64: _IOC_TYPE = _IOWR!(b'V', 15, v4l2_buffer_time64);
+pub const VIDIOC_DQBUF_TIME32: _IOC_TYPE = _IOWR!(b'V', 17, v4l2_buffer_time32);
+pub const VIDIOC_DQBUF_TIME64: _IOC_TYPE = _IOWR!(b'V', 17, v4l2_buffer_time64);
+pub const VIDIOC_PREPARE_BUF_TIME32: _IOC_TYPE = _IOWR!(b'V', 93, v4l2_buffer_time32);
+pub const VIDIOC_PREPARE_BUF_TIME64: _IOC_TYPE = _IOWR!(b'V', 93, v4l2_buffer_time64);
+
 pub const VIDIOC_QUERYCAP: _IOC_TYPE = _IOR!(b'V', 0, v4l2_capability);
 pub const VIDIOC_RESERVED: _IOC_TYPE = _IO!(b'V', 1);
 pub const VIDIOC_ENUM_FMT: _IOC_TYPE = _IOWR!(b'V', 2, v4l2_fmtdesc);
 pub const VIDIOC_G_FMT: _IOC_TYPE = _IOWR!(b'V', 4, v4l2_format);
 pub const VIDIOC_S_FMT: _IOC_TYPE = _IOWR!(b'V', 5, v4l2_format);
 pub const VIDIOC_REQBUFS: _IOC_TYPE = _IOWR!(b'V', 8, v4l2_requestbuffers);
-pub const VIDIOC_QUERYBUF: _IOC_TYPE = _IOWR!(b'V', 9, v4l2_buffer);
+pub const VIDIOC_QUERYBUF: _IOC_TYPE = time_abi!(VIDIOC_QUERYBUF_TIME64, VIDIOC_QUERYBUF_TIME32);
 pub const VIDIOC_G_FBUF: _IOC_TYPE = _IOR!(b'V', 10, v4l2_framebuffer);
 pub const VIDIOC_S_FBUF: _IOC_TYPE = _IOW!(b'V', 11, v4l2_framebuffer);
 pub const VIDIOC_OVERLAY: _IOC_TYPE = _IOW!(b'V', 14, std::os::raw::c_int);
-pub const VIDIOC_QBUF: _IOC_TYPE = _IOWR!(b'V', 15, v4l2_buffer);
+pub const VIDIOC_QBUF: _IOC_TYPE = time_abi!(VIDIOC_QBUF_TIME64, VIDIOC_QBUF_TIME32);
 pub const VIDIOC_EXPBUF: _IOC_TYPE = _IOWR!(b'V', 16, v4l2_exportbuffer);
-pub const VIDIOC_DQBUF: _IOC_TYPE = _IOWR!(b'V', 17, v4l2_buffer);
+pub const VIDIOC_DQBUF: _IOC_TYPE = time_abi!(VIDIOC_DQBUF_TIME64, VIDIOC_DQBUF_TIME32);
 pub const VIDIOC_STREAMON: _IOC_TYPE = _IOW!(b'V', 18, std::os::raw::c_int);
 pub const VIDIOC_STREAMOFF: _IOC_TYPE = _IOW!(b'V', 19, std::os::raw::c_int);
 pub const VIDIOC_G_PARM: _IOC_TYPE = _IOWR!(b'V', 21, v4l2_streamparm);
@@ -139,3 +224,181 @@ pub const VIDIOC_ENUM_FRAMEINTERVALS: _IOC_TYPE = _IOWR!(b'V', 75, v4l2_frmivale
 pub const VIDIOC_G_ENC_INDEX: _IOC_TYPE = _IOR!(b'V', 76, v4l2_enc_idx);
 pub const VIDIOC_ENCODER_CMD: _IOC_TYPE = _IOWR!(b'V', 77, v4l2_encoder_cmd);
 pub const VIDIOC_TRY_ENCODER_CMD: _IOC_TYPE = _IOWR!(b'V', 78, v4l2_encoder_cmd);
+pub const VIDIOC_S_DV_TIMINGS: _IOC_TYPE = _IOWR!(b'V', 87, v4l2_dv_timings);
+pub const VIDIOC_G_DV_TIMINGS: _IOC_TYPE = _IOWR!(b'V', 88, v4l2_dv_timings);
+pub const VIDIOC_DQEVENT: _IOC_TYPE = _IOR!(b'V', 89, v4l2_event);
+pub const VIDIOC_SUBSCRIBE_EVENT: _IOC_TYPE = _IOW!(b'V', 90, v4l2_event_subscription);
+pub const VIDIOC_UNSUBSCRIBE_EVENT: _IOC_TYPE = _IOW!(b'V', 91, v4l2_event_subscription);
+pub const VIDIOC_CREATE_BUFS: _IOC_TYPE = _IOWR!(b'V', 92, v4l2_create_buffers);
+pub const VIDIOC_PREPARE_BUF: _IOC_TYPE =
+    time_abi!(VIDIOC_PREPARE_BUF_TIME64, VIDIOC_PREPARE_BUF_TIME32);
+pub const VIDIOC_G_SELECTION: _IOC_TYPE = _IOWR!(b'V', 94, v4l2_selection);
+pub const VIDIOC_S_SELECTION: _IOC_TYPE = _IOWR!(b'V', 95, v4l2_selection);
+pub const VIDIOC_DECODER_CMD: _IOC_TYPE = _IOWR!(b'V', 96, v4l2_decoder_cmd);
+pub const VIDIOC_TRY_DECODER_CMD: _IOC_TYPE = _IOWR!(b'V', 97, v4l2_decoder_cmd);
+pub const VIDIOC_QUERY_DV_TIMINGS: _IOC_TYPE = _IOR!(b'V', 99, v4l2_dv_timings);
+pub const VIDIOC_QUERY_EXT_CTRL: _IOC_TYPE = _IOWR!(b'V', 103, v4l2_query_ext_ctrl);
+
+// linux uvcvideo.h
+
+/// Query of a control of a UVC extension unit
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct uvc_xu_control_query {
+    pub unit: __u8,
+    pub selector: __u8,
+    /// Request code, see the UVC specification A.8
+    pub query: __u8,
+    pub size: __u16,
+    pub data: *mut __u8,
+}
+
+pub const UVCIOC_CTRL_QUERY: _IOC_TYPE = _IOWR!(b'u', 0x21, uvc_xu_control_query);
+
+// linux media.h
+pub const MEDIA_IOC_SETUP_LINK: _IOC_TYPE = _IOWR!(b'|', 0x03, media_link_desc);
+pub const MEDIA_IOC_G_TOPOLOGY: _IOC_TYPE = _IOWR!(b'|', 0x04, media_v2_topology);
+pub const MEDIA_IOC_REQUEST_ALLOC: _IOC_TYPE = _IOR!(b'|', 0x05, std::os::raw::c_int);
+pub const MEDIA_REQUEST_IOC_QUEUE: _IOC_TYPE = _IO!(b'|', 0x80);
+pub const MEDIA_REQUEST_IOC_REINIT: _IOC_TYPE = _IO!(b'|', 0x81);
+
+// linux v4l2-subdev.h
+pub const VIDIOC_SUBDEV_G_FMT: _IOC_TYPE = _IOWR!(b'V', 4, v4l2_subdev_format);
+pub const VIDIOC_SUBDEV_S_FMT: _IOC_TYPE = _IOWR!(b'V', 5, v4l2_subdev_format);
+pub const VIDIOC_SUBDEV_G_SELECTION: _IOC_TYPE = _IOWR!(b'V', 61, v4l2_subdev_selection);
+pub const VIDIOC_SUBDEV_S_SELECTION: _IOC_TYPE = _IOWR!(b'V', 62, v4l2_subdev_selection);
+pub const VIDIOC_SUBDEV_G_EDID: _IOC_TYPE = _IOWR!(b'V', 40, v4l2_edid);
+pub const VIDIOC_SUBDEV_S_EDID: _IOC_TYPE = _IOWR!(b'V', 41, v4l2_edid);
+pub const VIDIOC_SUBDEV_S_DV_TIMINGS: _IOC_TYPE = _IOWR!(b'V', 87, v4l2_dv_timings);
+pub const VIDIOC_SUBDEV_G_DV_TIMINGS: _IOC_TYPE = _IOWR!(b'V', 88, v4l2_dv_timings);
+pub const VIDIOC_SUBDEV_ENUM_DV_TIMINGS: _IOC_TYPE = _IOWR!(b'V', 98, v4l2_enum_dv_timings);
+pub const VIDIOC_SUBDEV_QUERY_DV_TIMINGS: _IOC_TYPE = _IOR!(b'V', 99, v4l2_dv_timings);
+
+/// Returns the name of an ioctl request code, e.g. "VIDIOC_REQBUFS"
+///
+/// Used to give context to the errors returned by `v4l2::ioctl`, see `v4l2::IoctlError`.
+///
+/// # Arguments
+///
+/// * `request` - IO control code
+pub fn name(request: _IOC_TYPE) -> Option<&'static str> {
+    Some(match request {
+        VIDIOC_QUERYCAP => "VIDIOC_QUERYCAP",
+        VIDIOC_ENUM_FMT => "VIDIOC_ENUM_FMT",
+        VIDIOC_G_FMT => "VIDIOC_G_FMT",
+        VIDIOC_S_FMT => "VIDIOC_S_FMT",
+        VIDIOC_REQBUFS => "VIDIOC_REQBUFS",
+        VIDIOC_QUERYBUF => "VIDIOC_QUERYBUF",
+        VIDIOC_G_FBUF => "VIDIOC_G_FBUF",
+        VIDIOC_S_FBUF => "VIDIOC_S_FBUF",
+        VIDIOC_OVERLAY => "VIDIOC_OVERLAY",
+        VIDIOC_QBUF => "VIDIOC_QBUF",
+        VIDIOC_EXPBUF => "VIDIOC_EXPBUF",
+        VIDIOC_DQBUF => "VIDIOC_DQBUF",
+        VIDIOC_STREAMON => "VIDIOC_STREAMON",
+        VIDIOC_STREAMOFF => "VIDIOC_STREAMOFF",
+        VIDIOC_G_PARM => "VIDIOC_G_PARM",
+        VIDIOC_S_PARM => "VIDIOC_S_PARM",
+        VIDIOC_G_STD => "VIDIOC_G_STD",
+        VIDIOC_S_STD => "VIDIOC_S_STD",
+        VIDIOC_ENUMSTD => "VIDIOC_ENUMSTD",
+        VIDIOC_ENUMINPUT => "VIDIOC_ENUMINPUT",
+        VIDIOC_G_CTRL => "VIDIOC_G_CTRL",
+        VIDIOC_S_CTRL => "VIDIOC_S_CTRL",
+        VIDIOC_G_TUNER => "VIDIOC_G_TUNER",
+        VIDIOC_S_TUNER => "VIDIOC_S_TUNER",
+        VIDIOC_G_AUDIO => "VIDIOC_G_AUDIO",
+        VIDIOC_S_AUDIO => "VIDIOC_S_AUDIO",
+        VIDIOC_QUERYCTRL => "VIDIOC_QUERYCTRL",
+        VIDIOC_QUERYMENU => "VIDIOC_QUERYMENU",
+        VIDIOC_G_INPUT => "VIDIOC_G_INPUT",
+        VIDIOC_S_INPUT => "VIDIOC_S_INPUT",
+        VIDIOC_G_EDID => "VIDIOC_G_EDID",
+        VIDIOC_S_EDID => "VIDIOC_S_EDID",
+        VIDIOC_G_OUTPUT => "VIDIOC_G_OUTPUT",
+        VIDIOC_S_OUTPUT => "VIDIOC_S_OUTPUT",
+        VIDIOC_ENUMOUTPUT => "VIDIOC_ENUMOUTPUT",
+        VIDIOC_G_AUDOUT => "VIDIOC_G_AUDOUT",
+        VIDIOC_S_AUDOUT => "VIDIOC_S_AUDOUT",
+        VIDIOC_G_MODULATOR => "VIDIOC_G_MODULATOR",
+        VIDIOC_S_MODULATOR => "VIDIOC_S_MODULATOR",
+        VIDIOC_G_FREQUENCY => "VIDIOC_G_FREQUENCY",
+        VIDIOC_S_FREQUENCY => "VIDIOC_S_FREQUENCY",
+        VIDIOC_CROPCAP => "VIDIOC_CROPCAP",
+        VIDIOC_G_CROP => "VIDIOC_G_CROP",
+        VIDIOC_S_CROP => "VIDIOC_S_CROP",
+        VIDIOC_G_JPEGCOMP => "VIDIOC_G_JPEGCOMP",
+        VIDIOC_S_JPEGCOMP => "VIDIOC_S_JPEGCOMP",
+        VIDIOC_QUERYSTD => "VIDIOC_QUERYSTD",
+        VIDIOC_TRY_FMT => "VIDIOC_TRY_FMT",
+        VIDIOC_ENUMAUDIO => "VIDIOC_ENUMAUDIO",
+        VIDIOC_ENUMAUDOUT => "VIDIOC_ENUMAUDOUT",
+        VIDIOC_G_PRIORITY => "VIDIOC_G_PRIORITY",
+        VIDIOC_S_PRIORITY => "VIDIOC_S_PRIORITY",
+        VIDIOC_G_SLICED_VBI_CAP => "VIDIOC_G_SLICED_VBI_CAP",
+        VIDIOC_LOG_STATUS => "VIDIOC_LOG_STATUS",
+        VIDIOC_G_EXT_CTRLS => "VIDIOC_G_EXT_CTRLS",
+        VIDIOC_S_EXT_CTRLS => "VIDIOC_S_EXT_CTRLS",
+        VIDIOC_TRY_EXT_CTRLS => "VIDIOC_TRY_EXT_CTRLS",
+        VIDIOC_ENUM_FRAMESIZES => "VIDIOC_ENUM_FRAMESIZES",
+        VIDIOC_ENUM_FRAMEINTERVALS => "VIDIOC_ENUM_FRAMEINTERVALS",
+        VIDIOC_G_ENC_INDEX => "VIDIOC_G_ENC_INDEX",
+        VIDIOC_ENCODER_CMD => "VIDIOC_ENCODER_CMD",
+        VIDIOC_TRY_ENCODER_CMD => "VIDIOC_TRY_ENCODER_CMD",
+        VIDIOC_S_DV_TIMINGS => "VIDIOC_S_DV_TIMINGS",
+        VIDIOC_G_DV_TIMINGS => "VIDIOC_G_DV_TIMINGS",
+        VIDIOC_DQEVENT => "VIDIOC_DQEVENT",
+        VIDIOC_SUBSCRIBE_EVENT => "VIDIOC_SUBSCRIBE_EVENT",
+        VIDIOC_UNSUBSCRIBE_EVENT => "VIDIOC_UNSUBSCRIBE_EVENT",
+        VIDIOC_CREATE_BUFS => "VIDIOC_CREATE_BUFS",
+        VIDIOC_PREPARE_BUF => "VIDIOC_PREPARE_BUF",
+        VIDIOC_G_SELECTION => "VIDIOC_G_SELECTION",
+        VIDIOC_S_SELECTION => "VIDIOC_S_SELECTION",
+        VIDIOC_DECODER_CMD => "VIDIOC_DECODER_CMD",
+        VIDIOC_TRY_DECODER_CMD => "VIDIOC_TRY_DECODER_CMD",
+        VIDIOC_QUERY_DV_TIMINGS => "VIDIOC_QUERY_DV_TIMINGS",
+        VIDIOC_QUERY_EXT_CTRL => "VIDIOC_QUERY_EXT_CTRL",
+        UVCIOC_CTRL_QUERY => "UVCIOC_CTRL_QUERY",
+        MEDIA_IOC_SETUP_LINK => "MEDIA_IOC_SETUP_LINK",
+        MEDIA_IOC_G_TOPOLOGY => "MEDIA_IOC_G_TOPOLOGY",
+        MEDIA_IOC_REQUEST_ALLOC => "MEDIA_IOC_REQUEST_ALLOC",
+        MEDIA_REQUEST_IOC_QUEUE => "MEDIA_REQUEST_IOC_QUEUE",
+        MEDIA_REQUEST_IOC_REINIT => "MEDIA_REQUEST_IOC_REINIT",
+        VIDIOC_SUBDEV_G_FMT => "VIDIOC_SUBDEV_G_FMT",
+        VIDIOC_SUBDEV_S_FMT => "VIDIOC_SUBDEV_S_FMT",
+        VIDIOC_SUBDEV_G_SELECTION => "VIDIOC_SUBDEV_G_SELECTION",
+        VIDIOC_SUBDEV_S_SELECTION => "VIDIOC_SUBDEV_S_SELECTION",
+        // VIDIOC_SUBDEV_G_EDID and VIDIOC_SUBDEV_S_EDID share the codes of VIDIOC_G_EDID and
+        // VIDIOC_S_EDID
+        // VIDIOC_SUBDEV_S_DV_TIMINGS, VIDIOC_SUBDEV_G_DV_TIMINGS and
+        // VIDIOC_SUBDEV_QUERY_DV_TIMINGS share the codes of their video node counterparts
+        VIDIOC_SUBDEV_ENUM_DV_TIMINGS => "VIDIOC_SUBDEV_ENUM_DV_TIMINGS",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem;
+
+    #[test]
+    fn buffer_layout_matches_ioctls() {
+        let size = if TIME64 {
+            mem::size_of::<v4l2_buffer_time64>()
+        } else {
+            mem::size_of::<v4l2_buffer_time32>()
+        };
+        assert_eq!(mem::size_of::<v4l2_buffer>(), size);
+        assert_eq!(
+            (VIDIOC_QBUF >> _IOC_SIZESHIFT) as usize & ((1 << _IOC_SIZEBITS) - 1),
+            size
+        );
+
+        assert_eq!(mem::size_of::<v4l2_timeval32>(), 8);
+        assert_eq!(mem::size_of::<v4l2_timeval64>(), 16);
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(mem::size_of::<v4l2_buffer_time64>(), 88);
+    }
+}
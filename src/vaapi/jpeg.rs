@@ -0,0 +1,449 @@
+//! Minimal parser for baseline JPEG headers, as needed to program a hardware decoder
+
+use std::io;
+use std::ops::Range;
+
+/// Frame component as declared in the SOF segment
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Component {
+    pub id: u8,
+    pub h: u8,
+    pub v: u8,
+    pub tq: u8,
+}
+
+/// Component of the first scan as declared in the SOS segment
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ScanComponent {
+    pub selector: u8,
+    pub dc: u8,
+    pub ac: u8,
+}
+
+/// Huffman table as declared in the DHT segment
+#[derive(Debug, Clone)]
+pub(crate) struct HuffmanTable {
+    pub bits: [u8; 16],
+    pub values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(bits: &[u8], values: &[u8]) -> Self {
+        let mut table = HuffmanTable {
+            bits: [0; 16],
+            values: values.to_vec(),
+        };
+        table.bits.copy_from_slice(bits);
+        table
+    }
+}
+
+/// Tables from Annex K.3 of the JPEG specification
+///
+/// Most webcams strip the DHT segment from MJPG frames and rely on these.
+mod default {
+    pub const DC_LUMA_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+    pub const DC_CHROMA_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+    pub const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+    pub const AC_LUMA_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+    pub const AC_LUMA_VALUES: [u8; 162] = [
+        0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61,
+        0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52,
+        0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25,
+        0x26, 0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45,
+        0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64,
+        0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83,
+        0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99,
+        0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6,
+        0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3,
+        0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8,
+        0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ];
+
+    pub const AC_CHROMA_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+    pub const AC_CHROMA_VALUES: [u8; 162] = [
+        0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61,
+        0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33,
+        0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18,
+        0x19, 0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44,
+        0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63,
+        0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a,
+        0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97,
+        0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4,
+        0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca,
+        0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7,
+        0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ];
+}
+
+/// Everything a hardware decoder needs to know about a baseline JPEG image
+#[derive(Debug, Clone)]
+pub(crate) struct Header {
+    pub width: u16,
+    pub height: u16,
+    pub components: Vec<Component>,
+    /// quantization tables in zigzag order
+    pub quant: [Option<[u8; 64]>; 4],
+    pub dc: [Option<HuffmanTable>; 2],
+    pub ac: [Option<HuffmanTable>; 2],
+    pub restart_interval: u16,
+    pub scan: Vec<ScanComponent>,
+    /// entropy coded data of the first scan
+    pub data: Range<usize>,
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid JPEG: {}", msg))
+}
+
+impl Header {
+    /// Parses the headers up to and including the first scan
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - JPEG image, e.g. an MJPG frame
+    pub fn parse(data: &[u8]) -> io::Result<Self> {
+        if !data.starts_with(&[0xff, 0xd8]) {
+            return Err(invalid("missing SOI marker"));
+        }
+
+        let mut header = Header {
+            width: 0,
+            height: 0,
+            components: Vec::new(),
+            quant: [None; 4],
+            dc: [None, None],
+            ac: [None, None],
+            restart_interval: 0,
+            scan: Vec::new(),
+            data: 0..0,
+        };
+
+        let mut pos = 2;
+        loop {
+            // markers may be preceded by any number of fill bytes
+            while data.get(pos) == Some(&0xff) && data.get(pos + 1) == Some(&0xff) {
+                pos += 1;
+            }
+            let marker = match data.get(pos..pos + 4) {
+                Some([0xff, marker, _, _]) => *marker,
+                _ => return Err(invalid("truncated headers")),
+            };
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let segment = data
+                .get(pos + 4..pos + 2 + len)
+                .filter(|_| len >= 2)
+                .ok_or_else(|| invalid("truncated segment"))?;
+            pos += 2 + len;
+
+            match marker {
+                // baseline and extended sequential DCT, huffman coding
+                0xc0 | 0xc1 => header.parse_frame(segment)?,
+                0xc2..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+                    return Err(invalid("only baseline images are supported"))
+                }
+                0xc4 => header.parse_huffman(segment)?,
+                0xdb => header.parse_quant(segment)?,
+                0xdd => {
+                    let interval = segment.get(..2).ok_or_else(|| invalid("truncated DRI"))?;
+                    header.restart_interval = u16::from_be_bytes([interval[0], interval[1]]);
+                }
+                0xda => {
+                    header.parse_scan(segment)?;
+                    header.data = pos..scan_end(data, pos);
+                    break;
+                }
+                0xd9 => return Err(invalid("no scan before EOI")),
+                // APPn, COM and anything else we do not care about
+                _ => {}
+            }
+        }
+
+        if header.components.is_empty() {
+            return Err(invalid("missing SOF segment"));
+        }
+        header.apply_default_tables();
+        Ok(header)
+    }
+
+    fn parse_frame(&mut self, segment: &[u8]) -> io::Result<()> {
+        let (params, components) = match segment {
+            [8, h0, h1, w0, w1, count, rest @ ..] if rest.len() >= *count as usize * 3 => {
+                ((*h0, *h1, *w0, *w1), &rest[..*count as usize * 3])
+            }
+            [precision, ..] if *precision != 8 => {
+                return Err(invalid("only 8-bit samples are supported"))
+            }
+            _ => return Err(invalid("truncated SOF")),
+        };
+
+        self.height = u16::from_be_bytes([params.0, params.1]);
+        self.width = u16::from_be_bytes([params.2, params.3]);
+        self.components = components
+            .chunks(3)
+            .map(|c| Component {
+                id: c[0],
+                h: c[1] >> 4,
+                v: c[1] & 0xf,
+                tq: c[2] & 0x3,
+            })
+            .collect();
+
+        if self.width == 0 || self.height == 0 {
+            return Err(invalid("unsupported image size"));
+        }
+        Ok(())
+    }
+
+    fn parse_quant(&mut self, mut segment: &[u8]) -> io::Result<()> {
+        while let Some((&info, rest)) = segment.split_first() {
+            if info >> 4 != 0 {
+                return Err(invalid("only 8-bit quantization tables are supported"));
+            }
+            let values = rest.get(..64).ok_or_else(|| invalid("truncated DQT"))?;
+            let mut table = [0; 64];
+            table.copy_from_slice(values);
+            self.quant[(info & 0x3) as usize] = Some(table);
+            segment = &rest[64..];
+        }
+        Ok(())
+    }
+
+    fn parse_huffman(&mut self, mut segment: &[u8]) -> io::Result<()> {
+        while let Some((&info, rest)) = segment.split_first() {
+            let bits = rest.get(..16).ok_or_else(|| invalid("truncated DHT"))?;
+            let count = bits.iter().map(|&n| n as usize).sum::<usize>();
+            let values = rest
+                .get(16..16 + count)
+                .ok_or_else(|| invalid("truncated DHT"))?;
+
+            let index = (info & 0xf) as usize;
+            let (tables, max) = match info >> 4 {
+                0 => (&mut self.dc, 12),
+                _ => (&mut self.ac, 162),
+            };
+            if index > 1 || count > max {
+                return Err(invalid("unsupported huffman table"));
+            }
+            tables[index] = Some(HuffmanTable::new(bits, values));
+            segment = &rest[16 + count..];
+        }
+        Ok(())
+    }
+
+    fn parse_scan(&mut self, segment: &[u8]) -> io::Result<()> {
+        let count = *segment.first().ok_or_else(|| invalid("truncated SOS"))? as usize;
+        let components = segment
+            .get(1..1 + count * 2)
+            .ok_or_else(|| invalid("truncated SOS"))?;
+
+        self.scan = components
+            .chunks(2)
+            .map(|c| ScanComponent {
+                selector: c[0],
+                dc: c[1] >> 4,
+                ac: c[1] & 0xf,
+            })
+            .collect();
+
+        if self.scan.is_empty() || self.scan.len() > 4 {
+            return Err(invalid("unsupported number of scan components"));
+        }
+        if self.scan.iter().any(|c| c.dc > 1 || c.ac > 1) {
+            return Err(invalid("unsupported huffman table selector"));
+        }
+        Ok(())
+    }
+
+    fn apply_default_tables(&mut self) {
+        if self.dc[0].is_none() {
+            self.dc[0] = Some(HuffmanTable::new(
+                &default::DC_LUMA_BITS,
+                &default::DC_VALUES,
+            ));
+        }
+        if self.dc[1].is_none() {
+            self.dc[1] = Some(HuffmanTable::new(
+                &default::DC_CHROMA_BITS,
+                &default::DC_VALUES,
+            ));
+        }
+        if self.ac[0].is_none() {
+            self.ac[0] = Some(HuffmanTable::new(
+                &default::AC_LUMA_BITS,
+                &default::AC_LUMA_VALUES,
+            ));
+        }
+        if self.ac[1].is_none() {
+            self.ac[1] = Some(HuffmanTable::new(
+                &default::AC_CHROMA_BITS,
+                &default::AC_CHROMA_VALUES,
+            ));
+        }
+    }
+
+    /// Returns the maximum horizontal and vertical sampling factors
+    pub fn max_sampling(&self) -> (u8, u8) {
+        self.components
+            .iter()
+            .fold((1, 1), |(h, v), c| (h.max(c.h), v.max(c.v)))
+    }
+
+    /// Returns the number of MCUs in the first scan
+    pub fn num_mcus(&self) -> u32 {
+        let (h, v) = match self.scan.len() {
+            // non-interleaved scans consist of single 8x8 blocks
+            1 => (1, 1),
+            _ => self.max_sampling(),
+        };
+        let (width, height) = (self.width as u32, self.height as u32);
+        let (mcu_width, mcu_height) = (h as u32 * 8, v as u32 * 8);
+        (width / mcu_width + (width % mcu_width != 0) as u32)
+            * (height / mcu_height + (height % mcu_height != 0) as u32)
+    }
+}
+
+/// Returns the end of the entropy coded data starting at a given position
+fn scan_end(data: &[u8], start: usize) -> usize {
+    let mut pos = start;
+    while pos + 1 < data.len() {
+        // stuffed zero bytes and restart markers are part of the data
+        if data[pos] == 0xff && data[pos + 1] != 0 && !(0xd0..=0xd7).contains(&data[pos + 1]) {
+            return pos;
+        }
+        pos += 1;
+    }
+    data.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(marker: u8, payload: &[u8]) -> Vec<u8> {
+        let mut segment = vec![0xff, marker];
+        segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+        segment.extend_from_slice(payload);
+        segment
+    }
+
+    /// Builds a 4:2:0 image of 100x50 pixels without DHT, as sent by most webcams
+    fn image(restart_interval: Option<u16>, data: &[u8]) -> Vec<u8> {
+        let mut image = vec![0xff, 0xd8];
+        image.extend(segment(0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0"));
+
+        let mut dqt = vec![0x00];
+        dqt.extend(1..=64);
+        dqt.push(0x01);
+        dqt.extend(vec![7; 64]);
+        image.extend(segment(0xdb, &dqt));
+
+        image.extend(segment(
+            0xc0,
+            &[8, 0, 50, 0, 100, 3, 1, 0x22, 0, 2, 0x11, 1, 3, 0x11, 1],
+        ));
+        if let Some(interval) = restart_interval {
+            image.extend(segment(0xdd, &interval.to_be_bytes()));
+        }
+        image.extend(segment(0xda, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]));
+        image.extend_from_slice(data);
+        image.extend_from_slice(&[0xff, 0xd9]);
+        image
+    }
+
+    #[test]
+    fn parse_baseline() {
+        let data = [0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56];
+        let image = image(Some(4), &data);
+        let header = Header::parse(&image).unwrap();
+
+        assert_eq!((header.width, header.height), (100, 50));
+        assert_eq!(header.components.len(), 3);
+        let luma = header.components[0];
+        assert_eq!((luma.id, luma.h, luma.v, luma.tq), (1, 2, 2, 0));
+        let cr = header.components[2];
+        assert_eq!((cr.id, cr.h, cr.v, cr.tq), (3, 1, 1, 1));
+
+        let quant = header.quant[0].unwrap();
+        assert_eq!((quant[0], quant[63]), (1, 64));
+        assert_eq!(header.quant[1].unwrap(), [7; 64]);
+        assert!(header.quant[2].is_none());
+
+        assert_eq!(header.restart_interval, 4);
+        assert_eq!(header.scan.len(), 3);
+        assert_eq!((header.scan[1].selector, header.scan[1].dc), (2, 1));
+        assert_eq!(header.scan[1].ac, 1);
+
+        // stuffed bytes and restart markers belong to the scan, EOI does not
+        assert_eq!(&image[header.data.clone()], &data);
+    }
+
+    #[test]
+    fn parse_default_tables() {
+        let header = Header::parse(&image(None, &[0x00])).unwrap();
+
+        assert_eq!(header.restart_interval, 0);
+        let dc = header.dc[1].as_ref().unwrap();
+        assert_eq!(dc.bits, default::DC_CHROMA_BITS);
+        assert_eq!(dc.values, default::DC_VALUES);
+        let ac = header.ac[0].as_ref().unwrap();
+        assert_eq!(ac.bits, default::AC_LUMA_BITS);
+        assert_eq!(ac.values.len(), 162);
+    }
+
+    #[test]
+    fn parse_huffman_table() {
+        let mut image = image(None, &[0x00]);
+        let mut dht = vec![0x10];
+        dht.extend_from_slice(&[0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        dht.extend_from_slice(&[0x01, 0x00]);
+        let dht = segment(0xc4, &dht);
+        image.splice(2..2, dht);
+
+        let header = Header::parse(&image).unwrap();
+        let ac = header.ac[0].as_ref().unwrap();
+        assert_eq!(ac.bits[1], 2);
+        assert_eq!(ac.values, [0x01, 0x00]);
+        // tables not present in the image still fall back to the defaults
+        assert_eq!(header.ac[1].as_ref().unwrap().bits, default::AC_CHROMA_BITS);
+    }
+
+    #[test]
+    fn num_mcus() {
+        let mut header = Header::parse(&image(None, &[0x00])).unwrap();
+        assert_eq!(header.max_sampling(), (2, 2));
+        // 16x16 MCUs: 7 columns (the last one partial) and 4 rows
+        assert_eq!(header.num_mcus(), 28);
+
+        header.scan.truncate(1);
+        assert_eq!(header.num_mcus(), 13 * 7);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let image = image(None, &[0x00]);
+        let kind = |data: &[u8]| Header::parse(data).unwrap_err().kind();
+
+        assert_eq!(kind(&image[2..]), io::ErrorKind::InvalidData);
+        assert_eq!(kind(&image[..30]), io::ErrorKind::InvalidData);
+        assert_eq!(
+            kind(&[0xff, 0xd8, 0xff, 0xd9, 0, 2]),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut progressive = image.clone();
+        let sof = progressive
+            .windows(2)
+            .position(|w| w == [0xff, 0xc0])
+            .unwrap();
+        progressive[sof + 1] = 0xc2;
+        let err = Header::parse(&progressive).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("baseline"));
+
+        let mut precision = image;
+        precision[sof + 4] = 12;
+        assert_eq!(kind(&precision), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,633 @@
+//! VA-API interop for hardware accelerated decoding
+//!
+//! Many 4K webcams only deliver high frame rates as MJPG, and decoding those frames on the CPU
+//! easily becomes the bottleneck of a pipeline. This module hands the frames to the GPU through
+//! VA-API instead and returns the decoded surfaces, which can be exported as dmabufs and passed on
+//! to a renderer or encoder without copying the image data.
+//!
+//! Raw frames exported from a capture stream (see `mmap::Stream::export`) can be imported as
+//! surfaces as well, e.g. to feed them to VA-API video processing or encoding.
+//!
+//! Only baseline JPEG decoding is implemented, decoding H.264 requires a full bitstream parser
+//! and is not supported yet.
+//!
+//! This module is only available with the `vaapi` feature.
+
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+use std::{ffi, io, mem, ptr};
+
+use va_sys::*;
+
+use crate::format::{Format, FourCC};
+use crate::share::DmaBuf;
+
+mod jpeg;
+use jpeg::Header;
+
+/// Converts a VA status code into a result
+fn check(status: VAStatus, what: &str) -> io::Result<()> {
+    if status == VA_STATUS_SUCCESS as VAStatus {
+        return Ok(());
+    }
+
+    let kind = match status as u32 {
+        VA_STATUS_ERROR_ALLOCATION_FAILED => io::ErrorKind::OutOfMemory,
+        VA_STATUS_ERROR_UNSUPPORTED_PROFILE
+        | VA_STATUS_ERROR_UNSUPPORTED_ENTRYPOINT
+        | VA_STATUS_ERROR_UNSUPPORTED_RT_FORMAT
+        | VA_STATUS_ERROR_UNSUPPORTED_BUFFERTYPE
+        | VA_STATUS_ERROR_UNSUPPORTED_MEMORY_TYPE
+        | VA_STATUS_ERROR_INVALID_PARAMETER
+        | VA_STATUS_ERROR_INVALID_IMAGE_FORMAT => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::InvalidData,
+    };
+    let msg = unsafe { ffi::CStr::from_ptr(vaErrorStr(status)) };
+    Err(io::Error::new(
+        kind,
+        format!("{} failed: {}", what, msg.to_string_lossy()),
+    ))
+}
+
+/// VA display on top of a DRM render node
+pub struct Display {
+    dpy: VADisplay,
+    // the display uses the file descriptor until it is terminated
+    _node: File,
+}
+
+impl Display {
+    /// Returns a display on the first render node which can be initialized
+    pub fn new() -> io::Result<Self> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "no DRM render node found");
+        for minor in 128..192 {
+            match Display::open(format!("/dev/dri/renderD{}", minor)) {
+                Ok(display) => return Ok(display),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+                Err(e) => last = e,
+            }
+        }
+
+        Err(last)
+    }
+
+    /// Returns a display on a given DRM render node
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the node, e.g. /dev/dri/renderD128
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let node = OpenOptions::new().read(true).write(true).open(path)?;
+
+        let dpy = unsafe { vaGetDisplayDRM(node.as_raw_fd()) };
+        if dpy.is_null() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "vaGetDisplayDRM failed",
+            ));
+        }
+
+        let (mut major, mut minor) = (0, 0);
+        if let Err(e) = check(
+            unsafe { vaInitialize(dpy, &mut major, &mut minor) },
+            "vaInitialize",
+        ) {
+            unsafe {
+                vaTerminate(dpy);
+            }
+            return Err(e);
+        }
+
+        Ok(Display { dpy, _node: node })
+    }
+
+    /// Imports a dmabuf holding a raw frame as surface
+    ///
+    /// Supported formats are NV12, YUYV and UYVY.
+    ///
+    /// # Arguments
+    ///
+    /// * `buf` - Exported buffer, which must stay alive as long as the surface is used
+    /// * `fmt` - Format of the frame in the buffer
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::buffer::Type;
+    /// use v4l::io::traits::{CaptureStream, Stream};
+    /// use v4l::prelude::*;
+    /// use v4l::vaapi::Display;
+    /// use v4l::video::Capture;
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let fmt = dev.format().unwrap();
+    /// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+    /// let display = Display::new().unwrap();
+    ///
+    /// let bufs: Vec<_> = (0..4).map(|i| stream.export(i).unwrap()).collect();
+    /// let surfaces: Vec<_> = bufs
+    ///     .iter()
+    ///     .map(|buf| display.import(buf, &fmt).unwrap())
+    ///     .collect();
+    /// ```
+    pub fn import(&self, buf: &DmaBuf, fmt: &Format) -> io::Result<Surface<'_>> {
+        let (va_fourcc, rt_format, planes) = match &fmt.fourcc.repr {
+            b"NV12" => (b"NV12", VA_RT_FORMAT_YUV420, 2),
+            b"YUYV" => (b"YUY2", VA_RT_FORMAT_YUV422, 1),
+            b"UYVY" => (b"UYVY", VA_RT_FORMAT_YUV422, 1),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("cannot import {} frames", fmt.fourcc),
+                ))
+            }
+        };
+
+        let mut desc: VADRMPRIMESurfaceDescriptor = unsafe { mem::zeroed() };
+        desc.fourcc = FourCC::new(va_fourcc).into();
+        desc.width = fmt.width;
+        desc.height = fmt.height;
+        desc.num_objects = 1;
+        desc.objects[0].fd = buf.as_raw_fd();
+        desc.objects[0].size = buf.len() as u32;
+        desc.num_layers = 1;
+        desc.layers[0].drm_format = fmt.fourcc.into();
+        desc.layers[0].num_planes = planes;
+        for plane in 0..planes as usize {
+            desc.layers[0].offset[plane] = (plane as u32) * fmt.stride * fmt.height;
+            desc.layers[0].pitch[plane] = fmt.stride;
+        }
+
+        let mut attribs: [VASurfaceAttrib; 2] = unsafe { mem::zeroed() };
+        attribs[0].type_ = VASurfaceAttribType_VASurfaceAttribMemoryType;
+        attribs[0].flags = VA_SURFACE_ATTRIB_SETTABLE;
+        attribs[0].value.type_ = VAGenericValueType_VAGenericValueTypeInteger;
+        attribs[0].value.value.i = VA_SURFACE_ATTRIB_MEM_TYPE_DRM_PRIME_2 as i32;
+        attribs[1].type_ = VASurfaceAttribType_VASurfaceAttribExternalBufferDescriptor;
+        attribs[1].flags = VA_SURFACE_ATTRIB_SETTABLE;
+        attribs[1].value.type_ = VAGenericValueType_VAGenericValueTypePointer;
+        attribs[1].value.value.p = &mut desc as *mut _ as *mut std::os::raw::c_void;
+
+        self.create_surface(rt_format, fmt.width, fmt.height, &mut attribs)
+    }
+
+    fn create_surface(
+        &self,
+        rt_format: u32,
+        width: u32,
+        height: u32,
+        attribs: &mut [VASurfaceAttrib],
+    ) -> io::Result<Surface<'_>> {
+        let mut id = VA_INVALID_SURFACE;
+        check(
+            unsafe {
+                vaCreateSurfaces(
+                    self.dpy,
+                    rt_format,
+                    width,
+                    height,
+                    &mut id,
+                    1,
+                    attribs.as_mut_ptr(),
+                    attribs.len() as u32,
+                )
+            },
+            "vaCreateSurfaces",
+        )?;
+
+        Ok(Surface {
+            display: self,
+            id,
+            width,
+            height,
+        })
+    }
+}
+
+impl Drop for Display {
+    fn drop(&mut self) {
+        unsafe {
+            vaTerminate(self.dpy);
+        }
+    }
+}
+
+/// Image stored in GPU memory
+///
+/// The surface is destroyed on drop.
+pub struct Surface<'a> {
+    display: &'a Display,
+    id: VASurfaceID,
+    width: u32,
+    height: u32,
+}
+
+impl<'a> Surface<'a> {
+    /// Returns the VA surface ID, e.g. to use the surface with other VA-API calls
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Returns the width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Blocks until all pending operations on the surface are complete
+    pub fn sync(&self) -> io::Result<()> {
+        check(
+            unsafe { vaSyncSurface(self.display.dpy, self.id) },
+            "vaSyncSurface",
+        )
+    }
+
+    /// Exports the surface as one or more dmabufs
+    ///
+    /// Each plane is exported as a separate layer, which is what most consumers (e.g. EGL with
+    /// EGL_EXT_image_dma_buf_import) expect. Call `sync` first if the surface may still be
+    /// written to.
+    pub fn export(&self) -> io::Result<ExportedSurface> {
+        let mut desc: VADRMPRIMESurfaceDescriptor = unsafe { mem::zeroed() };
+        check(
+            unsafe {
+                vaExportSurfaceHandle(
+                    self.display.dpy,
+                    self.id,
+                    VA_SURFACE_ATTRIB_MEM_TYPE_DRM_PRIME_2,
+                    VA_EXPORT_SURFACE_READ_ONLY | VA_EXPORT_SURFACE_SEPARATE_LAYERS,
+                    &mut desc as *mut _ as *mut std::os::raw::c_void,
+                )
+            },
+            "vaExportSurfaceHandle",
+        )?;
+
+        // take ownership of the file descriptors first, so they are closed on error
+        let objects = desc.objects[..desc.num_objects as usize]
+            .iter()
+            .map(|obj| unsafe { DmaBuf::from_raw_fd(obj.fd as RawFd, obj.size as usize) })
+            .collect();
+
+        let layers = desc.layers[..desc.num_layers as usize]
+            .iter()
+            .map(|layer| Layer {
+                drm_format: FourCC::from(layer.drm_format),
+                planes: (0..layer.num_planes as usize)
+                    .map(|i| PlaneLayout {
+                        object: layer.object_index[i] as usize,
+                        offset: layer.offset[i],
+                        pitch: layer.pitch[i],
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(ExportedSurface {
+            fourcc: FourCC::from(desc.fourcc),
+            width: desc.width,
+            height: desc.height,
+            modifiers: desc.objects[..desc.num_objects as usize]
+                .iter()
+                .map(|obj| obj.drm_format_modifier)
+                .collect(),
+            objects,
+            layers,
+        })
+    }
+}
+
+impl<'a> Drop for Surface<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            vaDestroySurfaces(self.display.dpy, &mut self.id, 1);
+        }
+    }
+}
+
+/// Location of a plane within the exported dmabufs
+#[derive(Debug, Copy, Clone)]
+pub struct PlaneLayout {
+    /// index into `ExportedSurface::objects`
+    pub object: usize,
+    /// offset in bytes
+    pub offset: u32,
+    /// bytes per line
+    pub pitch: u32,
+}
+
+/// Single layer of an exported surface, e.g. the luma or chroma plane of NV12
+#[derive(Debug, Clone)]
+pub struct Layer {
+    /// DRM format code of the layer
+    pub drm_format: FourCC,
+    /// planes of the layer
+    pub planes: Vec<PlaneLayout>,
+}
+
+/// Surface exported as dmabufs
+pub struct ExportedSurface {
+    /// VA format code of the surface
+    pub fourcc: FourCC,
+    /// width in pixels
+    pub width: u32,
+    /// height in pixels
+    pub height: u32,
+    /// memory objects backing the surface
+    pub objects: Vec<DmaBuf>,
+    /// DRM format modifier of each object
+    pub modifiers: Vec<u64>,
+    /// layers of the surface
+    pub layers: Vec<Layer>,
+}
+
+/// Hardware JPEG decoder, e.g. for MJPG capture streams
+///
+/// # Example
+///
+/// ```no_run
+/// use v4l::buffer::Type;
+/// use v4l::io::traits::CaptureStream;
+/// use v4l::prelude::*;
+/// use v4l::vaapi::{Display, JpegDecoder};
+///
+/// let dev = Device::new(0).unwrap();
+/// let mut stream = MmapStream::new(&dev, Type::VideoCapture).unwrap();
+/// let display = Display::new().unwrap();
+/// let mut decoder = JpegDecoder::new(&display).unwrap();
+///
+/// loop {
+///     let (buf, meta) = stream.next().unwrap();
+///     let surface = decoder.decode(&buf[..meta.bytesused as usize]).unwrap();
+///     let exported = surface.export().unwrap();
+///     // hand the dmabufs to a renderer or encoder
+/// }
+/// ```
+pub struct JpegDecoder<'a> {
+    display: &'a Display,
+    config: VAConfigID,
+    context: Option<(VAContextID, u16, u16)>,
+}
+
+impl<'a> JpegDecoder<'a> {
+    /// Returns a decoder, failing if the GPU cannot decode baseline JPEG
+    ///
+    /// # Arguments
+    ///
+    /// * `display` - Display to decode on
+    pub fn new(display: &'a Display) -> io::Result<Self> {
+        let mut config = VA_INVALID_ID;
+        check(
+            unsafe {
+                vaCreateConfig(
+                    display.dpy,
+                    VAProfile_VAProfileJPEGBaseline,
+                    VAEntrypoint_VAEntrypointVLD,
+                    ptr::null_mut(),
+                    0,
+                    &mut config,
+                )
+            },
+            "vaCreateConfig",
+        )?;
+
+        Ok(JpegDecoder {
+            display,
+            config,
+            context: None,
+        })
+    }
+
+    /// Decodes a JPEG image into a new surface
+    ///
+    /// Images lacking huffman tables, as produced by most webcams, are decoded with the default
+    /// tables of the JPEG specification.
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - JPEG image, e.g. the payload of an MJPG buffer
+    pub fn decode(&mut self, data: &[u8]) -> io::Result<Surface<'a>> {
+        let header = Header::parse(data)?;
+        let rt_format = rt_format(&header)?;
+        let context = self.context(header.width, header.height)?;
+
+        let surface = self.display.create_surface(
+            rt_format,
+            header.width as u32,
+            header.height as u32,
+            &mut [],
+        )?;
+
+        let mut buffers = Vec::new();
+        let res = self.submit(context, &surface, &header, data, &mut buffers);
+        for buffer in buffers {
+            unsafe {
+                vaDestroyBuffer(self.display.dpy, buffer);
+            }
+        }
+        res?;
+
+        surface.sync()?;
+        Ok(surface)
+    }
+
+    fn context(&mut self, width: u16, height: u16) -> io::Result<VAContextID> {
+        if let Some((context, w, h)) = self.context {
+            if (w, h) == (width, height) {
+                return Ok(context);
+            }
+            unsafe {
+                vaDestroyContext(self.display.dpy, context);
+            }
+            self.context = None;
+        }
+
+        let mut context = VA_INVALID_ID;
+        check(
+            unsafe {
+                vaCreateContext(
+                    self.display.dpy,
+                    self.config,
+                    width as i32,
+                    height as i32,
+                    VA_PROGRESSIVE as i32,
+                    ptr::null_mut(),
+                    0,
+                    &mut context,
+                )
+            },
+            "vaCreateContext",
+        )?;
+
+        self.context = Some((context, width, height));
+        Ok(context)
+    }
+
+    fn buffer<T>(
+        &self,
+        context: VAContextID,
+        typ: VABufferType,
+        data: &mut T,
+        buffers: &mut Vec<VABufferID>,
+    ) -> io::Result<()> {
+        self.raw_buffer(
+            context,
+            typ,
+            data as *mut T as *mut std::os::raw::c_void,
+            mem::size_of::<T>(),
+            buffers,
+        )
+    }
+
+    fn raw_buffer(
+        &self,
+        context: VAContextID,
+        typ: VABufferType,
+        data: *mut std::os::raw::c_void,
+        size: usize,
+        buffers: &mut Vec<VABufferID>,
+    ) -> io::Result<()> {
+        let mut id = VA_INVALID_ID;
+        check(
+            unsafe {
+                vaCreateBuffer(
+                    self.display.dpy,
+                    context,
+                    typ,
+                    size as u32,
+                    1,
+                    data,
+                    &mut id,
+                )
+            },
+            "vaCreateBuffer",
+        )?;
+
+        buffers.push(id);
+        Ok(())
+    }
+
+    fn submit(
+        &self,
+        context: VAContextID,
+        surface: &Surface,
+        header: &Header,
+        data: &[u8],
+        buffers: &mut Vec<VABufferID>,
+    ) -> io::Result<()> {
+        let mut picture: VAPictureParameterBufferJPEGBaseline = unsafe { mem::zeroed() };
+        picture.picture_width = header.width;
+        picture.picture_height = header.height;
+        picture.num_components = header.components.len() as u8;
+        for (dst, src) in picture.components.iter_mut().zip(&header.components) {
+            dst.component_id = src.id;
+            dst.h_sampling_factor = src.h;
+            dst.v_sampling_factor = src.v;
+            dst.quantiser_table_selector = src.tq;
+        }
+        self.buffer(
+            context,
+            VABufferType_VAPictureParameterBufferType,
+            &mut picture,
+            buffers,
+        )?;
+
+        let mut iq: VAIQMatrixBufferJPEGBaseline = unsafe { mem::zeroed() };
+        for (i, table) in header.quant.iter().enumerate() {
+            if let Some(table) = table {
+                iq.load_quantiser_table[i] = 1;
+                iq.quantiser_table[i] = *table;
+            }
+        }
+        self.buffer(context, VABufferType_VAIQMatrixBufferType, &mut iq, buffers)?;
+
+        let mut huffman: VAHuffmanTableBufferJPEGBaseline = unsafe { mem::zeroed() };
+        for i in 0..2 {
+            let dst = &mut huffman.huffman_table[i];
+            if let (Some(dc), Some(ac)) = (&header.dc[i], &header.ac[i]) {
+                huffman.load_huffman_table[i] = 1;
+                dst.num_dc_codes = dc.bits;
+                dst.dc_values[..dc.values.len()].copy_from_slice(&dc.values);
+                dst.num_ac_codes = ac.bits;
+                dst.ac_values[..ac.values.len()].copy_from_slice(&ac.values);
+            }
+        }
+        self.buffer(
+            context,
+            VABufferType_VAHuffmanTableBufferType,
+            &mut huffman,
+            buffers,
+        )?;
+
+        let mut slice: VASliceParameterBufferJPEGBaseline = unsafe { mem::zeroed() };
+        slice.slice_data_size = header.data.len() as u32;
+        slice.slice_data_offset = 0;
+        slice.slice_data_flag = VA_SLICE_DATA_FLAG_ALL;
+        slice.num_components = header.scan.len() as u8;
+        for (dst, src) in slice.components.iter_mut().zip(&header.scan) {
+            dst.component_selector = src.selector;
+            dst.dc_table_selector = src.dc;
+            dst.ac_table_selector = src.ac;
+        }
+        slice.restart_interval = header.restart_interval;
+        slice.num_mcus = header.num_mcus();
+        self.buffer(
+            context,
+            VABufferType_VASliceParameterBufferType,
+            &mut slice,
+            buffers,
+        )?;
+
+        // VA copies the data into the buffer, so it is never written to
+        self.raw_buffer(
+            context,
+            VABufferType_VASliceDataBufferType,
+            data[header.data.clone()].as_ptr() as *mut std::os::raw::c_void,
+            header.data.len(),
+            buffers,
+        )?;
+
+        let dpy = self.display.dpy;
+        unsafe {
+            check(vaBeginPicture(dpy, context, surface.id), "vaBeginPicture")?;
+            let res = check(
+                vaRenderPicture(dpy, context, buffers.as_mut_ptr(), buffers.len() as i32),
+                "vaRenderPicture",
+            );
+            check(vaEndPicture(dpy, context), "vaEndPicture")?;
+            res
+        }
+    }
+}
+
+impl<'a> Drop for JpegDecoder<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some((context, _, _)) = self.context {
+                vaDestroyContext(self.display.dpy, context);
+            }
+            vaDestroyConfig(self.display.dpy, self.config);
+        }
+    }
+}
+
+/// Returns the surface format matching the chroma subsampling of an image
+fn rt_format(header: &Header) -> io::Result<u32> {
+    let luma = header.components[0];
+    let chroma_1x1 = header.components[1..].iter().all(|c| c.h == 1 && c.v == 1);
+
+    match (header.components.len(), luma.h, luma.v) {
+        (1, _, _) => Ok(VA_RT_FORMAT_YUV400),
+        (3, 2, 2) if chroma_1x1 => Ok(VA_RT_FORMAT_YUV420),
+        (3, 2, 1) if chroma_1x1 => Ok(VA_RT_FORMAT_YUV422),
+        (3, 1, 1) if chroma_1x1 => Ok(VA_RT_FORMAT_YUV444),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "unsupported JPEG chroma subsampling",
+        )),
+    }
+}
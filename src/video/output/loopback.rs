@@ -0,0 +1,187 @@
+use std::io;
+
+use crate::buffer::{StateError, Type};
+use crate::device::Device;
+use crate::format::Format;
+use crate::io::mmap::Stream;
+use crate::io::traits::{CaptureStream, OutputStream, Stream as StreamTrait};
+use crate::video::{Capture, Output};
+
+/// Name of the v4l2loopback driver as reported in the device capabilities
+const DRIVER: &str = "v4l2 loopback";
+
+/// Feeds frames into a v4l2loopback device, which other applications see as regular camera
+pub struct Loopback {
+    dev: Device,
+    stream: Stream<'static>,
+    format: Format,
+    free: Vec<usize>,
+
+    active: bool,
+}
+
+impl Loopback {
+    /// Returns a feeder for a v4l2loopback device
+    ///
+    /// The output format of the device is set to the given format. An error of kind
+    /// `InvalidInput` is returned if the device is not driven by v4l2loopback.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - v4l2loopback device
+    /// * `fmt` - Format of the frames which will be written
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::buffer::Type;
+    /// use v4l::prelude::*;
+    /// use v4l::video::output::Loopback;
+    ///
+    /// let camera = Device::new(0).unwrap();
+    /// let mut stream = MmapStream::new(&camera, Type::VideoCapture).unwrap();
+    /// let mut loopback = Loopback::for_source(Device::new(10).unwrap(), &camera).unwrap();
+    ///
+    /// loop {
+    ///     loopback.forward(&mut stream).unwrap();
+    /// }
+    /// ```
+    pub fn new(dev: Device, fmt: &Format) -> io::Result<Self> {
+        let caps = dev.query_caps()?;
+        if caps.driver != DRIVER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not a v4l2loopback device: {}", caps.driver),
+            ));
+        }
+
+        let format = Output::set_format(&dev, fmt)?;
+        let stream = Stream::new(&dev, Type::VideoOutput)?;
+        let count = stream.len();
+
+        Ok(Loopback {
+            dev,
+            stream,
+            format,
+            free: (0..count).rev().collect(),
+            active: false,
+        })
+    }
+
+    /// Returns a feeder whose format matches the current capture format of a source device
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - v4l2loopback device
+    /// * `source` - Capture device whose frames will be forwarded
+    pub fn for_source(dev: Device, source: &Device) -> io::Result<Self> {
+        let fmt = Capture::format(source)?;
+        Loopback::new(dev, &fmt)
+    }
+
+    /// Returns the loopback device
+    pub fn device(&self) -> &Device {
+        &self.dev
+    }
+
+    /// Returns the format negotiated with the loopback device
+    pub fn format(&self) -> &Format {
+        &self.format
+    }
+
+    /// Writes a single frame
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - Frame data, must not exceed the buffer size of the loopback device
+    pub fn write(&mut self, frame: &[u8]) -> io::Result<()> {
+        self.write_with(|buf| copy_frame(frame, buf))
+    }
+
+    /// Writes a single frame by filling the next buffer in place
+    ///
+    /// This allows converting frames directly into the buffer memory of the device.
+    ///
+    /// # Arguments
+    ///
+    /// * `f` - Closure filling the buffer and returning the number of bytes used
+    pub fn write_with<F>(&mut self, f: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut [u8]) -> io::Result<usize>,
+    {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => OutputStream::dequeue(&mut self.stream)?,
+        };
+
+        let result = match OutputStream::get(&mut self.stream, index) {
+            Some(buf) => f(buf),
+            None => Err(StateError::InvalidIndex(index).into()),
+        };
+        let bytesused = match result {
+            Ok(bytesused) => bytesused,
+            Err(e) => {
+                self.free.push(index);
+                return Err(e);
+            }
+        };
+
+        if let Some(meta) = OutputStream::get_meta(&mut self.stream, index) {
+            meta.bytesused = bytesused as u32;
+        }
+        OutputStream::queue(&mut self.stream, index)?;
+
+        if !self.active {
+            self.stream.start()?;
+            self.active = true;
+        }
+
+        Ok(())
+    }
+
+    /// Captures a frame from a stream and writes it to the loopback device
+    ///
+    /// Fails without writing anything if the frame exceeds the buffer size of the loopback
+    /// device, e.g. because the source format changed after the loopback was configured.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Capture stream
+    pub fn forward<S>(&mut self, source: &mut S) -> io::Result<()>
+    where
+        S: for<'b> CaptureStream<'b, Item = [u8]>,
+    {
+        self.forward_with(source, copy_frame)
+    }
+
+    /// Captures a frame from a stream, converts it and writes it to the loopback device
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - Capture stream
+    /// * `convert` - Closure converting the captured frame into the buffer, returns the number of
+    ///   bytes used
+    pub fn forward_with<S, F>(&mut self, source: &mut S, convert: F) -> io::Result<()>
+    where
+        S: for<'b> CaptureStream<'b, Item = [u8]>,
+        F: FnOnce(&[u8], &mut [u8]) -> io::Result<usize>,
+    {
+        let (frame, meta) = source.next()?;
+        let frame = meta.payload(frame);
+
+        self.write_with(|buf| convert(frame, buf))
+    }
+}
+
+/// Copies a frame into a buffer of the loopback device, returns the number of bytes used
+fn copy_frame(frame: &[u8], buf: &mut [u8]) -> io::Result<usize> {
+    if frame.len() > buf.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame too large: {} > {} bytes", frame.len(), buf.len()),
+        ));
+    }
+
+    buf[..frame.len()].copy_from_slice(frame);
+    Ok(frame.len())
+}
@@ -1,3 +1,6 @@
+pub mod loopback;
+pub use loopback::Loopback;
+
 pub mod parameters;
 pub use parameters::Parameters;
 
@@ -0,0 +1,238 @@
+//! Conveniences for the vivid (Virtual Video Test Driver) kernel module
+//!
+//! vivid emulates capture and output hardware and offers controls to select the generated test
+//! pattern and to inject errors at well defined points. This makes it possible to exercise error
+//! paths of an application deterministically, e.g. in CI after `modprobe vivid`.
+//!
+//! The driver specific controls are looked up by name, since their ids are private to the driver
+//! and are not part of the uapi headers.
+
+use std::{fmt, io};
+
+use crate::control::{Control, Description, MenuItem, Type};
+use crate::device::Device;
+
+/// Name of the vivid driver as reported in the device capabilities
+const DRIVER: &str = "vivid";
+
+/// Returns true if the device is driven by vivid
+///
+/// # Arguments
+///
+/// * `dev` - Device to check
+pub fn is_vivid(dev: &Device) -> io::Result<bool> {
+    Ok(dev.query_caps()?.driver == DRIVER)
+}
+
+/// Test pattern generated by vivid
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TestPattern {
+    ColorBar75,
+    ColorBar100,
+    CscColorBar,
+    HorizontalColorBar100,
+    ColorSquares100,
+    Black100,
+    White100,
+    Red100,
+    Green100,
+    Blue100,
+    Checkers16x16,
+    Checkers2x2,
+    Checkers1x1,
+    RedGreenCheckers2x2,
+    RedGreenCheckers1x1,
+    AlternatingHorizontalLines,
+    AlternatingVerticalLines,
+    Cross1,
+    Cross2,
+    Cross10,
+    GrayRamp,
+    Noise,
+}
+
+impl TestPattern {
+    /// Returns the name of the menu item as reported by the driver
+    pub fn name(&self) -> &'static str {
+        match self {
+            TestPattern::ColorBar75 => "75% Colorbar",
+            TestPattern::ColorBar100 => "100% Colorbar",
+            TestPattern::CscColorBar => "CSC Colorbar",
+            TestPattern::HorizontalColorBar100 => "Horizontal 100% Colorbar",
+            TestPattern::ColorSquares100 => "100% Color Squares",
+            TestPattern::Black100 => "100% Black",
+            TestPattern::White100 => "100% White",
+            TestPattern::Red100 => "100% Red",
+            TestPattern::Green100 => "100% Green",
+            TestPattern::Blue100 => "100% Blue",
+            TestPattern::Checkers16x16 => "16x16 Checkers",
+            TestPattern::Checkers2x2 => "2x2 Checkers",
+            TestPattern::Checkers1x1 => "1x1 Checkers",
+            TestPattern::RedGreenCheckers2x2 => "2x2 Red/Green Checkers",
+            TestPattern::RedGreenCheckers1x1 => "1x1 Red/Green Checkers",
+            TestPattern::AlternatingHorizontalLines => "Alternating Hor Lines",
+            TestPattern::AlternatingVerticalLines => "Alternating Vert Lines",
+            TestPattern::Cross1 => "One Pixel Wide Cross",
+            TestPattern::Cross2 => "Two Pixels Wide Cross",
+            TestPattern::Cross10 => "Ten Pixels Wide Cross",
+            TestPattern::GrayRamp => "Gray Ramp",
+            TestPattern::Noise => "Noise",
+        }
+    }
+}
+
+impl fmt::Display for TestPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Error which vivid injects on request
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Injection {
+    /// The next dequeued buffer carries V4L2_BUF_FLAG_ERROR
+    BufferError,
+    /// The next VIDIOC_REQBUFS or VIDIOC_CREATE_BUFS fails
+    QueueSetupError,
+    /// The next buffer preparation (VIDIOC_QBUF or VIDIOC_PREPARE_BUF) fails
+    BufferPrepareError,
+    /// The next VIDIOC_STREAMON fails
+    StartStreamingError,
+    /// The queue enters an unrecoverable error state, all further dequeue attempts fail
+    FatalStreamingError,
+    /// The device behaves as if it was unplugged
+    Disconnect,
+}
+
+impl Injection {
+    /// Returns the name of the control triggering the error
+    fn control_name(&self) -> &'static str {
+        match self {
+            Injection::BufferError => "Inject V4L2_BUF_FLAG_ERROR",
+            Injection::QueueSetupError => "Inject VIDIOC_REQBUFS Error",
+            Injection::BufferPrepareError => "Inject VIDIOC_QBUF Error",
+            Injection::StartStreamingError => "Inject VIDIOC_STREAMON Error",
+            Injection::FatalStreamingError => "Inject Fatal Streaming Error",
+            Injection::Disconnect => "Disconnect",
+        }
+    }
+}
+
+impl fmt::Display for Injection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// Typed access to the driver specific controls of a vivid device
+pub struct Vivid<'a> {
+    dev: &'a Device,
+    controls: Vec<Description>,
+}
+
+impl<'a> Vivid<'a> {
+    /// Returns a wrapper for a vivid device
+    ///
+    /// An error of kind `InvalidInput` is returned if the device is not driven by vivid.
+    ///
+    /// # Arguments
+    ///
+    /// * `dev` - vivid device
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use v4l::device::Device;
+    /// use v4l::vivid::{Injection, TestPattern, Vivid};
+    ///
+    /// let dev = Device::new(0).unwrap();
+    /// let vivid = Vivid::new(&dev).unwrap();
+    /// vivid.set_test_pattern(TestPattern::GrayRamp).unwrap();
+    /// vivid.inject(Injection::BufferError).unwrap();
+    /// ```
+    pub fn new(dev: &'a Device) -> io::Result<Self> {
+        if !is_vivid(dev)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not a vivid device",
+            ));
+        }
+
+        Ok(Vivid {
+            dev,
+            controls: dev.query_controls()?,
+        })
+    }
+
+    /// Returns the device
+    pub fn device(&self) -> &Device {
+        self.dev
+    }
+
+    /// Selects the generated test pattern
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - Test pattern
+    pub fn set_test_pattern(&self, pattern: TestPattern) -> io::Result<()> {
+        let desc = self.find("Test Pattern")?;
+        let index = desc
+            .items
+            .iter()
+            .flatten()
+            .find(|(_, item)| match item {
+                MenuItem::Name(name) => name == pattern.name(),
+                MenuItem::Value(_) => false,
+            })
+            .map(|(index, _)| *index)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("test pattern not supported: {}", pattern),
+                )
+            })?;
+
+        self.dev.set_control(desc.id, Control::Value(index as i32))
+    }
+
+    /// Makes vivid drop the given percentage of buffers, which are returned with an error flag
+    ///
+    /// # Arguments
+    ///
+    /// * `percentage` - Percentage of dropped buffers, from 0 to 100
+    pub fn set_dropped_buffers(&self, percentage: u8) -> io::Result<()> {
+        let desc = self.find("Percentage of Dropped Buffers")?;
+        self.dev
+            .set_control(desc.id, Control::Value(i32::from(percentage.min(100))))
+    }
+
+    /// Injects an error
+    ///
+    /// Most errors only affect the next matching operation, see `Injection`.
+    ///
+    /// # Arguments
+    ///
+    /// * `injection` - Error to inject
+    pub fn inject(&self, injection: Injection) -> io::Result<()> {
+        let desc = self.find(injection.control_name())?;
+        let value = match desc.typ {
+            // boolean controls arm the error for the next operation
+            Type::Boolean => 1,
+            _ => 0,
+        };
+
+        self.dev.set_control(desc.id, Control::Value(value))
+    }
+
+    fn find(&self, name: &str) -> io::Result<&Description> {
+        self.controls
+            .iter()
+            .find(|desc| desc.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("vivid control not found: {}", name),
+                )
+            })
+    }
+}
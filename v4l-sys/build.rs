@@ -1,20 +1,44 @@
 extern crate bindgen;
 
 use std::env;
+use std::fs;
 use std::path::PathBuf;
 
 fn main() {
     println!("cargo:rustc-link-lib=v4l1");
     println!("cargo:rustc-link-lib=v4l2");
     println!("cargo:rustc-link-lib=v4lconvert");
+    println!("cargo:rerun-if-changed=src/bindings.rs");
+
+    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap()).join("libv4l_bindings.rs");
+    if cfg!(feature = "bindgen") || !prebuilt() {
+        generate(&out_path);
+    } else {
+        fs::copy("src/bindings.rs", &out_path).expect("Failed to copy prebuilt bindings");
+    }
+
+    // Dependent crates generate typed wrappers from the bindings, see DEP_V4L2_BINDINGS
+    println!("cargo:bindings={}", out_path.display());
+}
+
+/// Returns true if the prebuilt bindings in src/bindings.rs match the target
+///
+/// They were generated on x86_64 Linux. 64-bit ARM has the same type sizes and struct layouts,
+/// bindings for any other target are generated from the installed headers.
+fn prebuilt() -> bool {
+    let os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    (os == "linux" || os == "android") && (arch == "x86_64" || arch == "aarch64")
+}
+
+fn generate(out_path: &PathBuf) {
+    println!("cargo:rerun-if-changed=wrapper.h");
 
     let bindings = bindgen::Builder::default()
         .header("wrapper.h")
         .generate()
         .expect("Failed to generate bindings");
-
-    let out_path = PathBuf::from(env::var("OUT_DIR").unwrap());
     bindings
-        .write_to_file(out_path.join("libv4l_bindings.rs"))
+        .write_to_file(out_path)
         .expect("Failed to write bindings");
 }
@@ -1,3 +1,6 @@
 #include <libv4l1.h>
 #include <libv4l2.h>
 #include <libv4lconvert.h>
+#include <linux/videodev2.h>
+#include <linux/v4l2-subdev.h>
+#include <linux/media.h>
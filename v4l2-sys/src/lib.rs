@@ -3,4 +3,5 @@
 #![allow(non_snake_case)]
 #![allow(clippy::all)]
 
+// Either the prebuilt bindings in bindings.rs or generated ones, see build.rs
 include!(concat!(env!("OUT_DIR"), "/v4l2_bindings.rs"));
@@ -1 +1,3 @@
 #include <linux/videodev2.h>
+#include <linux/v4l2-subdev.h>
+#include <linux/media.h>
@@ -0,0 +1,365 @@
+// Prebuilt bindings for the VA-API 1.x headers (libva 2.x) on x86_64: va/va.h, va/va_drm.h and
+// va/va_drmcommon.h
+//
+// Only the definitions used by the v4l crate are included, in the shape bindgen emits them.
+// Enable the "bindgen" feature to generate the complete bindings from the installed headers at
+// build time instead. Targets other than x86_64 and aarch64 Linux or Android always generate
+// them, see build.rs.
+
+pub const VA_STATUS_SUCCESS: u32 = 0;
+pub const VA_STATUS_ERROR_OPERATION_FAILED: u32 = 1;
+pub const VA_STATUS_ERROR_ALLOCATION_FAILED: u32 = 2;
+pub const VA_STATUS_ERROR_INVALID_DISPLAY: u32 = 3;
+pub const VA_STATUS_ERROR_INVALID_CONFIG: u32 = 4;
+pub const VA_STATUS_ERROR_INVALID_CONTEXT: u32 = 5;
+pub const VA_STATUS_ERROR_INVALID_SURFACE: u32 = 6;
+pub const VA_STATUS_ERROR_INVALID_BUFFER: u32 = 7;
+pub const VA_STATUS_ERROR_INVALID_IMAGE: u32 = 8;
+pub const VA_STATUS_ERROR_INVALID_SUBPICTURE: u32 = 9;
+pub const VA_STATUS_ERROR_ATTR_NOT_SUPPORTED: u32 = 10;
+pub const VA_STATUS_ERROR_MAX_NUM_EXCEEDED: u32 = 11;
+pub const VA_STATUS_ERROR_UNSUPPORTED_PROFILE: u32 = 12;
+pub const VA_STATUS_ERROR_UNSUPPORTED_ENTRYPOINT: u32 = 13;
+pub const VA_STATUS_ERROR_UNSUPPORTED_RT_FORMAT: u32 = 14;
+pub const VA_STATUS_ERROR_UNSUPPORTED_BUFFERTYPE: u32 = 15;
+pub const VA_STATUS_ERROR_SURFACE_BUSY: u32 = 16;
+pub const VA_STATUS_ERROR_FLAG_NOT_SUPPORTED: u32 = 17;
+pub const VA_STATUS_ERROR_INVALID_PARAMETER: u32 = 18;
+pub const VA_STATUS_ERROR_RESOLUTION_NOT_SUPPORTED: u32 = 19;
+pub const VA_STATUS_ERROR_UNIMPLEMENTED: u32 = 20;
+pub const VA_STATUS_ERROR_SURFACE_IN_DISPLAYING: u32 = 21;
+pub const VA_STATUS_ERROR_INVALID_IMAGE_FORMAT: u32 = 22;
+pub const VA_STATUS_ERROR_DECODING_ERROR: u32 = 23;
+pub const VA_STATUS_ERROR_ENCODING_ERROR: u32 = 24;
+pub const VA_STATUS_ERROR_INVALID_VALUE: u32 = 25;
+pub const VA_STATUS_ERROR_UNSUPPORTED_FILTER: u32 = 32;
+pub const VA_STATUS_ERROR_INVALID_FILTER_CHAIN: u32 = 33;
+pub const VA_STATUS_ERROR_HW_BUSY: u32 = 34;
+pub const VA_STATUS_ERROR_UNSUPPORTED_MEMORY_TYPE: u32 = 36;
+pub const VA_STATUS_ERROR_NOT_ENOUGH_BUFFER: u32 = 37;
+pub const VA_STATUS_ERROR_TIMEDOUT: u32 = 38;
+pub const VA_STATUS_ERROR_UNKNOWN: u32 = 4294967295;
+pub const VA_PROGRESSIVE: u32 = 1;
+pub const VA_INVALID_ID: u32 = 4294967295;
+pub const VA_INVALID_SURFACE: u32 = 4294967295;
+pub const VA_RT_FORMAT_YUV420: u32 = 1;
+pub const VA_RT_FORMAT_YUV422: u32 = 2;
+pub const VA_RT_FORMAT_YUV444: u32 = 4;
+pub const VA_RT_FORMAT_YUV411: u32 = 8;
+pub const VA_RT_FORMAT_YUV400: u32 = 16;
+pub const VA_SLICE_DATA_FLAG_ALL: u32 = 0;
+pub const VA_SLICE_DATA_FLAG_BEGIN: u32 = 1;
+pub const VA_SLICE_DATA_FLAG_MIDDLE: u32 = 2;
+pub const VA_SLICE_DATA_FLAG_END: u32 = 4;
+pub const VA_SURFACE_ATTRIB_NOT_SUPPORTED: u32 = 0;
+pub const VA_SURFACE_ATTRIB_GETTABLE: u32 = 1;
+pub const VA_SURFACE_ATTRIB_SETTABLE: u32 = 2;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_VA: u32 = 1;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_V4L2: u32 = 2;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_USER_PTR: u32 = 4;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_KERNEL_DRM: u32 = 268435456;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_DRM_PRIME: u32 = 536870912;
+pub const VA_SURFACE_ATTRIB_MEM_TYPE_DRM_PRIME_2: u32 = 1073741824;
+pub const VA_EXPORT_SURFACE_READ_ONLY: u32 = 1;
+pub const VA_EXPORT_SURFACE_WRITE_ONLY: u32 = 2;
+pub const VA_EXPORT_SURFACE_READ_WRITE: u32 = 3;
+pub const VA_EXPORT_SURFACE_SEPARATE_LAYERS: u32 = 4;
+pub const VA_EXPORT_SURFACE_COMPOSED_LAYERS: u32 = 8;
+pub const VA_PADDING_LOW: u32 = 4;
+pub const VA_PADDING_MEDIUM: u32 = 8;
+pub type VADisplay = *mut ::std::os::raw::c_void;
+pub type VAStatus = ::std::os::raw::c_int;
+extern "C" {
+    pub fn vaErrorStr(error_status: VAStatus) -> *const ::std::os::raw::c_char;
+}
+extern "C" {
+    pub fn vaInitialize(
+        dpy: VADisplay,
+        major_version: *mut ::std::os::raw::c_int,
+        minor_version: *mut ::std::os::raw::c_int,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaTerminate(dpy: VADisplay) -> VAStatus;
+}
+pub const VAProfile_VAProfileNone: VAProfile = -1;
+pub const VAProfile_VAProfileMPEG2Simple: VAProfile = 0;
+pub const VAProfile_VAProfileMPEG2Main: VAProfile = 1;
+pub const VAProfile_VAProfileMPEG4Simple: VAProfile = 2;
+pub const VAProfile_VAProfileMPEG4AdvancedSimple: VAProfile = 3;
+pub const VAProfile_VAProfileMPEG4Main: VAProfile = 4;
+pub const VAProfile_VAProfileH264Baseline: VAProfile = 5;
+pub const VAProfile_VAProfileH264Main: VAProfile = 6;
+pub const VAProfile_VAProfileH264High: VAProfile = 7;
+pub const VAProfile_VAProfileVC1Simple: VAProfile = 8;
+pub const VAProfile_VAProfileVC1Main: VAProfile = 9;
+pub const VAProfile_VAProfileVC1Advanced: VAProfile = 10;
+pub const VAProfile_VAProfileH263Baseline: VAProfile = 11;
+pub const VAProfile_VAProfileJPEGBaseline: VAProfile = 12;
+pub const VAProfile_VAProfileH264ConstrainedBaseline: VAProfile = 13;
+pub type VAProfile = ::std::os::raw::c_int;
+pub const VAEntrypoint_VAEntrypointVLD: VAEntrypoint = 1;
+pub const VAEntrypoint_VAEntrypointIZZ: VAEntrypoint = 2;
+pub const VAEntrypoint_VAEntrypointIDCT: VAEntrypoint = 3;
+pub const VAEntrypoint_VAEntrypointMoComp: VAEntrypoint = 4;
+pub const VAEntrypoint_VAEntrypointDeblocking: VAEntrypoint = 5;
+pub const VAEntrypoint_VAEntrypointEncSlice: VAEntrypoint = 6;
+pub const VAEntrypoint_VAEntrypointEncPicture: VAEntrypoint = 7;
+pub type VAEntrypoint = ::std::os::raw::c_uint;
+pub type VAConfigAttribType = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAConfigAttrib {
+    pub type_: VAConfigAttribType,
+    pub value: u32,
+}
+pub type VAConfigAttrib = _VAConfigAttrib;
+pub type VAGenericID = ::std::os::raw::c_uint;
+pub type VAConfigID = VAGenericID;
+extern "C" {
+    pub fn vaCreateConfig(
+        dpy: VADisplay,
+        profile: VAProfile,
+        entrypoint: VAEntrypoint,
+        attrib_list: *mut VAConfigAttrib,
+        num_attribs: ::std::os::raw::c_int,
+        config_id: *mut VAConfigID,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaDestroyConfig(dpy: VADisplay, config_id: VAConfigID) -> VAStatus;
+}
+pub type VAContextID = VAGenericID;
+pub type VASurfaceID = VAGenericID;
+pub const VAGenericValueType_VAGenericValueTypeInteger: VAGenericValueType = 1;
+pub const VAGenericValueType_VAGenericValueTypeFloat: VAGenericValueType = 2;
+pub const VAGenericValueType_VAGenericValueTypePointer: VAGenericValueType = 3;
+pub const VAGenericValueType_VAGenericValueTypeFunc: VAGenericValueType = 4;
+pub type VAGenericValueType = ::std::os::raw::c_uint;
+pub type VAGenericFunc = ::std::option::Option<unsafe extern "C" fn()>;
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct _VAGenericValue {
+    pub type_: VAGenericValueType,
+    pub value: _VAGenericValue__bindgen_ty_1,
+}
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub union _VAGenericValue__bindgen_ty_1 {
+    pub i: i32,
+    pub f: f32,
+    pub p: *mut ::std::os::raw::c_void,
+    pub fn_: VAGenericFunc,
+}
+pub type VAGenericValue = _VAGenericValue;
+pub const VASurfaceAttribType_VASurfaceAttribNone: VASurfaceAttribType = 0;
+pub const VASurfaceAttribType_VASurfaceAttribPixelFormat: VASurfaceAttribType = 1;
+pub const VASurfaceAttribType_VASurfaceAttribMinWidth: VASurfaceAttribType = 2;
+pub const VASurfaceAttribType_VASurfaceAttribMaxWidth: VASurfaceAttribType = 3;
+pub const VASurfaceAttribType_VASurfaceAttribMinHeight: VASurfaceAttribType = 4;
+pub const VASurfaceAttribType_VASurfaceAttribMaxHeight: VASurfaceAttribType = 5;
+pub const VASurfaceAttribType_VASurfaceAttribMemoryType: VASurfaceAttribType = 6;
+pub const VASurfaceAttribType_VASurfaceAttribExternalBufferDescriptor: VASurfaceAttribType = 7;
+pub const VASurfaceAttribType_VASurfaceAttribUsageHint: VASurfaceAttribType = 8;
+pub const VASurfaceAttribType_VASurfaceAttribDRMFormatModifiers: VASurfaceAttribType = 9;
+pub type VASurfaceAttribType = ::std::os::raw::c_uint;
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct _VASurfaceAttrib {
+    pub type_: VASurfaceAttribType,
+    pub flags: u32,
+    pub value: VAGenericValue,
+}
+pub type VASurfaceAttrib = _VASurfaceAttrib;
+extern "C" {
+    pub fn vaCreateSurfaces(
+        dpy: VADisplay,
+        format: ::std::os::raw::c_uint,
+        width: ::std::os::raw::c_uint,
+        height: ::std::os::raw::c_uint,
+        surfaces: *mut VASurfaceID,
+        num_surfaces: ::std::os::raw::c_uint,
+        attrib_list: *mut VASurfaceAttrib,
+        num_attribs: ::std::os::raw::c_uint,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaDestroySurfaces(
+        dpy: VADisplay,
+        surfaces: *mut VASurfaceID,
+        num_surfaces: ::std::os::raw::c_int,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaCreateContext(
+        dpy: VADisplay,
+        config_id: VAConfigID,
+        picture_width: ::std::os::raw::c_int,
+        picture_height: ::std::os::raw::c_int,
+        flag: ::std::os::raw::c_int,
+        render_targets: *mut VASurfaceID,
+        num_render_targets: ::std::os::raw::c_int,
+        context: *mut VAContextID,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaDestroyContext(dpy: VADisplay, context: VAContextID) -> VAStatus;
+}
+pub type VABufferID = VAGenericID;
+pub const VABufferType_VAPictureParameterBufferType: VABufferType = 0;
+pub const VABufferType_VAIQMatrixBufferType: VABufferType = 1;
+pub const VABufferType_VABitPlaneBufferType: VABufferType = 2;
+pub const VABufferType_VASliceGroupMapBufferType: VABufferType = 3;
+pub const VABufferType_VASliceParameterBufferType: VABufferType = 4;
+pub const VABufferType_VASliceDataBufferType: VABufferType = 5;
+pub const VABufferType_VAMacroblockParameterBufferType: VABufferType = 6;
+pub const VABufferType_VAResidualDataBufferType: VABufferType = 7;
+pub const VABufferType_VADeblockingParameterBufferType: VABufferType = 8;
+pub const VABufferType_VAImageBufferType: VABufferType = 9;
+pub const VABufferType_VAProtectedSliceDataBufferType: VABufferType = 10;
+pub const VABufferType_VAQMatrixBufferType: VABufferType = 11;
+pub const VABufferType_VAHuffmanTableBufferType: VABufferType = 12;
+pub const VABufferType_VAProbabilityBufferType: VABufferType = 13;
+pub type VABufferType = ::std::os::raw::c_uint;
+extern "C" {
+    pub fn vaCreateBuffer(
+        dpy: VADisplay,
+        context: VAContextID,
+        type_: VABufferType,
+        size: ::std::os::raw::c_uint,
+        num_elements: ::std::os::raw::c_uint,
+        data: *mut ::std::os::raw::c_void,
+        buf_id: *mut VABufferID,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaDestroyBuffer(dpy: VADisplay, buffer_id: VABufferID) -> VAStatus;
+}
+extern "C" {
+    pub fn vaBeginPicture(
+        dpy: VADisplay,
+        context: VAContextID,
+        render_target: VASurfaceID,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaRenderPicture(
+        dpy: VADisplay,
+        context: VAContextID,
+        buffers: *mut VABufferID,
+        num_buffers: ::std::os::raw::c_int,
+    ) -> VAStatus;
+}
+extern "C" {
+    pub fn vaEndPicture(dpy: VADisplay, context: VAContextID) -> VAStatus;
+}
+extern "C" {
+    pub fn vaSyncSurface(dpy: VADisplay, render_target: VASurfaceID) -> VAStatus;
+}
+extern "C" {
+    pub fn vaExportSurfaceHandle(
+        dpy: VADisplay,
+        surface_id: VASurfaceID,
+        mem_type: u32,
+        flags: u32,
+        descriptor: *mut ::std::os::raw::c_void,
+    ) -> VAStatus;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAPictureParameterBufferJPEGBaseline {
+    pub picture_width: u16,
+    pub picture_height: u16,
+    pub components: [_VAPictureParameterBufferJPEGBaseline__bindgen_ty_1; 255usize],
+    pub num_components: u8,
+    pub color_space: u8,
+    pub rotation: u32,
+    pub va_reserved: [u32; 7usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAPictureParameterBufferJPEGBaseline__bindgen_ty_1 {
+    pub component_id: u8,
+    pub h_sampling_factor: u8,
+    pub v_sampling_factor: u8,
+    pub quantiser_table_selector: u8,
+}
+pub type VAPictureParameterBufferJPEGBaseline = _VAPictureParameterBufferJPEGBaseline;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAIQMatrixBufferJPEGBaseline {
+    pub load_quantiser_table: [u8; 4usize],
+    pub quantiser_table: [[u8; 64usize]; 4usize],
+    pub va_reserved: [u32; 4usize],
+}
+pub type VAIQMatrixBufferJPEGBaseline = _VAIQMatrixBufferJPEGBaseline;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAHuffmanTableBufferJPEGBaseline {
+    pub load_huffman_table: [u8; 2usize],
+    pub huffman_table: [_VAHuffmanTableBufferJPEGBaseline__bindgen_ty_1; 2usize],
+    pub va_reserved: [u32; 4usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VAHuffmanTableBufferJPEGBaseline__bindgen_ty_1 {
+    pub num_dc_codes: [u8; 16usize],
+    pub dc_values: [u8; 12usize],
+    pub num_ac_codes: [u8; 16usize],
+    pub ac_values: [u8; 162usize],
+    pub pad: [u8; 2usize],
+}
+pub type VAHuffmanTableBufferJPEGBaseline = _VAHuffmanTableBufferJPEGBaseline;
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VASliceParameterBufferJPEGBaseline {
+    pub slice_data_size: u32,
+    pub slice_data_offset: u32,
+    pub slice_data_flag: u32,
+    pub slice_horizontal_position: u32,
+    pub slice_vertical_position: u32,
+    pub components: [_VASliceParameterBufferJPEGBaseline__bindgen_ty_1; 4usize],
+    pub num_components: u8,
+    pub restart_interval: u16,
+    pub num_mcus: u32,
+    pub va_reserved: [u32; 4usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VASliceParameterBufferJPEGBaseline__bindgen_ty_1 {
+    pub component_selector: u8,
+    pub dc_table_selector: u8,
+    pub ac_table_selector: u8,
+}
+pub type VASliceParameterBufferJPEGBaseline = _VASliceParameterBufferJPEGBaseline;
+extern "C" {
+    pub fn vaGetDisplayDRM(fd: ::std::os::raw::c_int) -> VADisplay;
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VADRMPRIMESurfaceDescriptor {
+    pub fourcc: u32,
+    pub width: u32,
+    pub height: u32,
+    pub num_objects: u32,
+    pub objects: [_VADRMPRIMESurfaceDescriptor__bindgen_ty_1; 4usize],
+    pub num_layers: u32,
+    pub layers: [_VADRMPRIMESurfaceDescriptor__bindgen_ty_2; 4usize],
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VADRMPRIMESurfaceDescriptor__bindgen_ty_1 {
+    pub fd: ::std::os::raw::c_int,
+    pub size: u32,
+    pub drm_format_modifier: u64,
+}
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct _VADRMPRIMESurfaceDescriptor__bindgen_ty_2 {
+    pub drm_format: u32,
+    pub num_planes: u32,
+    pub object_index: [u32; 4usize],
+    pub offset: [u32; 4usize],
+    pub pitch: [u32; 4usize],
+}
+pub type VADRMPRIMESurfaceDescriptor = _VADRMPRIMESurfaceDescriptor;
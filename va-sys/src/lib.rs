@@ -0,0 +1,7 @@
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(clippy::all)]
+
+// Either the prebuilt bindings in bindings.rs or generated ones, see build.rs
+include!(concat!(env!("OUT_DIR"), "/va_bindings.rs"));